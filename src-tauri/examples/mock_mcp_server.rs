@@ -0,0 +1,46 @@
+// Scripted stdio MCP server used by the McpClient tests.
+//
+//   MOCK_MCP_SCRIPT='{"tools":[{"name":"echo"}]}' mock_mcp_server
+//
+// The script format is documented in src/mcp/mock_script.rs.
+
+#[path = "../src/mcp/mock_script.rs"]
+mod mock_script;
+
+use std::io::{BufRead, Write};
+use mock_script::{MockAction, MockMcp, MockScript};
+
+fn main() {
+    let script = std::env::var("MOCK_MCP_SCRIPT")
+        .ok()
+        .or_else(|| std::env::args().nth(1).map(|path| std::fs::read_to_string(path).expect("unreadable script")))
+        .map(|json| MockScript::from_value(serde_json::from_str(&json).expect("script is not JSON")))
+        .unwrap_or_default();
+
+    for line in &script.stderr {
+        eprintln!("{}", line);
+    }
+
+    let mut server = MockMcp::new(script);
+    let stdin = std::io::stdin();
+    let mut stdout = std::io::stdout();
+    for line in stdin.lock().lines() {
+        let Ok(line) = line else { break };
+        let Ok(message) = serde_json::from_str(&line) else {
+            eprintln!("mock: ignoring non-JSON input: {}", line);
+            continue;
+        };
+        for action in server.handle(&message) {
+            match action {
+                MockAction::Send(value) => {
+                    writeln!(stdout, "{}", value).unwrap();
+                    stdout.flush().unwrap();
+                }
+                MockAction::Exit(code) => {
+                    eprintln!("mock: exiting with {}", code);
+                    std::process::exit(code);
+                }
+            }
+        }
+    }
+}
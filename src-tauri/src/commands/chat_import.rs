@@ -0,0 +1,496 @@
+//! Importers for the official export archives of other chat assistants.
+//!
+//! ChatGPT and Claude both export a ZIP containing `conversations.json`. Each conversation is
+//! mapped onto a `ConversationArchive` (titles, timestamps and branches included) and stored
+//! through `conversation_export::import_archive`, so imported chats behave like native ones.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{json, Value};
+use crate::database::Database;
+use super::conversation_export::{self, ArchiveMessage, ConversationArchive, ARCHIVE_FORMAT, ARCHIVE_VERSION};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatSource {
+    ChatGpt,
+    Claude,
+}
+
+/// What an import did (or would do, on a dry run)
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportSummary {
+    pub source: Option<ChatSource>,
+    pub dry_run: bool,
+    pub conversations: usize,
+    pub messages: usize,
+    /// Conversations that contain edited or regenerated branches
+    pub branched_conversations: usize,
+    /// Conversations without any importable message
+    pub skipped: usize,
+    /// Images found in the export for the imported messages
+    pub images: usize,
+    pub earliest: Option<String>,
+    pub latest: Option<String>,
+    /// Ids of the created conversations; empty on a dry run
+    pub imported_ids: Vec<String>,
+}
+
+/// The opened export: `conversations.json` plus the ZIP for looking up attached images
+struct ExportFile {
+    conversations: Value,
+    zip: Option<zip::ZipArchive<File>>,
+}
+
+impl ExportFile {
+    /// Accepts the ZIP as downloaded or an extracted `conversations.json`
+    fn open(path: &Path) -> Result<Self, String> {
+        let is_json = path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("json"));
+        if is_json {
+            let json = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            let conversations = serde_json::from_str(&json).map_err(|e| format!("Invalid conversations.json: {}", e))?;
+            return Ok(Self { conversations, zip: None });
+        }
+
+        let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let mut zip = zip::ZipArchive::new(file).map_err(|e| format!("Not a ZIP archive: {}", e))?;
+        let name = zip.file_names()
+            .filter(|n| n.rsplit('/').next() == Some("conversations.json"))
+            .min_by_key(|n| n.len())
+            .map(str::to_string)
+            .ok_or("The archive has no conversations.json")?;
+        let mut json = String::new();
+        zip.by_name(&name)
+            .map_err(|e| e.to_string())?
+            .read_to_string(&mut json)
+            .map_err(|e| format!("Failed to read conversations.json: {}", e))?;
+        let conversations = serde_json::from_str(&json).map_err(|e| format!("Invalid conversations.json: {}", e))?;
+        Ok(Self { conversations, zip: Some(zip) })
+    }
+
+    /// Name of the image stored next to `conversations.json` whose file name starts with `file_id`
+    fn image_name(&self, file_id: &str) -> Option<String> {
+        self.zip.as_ref()?
+            .file_names()
+            .find(|n| n.rsplit('/').next().is_some_and(|f| f.starts_with(file_id)))
+            .map(str::to_string)
+    }
+
+    /// Data URL of the image for `file_id`
+    fn image_data_url(&mut self, file_id: &str) -> Option<String> {
+        let name = self.image_name(file_id)?;
+        let mut data = Vec::new();
+        self.zip.as_mut()?.by_name(&name).ok()?.read_to_end(&mut data).ok()?;
+        let mime_type = match name.rsplit('.').next().map(|e| e.to_ascii_lowercase()).as_deref() {
+            Some("jpg") | Some("jpeg") => "image/jpeg",
+            Some("gif") => "image/gif",
+            Some("webp") => "image/webp",
+            _ => "image/png",
+        };
+        Some(format!("data:{};base64,{}", mime_type, BASE64.encode(data)))
+    }
+}
+
+fn detect_source(conversations: &Value) -> Option<ChatSource> {
+    let first = conversations.as_array()?.first()?;
+    if first.get("mapping").is_some() {
+        Some(ChatSource::ChatGpt)
+    } else if first.get("chat_messages").is_some() {
+        Some(ChatSource::Claude)
+    } else {
+        None
+    }
+}
+
+/// ChatGPT uses fractional unix seconds
+fn unix_time(value: &Value) -> Option<String> {
+    let secs = value.as_f64()?;
+    DateTime::from_timestamp(secs.trunc() as i64, (secs.fract() * 1e9) as u32).map(|t| t.to_rfc3339())
+}
+
+/// Claude uses ISO 8601; normalise so timestamps sort like native ones
+fn iso_time(value: &Value) -> Option<String> {
+    DateTime::parse_from_rfc3339(value.as_str()?).ok().map(|t| t.with_timezone(&Utc).to_rfc3339())
+}
+
+fn empty_archive(title: Option<String>, created_at: String, updated_at: String) -> ConversationArchive {
+    ConversationArchive {
+        format: ARCHIVE_FORMAT.to_string(),
+        version: ARCHIVE_VERSION,
+        exported_at: Utc::now().to_rfc3339(),
+        title,
+        pet_name: None,
+        created_at,
+        updated_at,
+        active_leaf_id: None,
+        messages: Vec::new(),
+    }
+}
+
+/// Collapse text-only parts into a plain string, the way the chat stores simple messages
+fn into_content(parts: Vec<Value>) -> Option<Value> {
+    if parts.is_empty() {
+        return None;
+    }
+    if parts.iter().all(|p| p["type"] == "text") {
+        let text = parts.iter().filter_map(|p| p["text"].as_str()).collect::<Vec<_>>().join("\n\n");
+        return Some(Value::String(text));
+    }
+    Some(Value::Array(parts))
+}
+
+// ---------- ChatGPT ----------
+
+/// Content of a visible user / assistant node; tool traffic, hidden context and reasoning are dropped
+fn chatgpt_content(message: &Value, images: &mut dyn FnMut(&str) -> Option<String>) -> Option<(String, Value)> {
+    let role = match message["author"]["role"].as_str()? {
+        "user" => "user",
+        "assistant" => "assistant",
+        _ => return None,
+    };
+    if message["metadata"]["is_visually_hidden_from_conversation"].as_bool() == Some(true)
+        || message["recipient"].as_str().is_some_and(|r| r != "all")
+    {
+        return None;
+    }
+    let content = &message["content"];
+    if !matches!(content["content_type"].as_str(), Some("text") | Some("multimodal_text")) {
+        return None;
+    }
+
+    let mut parts = Vec::new();
+    for part in content["parts"].as_array().into_iter().flatten() {
+        match part {
+            Value::String(text) if !text.trim().is_empty() => parts.push(json!({ "type": "text", "text": text })),
+            Value::Object(_) if part["content_type"] == "image_asset_pointer" => {
+                // "file-service://file-abc" / "sediment://file_abc" -> image file in the archive
+                let file_id = part["asset_pointer"].as_str().and_then(|p| p.split("://").nth(1));
+                match file_id.and_then(&mut *images) {
+                    Some(url) => parts.push(json!({ "type": "image_url", "image_url": { "url": url } })),
+                    None => parts.push(json!({ "type": "text", "text": "[image not included in export]" })),
+                }
+            }
+            _ => {}
+        }
+    }
+    into_content(parts).map(|content| (role.to_string(), content))
+}
+
+/// Map ChatGPT's node tree; dropped nodes are skipped by re-parenting onto the nearest kept ancestor
+fn chatgpt_conversation(conversation: &Value, images: &mut dyn FnMut(&str) -> Option<String>) -> ConversationArchive {
+    let created_at = unix_time(&conversation["create_time"]).unwrap_or_else(|| Utc::now().to_rfc3339());
+    let updated_at = unix_time(&conversation["update_time"]).unwrap_or_else(|| created_at.clone());
+    let mut archive = empty_archive(conversation["title"].as_str().map(str::to_string), created_at.clone(), updated_at);
+    let Some(mapping) = conversation["mapping"].as_object() else { return archive };
+
+    let mut kept: HashMap<&str, (String, Value, String)> = HashMap::new();
+    for (node_id, node) in mapping {
+        if let Some((role, content)) = chatgpt_content(&node["message"], images) {
+            let time = unix_time(&node["message"]["create_time"]).unwrap_or_else(|| created_at.clone());
+            kept.insert(node_id.as_str(), (role, content, time));
+        }
+    }
+
+    let nearest_kept = |start: Option<&str>| -> Option<String> {
+        let mut id = start.map(str::to_string);
+        for _ in 0..=mapping.len() {
+            let current = id?;
+            if kept.contains_key(current.as_str()) {
+                return Some(current);
+            }
+            id = mapping.get(&current).and_then(|n| n["parent"].as_str()).map(str::to_string);
+        }
+        None
+    };
+
+    for (node_id, node) in mapping {
+        let Some((role, content, time)) = kept.get(node_id.as_str()) else { continue };
+        archive.messages.push(ArchiveMessage {
+            id: node_id.clone(),
+            parent_id: nearest_kept(node["parent"].as_str()),
+            role: role.clone(),
+            content: content.clone(),
+            tool_call_history: None,
+            created_at: time.clone(),
+        });
+    }
+    archive.messages.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+    archive.active_leaf_id = nearest_kept(conversation["current_node"].as_str());
+    archive
+}
+
+// ---------- Claude ----------
+
+fn claude_content(message: &Value) -> Option<(String, Value)> {
+    let role = match message["sender"].as_str()? {
+        "human" => "user",
+        "assistant" => "assistant",
+        _ => return None,
+    };
+    let mut parts: Vec<Value> = message["content"].as_array().into_iter().flatten()
+        .filter(|c| c["type"] == "text")
+        .filter_map(|c| c["text"].as_str())
+        .filter(|t| !t.trim().is_empty())
+        .map(|text| json!({ "type": "text", "text": text }))
+        .collect();
+    if parts.is_empty() {
+        if let Some(text) = message["text"].as_str().filter(|t| !t.trim().is_empty()) {
+            parts.push(json!({ "type": "text", "text": text }));
+        }
+    }
+    // Pasted / uploaded documents come with their extracted text
+    for attachment in message["attachments"].as_array().into_iter().flatten() {
+        if let Some(text) = attachment["extracted_content"].as_str().filter(|t| !t.trim().is_empty()) {
+            let name = attachment["file_name"].as_str().unwrap_or("attachment");
+            parts.push(json!({ "type": "text", "text": format!("📎 {}\n\n{}", name, text) }));
+        }
+    }
+    into_content(parts).map(|content| (role.to_string(), content))
+}
+
+/// Claude exports list messages chronologically; newer exports also carry `parent_message_uuid`
+fn claude_conversation(conversation: &Value) -> ConversationArchive {
+    let created_at = iso_time(&conversation["created_at"]).unwrap_or_else(|| Utc::now().to_rfc3339());
+    let updated_at = iso_time(&conversation["updated_at"]).unwrap_or_else(|| created_at.clone());
+    let title = conversation["name"].as_str().filter(|n| !n.is_empty()).map(str::to_string);
+    let mut archive = empty_archive(title, created_at.clone(), updated_at);
+
+    // Every message id seen so far -> the kept message it resolves to (itself, or a dropped one's parent)
+    let mut resolved: HashMap<String, Option<String>> = HashMap::new();
+    let mut previous: Option<String> = None;
+    for message in conversation["chat_messages"].as_array().into_iter().flatten() {
+        let Some(id) = message["uuid"].as_str() else { continue };
+        let parent = match message.get("parent_message_uuid").and_then(Value::as_str) {
+            Some(parent) => resolved.get(parent).cloned().flatten(),
+            None => previous.clone(),
+        };
+        match claude_content(message) {
+            Some((role, content)) => {
+                archive.messages.push(ArchiveMessage {
+                    id: id.to_string(),
+                    parent_id: parent,
+                    role,
+                    content,
+                    tool_call_history: None,
+                    created_at: iso_time(&message["created_at"]).unwrap_or_else(|| created_at.clone()),
+                });
+                resolved.insert(id.to_string(), Some(id.to_string()));
+                previous = Some(id.to_string());
+            }
+            None => {
+                resolved.insert(id.to_string(), parent);
+            }
+        }
+    }
+    archive
+}
+
+/// Add one conversation to the summary; false when it has nothing to import
+fn summarize(summary: &mut ImportSummary, archive: &ConversationArchive, images: usize) -> bool {
+    if archive.messages.is_empty() {
+        summary.skipped += 1;
+        return false;
+    }
+    summary.conversations += 1;
+    summary.messages += archive.messages.len();
+    summary.images += images;
+    let mut parents = HashSet::new();
+    if archive.messages.iter().any(|m| !parents.insert(m.parent_id.clone())) {
+        summary.branched_conversations += 1;
+    }
+    if summary.earliest.as_ref().map_or(true, |t| archive.created_at < *t) {
+        summary.earliest = Some(archive.created_at.clone());
+    }
+    if summary.latest.as_ref().map_or(true, |t| archive.updated_at > *t) {
+        summary.latest = Some(archive.updated_at.clone());
+    }
+    true
+}
+
+/// Import every conversation of a ChatGPT or Claude export under `pet_id`; with `dry_run` only report.
+/// Conversations are mapped and stored one at a time so only one conversation's images are held in
+/// memory; a dry run only looks the images up without reading them.
+pub fn import_export_file(db: &Database, pet_id: &str, path: &Path, dry_run: bool, uploads_dir: &Path) -> Result<ImportSummary, String> {
+    let mut export = ExportFile::open(path)?;
+    let source = detect_source(&export.conversations)
+        .ok_or("Unrecognised export: expected a ChatGPT or Claude conversations.json")?;
+    let mut summary = ImportSummary { source: Some(source), dry_run, ..Default::default() };
+    let conversations = std::mem::take(&mut export.conversations);
+    for conversation in conversations.as_array().into_iter().flatten() {
+        let mut images = 0;
+        let archive = match source {
+            ChatSource::ChatGpt => chatgpt_conversation(conversation, &mut |id| {
+                let url = if dry_run { export.image_name(id) } else { export.image_data_url(id) };
+                if url.is_some() {
+                    images += 1;
+                }
+                url
+            }),
+            ChatSource::Claude => claude_conversation(conversation),
+        };
+        if summarize(&mut summary, &archive, images) && !dry_run {
+            let conversation = conversation_export::import_archive(db, pet_id, archive, uploads_dir)?;
+            summary.imported_ids.push(conversation.id);
+        }
+    }
+    if dry_run {
+        return Ok(summary);
+    }
+    log::info!("[ChatImport] Imported {} conversations ({} messages) from {:?}", summary.conversations, summary.messages, source);
+    Ok(summary)
+}
+
+// ============ Tauri Commands ============
+
+/// Import a ChatGPT / Claude export archive (ZIP or conversations.json) under `pet_id`
+#[tauri::command]
+pub async fn import_chat_archive(
+    app: tauri::AppHandle,
+    db: tauri::State<'_, Arc<Database>>,
+    pet_id: String,
+    path: String,
+    dry_run: Option<bool>,
+) -> Result<ImportSummary, String> {
+    db.get_pet_by_id(&pet_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Pet not found: {}", pet_id))?;
+    let uploads_dir = crate::get_uploads_dir(&app)?;
+    let db = db.inner().clone();
+    tokio::task::spawn_blocking(move || {
+        import_export_file(&db, &pet_id, Path::new(&path), dry_run.unwrap_or(false), &uploads_dir)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    fn chatgpt_export() -> Value {
+        json!([{
+            "title": "Cat names",
+            "create_time": 1700000000.5,
+            "update_time": 1700000300.0,
+            "current_node": "a2",
+            "mapping": {
+                "root": { "id": "root", "message": null, "parent": null, "children": ["sys"] },
+                "sys": { "id": "sys", "parent": "root", "children": ["u1"], "message": {
+                    "author": { "role": "system" }, "content": { "content_type": "text", "parts": [""] } } },
+                "u1": { "id": "u1", "parent": "sys", "children": ["a1", "a2"], "message": {
+                    "author": { "role": "user" }, "create_time": 1700000010.0, "recipient": "all",
+                    "content": { "content_type": "multimodal_text", "parts": [
+                        { "content_type": "image_asset_pointer", "asset_pointer": "file-service://file-abc" },
+                        "Name this cat"
+                    ] } } },
+                "a1": { "id": "a1", "parent": "u1", "children": [], "message": {
+                    "author": { "role": "assistant" }, "create_time": 1700000020.0, "recipient": "all",
+                    "content": { "content_type": "text", "parts": ["Mochi"] } } },
+                "a2": { "id": "a2", "parent": "u1", "children": [], "message": {
+                    "author": { "role": "assistant" }, "create_time": 1700000030.0, "recipient": "all",
+                    "content": { "content_type": "text", "parts": ["Biscuit"] } } }
+            }
+        }])
+    }
+
+    fn claude_export() -> Value {
+        json!([
+            {
+                "uuid": "c1", "name": "Naps", "created_at": "2024-03-01T10:00:00.000000Z", "updated_at": "2024-03-01T10:05:00Z",
+                "chat_messages": [
+                    { "uuid": "m1", "sender": "human", "text": "When do cats nap?", "created_at": "2024-03-01T10:00:01Z",
+                      "content": [{ "type": "text", "text": "When do cats nap?" }],
+                      "attachments": [{ "file_name": "notes.txt", "extracted_content": "my notes" }] },
+                    { "uuid": "m2", "sender": "assistant", "text": "", "created_at": "2024-03-01T10:00:02Z",
+                      "content": [{ "type": "text", "text": "All day." }] }
+                ]
+            },
+            { "uuid": "c2", "name": "", "created_at": "2024-03-02T10:00:00Z", "updated_at": "2024-03-02T10:00:00Z", "chat_messages": [] }
+        ])
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("petgpt-import-{}-{}", uuid::Uuid::new_v4(), name))
+    }
+
+    fn db_with_pet() -> Database {
+        let db = Database::new(PathBuf::from(":memory:")).unwrap();
+        db.conn.lock().unwrap().execute_batch(
+            "INSERT INTO pets (id, name, created_at, updated_at) VALUES ('p', 'Mimi', '', '');",
+        ).unwrap();
+        db
+    }
+
+    #[test]
+    fn chatgpt_zip_keeps_branches_times_and_images() {
+        let zip_path = temp_path("chatgpt.zip");
+        {
+            let mut zip = zip::ZipWriter::new(File::create(&zip_path).unwrap());
+            let options = zip::write::SimpleFileOptions::default();
+            zip.start_file("conversations.json", options).unwrap();
+            zip.write_all(chatgpt_export().to_string().as_bytes()).unwrap();
+            zip.start_file("file-abc-cat.png", options).unwrap();
+            zip.write_all(b"png").unwrap();
+            zip.finish().unwrap();
+        }
+        let uploads = temp_path("uploads");
+        std::fs::create_dir_all(&uploads).unwrap();
+        let db = db_with_pet();
+
+        let dry = import_export_file(&db, "p", &zip_path, true, &uploads).unwrap();
+        assert_eq!(dry.source, Some(ChatSource::ChatGpt));
+        assert_eq!((dry.conversations, dry.messages, dry.branched_conversations, dry.images), (1, 3, 1, 1));
+        assert!(dry.imported_ids.is_empty());
+        assert!(db.get_conversations_by_pet("p").unwrap().is_empty());
+
+        assert_eq!(std::fs::read_dir(&uploads).unwrap().count(), 0);
+
+        let summary = import_export_file(&db, "p", &zip_path, false, &uploads).unwrap();
+        assert_eq!(summary.images, 1);
+        let id = &summary.imported_ids[0];
+        let conversation = db.get_conversation_by_id(id).unwrap().unwrap();
+        assert_eq!(conversation.title.as_deref(), Some("Cat names"));
+        assert!(conversation.created_at.starts_with("2023-11-14T22:13:20.5"));
+
+        let path = db.get_messages_by_conversation(id).unwrap();
+        assert_eq!(path.len(), 2);
+        assert_eq!(path[0].parent_id, None);
+        assert_eq!(path[1].content, "Biscuit");
+        let parts: Vec<Value> = serde_json::from_str(&path[0].content).unwrap();
+        assert_eq!(std::fs::read(parts[0]["image_url"]["url"].as_str().unwrap()).unwrap(), b"png");
+        assert_eq!(db.get_message_branches(id).unwrap()[0].message_ids.len(), 2);
+
+        let _ = std::fs::remove_file(&zip_path);
+        let _ = std::fs::remove_dir_all(&uploads);
+    }
+
+    #[test]
+    fn claude_json_is_imported_and_searchable() {
+        let json_path = temp_path("conversations.json");
+        std::fs::write(&json_path, claude_export().to_string()).unwrap();
+        let db = db_with_pet();
+
+        let summary = import_export_file(&db, "p", &json_path, false, &std::env::temp_dir()).unwrap();
+        assert_eq!(summary.source, Some(ChatSource::Claude));
+        assert_eq!((summary.conversations, summary.messages, summary.skipped), (1, 2, 1));
+        assert_eq!(summary.earliest.as_deref(), Some("2024-03-01T10:00:00+00:00"));
+
+        let path = db.get_messages_by_conversation(&summary.imported_ids[0]).unwrap();
+        assert_eq!(path[0].role, "user");
+        assert_eq!(path[0].content, "When do cats nap?\n\n📎 notes.txt\n\nmy notes");
+        assert_eq!(path[1].parent_id.as_deref(), Some(path[0].id.as_str()));
+        assert_eq!(path[1].created_at, "2024-03-01T10:00:02+00:00");
+        assert!(!db.search_conversations("All day").unwrap().is_empty());
+
+        let _ = std::fs::remove_file(&json_path);
+    }
+}
@@ -0,0 +1,583 @@
+//! Conversation export / import.
+//!
+//! A conversation renders to Markdown, to a standalone HTML page with uploaded images inlined as
+//! data URLs, or to a lossless JSON archive that keeps every branch and `tool_call_history`.
+//! `import_conversation` recreates a JSON archive under a chosen pet.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use uuid::Uuid;
+use crate::database::Database;
+use crate::database::conversations::{Conversation, CreateConversationData};
+use crate::database::messages::Message;
+
+pub const ARCHIVE_FORMAT: &str = "petgpt.conversation";
+pub const ARCHIVE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Markdown,
+    Html,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "md",
+            ExportFormat::Html => "html",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+/// Lossless JSON export of one conversation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationArchive {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: String,
+    pub title: Option<String>,
+    #[serde(default)]
+    pub pet_name: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+    /// Last message of the branch shown when exporting
+    #[serde(default)]
+    pub active_leaf_id: Option<String>,
+    /// Every message across all branches, oldest first
+    pub messages: Vec<ArchiveMessage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveMessage {
+    pub id: String,
+    #[serde(default)]
+    pub parent_id: Option<String>,
+    pub role: String,
+    /// A plain string or an array of content parts
+    pub content: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_history: Option<Value>,
+    pub created_at: String,
+}
+
+impl ArchiveMessage {
+    fn from_db(message: &Message) -> Self {
+        // The database stores multimodal content and tool calls as JSON strings
+        let content = Some(&message.content)
+            .filter(|c| c.starts_with('['))
+            .and_then(|c| serde_json::from_str::<Vec<Value>>(c).ok())
+            .map(Value::Array)
+            .unwrap_or_else(|| Value::String(message.content.clone()));
+        let tool_call_history = message.tool_call_history.as_deref()
+            .map(|h| serde_json::from_str(h).unwrap_or_else(|_| Value::String(h.to_string())));
+        Self {
+            id: message.id.clone(),
+            parent_id: message.parent_id.clone(),
+            role: message.role.clone(),
+            content,
+            tool_call_history,
+            created_at: message.created_at.clone(),
+        }
+    }
+
+    fn to_db(&self, id: String, conversation_id: &str, parent_id: Option<String>) -> Message {
+        let as_string = |value: &Value| match value {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+        Message {
+            id,
+            conversation_id: conversation_id.to_string(),
+            parent_id,
+            role: self.role.clone(),
+            content: as_string(&self.content),
+            tool_call_history: self.tool_call_history.as_ref().map(as_string),
+            created_at: self.created_at.clone(),
+        }
+    }
+
+    fn parts(&self) -> Vec<Value> {
+        match &self.content {
+            Value::Array(parts) => parts.clone(),
+            Value::String(text) => vec![serde_json::json!({ "type": "text", "text": text })],
+            other => vec![serde_json::json!({ "type": "text", "text": other.to_string() })],
+        }
+    }
+
+    fn tool_calls(&self) -> Vec<Value> {
+        match &self.tool_call_history {
+            Some(Value::Array(calls)) => calls.clone(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+pub fn build_archive(conversation: &Conversation, pet_name: Option<String>, tree: &[Message], active_path: &[Message]) -> ConversationArchive {
+    ConversationArchive {
+        format: ARCHIVE_FORMAT.to_string(),
+        version: ARCHIVE_VERSION,
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        title: conversation.title.clone(),
+        pet_name,
+        created_at: conversation.created_at.clone(),
+        updated_at: conversation.updated_at.clone(),
+        active_leaf_id: active_path.last().map(|m| m.id.clone()),
+        messages: tree.iter().map(ArchiveMessage::from_db).collect(),
+    }
+}
+
+/// Messages of the exported branch, root first
+fn active_path(archive: &ConversationArchive) -> Vec<&ArchiveMessage> {
+    let by_id: HashMap<&str, &ArchiveMessage> = archive.messages.iter().map(|m| (m.id.as_str(), m)).collect();
+    let mut current = archive.active_leaf_id.as_deref()
+        .and_then(|id| by_id.get(id).copied())
+        .or_else(|| archive.messages.last());
+    let mut path = Vec::new();
+    while let Some(message) = current {
+        if path.len() > archive.messages.len() {
+            break;
+        }
+        path.push(message);
+        current = message.parent_id.as_deref().and_then(|id| by_id.get(id).copied());
+    }
+    path.reverse();
+    path
+}
+
+/// Rewrite image part URLs in place; `f` returns the replacement, or None to keep the URL
+fn rewrite_image_urls(messages: &mut [ArchiveMessage], mut f: impl FnMut(&str) -> Option<String>) {
+    for message in messages {
+        let Value::Array(parts) = &mut message.content else { continue };
+        for part in parts.iter_mut().filter(|p| p["type"] == "image_url") {
+            let replacement = part.get("image_url")
+                .and_then(|i| i.get("url"))
+                .and_then(Value::as_str)
+                .and_then(&mut f);
+            if let Some(url) = replacement {
+                part["image_url"]["url"] = Value::String(url);
+            }
+        }
+    }
+}
+
+/// Turn a local upload into a data URL. Like the chat view, paths are looked up by file name in
+/// the uploads dir first so exports from another machine's paths still resolve. Anything that
+/// doesn't end up inside the uploads dir is left alone, so an imported archive can't name
+/// arbitrary local files to be inlined by the next export.
+fn inline_upload(url: &str, uploads_dir: &Path) -> Option<String> {
+    if url.starts_with("data:") || url.starts_with("http://") || url.starts_with("https://") {
+        return None;
+    }
+    let path = Path::new(url.strip_prefix("file://").unwrap_or(url));
+    let file = path.file_name()
+        .map(|name| uploads_dir.join(name))
+        .filter(|p| p.is_file())
+        .unwrap_or_else(|| path.to_path_buf())
+        .canonicalize()
+        .ok()?;
+    if !file.starts_with(uploads_dir.canonicalize().ok()?) {
+        return None;
+    }
+    let data = std::fs::read(&file).ok()?;
+    let mime_type = match file.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref() {
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("bmp") => "image/bmp",
+        Some("svg") => "image/svg+xml",
+        _ => "image/png",
+    };
+    Some(format!("data:{};base64,{}", mime_type, BASE64.encode(data)))
+}
+
+/// Write a base64 data URL into the uploads dir and return the file path
+fn save_data_url(url: &str, uploads_dir: &Path) -> Option<String> {
+    let (meta, data) = url.strip_prefix("data:")?.split_once(',')?;
+    let mime_type = meta.strip_suffix(";base64")?;
+    let bytes = BASE64.decode(data).ok()?;
+    let extension = crate::mcp::resources::extension_for_mime(mime_type).unwrap_or("bin");
+    let file_path = uploads_dir.join(format!("{}.{}", Uuid::new_v4(), extension));
+    std::fs::write(&file_path, bytes).ok()?;
+    Some(file_path.to_string_lossy().to_string())
+}
+
+fn speaker<'a>(role: &str, pet_name: Option<&'a str>) -> &'a str {
+    match role {
+        "user" => "User",
+        "system" => "System",
+        _ => pet_name.unwrap_or("Assistant"),
+    }
+}
+
+fn display_time(timestamp: &str) -> String {
+    chrono::DateTime::parse_from_rfc3339(timestamp)
+        .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|_| timestamp.to_string())
+}
+
+fn title_of(archive: &ConversationArchive) -> &str {
+    archive.title.as_deref().filter(|t| !t.trim().is_empty()).unwrap_or("Untitled conversation")
+}
+
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => serde_json::to_string_pretty(other).unwrap_or_default(),
+    }
+}
+
+/// Fenced code block whose fence is longer than any backtick run inside `body`
+fn fenced(body: &str, language: &str) -> String {
+    let longest = body.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest.max(2) + 1);
+    format!("{fence}{language}\n{body}\n{fence}")
+}
+
+pub fn render_markdown(archive: &ConversationArchive) -> String {
+    let mut out = format!("# {}\n\n", title_of(archive));
+    if let Some(pet_name) = &archive.pet_name {
+        out.push_str(&format!("> {} · {}\n\n", pet_name, display_time(&archive.created_at)));
+    }
+
+    for message in active_path(archive) {
+        out.push_str(&format!(
+            "---\n\n**{}** · {}\n\n",
+            speaker(&message.role, archive.pet_name.as_deref()),
+            display_time(&message.created_at)
+        ));
+        for part in message.parts() {
+            match part["type"].as_str() {
+                Some("text") => out.push_str(part["text"].as_str().unwrap_or_default()),
+                Some("image_url") => out.push_str(&format!("![image]({})", part["image_url"]["url"].as_str().unwrap_or_default())),
+                Some("file_url") => {
+                    let url = part["file_url"]["url"].as_str().unwrap_or_default();
+                    let name = part["file_url"]["name"].as_str().unwrap_or(url);
+                    out.push_str(&format!("[📎 {}]({})", name, url));
+                }
+                _ => continue,
+            }
+            out.push_str("\n\n");
+        }
+        for call in message.tool_calls() {
+            out.push_str(&format!(
+                "<details><summary>🔧 {}</summary>\n\n{}\n\n{}\n\n</details>\n\n",
+                call["name"].as_str().unwrap_or("tool"),
+                fenced(&value_text(&call["arguments"]), "json"),
+                fenced(&value_text(&call["result"]), "")
+            ));
+        }
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const HTML_STYLE: &str = "body{font-family:-apple-system,BlinkMacSystemFont,'Segoe UI',sans-serif;max-width:760px;margin:2em auto;padding:0 1em;color:#222;background:#fafafa}\
+h1{font-size:1.4em}.meta{color:#888;font-size:.85em}\
+.message{margin:1em 0;padding:.8em 1em;border-radius:12px;background:#fff;box-shadow:0 1px 2px rgba(0,0,0,.08)}\
+.message.user{background:#e8f0fe}.message.system{background:#f3f3f3;font-style:italic}\
+.who{font-weight:600;margin-bottom:.4em}.who span{font-weight:normal;color:#999;font-size:.8em;margin-left:.5em}\
+.text{white-space:pre-wrap;word-wrap:break-word}img{max-width:100%;border-radius:8px;margin:.4em 0}\
+details{margin-top:.5em;font-size:.9em}pre{background:#f5f5f5;padding:.6em;overflow-x:auto;white-space:pre-wrap}";
+
+/// Standalone page; images should already be inlined as data URLs
+pub fn render_html(archive: &ConversationArchive) -> String {
+    let title = escape_html(title_of(archive));
+    let mut body = String::new();
+    for message in active_path(archive) {
+        body.push_str(&format!(
+            "<div class=\"message {}\"><div class=\"who\">{}<span>{}</span></div>\n",
+            escape_html(&message.role),
+            escape_html(speaker(&message.role, archive.pet_name.as_deref())),
+            escape_html(&display_time(&message.created_at))
+        ));
+        for part in message.parts() {
+            match part["type"].as_str() {
+                Some("text") => body.push_str(&format!("<div class=\"text\">{}</div>\n", escape_html(part["text"].as_str().unwrap_or_default()))),
+                Some("image_url") => body.push_str(&format!("<img src=\"{}\" alt=\"image\">\n", escape_html(part["image_url"]["url"].as_str().unwrap_or_default()))),
+                Some("file_url") => {
+                    let url = part["file_url"]["url"].as_str().unwrap_or_default();
+                    let name = part["file_url"]["name"].as_str().unwrap_or(url);
+                    body.push_str(&format!("<div>📎 {}</div>\n", escape_html(name)));
+                }
+                _ => {}
+            }
+        }
+        for call in message.tool_calls() {
+            body.push_str(&format!(
+                "<details><summary>🔧 {}</summary><pre>{}</pre><pre>{}</pre></details>\n",
+                escape_html(call["name"].as_str().unwrap_or("tool")),
+                escape_html(&value_text(&call["arguments"])),
+                escape_html(&value_text(&call["result"]))
+            ));
+        }
+        body.push_str("</div>\n");
+    }
+    let meta = archive.pet_name.as_deref()
+        .map(|name| format!("<p class=\"meta\">{} · {}</p>\n", escape_html(name), escape_html(&display_time(&archive.created_at))))
+        .unwrap_or_default();
+    format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{title}</title><style>{HTML_STYLE}</style></head>\n<body>\n<h1>{title}</h1>\n{meta}{body}</body></html>\n"
+    )
+}
+
+fn load_archive(db: &Database, conversation_id: &str, uploads_dir: Option<&Path>) -> Result<ConversationArchive, String> {
+    let conversation = db.get_conversation_by_id(conversation_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Conversation not found: {}", conversation_id))?;
+    let pet_name = db.get_pet_by_id(&conversation.pet_id).map_err(|e| e.to_string())?.map(|p| p.name);
+    let tree = db.get_message_tree(conversation_id).map_err(|e| e.to_string())?;
+    let active = db.get_messages_by_conversation(conversation_id).map_err(|e| e.to_string())?;
+    let mut archive = build_archive(&conversation, pet_name, &tree, &active);
+    if let Some(uploads_dir) = uploads_dir {
+        rewrite_image_urls(&mut archive.messages, |url| inline_upload(url, uploads_dir));
+    }
+    Ok(archive)
+}
+
+fn render(db: &Database, conversation_id: &str, format: ExportFormat, uploads_dir: &Path) -> Result<String, String> {
+    // Markdown keeps local paths; HTML and JSON carry their images so they can be shared
+    let inline = (format != ExportFormat::Markdown).then_some(uploads_dir);
+    let archive = load_archive(db, conversation_id, inline)?;
+    Ok(match format {
+        ExportFormat::Markdown => render_markdown(&archive),
+        ExportFormat::Html => render_html(&archive),
+        ExportFormat::Json => serde_json::to_string_pretty(&archive).map_err(|e| e.to_string())?,
+    })
+}
+
+/// File name for a bulk export: title slug plus a short id so duplicates don't collide
+fn export_file_name(conversation: &Conversation, format: ExportFormat) -> String {
+    let slug: String = conversation.title.as_deref().unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect::<String>()
+        .split('-')
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+        .chars()
+        .take(60)
+        .collect();
+    let slug = if slug.is_empty() { "conversation".to_string() } else { slug };
+    let short_id: String = conversation.id.chars().take(8).collect();
+    format!("{}-{}.{}", slug, short_id, format.extension())
+}
+
+/// Recreate an archive as a new conversation under `pet_id`, keeping branches and timestamps.
+/// Image parts carrying data URLs are written to `uploads_dir`.
+pub fn import_archive(db: &Database, pet_id: &str, mut archive: ConversationArchive, uploads_dir: &Path) -> Result<Conversation, String> {
+    if archive.format != ARCHIVE_FORMAT {
+        return Err(format!("Not a conversation export (format: {})", archive.format));
+    }
+    if archive.version > ARCHIVE_VERSION {
+        return Err(format!("Unsupported export version {}", archive.version));
+    }
+    rewrite_image_urls(&mut archive.messages, |url| save_data_url(url, uploads_dir));
+
+    let conversation = db.create_conversation(CreateConversationData {
+        pet_id: pet_id.to_string(),
+        title: archive.title.clone(),
+    }).map_err(|e| e.to_string())?;
+    db.set_conversation_timestamps(&conversation.id, &archive.created_at, &archive.updated_at)
+        .map_err(|e| e.to_string())?;
+
+    // Insert parents before children; a parent missing from the archive makes a root
+    let known: std::collections::HashSet<&str> = archive.messages.iter().map(|m| m.id.as_str()).collect();
+    let mut new_ids: HashMap<&str, String> = HashMap::new();
+    let mut pending: Vec<&ArchiveMessage> = archive.messages.iter().collect();
+    while !pending.is_empty() {
+        let mut waiting = Vec::new();
+        for message in &pending {
+            let parent = match message.parent_id.as_deref() {
+                Some(parent) if known.contains(parent) => match new_ids.get(parent) {
+                    Some(new_parent) => Some(new_parent.clone()),
+                    None => {
+                        waiting.push(*message);
+                        continue;
+                    }
+                },
+                _ => None,
+            };
+            let id = Uuid::new_v4().to_string();
+            db.insert_message(&message.to_db(id.clone(), &conversation.id, parent)).map_err(|e| e.to_string())?;
+            new_ids.insert(message.id.as_str(), id);
+        }
+        if waiting.len() == pending.len() {
+            return Err("Export has a cycle in its message parents".to_string());
+        }
+        pending = waiting;
+    }
+
+    let leaf = archive.active_leaf_id.as_deref()
+        .and_then(|id| new_ids.get(id))
+        .or_else(|| archive.messages.last().and_then(|m| new_ids.get(m.id.as_str())));
+    if let Some(leaf) = leaf {
+        db.switch_message_branch(leaf).map_err(|e| e.to_string())?;
+    }
+
+    db.get_conversation_by_id(&conversation.id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Imported conversation disappeared".to_string())
+}
+
+// ============ Tauri Commands ============
+
+/// Export one conversation to `output_path`; returns the written path
+#[tauri::command]
+pub fn export_conversation(
+    app: tauri::AppHandle,
+    db: tauri::State<'_, Arc<Database>>,
+    id: String,
+    format: ExportFormat,
+    output_path: String,
+) -> Result<String, String> {
+    let uploads_dir = crate::get_uploads_dir(&app)?;
+    let rendered = render(&db, &id, format, &uploads_dir)?;
+    std::fs::write(&output_path, rendered).map_err(|e| format!("Failed to write export: {}", e))?;
+    Ok(output_path)
+}
+
+/// Export every conversation of a pet into `output_dir`, one file each; returns the written paths
+#[tauri::command]
+pub fn export_pet_conversations(
+    app: tauri::AppHandle,
+    db: tauri::State<'_, Arc<Database>>,
+    pet_id: String,
+    format: ExportFormat,
+    output_dir: String,
+) -> Result<Vec<String>, String> {
+    let uploads_dir = crate::get_uploads_dir(&app)?;
+    let output_dir = PathBuf::from(output_dir);
+    std::fs::create_dir_all(&output_dir).map_err(|e| format!("Failed to create export dir: {}", e))?;
+    let conversations = db.get_conversations_by_pet(&pet_id).map_err(|e| e.to_string())?;
+    let mut written = Vec::with_capacity(conversations.len());
+    for conversation in &conversations {
+        let rendered = render(&db, &conversation.id, format, &uploads_dir)?;
+        let path = output_dir.join(export_file_name(conversation, format));
+        std::fs::write(&path, rendered).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        written.push(path.to_string_lossy().to_string());
+    }
+    Ok(written)
+}
+
+/// Import a JSON export as a new conversation under `pet_id`
+#[tauri::command]
+pub fn import_conversation(
+    app: tauri::AppHandle,
+    db: tauri::State<'_, Arc<Database>>,
+    pet_id: String,
+    path: String,
+) -> Result<Conversation, String> {
+    db.get_pet_by_id(&pet_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Pet not found: {}", pet_id))?;
+    let json = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let archive: ConversationArchive = serde_json::from_str(&json).map_err(|e| format!("Invalid conversation export: {}", e))?;
+    import_archive(&db, &pet_id, archive, &crate::get_uploads_dir(&app)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::messages::CreateMessageData;
+
+    fn seeded_db() -> (Database, Conversation) {
+        let db = Database::new(PathBuf::from(":memory:")).unwrap();
+        db.conn.lock().unwrap().execute_batch(
+            "INSERT INTO pets (id, name, created_at, updated_at) VALUES ('p', 'Mimi', '', '');",
+        ).unwrap();
+        let conversation = db.create_conversation(CreateConversationData { pet_id: "p".to_string(), title: Some("Nap <plans>".to_string()) }).unwrap();
+        let say = |role: &str, content: &str, tools: Option<&str>| db.create_message(CreateMessageData {
+            conversation_id: conversation.id.clone(),
+            role: role.to_string(),
+            content: content.to_string(),
+            tool_call_history: tools.map(str::to_string),
+            parent_id: None,
+        }).unwrap();
+        say("user", r#"[{"type":"text","text":"Look"},{"type":"image_url","image_url":{"url":"/elsewhere/cat.png"}}]"#, None);
+        let reply = say("assistant", "A sleepy cat", Some(r#"[{"name":"vision","arguments":{"q":1},"result":"cat"}]"#));
+        db.rewind_to_parent(&reply.id).unwrap();
+        say("assistant", "A very sleepy cat", None);
+        (db, conversation)
+    }
+
+    #[test]
+    fn renders_the_active_branch() {
+        let (db, conversation) = seeded_db();
+        let archive = load_archive(&db, &conversation.id, None).unwrap();
+        assert_eq!(archive.messages.len(), 3);
+
+        let markdown = render_markdown(&archive);
+        assert!(markdown.starts_with("# Nap <plans>\n"));
+        assert!(markdown.contains("![image](/elsewhere/cat.png)"));
+        assert!(markdown.contains("**Mimi**"));
+        assert!(markdown.contains("A very sleepy cat"));
+        assert!(!markdown.contains("A sleepy cat"));
+
+        let html = render_html(&archive);
+        assert!(html.contains("<title>Nap &lt;plans&gt;</title>"));
+        assert!(html.contains("A very sleepy cat"));
+    }
+
+    #[test]
+    fn json_round_trip_keeps_branches_tools_and_images() {
+        let (db, conversation) = seeded_db();
+        let uploads = std::env::temp_dir().join(format!("petgpt-export-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&uploads).unwrap();
+        std::fs::write(uploads.join("cat.png"), b"png-bytes").unwrap();
+
+        let json = render(&db, &conversation.id, ExportFormat::Json, &uploads).unwrap();
+        assert!(json.contains(&format!("data:image/png;base64,{}", BASE64.encode(b"png-bytes"))));
+
+        let archive: ConversationArchive = serde_json::from_str(&json).unwrap();
+        let imported = import_archive(&db, "p", archive.clone(), &uploads).unwrap();
+        // A second import right away must not overwrite the first one's images
+        let again = import_archive(&db, "p", archive, &uploads).unwrap();
+        let first_image = |id: &str| -> String {
+            let parts: Vec<Value> = serde_json::from_str(&db.get_message_tree(id).unwrap()[0].content).unwrap();
+            parts[1]["image_url"]["url"].as_str().unwrap().to_string()
+        };
+        assert_ne!(first_image(&imported.id), first_image(&again.id));
+        assert_ne!(imported.id, conversation.id);
+        assert_eq!(imported.message_count, 3);
+
+        let path = db.get_messages_by_conversation(&imported.id).unwrap();
+        assert_eq!(path.last().map(|m| m.content.as_str()), Some("A very sleepy cat"));
+        let tree = db.get_message_tree(&imported.id).unwrap();
+        let original = db.get_message_tree(&conversation.id).unwrap();
+        assert_eq!(tree[1].tool_call_history.as_deref().map(|h| serde_json::from_str::<Value>(h).unwrap()),
+            original[1].tool_call_history.as_deref().map(|h| serde_json::from_str::<Value>(h).unwrap()));
+        assert_eq!(tree[1].created_at, original[1].created_at);
+
+        // The inlined image went back into the uploads dir
+        let parts: Vec<Value> = serde_json::from_str(&tree[0].content).unwrap();
+        let url = parts[1]["image_url"]["url"].as_str().unwrap();
+        assert!(url.starts_with(uploads.to_str().unwrap()));
+        assert_eq!(std::fs::read(url).unwrap(), b"png-bytes");
+
+        // Files outside the uploads dir are never inlined
+        let outside = std::env::temp_dir().join(format!("petgpt-secret-{}.png", Uuid::new_v4()));
+        std::fs::write(&outside, b"secret").unwrap();
+        assert_eq!(inline_upload(outside.to_str().unwrap(), &uploads), None);
+        let escaping = uploads.join("..").join(outside.file_name().unwrap());
+        assert_eq!(inline_upload(escaping.to_str().unwrap(), &uploads), None);
+        let _ = std::fs::remove_file(&outside);
+        let _ = std::fs::remove_dir_all(&uploads);
+    }
+}
@@ -1 +1,3 @@
 pub mod training_export;
+pub mod conversation_export;
+pub mod chat_import;
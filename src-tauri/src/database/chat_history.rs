@@ -374,4 +374,30 @@ impl Database {
 
         Ok(ChatContextResult { before: before_msgs, anchor: anchor_msg, after: after_msgs })
     }
+
+    /// 某个发送者排在 (since_ts 毫秒, since_id) 之后的消息，按 (timestamp, message_id) 正序；
+    /// 同一毫秒的多条消息靠 message_id 区分
+    pub fn chat_messages_from_sender(&self, sender_id: &str, since_ts: i64, since_id: &str, limit: i64) -> Result<Vec<ChatMessage>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT message_id, target_id, target_type, sender_id, content, timestamp, reply_to_id, is_bot, raw_json
+             FROM chat_history
+             WHERE sender_id = ?1 AND (timestamp > ?2 OR (timestamp = ?2 AND message_id > ?3))
+             ORDER BY timestamp ASC, message_id ASC LIMIT ?4",
+        )?;
+        let rows = stmt.query_map(params![sender_id, since_ts, since_id, limit], |row| {
+            Ok(ChatMessage {
+                message_id: row.get(0)?,
+                target_id: row.get(1)?,
+                target_type: row.get(2)?,
+                sender_id: row.get(3)?,
+                content: row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+                timestamp: row.get(5)?,
+                reply_to_id: row.get(6)?,
+                is_bot: row.get::<_, i64>(7)? != 0,
+                raw_json: row.get(8)?,
+            })
+        })?;
+        rows.collect()
+    }
 }
@@ -1,4 +1,4 @@
-use rusqlite::{params, Result};
+use rusqlite::{params, OptionalExtension, Result};
 use serde::{Deserialize, Serialize};
 use chrono::Utc;
 use uuid::Uuid;
@@ -38,6 +38,15 @@ pub struct CreateConversationData {
     pub title: Option<String>,
 }
 
+/// Rolling summary of a conversation's older messages
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationSummary {
+    pub summary: String,
+    /// Last message folded into the summary
+    pub until_message_id: String,
+}
+
 /// Conversation + its messages in a single response
 #[derive(Debug, Serialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -176,10 +185,75 @@ impl Database {
         Ok(rows > 0)
     }
 
+    pub fn get_conversation_summary(&self, id: &str) -> Result<Option<ConversationSummary>> {
+        let conn = self.conn.lock().unwrap();
+        let row: Option<(Option<String>, Option<String>)> = conn.query_row(
+            "SELECT summary, summary_until_id FROM conversations WHERE id = ?",
+            params![id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).optional()?;
+        Ok(match row {
+            Some((Some(summary), Some(until_message_id))) => Some(ConversationSummary { summary, until_message_id }),
+            _ => None,
+        })
+    }
+
+    pub fn set_conversation_summary(&self, id: &str, summary: &str, until_message_id: &str) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let rows = conn.execute(
+            "UPDATE conversations SET summary = ?, summary_until_id = ? WHERE id = ?",
+            params![summary, until_message_id, id],
+        )?;
+        Ok(rows > 0)
+    }
+
+    /// The conversation's own memory switch; None until it has been set
+    pub fn get_conversation_memory_enabled(&self, id: &str) -> Result<Option<bool>> {
+        let conn = self.conn.lock().unwrap();
+        let enabled: Option<Option<bool>> = conn.query_row(
+            "SELECT memory_enabled FROM conversations WHERE id = ?",
+            params![id],
+            |row| row.get(0),
+        ).optional()?;
+        Ok(enabled.flatten())
+    }
+
+    pub fn set_conversation_memory_enabled(&self, id: &str, enabled: bool) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let rows = conn.execute(
+            "UPDATE conversations SET memory_enabled = ? WHERE id = ?",
+            params![enabled, id],
+        )?;
+        Ok(rows > 0)
+    }
+
+    /// Whether memory is on: the conversation's own switch, else the global `memoryEnabledByDefault`
+    pub fn is_memory_enabled(&self, conversation_id: Option<&str>) -> Result<bool> {
+        let own = match conversation_id {
+            Some(id) => self.get_conversation_memory_enabled(id)?,
+            None => None,
+        };
+        match own {
+            Some(enabled) => Ok(enabled),
+            None => Ok(!matches!(self.get_setting("memoryEnabledByDefault")?.as_deref(), Some("false"))),
+        }
+    }
+
+    /// Overwrite both timestamps; used when importing conversations from elsewhere
+    pub fn set_conversation_timestamps(&self, id: &str, created_at: &str, updated_at: &str) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let rows = conn.execute(
+            "UPDATE conversations SET created_at = ?, updated_at = ? WHERE id = ?",
+            params![created_at, updated_at, id],
+        )?;
+        Ok(rows > 0)
+    }
+
     pub fn delete_conversation(&self, id: &str) -> Result<bool> {
         let conn = self.conn.lock().unwrap();
-        // Delete messages first
+        // Delete messages and any open tab first
         conn.execute("DELETE FROM messages WHERE conversation_id = ?", params![id])?;
+        conn.execute("DELETE FROM open_tabs WHERE conversation_id = ?", params![id])?;
         // Delete conversation
         let rows = conn.execute("DELETE FROM conversations WHERE id = ?", params![id])?;
         Ok(rows > 0)
@@ -196,6 +270,7 @@ impl Database {
         // Delete messages for each conversation
         for conv_id in &conv_ids {
             conn.execute("DELETE FROM messages WHERE conversation_id = ?", params![conv_id])?;
+            conn.execute("DELETE FROM open_tabs WHERE conversation_id = ?", params![conv_id])?;
         }
         
         // Delete all conversations
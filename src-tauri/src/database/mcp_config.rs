@@ -0,0 +1,434 @@
+// Import/export of the `mcpServers` JSON config format shared by other MCP hosts:
+//
+// {
+//   "mcpServers": {
+//     "filesystem": { "command": "npx", "args": ["-y", "..."], "env": { "KEY": "value" } },
+//     "remote": { "type": "http", "url": "https://...", "headers": { "Authorization": "Bearer ..." } }
+//   }
+// }
+//
+// A top-level "servers" key (VS Code) is accepted as well.
+
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde::{Deserialize, Serialize};
+use chrono::Utc;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use super::Database;
+use super::mcp_servers::{insert_mcp_server, CreateMcpServerData, McpServer, TransportType};
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct McpConfigFile {
+    #[serde(default, alias = "servers")]
+    mcp_servers: BTreeMap<String, McpConfigEntry>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct McpConfigEntry {
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    transport: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    command: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    args: Vec<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    env: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    headers: HashMap<String, String>,
+}
+
+/// One server read from a config file, mapped onto our columns
+#[derive(Debug, Clone)]
+pub struct ImportedMcpServer {
+    pub name: String,
+    pub transport: TransportType,
+    pub command: String,
+    pub args: Vec<String>,
+    pub env: HashMap<String, String>,
+    pub url: Option<String>,
+    pub api_key: Option<String>,
+    pub warnings: Vec<String>,
+    /// Set when the entry cannot be imported at all
+    pub error: Option<String>,
+}
+
+/// What to do when an imported name is already taken (the `name` column is UNIQUE)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum McpImportConflict {
+    #[default]
+    Skip,
+    /// Import under "name (2)", "name (3)", ...
+    Rename,
+    /// Replace the transport settings of the existing server, keeping its id and UI settings
+    Overwrite,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum McpImportAction {
+    Create,
+    Rename,
+    Overwrite,
+    Skip,
+    Invalid,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpImportItem {
+    /// Key in the config file
+    pub source_name: String,
+    /// Name the server has (or would have) after import
+    pub name: String,
+    pub transport: TransportType,
+    pub action: McpImportAction,
+    /// Created or overwritten server (None for dry runs, skips and invalid entries)
+    pub server_id: Option<String>,
+    pub warnings: Vec<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpImportReport {
+    pub dry_run: bool,
+    pub items: Vec<McpImportItem>,
+}
+
+/// Parse an `mcpServers` config file
+pub fn parse_mcp_config(json: &str) -> std::result::Result<Vec<ImportedMcpServer>, String> {
+    let file: McpConfigFile = serde_json::from_str(json).map_err(|e| format!("Invalid MCP config: {}", e))?;
+    if file.mcp_servers.is_empty() {
+        return Err("No servers found (expected an \"mcpServers\" object)".to_string());
+    }
+    Ok(file.mcp_servers.into_iter().map(|(name, entry)| imported_server(name, entry)).collect())
+}
+
+fn imported_server(name: String, entry: McpConfigEntry) -> ImportedMcpServer {
+    let mut warnings = Vec::new();
+    let mut error = None;
+
+    let transport = match entry.transport.as_deref().map(str::to_lowercase).as_deref() {
+        Some("stdio") => TransportType::Stdio,
+        Some("http") | Some("streamable-http") | Some("streamablehttp") => TransportType::Http,
+        Some("sse") => {
+            warnings.push("Legacy SSE transport; connecting with Streamable HTTP instead".to_string());
+            TransportType::Http
+        }
+        Some(other) => {
+            warnings.push(format!("Unknown type \"{}\", inferred from the other fields", other));
+            if entry.url.is_some() { TransportType::Http } else { TransportType::Stdio }
+        }
+        None if entry.command.is_none() && entry.url.is_some() => TransportType::Http,
+        None => TransportType::Stdio,
+    };
+
+    let mut api_key = None;
+    match transport {
+        TransportType::Stdio => {
+            if !entry.command.as_deref().is_some_and(|c| !c.trim().is_empty()) {
+                error = Some("Missing \"command\"".to_string());
+            }
+            if !entry.headers.is_empty() {
+                warnings.push("Headers are ignored for stdio servers".to_string());
+            }
+        }
+        TransportType::Http => {
+            if !entry.url.as_deref().is_some_and(|u| !u.trim().is_empty()) {
+                error = Some("Missing \"url\"".to_string());
+            }
+            let mut header_names: Vec<&String> = entry.headers.keys().collect();
+            header_names.sort();
+            for header in header_names {
+                let value = &entry.headers[header];
+                let bearer = value.strip_prefix("Bearer ").or_else(|| value.strip_prefix("bearer "));
+                match bearer {
+                    Some(token) if header.eq_ignore_ascii_case("authorization") => {
+                        api_key = Some(token.trim().to_string());
+                    }
+                    _ => warnings.push(format!("Header \"{}\" is not supported and was skipped", header)),
+                }
+            }
+            if !entry.args.is_empty() || !entry.env.is_empty() {
+                warnings.push("args/env are ignored for HTTP servers".to_string());
+            }
+        }
+    }
+
+    ImportedMcpServer {
+        name: name.trim().to_string(),
+        transport,
+        command: entry.command.unwrap_or_default(),
+        args: entry.args,
+        env: entry.env,
+        url: entry.url,
+        api_key,
+        warnings,
+        error,
+    }
+}
+
+/// Serialize servers as an `mcpServers` config file
+pub fn export_mcp_config(servers: &[McpServer]) -> String {
+    let mcp_servers = servers
+        .iter()
+        .map(|server| {
+            let entry = match server.transport {
+                TransportType::Stdio => McpConfigEntry {
+                    command: Some(server.command.clone()),
+                    args: server.args.clone().unwrap_or_default(),
+                    env: server.env.clone().unwrap_or_default(),
+                    ..Default::default()
+                },
+                TransportType::Http => McpConfigEntry {
+                    transport: Some("http".to_string()),
+                    url: server.url.clone(),
+                    headers: server
+                        .api_key
+                        .iter()
+                        .filter(|key| !key.is_empty())
+                        .map(|key| ("Authorization".to_string(), format!("Bearer {}", key)))
+                        .collect(),
+                    ..Default::default()
+                },
+            };
+            (server.name.clone(), entry)
+        })
+        .collect();
+    serde_json::to_string_pretty(&McpConfigFile { mcp_servers }).unwrap_or_default()
+}
+
+/// First free "name (n)" for a taken name
+fn unique_name(name: &str, taken: &HashSet<String>) -> String {
+    (2..)
+        .map(|n| format!("{} ({})", name, n))
+        .find(|candidate| !taken.contains(candidate))
+        .unwrap()
+}
+
+impl Database {
+    /// Import parsed servers. With `dry_run` nothing is written and the report
+    /// describes what would happen.
+    pub fn import_mcp_servers(
+        &self,
+        servers: Vec<ImportedMcpServer>,
+        conflict: McpImportConflict,
+        dry_run: bool,
+    ) -> Result<McpImportReport> {
+        let existing: HashMap<String, String> = self
+            .get_all_mcp_servers()?
+            .into_iter()
+            .map(|s| (s.name, s.id))
+            .collect();
+        let mut taken: HashSet<String> = existing.keys().cloned().collect();
+        let mut items = Vec::new();
+
+        // All-or-nothing: a failure partway through must not leave a half-applied import
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+
+        for server in servers {
+            let mut item = McpImportItem {
+                source_name: server.name.clone(),
+                name: server.name.clone(),
+                transport: server.transport.clone(),
+                action: McpImportAction::Create,
+                server_id: None,
+                warnings: server.warnings.clone(),
+                error: server.error.clone(),
+            };
+
+            if item.error.is_some() || server.name.is_empty() {
+                item.action = McpImportAction::Invalid;
+                item.error.get_or_insert_with(|| "Empty server name".to_string());
+                items.push(item);
+                continue;
+            }
+
+            if taken.contains(&server.name) {
+                item.action = match conflict {
+                    McpImportConflict::Skip => McpImportAction::Skip,
+                    McpImportConflict::Rename => {
+                        item.name = unique_name(&server.name, &taken);
+                        McpImportAction::Rename
+                    }
+                    McpImportConflict::Overwrite => match existing.get(&server.name) {
+                        Some(_) => McpImportAction::Overwrite,
+                        // Taken by an earlier entry of this same import
+                        None => McpImportAction::Skip,
+                    },
+                };
+            }
+            taken.insert(item.name.clone());
+
+            if !dry_run {
+                match item.action {
+                    McpImportAction::Create | McpImportAction::Rename => {
+                        let created = insert_mcp_server(&tx, CreateMcpServerData {
+                            name: item.name.clone(),
+                            transport: Some(server.transport.clone()),
+                            command: Some(server.command.clone()),
+                            args: Some(server.args.clone()),
+                            env: Some(server.env.clone()),
+                            url: server.url.clone(),
+                            api_key: server.api_key.clone(),
+                            icon: None,
+                            auto_start: None,
+                            show_in_toolbar: None,
+                            max_iterations: None,
+                            restart_policy: None,
+                            sandbox: None,
+                        })?;
+                        item.server_id = Some(created.id);
+                    }
+                    McpImportAction::Overwrite => {
+                        let id = existing[&server.name].clone();
+                        replace_mcp_server_transport(&tx, &id, &server)?;
+                        item.server_id = Some(id);
+                    }
+                    McpImportAction::Skip | McpImportAction::Invalid => {}
+                }
+            }
+            items.push(item);
+        }
+        tx.commit()?;
+
+        Ok(McpImportReport { dry_run, items })
+    }
+}
+
+/// Overwrite the connection settings of a server (clears fields of the other transport).
+/// OAuth credentials were issued for the old URL, so they are dropped when it changes.
+fn replace_mcp_server_transport(conn: &Connection, id: &str, server: &ImportedMcpServer) -> Result<()> {
+    let (transport, command, args, env, url, api_key) = match server.transport {
+        TransportType::Stdio => (
+            "stdio",
+            server.command.clone(),
+            Some(serde_json::to_string(&server.args).unwrap()),
+            Some(serde_json::to_string(&server.env).unwrap()),
+            None,
+            None,
+        ),
+        TransportType::Http => ("http", String::new(), None, None, server.url.clone(), server.api_key.clone()),
+    };
+    let previous: Option<(String, Option<String>)> = conn.query_row(
+        "SELECT transport, url FROM mcp_servers WHERE id = ?", params![id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).optional()?;
+    conn.execute(
+        "UPDATE mcp_servers SET transport = ?1, command = ?2, args = ?3, env = ?4, url = ?5, api_key = ?6, updated_at = ?7
+         WHERE id = ?8",
+        params![transport, command, args, env, url, api_key, Utc::now().to_rfc3339(), id],
+    )?;
+    if previous != Some((transport.to_string(), url)) {
+        conn.execute("DELETE FROM mcp_oauth_credentials WHERE server_id = ?", params![id])?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> Database {
+        Database::new(std::path::PathBuf::from(":memory:")).unwrap()
+    }
+
+    const CONFIG: &str = r#"{
+        "mcpServers": {
+            "filesystem": { "command": "npx", "args": ["-y", "@modelcontextprotocol/server-filesystem", "/tmp"], "env": { "DEBUG": "1" } },
+            "remote": { "type": "http", "url": "https://example.com/mcp", "headers": { "Authorization": "Bearer secret", "X-Team": "a" } },
+            "broken": { "args": ["x"] }
+        }
+    }"#;
+
+    #[test]
+    fn parses_stdio_http_and_invalid_entries() {
+        let servers = parse_mcp_config(CONFIG).unwrap();
+        let by_name: HashMap<&str, &ImportedMcpServer> = servers.iter().map(|s| (s.name.as_str(), s)).collect();
+
+        let fs = by_name["filesystem"];
+        assert_eq!(fs.transport, TransportType::Stdio);
+        assert_eq!(fs.command, "npx");
+        assert_eq!(fs.args.len(), 3);
+        assert_eq!(fs.env["DEBUG"], "1");
+
+        let remote = by_name["remote"];
+        assert_eq!(remote.transport, TransportType::Http);
+        assert_eq!(remote.api_key.as_deref(), Some("secret"));
+        assert_eq!(remote.warnings.len(), 1);
+
+        assert!(by_name["broken"].error.is_some());
+        assert!(parse_mcp_config("{}").is_err());
+    }
+
+    #[test]
+    fn dry_run_does_not_write() {
+        let db = test_db();
+        let report = db
+            .import_mcp_servers(parse_mcp_config(CONFIG).unwrap(), McpImportConflict::Skip, true)
+            .unwrap();
+        assert!(report.dry_run);
+        assert_eq!(report.items.iter().filter(|i| i.action == McpImportAction::Create).count(), 2);
+        assert!(db.get_all_mcp_servers().unwrap().is_empty());
+    }
+
+    #[test]
+    fn conflicts_skip_rename_and_overwrite() {
+        let db = test_db();
+        let servers = parse_mcp_config(CONFIG).unwrap();
+        db.import_mcp_servers(servers.clone(), McpImportConflict::Skip, false).unwrap();
+        let original_id = db.get_mcp_server_by_name("remote").unwrap().unwrap().id;
+
+        let report = db.import_mcp_servers(servers.clone(), McpImportConflict::Skip, false).unwrap();
+        assert!(report.items.iter().all(|i| matches!(i.action, McpImportAction::Skip | McpImportAction::Invalid)));
+
+        let report = db.import_mcp_servers(servers.clone(), McpImportConflict::Rename, false).unwrap();
+        let renamed: Vec<&str> = report.items.iter().filter(|i| i.action == McpImportAction::Rename).map(|i| i.name.as_str()).collect();
+        assert_eq!(renamed, vec!["filesystem (2)", "remote (2)"]);
+
+        let stdio_remote = parse_mcp_config(r#"{"mcpServers": {"remote": {"command": "uvx", "args": ["remote-mcp"]}}}"#).unwrap();
+        let report = db.import_mcp_servers(stdio_remote, McpImportConflict::Overwrite, false).unwrap();
+        assert_eq!(report.items[0].action, McpImportAction::Overwrite);
+        let remote = db.get_mcp_server_by_name("remote").unwrap().unwrap();
+        assert_eq!(remote.id, original_id);
+        assert_eq!(remote.transport, TransportType::Stdio);
+        assert_eq!(remote.command, "uvx");
+        assert_eq!(remote.url, None);
+        assert_eq!(remote.api_key, None);
+    }
+
+    #[test]
+    fn overwrite_with_new_url_drops_oauth_credentials() {
+        let db = test_db();
+        db.import_mcp_servers(parse_mcp_config(CONFIG).unwrap(), McpImportConflict::Skip, false).unwrap();
+        let id = db.get_mcp_server_by_name("remote").unwrap().unwrap().id;
+        db.set_mcp_oauth_credentials(&id, "{}").unwrap();
+
+        let same = parse_mcp_config(r#"{"mcpServers": {"remote": {"type": "http", "url": "https://example.com/mcp"}}}"#).unwrap();
+        db.import_mcp_servers(same, McpImportConflict::Overwrite, false).unwrap();
+        assert!(db.get_mcp_oauth_credentials(&id).unwrap().is_some());
+
+        let moved = parse_mcp_config(r#"{"mcpServers": {"remote": {"type": "http", "url": "https://evil.example/mcp"}}}"#).unwrap();
+        db.import_mcp_servers(moved, McpImportConflict::Overwrite, false).unwrap();
+        assert!(db.get_mcp_oauth_credentials(&id).unwrap().is_none());
+    }
+
+    #[test]
+    fn export_round_trips() {
+        let db = test_db();
+        db.import_mcp_servers(parse_mcp_config(CONFIG).unwrap(), McpImportConflict::Skip, false).unwrap();
+        let exported = export_mcp_config(&db.get_all_mcp_servers().unwrap());
+
+        let value: serde_json::Value = serde_json::from_str(&exported).unwrap();
+        assert_eq!(value["mcpServers"]["remote"]["headers"]["Authorization"], "Bearer secret");
+        assert_eq!(value["mcpServers"]["filesystem"]["command"], "npx");
+
+        let reparsed = parse_mcp_config(&exported).unwrap();
+        assert_eq!(reparsed.len(), 2);
+        assert!(reparsed.iter().all(|s| s.error.is_none() && s.warnings.is_empty()));
+    }
+}
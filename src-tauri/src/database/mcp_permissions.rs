@@ -0,0 +1,377 @@
+// Per-tool permission rules, per-pet disabled tools and the audit log of MCP tool calls.
+// Rules are matched in `mcp::permissions`; this module only persists them.
+
+use rusqlite::{params, Result, Row};
+use serde::{Deserialize, Serialize};
+use chrono::Utc;
+use uuid::Uuid;
+use super::Database;
+
+// Arguments and results larger than this are truncated in the audit log
+const AUDIT_TEXT_MAX_CHARS: usize = 8000;
+// Older entries beyond this many rows are pruned on insert
+const AUDIT_LOG_MAX_ROWS: i64 = 5000;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolPolicy {
+    pub id: String,
+    pub server_id: String,
+    // Exact tool name, a glob such as "delete_*", or "*" for every tool on the server
+    pub tool_name: String,
+    // None = applies to every pet
+    pub pet_id: Option<String>,
+    // "allow" | "ask" | "deny"
+    pub action: String,
+    // "key=glob" matched against one argument, or a bare glob matched against the JSON arguments
+    pub arg_pattern: Option<String>,
+    // Created from an approval prompt with "remember" ticked
+    #[serde(default)]
+    pub remembered: bool,
+    pub created_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateToolPolicyData {
+    pub server_id: String,
+    pub tool_name: Option<String>,
+    pub pet_id: Option<String>,
+    pub action: String,
+    pub arg_pattern: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolAuditEntry {
+    pub id: i64,
+    pub server_id: String,
+    pub server_name: Option<String>,
+    pub tool_name: String,
+    pub pet_id: Option<String>,
+    pub arguments: Option<String>,
+    // "allow" | "approved" | "deny" | "disabled" | "rejected" | "timeout" | "unattended"
+    pub decision: String,
+    pub success: bool,
+    pub result_summary: Option<String>,
+    pub error: Option<String>,
+    pub duration_ms: i64,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct NewToolAuditEntry {
+    pub server_id: String,
+    pub server_name: Option<String>,
+    pub tool_name: String,
+    pub pet_id: Option<String>,
+    pub arguments: Option<serde_json::Value>,
+    pub decision: String,
+    pub success: bool,
+    pub result_summary: Option<String>,
+    pub error: Option<String>,
+    pub duration_ms: i64,
+}
+
+/// A tool hidden from one pet
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct PetToolRef {
+    pub server_id: String,
+    pub tool_name: String,
+}
+
+fn row_to_policy(row: &Row) -> Result<ToolPolicy> {
+    Ok(ToolPolicy {
+        id: row.get(0)?,
+        server_id: row.get(1)?,
+        tool_name: row.get(2)?,
+        pet_id: row.get(3)?,
+        action: row.get(4)?,
+        arg_pattern: row.get(5)?,
+        remembered: row.get::<_, i32>(6)? != 0,
+        created_at: row.get(7)?,
+    })
+}
+
+fn truncate_audit_text(text: &str) -> String {
+    if text.chars().count() <= AUDIT_TEXT_MAX_CHARS {
+        return text.to_string();
+    }
+    let truncated: String = text.chars().take(AUDIT_TEXT_MAX_CHARS).collect();
+    format!("{}…", truncated)
+}
+
+impl Database {
+    /// Permission rules, optionally limited to one server
+    pub fn get_tool_policies(&self, server_id: Option<&str>) -> Result<Vec<ToolPolicy>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, server_id, tool_name, pet_id, action, arg_pattern, remembered, created_at
+             FROM mcp_tool_policies
+             WHERE (?1 IS NULL OR server_id = ?1)
+             ORDER BY created_at"
+        )?;
+        let policies = stmt.query_map(params![server_id], row_to_policy)?
+            .collect::<Result<Vec<_>>>()?;
+        Ok(policies)
+    }
+
+    pub fn create_tool_policy(&self, data: CreateToolPolicyData) -> Result<ToolPolicy> {
+        let policy = ToolPolicy {
+            id: Uuid::new_v4().to_string(),
+            server_id: data.server_id,
+            tool_name: data.tool_name.filter(|t| !t.trim().is_empty()).unwrap_or_else(|| "*".to_string()),
+            pet_id: data.pet_id.filter(|p| !p.is_empty()),
+            action: data.action.trim().to_lowercase(),
+            arg_pattern: data.arg_pattern.filter(|p| !p.trim().is_empty()),
+            remembered: false,
+            created_at: Utc::now().to_rfc3339(),
+        };
+        self.insert_tool_policy(&policy)?;
+        Ok(policy)
+    }
+
+    /// Store an approval-prompt decision for one pet, replacing any earlier one for the same tool
+    pub fn remember_tool_decision(
+        &self,
+        pet_id: &str,
+        server_id: &str,
+        tool_name: &str,
+        action: &str,
+    ) -> Result<ToolPolicy> {
+        {
+            let conn = self.conn.lock().unwrap();
+            conn.execute(
+                "DELETE FROM mcp_tool_policies
+                 WHERE remembered = 1 AND pet_id = ? AND server_id = ? AND tool_name = ?",
+                params![pet_id, server_id, tool_name],
+            )?;
+        }
+        let policy = ToolPolicy {
+            id: Uuid::new_v4().to_string(),
+            server_id: server_id.to_string(),
+            tool_name: tool_name.to_string(),
+            pet_id: Some(pet_id.to_string()),
+            action: action.to_string(),
+            arg_pattern: None,
+            remembered: true,
+            created_at: Utc::now().to_rfc3339(),
+        };
+        self.insert_tool_policy(&policy)?;
+        Ok(policy)
+    }
+
+    fn insert_tool_policy(&self, policy: &ToolPolicy) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO mcp_tool_policies (id, server_id, tool_name, pet_id, action, arg_pattern, remembered, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                policy.id,
+                policy.server_id,
+                policy.tool_name,
+                policy.pet_id,
+                policy.action,
+                policy.arg_pattern,
+                policy.remembered as i32,
+                policy.created_at,
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_tool_policy(&self, id: &str) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let rows = conn.execute("DELETE FROM mcp_tool_policies WHERE id = ?", params![id])?;
+        Ok(rows > 0)
+    }
+
+    pub fn insert_tool_audit(&self, entry: &NewToolAuditEntry) -> Result<i64> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO mcp_tool_audit_log
+                (server_id, server_name, tool_name, pet_id, arguments, decision, success,
+                 result_summary, error, duration_ms, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            params![
+                entry.server_id,
+                entry.server_name,
+                entry.tool_name,
+                entry.pet_id,
+                entry.arguments.as_ref().map(|a| truncate_audit_text(&a.to_string())),
+                entry.decision,
+                entry.success as i32,
+                entry.result_summary.as_deref().map(truncate_audit_text),
+                entry.error.as_deref().map(truncate_audit_text),
+                entry.duration_ms,
+                Utc::now().to_rfc3339(),
+            ],
+        )?;
+        let id = conn.last_insert_rowid();
+        drop(conn);
+        self.prune_tool_audit_log(AUDIT_LOG_MAX_ROWS)?;
+        Ok(id)
+    }
+
+    /// Keep only the newest `keep` audit entries; returns how many were removed
+    pub fn prune_tool_audit_log(&self, keep: i64) -> Result<usize> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "DELETE FROM mcp_tool_audit_log
+             WHERE id <= (SELECT id FROM mcp_tool_audit_log ORDER BY id DESC LIMIT 1 OFFSET ?)",
+            params![keep],
+        )
+    }
+
+    /// Most recent audit entries first
+    pub fn get_tool_audit_log(
+        &self,
+        server_id: Option<&str>,
+        pet_id: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<ToolAuditEntry>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, server_id, server_name, tool_name, pet_id, arguments, decision, success,
+                    result_summary, error, duration_ms, created_at
+             FROM mcp_tool_audit_log
+             WHERE (?1 IS NULL OR server_id = ?1) AND (?2 IS NULL OR pet_id = ?2)
+             ORDER BY id DESC
+             LIMIT ?3"
+        )?;
+        let entries = stmt.query_map(params![server_id, pet_id, limit], |row| {
+            Ok(ToolAuditEntry {
+                id: row.get(0)?,
+                server_id: row.get(1)?,
+                server_name: row.get(2)?,
+                tool_name: row.get(3)?,
+                pet_id: row.get(4)?,
+                arguments: row.get(5)?,
+                decision: row.get(6)?,
+                success: row.get::<_, i32>(7)? != 0,
+                result_summary: row.get(8)?,
+                error: row.get(9)?,
+                duration_ms: row.get(10)?,
+                created_at: row.get(11)?,
+            })
+        })?.collect::<Result<Vec<_>>>()?;
+        Ok(entries)
+    }
+
+    pub fn clear_tool_audit_log(&self) -> Result<usize> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM mcp_tool_audit_log", [])
+    }
+
+    /// Tools this pet has switched off
+    pub fn get_pet_disabled_tools(&self, pet_id: &str) -> Result<Vec<PetToolRef>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT server_id, tool_name FROM pet_disabled_tools
+             WHERE pet_id = ? ORDER BY server_id, tool_name"
+        )?;
+        let tools = stmt.query_map(params![pet_id], |row| {
+            Ok(PetToolRef {
+                server_id: row.get(0)?,
+                tool_name: row.get(1)?,
+            })
+        })?.collect::<Result<Vec<_>>>()?;
+        Ok(tools)
+    }
+
+    pub fn set_pet_tool_enabled(
+        &self,
+        pet_id: &str,
+        server_id: &str,
+        tool_name: &str,
+        enabled: bool,
+    ) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        if enabled {
+            conn.execute(
+                "DELETE FROM pet_disabled_tools WHERE pet_id = ? AND server_id = ? AND tool_name = ?",
+                params![pet_id, server_id, tool_name],
+            )?;
+        } else {
+            conn.execute(
+                "INSERT OR IGNORE INTO pet_disabled_tools (pet_id, server_id, tool_name, created_at)
+                 VALUES (?, ?, ?, ?)",
+                params![pet_id, server_id, tool_name, Utc::now().to_rfc3339()],
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> Database {
+        Database::new(std::path::PathBuf::from(":memory:")).unwrap()
+    }
+
+    #[test]
+    fn remembered_decision_replaces_previous_one() {
+        let db = test_db();
+        db.create_tool_policy(CreateToolPolicyData {
+            server_id: "fs".into(),
+            tool_name: None,
+            pet_id: None,
+            action: "Ask".into(),
+            arg_pattern: Some("  ".into()),
+        }).unwrap();
+        db.remember_tool_decision("pet-1", "fs", "delete_file", "deny").unwrap();
+        db.remember_tool_decision("pet-1", "fs", "delete_file", "allow").unwrap();
+
+        let policies = db.get_tool_policies(Some("fs")).unwrap();
+        assert_eq!(policies.len(), 2);
+        assert_eq!(policies[0].tool_name, "*");
+        assert_eq!(policies[0].action, "ask");
+        assert_eq!(policies[0].arg_pattern, None);
+        assert!(policies[1].remembered);
+        assert_eq!(policies[1].action, "allow");
+        assert!(db.get_tool_policies(Some("other")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn audit_log_is_filtered_and_newest_first() {
+        let db = test_db();
+        for (tool, pet) in [("read", "a"), ("write", "b"), ("delete", "a")] {
+            db.insert_tool_audit(&NewToolAuditEntry {
+                server_id: "fs".into(),
+                tool_name: tool.into(),
+                pet_id: Some(pet.into()),
+                arguments: Some(serde_json::json!({ "path": "x".repeat(AUDIT_TEXT_MAX_CHARS) })),
+                result_summary: Some("y".repeat(AUDIT_TEXT_MAX_CHARS * 2)),
+                decision: "allow".into(),
+                success: true,
+                duration_ms: 5,
+                ..Default::default()
+            }).unwrap();
+        }
+
+        let entries = db.get_tool_audit_log(None, Some("a"), 10).unwrap();
+        assert_eq!(entries.iter().map(|e| e.tool_name.as_str()).collect::<Vec<_>>(), ["delete", "read"]);
+        assert!(entries[0].arguments.as_ref().unwrap().ends_with('…'));
+        assert!(entries[0].result_summary.as_ref().unwrap().ends_with('…'));
+        assert_eq!(db.get_tool_audit_log(Some("fs"), None, 1).unwrap().len(), 1);
+        assert_eq!(db.prune_tool_audit_log(2).unwrap(), 1);
+        assert_eq!(db.get_tool_audit_log(None, Some("a"), 10).unwrap().len(), 1);
+        assert_eq!(db.clear_tool_audit_log().unwrap(), 2);
+    }
+
+    #[test]
+    fn pet_tool_toggles() {
+        let db = test_db();
+        db.set_pet_tool_enabled("pet-1", "fs", "delete_file", false).unwrap();
+        db.set_pet_tool_enabled("pet-1", "fs", "delete_file", false).unwrap();
+        db.set_pet_tool_enabled("pet-1", "fs", "write_file", false).unwrap();
+        db.set_pet_tool_enabled("pet-1", "fs", "write_file", true).unwrap();
+
+        let disabled = db.get_pet_disabled_tools("pet-1").unwrap();
+        assert_eq!(disabled, vec![PetToolRef { server_id: "fs".into(), tool_name: "delete_file".into() }]);
+        assert!(db.get_pet_disabled_tools("pet-2").unwrap().is_empty());
+    }
+}
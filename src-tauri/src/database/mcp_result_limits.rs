@@ -0,0 +1,120 @@
+// Per-tool size limits for MCP tool results (see `mcp::types::limit_tool_result`).
+// A row with tool_name "*" sets the default for every tool on that server.
+
+use rusqlite::{params, Result, Row};
+use serde::{Deserialize, Serialize};
+use chrono::Utc;
+use crate::mcp::ResultLimits;
+use super::Database;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolResultLimits {
+    pub server_id: String,
+    pub tool_name: String,
+    #[serde(flatten)]
+    pub limits: ResultLimits,
+    pub updated_at: String,
+}
+
+fn row_to_limits(row: &Row) -> Result<ToolResultLimits> {
+    Ok(ToolResultLimits {
+        server_id: row.get(0)?,
+        tool_name: row.get(1)?,
+        limits: ResultLimits {
+            max_text_chars: row.get::<_, i64>(2)?.max(0) as usize,
+            max_image_dimension: row.get::<_, i64>(3)?.max(1) as u32,
+            max_image_bytes: row.get::<_, i64>(4)?.max(0) as usize,
+            spill: row.get::<_, i32>(5)? != 0,
+        },
+        updated_at: row.get(6)?,
+    })
+}
+
+impl Database {
+    /// Configured limits, optionally for one server only
+    pub fn get_tool_result_limits(&self, server_id: Option<&str>) -> Result<Vec<ToolResultLimits>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT server_id, tool_name, max_text_chars, max_image_dimension, max_image_bytes, spill, updated_at
+             FROM mcp_tool_result_limits
+             WHERE (?1 IS NULL OR server_id = ?1)
+             ORDER BY server_id, tool_name"
+        )?;
+        let limits = stmt.query_map(params![server_id], row_to_limits)?
+            .collect::<Result<Vec<_>>>()?;
+        Ok(limits)
+    }
+
+    /// Limits for one tool: its own row, else the server's "*" row, else the defaults
+    pub fn resolve_tool_result_limits(&self, server_id: &str, tool_name: &str) -> Result<ResultLimits> {
+        let rows = self.get_tool_result_limits(Some(server_id))?;
+        let exact = rows.iter().find(|r| r.tool_name == tool_name);
+        let fallback = rows.iter().find(|r| r.tool_name == "*");
+        Ok(exact.or(fallback).map(|r| r.limits.clone()).unwrap_or_default())
+    }
+
+    pub fn set_tool_result_limits(
+        &self,
+        server_id: &str,
+        tool_name: &str,
+        limits: &ResultLimits,
+    ) -> Result<ToolResultLimits> {
+        let tool_name = if tool_name.trim().is_empty() { "*" } else { tool_name };
+        let now = Utc::now().to_rfc3339();
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO mcp_tool_result_limits
+                (server_id, tool_name, max_text_chars, max_image_dimension, max_image_bytes, spill, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+            params![
+                server_id,
+                tool_name,
+                limits.max_text_chars as i64,
+                limits.max_image_dimension as i64,
+                limits.max_image_bytes as i64,
+                limits.spill as i32,
+                now,
+            ],
+        )?;
+        Ok(ToolResultLimits {
+            server_id: server_id.to_string(),
+            tool_name: tool_name.to_string(),
+            limits: limits.clone(),
+            updated_at: now,
+        })
+    }
+
+    pub fn delete_tool_result_limits(&self, server_id: &str, tool_name: &str) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let rows = conn.execute(
+            "DELETE FROM mcp_tool_result_limits WHERE server_id = ? AND tool_name = ?",
+            params![server_id, tool_name],
+        )?;
+        Ok(rows > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tool_row_beats_server_default() {
+        let db = Database::new(std::path::PathBuf::from(":memory:")).unwrap();
+        assert_eq!(db.resolve_tool_result_limits("fs", "read").unwrap(), ResultLimits::default());
+
+        let server_wide = ResultLimits { max_text_chars: 5000, ..Default::default() };
+        let read = ResultLimits { max_text_chars: 100, spill: false, ..Default::default() };
+        db.set_tool_result_limits("fs", "", &server_wide).unwrap();
+        db.set_tool_result_limits("fs", "read", &ResultLimits::default()).unwrap();
+        db.set_tool_result_limits("fs", "read", &read).unwrap();
+
+        assert_eq!(db.resolve_tool_result_limits("fs", "read").unwrap(), read);
+        assert_eq!(db.resolve_tool_result_limits("fs", "write").unwrap(), server_wide);
+        assert_eq!(db.get_tool_result_limits(None).unwrap().len(), 2);
+
+        assert!(db.delete_tool_result_limits("fs", "read").unwrap());
+        assert_eq!(db.resolve_tool_result_limits("fs", "read").unwrap(), server_wide);
+    }
+}
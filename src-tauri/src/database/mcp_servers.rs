@@ -1,7 +1,8 @@
-use rusqlite::{params, Result};
+use rusqlite::{params, Connection, OptionalExtension, Result};
 use serde::{Deserialize, Serialize};
 use chrono::Utc;
 use uuid::Uuid;
+use crate::mcp::sandbox::SandboxProfile;
 use super::Database;
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
@@ -41,6 +42,12 @@ pub struct McpServer {
     pub toolbar_order: i32,
     // Max tool call iterations for this server (None = unlimited)
     pub max_iterations: Option<i32>,
+    // "never" | "on-failure" | "always" (None = on-failure for auto_start servers, never otherwise)
+    #[serde(default)]
+    pub restart_policy: Option<String>,
+    // Opt-in isolation for stdio servers (None = unsandboxed)
+    #[serde(default)]
+    pub sandbox: Option<SandboxProfile>,
     pub created_at: String,
     pub updated_at: String,
     // Runtime state (not persisted)
@@ -67,6 +74,9 @@ pub struct CreateMcpServerData {
     pub show_in_toolbar: Option<bool>,
     // Max iterations (None = unlimited)
     pub max_iterations: Option<i32>,
+    pub restart_policy: Option<String>,
+    #[serde(default)]
+    pub sandbox: Option<SandboxProfile>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -86,6 +96,10 @@ pub struct UpdateMcpServerData {
     // Max iterations (None = unlimited, Some(0) to clear/reset to unlimited)
     #[serde(default, deserialize_with = "deserialize_optional_max_iterations")]
     pub max_iterations: Option<Option<i32>>,
+    pub restart_policy: Option<String>,
+    // Send `{ "enabled": false }` to turn the sandbox off
+    #[serde(default)]
+    pub sandbox: Option<SandboxProfile>,
 }
 
 // Custom deserializer to handle max_iterations: null vs absent vs 0
@@ -117,7 +131,7 @@ impl Database {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
             "SELECT id, name, transport, command, args, env, url, api_key, icon, auto_start, 
-                    show_in_toolbar, toolbar_order, max_iterations, created_at, updated_at 
+                    show_in_toolbar, toolbar_order, max_iterations, created_at, updated_at, restart_policy, sandbox 
              FROM mcp_servers ORDER BY toolbar_order"
         )?;
         
@@ -142,6 +156,8 @@ impl Database {
                 max_iterations: row.get(12)?,
                 created_at: row.get(13)?,
                 updated_at: row.get(14)?,
+                restart_policy: row.get(15)?,
+                sandbox: row.get::<_, Option<String>>(16)?.and_then(|s| serde_json::from_str(&s).ok()),
                 is_running: false,
             })
         })?.collect::<Result<Vec<_>>>()?;
@@ -153,7 +169,7 @@ impl Database {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
             "SELECT id, name, transport, command, args, env, url, api_key, icon, auto_start, 
-                    show_in_toolbar, toolbar_order, max_iterations, created_at, updated_at 
+                    show_in_toolbar, toolbar_order, max_iterations, created_at, updated_at, restart_policy, sandbox 
              FROM mcp_servers WHERE id = ?"
         )?;
         
@@ -180,6 +196,8 @@ impl Database {
                 max_iterations: row.get(12)?,
                 created_at: row.get(13)?,
                 updated_at: row.get(14)?,
+                restart_policy: row.get(15)?,
+                sandbox: row.get::<_, Option<String>>(16)?.and_then(|s| serde_json::from_str(&s).ok()),
                 is_running: false,
             }))
         } else {
@@ -191,7 +209,7 @@ impl Database {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
             "SELECT id, name, transport, command, args, env, url, api_key, icon, auto_start, 
-                    show_in_toolbar, toolbar_order, max_iterations, created_at, updated_at 
+                    show_in_toolbar, toolbar_order, max_iterations, created_at, updated_at, restart_policy, sandbox 
              FROM mcp_servers WHERE name = ?"
         )?;
         
@@ -218,6 +236,8 @@ impl Database {
                 max_iterations: row.get(12)?,
                 created_at: row.get(13)?,
                 updated_at: row.get(14)?,
+                restart_policy: row.get(15)?,
+                sandbox: row.get::<_, Option<String>>(16)?.and_then(|s| serde_json::from_str(&s).ok()),
                 is_running: false,
             }))
         } else {
@@ -227,56 +247,7 @@ impl Database {
 
     pub fn create_mcp_server(&self, data: CreateMcpServerData) -> Result<McpServer> {
         let conn = self.conn.lock().unwrap();
-        let id = Uuid::new_v4().to_string();
-        let now = Utc::now().to_rfc3339();
-        let auto_start = data.auto_start.unwrap_or(false);
-        let show_in_toolbar = data.show_in_toolbar.unwrap_or(true);
-        let transport = data.transport.unwrap_or(TransportType::Stdio);
-        let args_json = data.args.as_ref().map(|a| serde_json::to_string(a).unwrap());
-        let env_json = data.env.as_ref().map(|e| serde_json::to_string(e).unwrap());
-        // For HTTP transport, command can be empty; use empty string to satisfy NOT NULL constraint
-        let command = data.command.clone().unwrap_or_default();
-        
-        conn.execute(
-            "INSERT INTO mcp_servers (id, name, transport, command, args, env, url, api_key, icon, auto_start, 
-                                      show_in_toolbar, toolbar_order, max_iterations, created_at, updated_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, 0, ?12, ?13, ?14)",
-            params![
-                id,
-                data.name,
-                transport_to_string(&transport),
-                command,
-                args_json,
-                env_json,
-                data.url,
-                data.api_key,
-                data.icon,
-                auto_start as i32,
-                show_in_toolbar as i32,
-                data.max_iterations,
-                now,
-                now
-            ],
-        )?;
-        
-        Ok(McpServer {
-            id,
-            name: data.name,
-            transport,
-            command: data.command.unwrap_or_default(),
-            args: data.args,
-            env: data.env,
-            url: data.url,
-            api_key: data.api_key,
-            icon: data.icon,
-            auto_start,
-            show_in_toolbar,
-            toolbar_order: 0,
-            max_iterations: data.max_iterations,
-            created_at: now.clone(),
-            updated_at: now,
-            is_running: false,
-        })
+        insert_mcp_server(&conn, data)
     }
 
     pub fn update_mcp_server(&self, id: &str, data: UpdateMcpServerData) -> Result<Option<McpServer>> {
@@ -335,6 +306,14 @@ impl Database {
             updates.push(format!("max_iterations = ?{}", param_count));
             param_count += 1;
         }
+        if data.restart_policy.is_some() {
+            updates.push(format!("restart_policy = ?{}", param_count));
+            param_count += 1;
+        }
+        if data.sandbox.is_some() {
+            updates.push(format!("sandbox = ?{}", param_count));
+            param_count += 1;
+        }
         
         let sql = format!(
             "UPDATE mcp_servers SET {} WHERE id = ?{}",
@@ -356,10 +335,22 @@ impl Database {
         if let Some(show_in_toolbar) = data.show_in_toolbar { params_vec.push(Box::new(show_in_toolbar as i32)); }
         if let Some(toolbar_order) = data.toolbar_order { params_vec.push(Box::new(toolbar_order)); }
         if let Some(max_iterations) = &data.max_iterations { params_vec.push(Box::new(*max_iterations)); }
+        if let Some(restart_policy) = &data.restart_policy { params_vec.push(Box::new(restart_policy.clone())); }
+        if let Some(sandbox) = &data.sandbox { params_vec.push(Box::new(serde_json::to_string(sandbox).unwrap())); }
         params_vec.push(Box::new(id.to_string()));
         
+        // OAuth tokens belong to the old URL; forget them before they can reach another host
+        let old_url: Option<String> = conn.query_row(
+            "SELECT url FROM mcp_servers WHERE id = ?", params![id], |row| row.get(0),
+        ).optional()?.flatten();
+        let url_changed = data.url.as_ref().is_some_and(|url| old_url.as_ref() != Some(url));
+        let left_http = data.transport.as_ref().is_some_and(|t| transport_to_string(t) != "http");
+
         let params: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|v| v.as_ref()).collect();
         conn.execute(&sql, params.as_slice())?;
+        if url_changed || left_http {
+            conn.execute("DELETE FROM mcp_oauth_credentials WHERE server_id = ?", params![id])?;
+        }
         
         drop(conn);
         self.get_mcp_server_by_id(id)
@@ -368,6 +359,98 @@ impl Database {
     pub fn delete_mcp_server(&self, id: &str) -> Result<bool> {
         let conn = self.conn.lock().unwrap();
         let rows = conn.execute("DELETE FROM mcp_servers WHERE id = ?", params![id])?;
+        conn.execute("DELETE FROM mcp_oauth_credentials WHERE server_id = ?", params![id])?;
+        conn.execute("DELETE FROM mcp_tool_policies WHERE server_id = ?", params![id])?;
+        conn.execute("DELETE FROM pet_disabled_tools WHERE server_id = ?", params![id])?;
+        conn.execute("DELETE FROM mcp_tool_result_limits WHERE server_id = ?", params![id])?;
         Ok(rows > 0)
     }
+
+    /// Stored OAuth credentials (JSON) for an HTTP server
+    pub fn get_mcp_oauth_credentials(&self, server_id: &str) -> Result<Option<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT credentials FROM mcp_oauth_credentials WHERE server_id = ?")?;
+        let mut rows = stmt.query(params![server_id])?;
+
+        if let Some(row) = rows.next()? {
+            Ok(Some(row.get(0)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn set_mcp_oauth_credentials(&self, server_id: &str, credentials: &str) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let now = Utc::now().to_rfc3339();
+        conn.execute(
+            "INSERT OR REPLACE INTO mcp_oauth_credentials (server_id, credentials, updated_at) VALUES (?1, ?2, ?3)",
+            params![server_id, credentials, now],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_mcp_oauth_credentials(&self, server_id: &str) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let rows = conn.execute("DELETE FROM mcp_oauth_credentials WHERE server_id = ?", params![server_id])?;
+        Ok(rows > 0)
+    }
+}
+
+/// Insert a server row on an already locked connection (or an open transaction)
+pub(super) fn insert_mcp_server(conn: &Connection, data: CreateMcpServerData) -> Result<McpServer> {
+    let id = Uuid::new_v4().to_string();
+    let now = Utc::now().to_rfc3339();
+    let auto_start = data.auto_start.unwrap_or(false);
+    let show_in_toolbar = data.show_in_toolbar.unwrap_or(true);
+    let transport = data.transport.unwrap_or(TransportType::Stdio);
+    let args_json = data.args.as_ref().map(|a| serde_json::to_string(a).unwrap());
+    let env_json = data.env.as_ref().map(|e| serde_json::to_string(e).unwrap());
+    let sandbox_json = data.sandbox.as_ref().map(|p| serde_json::to_string(p).unwrap());
+    // For HTTP transport, command can be empty; use empty string to satisfy NOT NULL constraint
+    let command = data.command.clone().unwrap_or_default();
+    
+    conn.execute(
+        "INSERT INTO mcp_servers (id, name, transport, command, args, env, url, api_key, icon, auto_start, 
+                                  show_in_toolbar, toolbar_order, max_iterations, created_at, updated_at, restart_policy, sandbox)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, 0, ?12, ?13, ?14, ?15, ?16)",
+        params![
+            id,
+            data.name,
+            transport_to_string(&transport),
+            command,
+            args_json,
+            env_json,
+            data.url,
+            data.api_key,
+            data.icon,
+            auto_start as i32,
+            show_in_toolbar as i32,
+            data.max_iterations,
+            now,
+            now,
+            data.restart_policy,
+            sandbox_json
+        ],
+    )?;
+    
+    Ok(McpServer {
+        id,
+        name: data.name,
+        transport,
+        command: data.command.unwrap_or_default(),
+        args: data.args,
+        env: data.env,
+        url: data.url,
+        api_key: data.api_key,
+        icon: data.icon,
+        auto_start,
+        show_in_toolbar,
+        toolbar_order: 0,
+        max_iterations: data.max_iterations,
+        restart_policy: data.restart_policy,
+        sandbox: data.sandbox,
+        created_at: now.clone(),
+        updated_at: now,
+        is_running: false,
+    })
 }
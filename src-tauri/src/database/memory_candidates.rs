@@ -0,0 +1,139 @@
+// Review queue for facts the memory consolidator extracted from recent chats.
+// A candidate stays pending until the user approves it (it is then written to
+// USER.md / MEMORY.md) or rejects it.
+
+use rusqlite::{params, OptionalExtension, Result, Row};
+use serde::{Deserialize, Serialize};
+use chrono::Utc;
+use uuid::Uuid;
+use super::Database;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryCandidate {
+    pub id: String,
+    pub pet_id: String,
+    /// "USER.md" or "MEMORY.md"
+    pub file: String,
+    /// `## ` heading the fact belongs under
+    pub section: Option<String>,
+    pub fact: String,
+    /// Existing line the fact updates, if any
+    pub replaces: Option<String>,
+    /// "conversation" or "chat" (QQ chat_history)
+    pub source_kind: String,
+    /// Conversation id, or the chat target (group / friend) id
+    pub source_id: String,
+    pub source_message_id: Option<String>,
+    /// RFC 3339 time of the source message
+    pub source_time: String,
+    /// "pending", "approved" or "rejected"
+    pub status: String,
+    pub created_at: String,
+    pub reviewed_at: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewMemoryCandidate {
+    pub pet_id: String,
+    pub file: String,
+    pub section: Option<String>,
+    pub fact: String,
+    pub replaces: Option<String>,
+    pub source_kind: String,
+    pub source_id: String,
+    pub source_message_id: Option<String>,
+    pub source_time: String,
+}
+
+const CANDIDATE_COLUMNS: &str = "id, pet_id, file, section, fact, replaces, source_kind, source_id, \
+    source_message_id, source_time, status, created_at, reviewed_at";
+
+fn row_to_candidate(row: &Row) -> Result<MemoryCandidate> {
+    Ok(MemoryCandidate {
+        id: row.get(0)?,
+        pet_id: row.get(1)?,
+        file: row.get(2)?,
+        section: row.get(3)?,
+        fact: row.get(4)?,
+        replaces: row.get(5)?,
+        source_kind: row.get(6)?,
+        source_id: row.get(7)?,
+        source_message_id: row.get(8)?,
+        source_time: row.get(9)?,
+        status: row.get(10)?,
+        created_at: row.get(11)?,
+        reviewed_at: row.get(12)?,
+    })
+}
+
+impl Database {
+    pub fn insert_memory_candidate(&self, data: &NewMemoryCandidate) -> Result<MemoryCandidate> {
+        let candidate = MemoryCandidate {
+            id: Uuid::new_v4().to_string(),
+            pet_id: data.pet_id.clone(),
+            file: data.file.clone(),
+            section: data.section.clone(),
+            fact: data.fact.clone(),
+            replaces: data.replaces.clone(),
+            source_kind: data.source_kind.clone(),
+            source_id: data.source_id.clone(),
+            source_message_id: data.source_message_id.clone(),
+            source_time: data.source_time.clone(),
+            status: "pending".to_string(),
+            created_at: Utc::now().to_rfc3339(),
+            reviewed_at: None,
+        };
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            &format!("INSERT INTO memory_candidates ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)", CANDIDATE_COLUMNS),
+            params![
+                candidate.id,
+                candidate.pet_id,
+                candidate.file,
+                candidate.section,
+                candidate.fact,
+                candidate.replaces,
+                candidate.source_kind,
+                candidate.source_id,
+                candidate.source_message_id,
+                candidate.source_time,
+                candidate.status,
+                candidate.created_at,
+                candidate.reviewed_at,
+            ],
+        )?;
+        Ok(candidate)
+    }
+
+    /// Candidates of the pet, oldest first; `status` None returns every status
+    pub fn get_memory_candidates(&self, pet_id: &str, status: Option<&str>) -> Result<Vec<MemoryCandidate>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM memory_candidates WHERE pet_id = ?1 AND (?2 IS NULL OR status = ?2)
+             ORDER BY created_at ASC, rowid ASC",
+            CANDIDATE_COLUMNS
+        ))?;
+        let candidates = stmt.query_map(params![pet_id, status], row_to_candidate)?.collect::<Result<Vec<_>>>()?;
+        Ok(candidates)
+    }
+
+    pub fn get_memory_candidate(&self, id: &str) -> Result<Option<MemoryCandidate>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            &format!("SELECT {} FROM memory_candidates WHERE id = ?", CANDIDATE_COLUMNS),
+            params![id],
+            row_to_candidate,
+        ).optional()
+    }
+
+    /// Record the review decision; `fact` replaces the text when the user edited it first
+    pub fn review_memory_candidate(&self, id: &str, status: &str, fact: Option<&str>) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let rows = conn.execute(
+            "UPDATE memory_candidates SET status = ?, fact = COALESCE(?, fact), reviewed_at = ? WHERE id = ?",
+            params![status, fact, Utc::now().to_rfc3339(), id],
+        )?;
+        Ok(rows > 0)
+    }
+}
@@ -1,4 +1,4 @@
-use rusqlite::{params, Result};
+use rusqlite::{params, Connection, OptionalExtension, Result};
 use serde::{Deserialize, Serialize};
 use chrono::Utc;
 use uuid::Uuid;
@@ -10,6 +10,9 @@ pub struct Message {
     #[serde(rename = "_id")]
     pub id: String,
     pub conversation_id: String,
+    /// Previous message in the tree; None for the first message of a branch root
+    #[serde(default)]
+    pub parent_id: Option<String>,
     pub role: String,
     pub content: String,
     pub tool_call_history: Option<String>,
@@ -23,59 +26,210 @@ pub struct CreateMessageData {
     pub role: String,
     pub content: String,
     pub tool_call_history: Option<String>,
+    /// Attach under this message instead of the end of the active branch
+    #[serde(default)]
+    pub parent_id: Option<String>,
+}
+
+/// One entry of a full history save (see `save_active_branch`)
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryMessage {
+    pub role: String,
+    pub content: String,
+    pub tool_call_history: Option<String>,
+}
+
+/// A fork on the active path: several messages share the same parent
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BranchPoint {
+    pub parent_id: Option<String>,
+    /// Sibling message ids, oldest first
+    pub message_ids: Vec<String>,
+    /// Index into `message_ids` of the sibling on the active path
+    pub active_index: usize,
+}
+
+const MESSAGE_COLUMNS: &str = "id, conversation_id, parent_id, role, content, tool_call_history, created_at";
+
+fn row_to_message(row: &rusqlite::Row) -> Result<Message> {
+    Ok(Message {
+        id: row.get(0)?,
+        conversation_id: row.get(1)?,
+        parent_id: row.get(2)?,
+        role: row.get(3)?,
+        content: row.get(4)?,
+        tool_call_history: row.get(5)?,
+        created_at: row.get(6)?,
+    })
+}
+
+// Every message of the conversation (all branches), oldest first
+fn load_tree(conn: &Connection, conversation_id: &str) -> Result<Vec<Message>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM messages WHERE conversation_id = ? ORDER BY created_at ASC, rowid ASC",
+        MESSAGE_COLUMNS
+    ))?;
+    let messages = stmt.query_map(params![conversation_id], row_to_message)?.collect::<Result<Vec<_>>>()?;
+    Ok(messages)
+}
+
+fn load_message(conn: &Connection, id: &str) -> Result<Option<Message>> {
+    conn.query_row(
+        &format!("SELECT {} FROM messages WHERE id = ?", MESSAGE_COLUMNS),
+        params![id],
+        row_to_message,
+    ).optional()
+}
+
+fn set_active_leaf(conn: &Connection, conversation_id: &str, leaf_id: Option<&str>) -> Result<()> {
+    conn.execute(
+        "UPDATE conversations SET active_leaf_id = ? WHERE id = ?",
+        params![leaf_id, conversation_id],
+    )?;
+    Ok(())
+}
+
+// The stored active leaf if it still exists, otherwise the newest message
+fn active_leaf(conn: &Connection, conversation_id: &str, tree: &[Message]) -> Result<Option<String>> {
+    let stored: Option<String> = conn.query_row(
+        "SELECT active_leaf_id FROM conversations WHERE id = ?",
+        params![conversation_id],
+        |row| row.get(0),
+    ).optional()?.flatten();
+    Ok(stored
+        .filter(|id| tree.iter().any(|m| &m.id == id))
+        .or_else(|| tree.last().map(|m| m.id.clone())))
+}
+
+/// Messages from the root down to `leaf_id`
+fn path_to(tree: &[Message], leaf_id: &str) -> Vec<Message> {
+    let mut path = Vec::new();
+    let mut current = tree.iter().find(|m| m.id == leaf_id);
+    while let Some(message) = current {
+        // Guard against a corrupted cycle
+        if path.len() > tree.len() {
+            break;
+        }
+        path.push(message.clone());
+        current = message.parent_id.as_ref().and_then(|p| tree.iter().find(|m| &m.id == p));
+    }
+    path.reverse();
+    path
+}
+
+/// Follow the newest child from `id` until reaching a leaf
+fn newest_leaf_under(tree: &[Message], id: &str) -> String {
+    let mut leaf = id.to_string();
+    for _ in 0..tree.len() {
+        match tree.iter().rev().find(|m| m.parent_id.as_deref() == Some(leaf.as_str())) {
+            Some(child) => leaf = child.id.clone(),
+            None => break,
+        }
+    }
+    leaf
+}
+
+/// A parent must be an existing message of the same conversation; anything else would
+/// link branches across conversation trees
+fn check_parent(conn: &Connection, conversation_id: &str, parent_id: &str) -> Result<()> {
+    let owner: Option<String> = conn
+        .query_row("SELECT conversation_id FROM messages WHERE id = ?", params![parent_id], |row| row.get(0))
+        .optional()?;
+    if owner.as_deref() == Some(conversation_id) {
+        return Ok(());
+    }
+    Err(rusqlite::Error::SqliteFailure(
+        rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CONSTRAINT),
+        Some(format!("Parent message {} does not belong to conversation {}", parent_id, conversation_id)),
+    ))
 }
 
 impl Database {
+    /// Messages on the active branch, root first
     pub fn get_messages_by_conversation(&self, conversation_id: &str) -> Result<Vec<Message>> {
         let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT id, conversation_id, role, content, tool_call_history, created_at 
-             FROM messages 
-             WHERE conversation_id = ? 
-             ORDER BY created_at ASC"
-        )?;
-        
-        let messages = stmt.query_map(params![conversation_id], |row| {
-            Ok(Message {
-                id: row.get(0)?,
-                conversation_id: row.get(1)?,
-                role: row.get(2)?,
-                content: row.get(3)?,
-                tool_call_history: row.get(4)?,
-                created_at: row.get(5)?,
-            })
-        })?.collect::<Result<Vec<_>>>()?;
-        
+        let tree = load_tree(&conn, conversation_id)?;
+        Ok(match active_leaf(&conn, conversation_id, &tree)? {
+            Some(leaf) => path_to(&tree, &leaf),
+            None => Vec::new(),
+        })
+    }
+
+    /// Every message of the conversation across all branches, oldest first
+    pub fn get_message_tree(&self, conversation_id: &str) -> Result<Vec<Message>> {
+        let conn = self.conn.lock().unwrap();
+        load_tree(&conn, conversation_id)
+    }
+
+    /// User and assistant messages of every conversation of the pet that come after
+    /// `after` = (created_at RFC 3339, message id) in (created_at, id) order; None = from the start.
+    /// The id breaks ties between messages created at the same instant.
+    pub fn get_pet_messages_since(&self, pet_id: &str, after: Option<(&str, &str)>, limit: usize) -> Result<Vec<Message>> {
+        let conn = self.conn.lock().unwrap();
+        let columns = MESSAGE_COLUMNS.split(", ").map(|c| format!("m.{}", c)).collect::<Vec<_>>().join(", ");
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM messages m JOIN conversations c ON c.id = m.conversation_id
+             WHERE c.pet_id = ?1 AND (m.created_at > ?2 OR (m.created_at = ?2 AND m.id > ?3))
+               AND m.role IN ('user', 'assistant')
+             ORDER BY m.created_at ASC, m.id ASC LIMIT ?4",
+            columns
+        ))?;
+        let (since, since_id) = after.unwrap_or(("", ""));
+        let messages = stmt
+            .query_map(params![pet_id, since, since_id, limit as i64], row_to_message)?
+            .collect::<Result<Vec<_>>>()?;
         Ok(messages)
     }
 
+    pub fn get_message_by_id(&self, id: &str) -> Result<Option<Message>> {
+        let conn = self.conn.lock().unwrap();
+        load_message(&conn, id)
+    }
+
+    /// Append a message; without an explicit parent it continues the active branch.
+    /// The new message becomes the active leaf.
     pub fn create_message(&self, data: CreateMessageData) -> Result<Message> {
         let conn = self.conn.lock().unwrap();
         let id = Uuid::new_v4().to_string();
         let now = Utc::now().to_rfc3339();
-        
+
+        let parent_id = match data.parent_id {
+            Some(parent_id) => {
+                check_parent(&conn, &data.conversation_id, &parent_id)?;
+                Some(parent_id)
+            }
+            None => {
+                let tree = load_tree(&conn, &data.conversation_id)?;
+                active_leaf(&conn, &data.conversation_id, &tree)?
+            }
+        };
+
         conn.execute(
-            "INSERT INTO messages (id, conversation_id, role, content, tool_call_history, created_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            "INSERT INTO messages (id, conversation_id, parent_id, role, content, tool_call_history, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
             params![
                 id,
                 data.conversation_id,
+                parent_id,
                 data.role,
                 data.content,
                 data.tool_call_history,
                 now
             ],
         )?;
-        
-        // Update conversation's updated_at
+
+        // Update conversation's updated_at and move the active branch to the new message
         conn.execute(
-            "UPDATE conversations SET updated_at = ? WHERE id = ?",
-            params![now, data.conversation_id],
+            "UPDATE conversations SET updated_at = ?, active_leaf_id = ? WHERE id = ?",
+            params![now, id, data.conversation_id],
         )?;
-        
+
         Ok(Message {
             id,
             conversation_id: data.conversation_id,
+            parent_id,
             role: data.role,
             content: data.content,
             tool_call_history: data.tool_call_history,
@@ -83,9 +237,162 @@ impl Database {
         })
     }
 
+    /// Insert a message exactly as given (id, parent and timestamp included); used by importers.
+    /// Does not move the active branch.
+    pub fn insert_message(&self, message: &Message) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        if let Some(parent_id) = &message.parent_id {
+            check_parent(&conn, &message.conversation_id, parent_id)?;
+        }
+        conn.execute(
+            "INSERT INTO messages (id, conversation_id, parent_id, role, content, tool_call_history, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                message.id,
+                message.conversation_id,
+                message.parent_id,
+                message.role,
+                message.content,
+                message.tool_call_history,
+                message.created_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Edit a message by adding a sibling with the new content; the original stays on its own branch.
+    /// Returns the new message, which becomes the active leaf.
+    pub fn edit_message(&self, id: &str, content: &str) -> Result<Option<Message>> {
+        let original = match self.get_message_by_id(id)? {
+            Some(message) => message,
+            None => return Ok(None),
+        };
+        self.create_message(CreateMessageData {
+            conversation_id: original.conversation_id,
+            role: original.role,
+            content: content.to_string(),
+            tool_call_history: None,
+            parent_id: original.parent_id,
+        }).map(Some)
+    }
+
+    /// Rewind the active branch to just before `id` so the next message is created as its sibling.
+    /// Returns the new active path.
+    pub fn rewind_to_parent(&self, id: &str) -> Result<Option<Vec<Message>>> {
+        let conn = self.conn.lock().unwrap();
+        let message = match load_message(&conn, id)? {
+            Some(message) => message,
+            None => return Ok(None),
+        };
+        let Some(parent_id) = message.parent_id else {
+            return Ok(None);
+        };
+        set_active_leaf(&conn, &message.conversation_id, Some(&parent_id))?;
+        let tree = load_tree(&conn, &message.conversation_id)?;
+        Ok(Some(path_to(&tree, &parent_id)))
+    }
+
+    /// Make the branch containing `id` active, continuing down its newest replies.
+    /// Returns the new active path.
+    pub fn switch_message_branch(&self, id: &str) -> Result<Option<Vec<Message>>> {
+        let conn = self.conn.lock().unwrap();
+        let message = match load_message(&conn, id)? {
+            Some(message) => message,
+            None => return Ok(None),
+        };
+        let tree = load_tree(&conn, &message.conversation_id)?;
+        let leaf = newest_leaf_under(&tree, &message.id);
+        set_active_leaf(&conn, &message.conversation_id, Some(&leaf))?;
+        Ok(Some(path_to(&tree, &leaf)))
+    }
+
+    /// Forks along the active path
+    pub fn get_message_branches(&self, conversation_id: &str) -> Result<Vec<BranchPoint>> {
+        let conn = self.conn.lock().unwrap();
+        let tree = load_tree(&conn, conversation_id)?;
+        let Some(leaf) = active_leaf(&conn, conversation_id, &tree)? else {
+            return Ok(Vec::new());
+        };
+        Ok(path_to(&tree, &leaf).into_iter().filter_map(|active| {
+            let siblings: Vec<String> = tree.iter()
+                .filter(|m| m.parent_id == active.parent_id)
+                .map(|m| m.id.clone())
+                .collect();
+            if siblings.len() < 2 {
+                return None;
+            }
+            let active_index = siblings.iter().position(|id| *id == active.id).unwrap_or(0);
+            Some(BranchPoint { parent_id: active.parent_id, message_ids: siblings, active_index })
+        }).collect())
+    }
+
+    /// Make `history` the active branch, reusing the longest matching prefix of the current one.
+    /// The old tail past the divergence is kept as a sibling branch when `keep_branches` is set,
+    /// otherwise it is deleted up to the first message other branches still hang off.
+    pub fn save_active_branch(&self, conversation_id: &str, history: Vec<HistoryMessage>, keep_branches: bool) -> Result<Vec<Message>> {
+        let conn = self.conn.lock().unwrap();
+        // Deleting, re-inserting and moving the leaf must land together or the parent chain breaks
+        let tx = conn.unchecked_transaction()?;
+        let tree = load_tree(&tx, conversation_id)?;
+        let path = match active_leaf(&tx, conversation_id, &tree)? {
+            Some(leaf) => path_to(&tree, &leaf),
+            None => Vec::new(),
+        };
+        let common = path.iter().zip(&history)
+            .take_while(|(old, new)| old.role == new.role && old.content == new.content)
+            .count();
+
+        if !keep_branches {
+            for old in path[common..].iter().rev() {
+                let children: i64 = tx.query_row(
+                    "SELECT COUNT(*) FROM messages WHERE parent_id = ?",
+                    params![old.id],
+                    |row| row.get(0),
+                )?;
+                if children > 0 {
+                    break;
+                }
+                tx.execute("DELETE FROM messages WHERE id = ?", params![old.id])?;
+            }
+        }
+
+        let mut leaf = common.checked_sub(1).map(|i| path[i].id.clone());
+        for message in history.into_iter().skip(common) {
+            let id = Uuid::new_v4().to_string();
+            tx.execute(
+                "INSERT INTO messages (id, conversation_id, parent_id, role, content, tool_call_history, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![id, conversation_id, leaf, message.role, message.content, message.tool_call_history, Utc::now().to_rfc3339()],
+            )?;
+            leaf = Some(id);
+        }
+        tx.execute(
+            "UPDATE conversations SET updated_at = ?, active_leaf_id = ? WHERE id = ?",
+            params![Utc::now().to_rfc3339(), leaf, conversation_id],
+        )?;
+
+        let tree = load_tree(&tx, conversation_id)?;
+        tx.commit()?;
+        Ok(leaf.map(|leaf| path_to(&tree, &leaf)).unwrap_or_default())
+    }
+
+    /// Delete a single message; its replies are re-attached to its parent
     pub fn delete_message(&self, id: &str) -> Result<bool> {
         let conn = self.conn.lock().unwrap();
-        let rows = conn.execute("DELETE FROM messages WHERE id = ?", params![id])?;
+        let tx = conn.unchecked_transaction()?;
+        let Some(message) = load_message(&tx, id)? else {
+            return Ok(false);
+        };
+        tx.execute(
+            "UPDATE messages SET parent_id = ? WHERE parent_id = ?",
+            params![message.parent_id, id],
+        )?;
+        tx.execute(
+            "UPDATE conversations SET active_leaf_id = ? WHERE id = ? AND active_leaf_id = ?",
+            params![message.parent_id, message.conversation_id, id],
+        )?;
+        let rows = tx.execute("DELETE FROM messages WHERE id = ?", params![id])?;
+        tx.commit()?;
         Ok(rows > 0)
     }
 
@@ -95,6 +402,138 @@ impl Database {
             "DELETE FROM messages WHERE conversation_id = ?",
             params![conversation_id],
         )?;
+        set_active_leaf(&conn, conversation_id, None)?;
         Ok(rows)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn db_with_conversation() -> Database {
+        let db = Database::new(PathBuf::from(":memory:")).unwrap();
+        db.conn.lock().unwrap().execute_batch(
+            "INSERT INTO pets (id, name, created_at, updated_at) VALUES ('p', 'Mimi', '', '');
+             INSERT INTO conversations (id, pet_id, title, created_at, updated_at) VALUES ('c', 'p', 't', '', '');",
+        ).unwrap();
+        db
+    }
+
+    fn say(db: &Database, role: &str, content: &str) -> Message {
+        db.create_message(CreateMessageData {
+            conversation_id: "c".to_string(),
+            role: role.to_string(),
+            content: content.to_string(),
+            tool_call_history: None,
+            parent_id: None,
+        }).unwrap()
+    }
+
+    fn contents(messages: &[Message]) -> Vec<&str> {
+        messages.iter().map(|m| m.content.as_str()).collect()
+    }
+
+    #[test]
+    fn edit_and_regenerate_create_switchable_branches() {
+        let db = db_with_conversation();
+        say(&db, "user", "hi");
+        say(&db, "assistant", "meow");
+        let question = say(&db, "user", "nap?");
+        let answer = say(&db, "assistant", "no");
+        assert_eq!(contents(&db.get_messages_by_conversation("c").unwrap()), ["hi", "meow", "nap?", "no"]);
+
+        // Regenerate: rewind before the reply, the next reply becomes its sibling
+        let path = db.rewind_to_parent(&answer.id).unwrap().unwrap();
+        assert_eq!(contents(&path), ["hi", "meow", "nap?"]);
+        say(&db, "assistant", "yes");
+        assert_eq!(contents(&db.get_messages_by_conversation("c").unwrap()), ["hi", "meow", "nap?", "yes"]);
+
+        // Edit the question: a new sibling branch without any reply yet
+        let edited = db.edit_message(&question.id, "food?").unwrap().unwrap();
+        assert_eq!(edited.parent_id, question.parent_id);
+        assert_eq!(contents(&db.get_messages_by_conversation("c").unwrap()), ["hi", "meow", "food?"]);
+        let branches = db.get_message_branches("c").unwrap();
+        assert_eq!(branches.len(), 1);
+        assert_eq!(branches[0].message_ids, [question.id.clone(), edited.id.clone()]);
+        assert_eq!(branches[0].active_index, 1);
+
+        // Back to the original question follows its newest reply
+        let path = db.switch_message_branch(&question.id).unwrap().unwrap();
+        assert_eq!(contents(&path), ["hi", "meow", "nap?", "yes"]);
+        let branches = db.get_message_branches("c").unwrap();
+        assert_eq!(branches.len(), 2);
+        assert_eq!(branches[0].active_index, 0);
+        assert_eq!(branches[1].active_index, 1);
+
+        assert_eq!(db.get_message_tree("c").unwrap().len(), 6);
+    }
+
+    #[test]
+    fn saving_a_history_reuses_the_matching_prefix() {
+        let db = db_with_conversation();
+        let entry = |role: &str, content: &str| HistoryMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+            tool_call_history: None,
+        };
+        let first = db.save_active_branch("c", vec![entry("user", "hi"), entry("assistant", "meow")], false).unwrap();
+        let saved = db.save_active_branch("c", vec![entry("user", "hi"), entry("assistant", "meow"), entry("user", "pet")], false).unwrap();
+        assert_eq!(saved[0].id, first[0].id);
+        assert_eq!(saved[1].id, first[1].id);
+
+        // An edited reply keeps the original as a branch
+        db.save_active_branch("c", vec![entry("user", "hi"), entry("assistant", "purr")], true).unwrap();
+        assert_eq!(db.get_message_branches("c").unwrap()[0].message_ids.len(), 2);
+
+        // Without keep_branches the divergent tail is dropped
+        db.save_active_branch("c", vec![entry("user", "hi")], false).unwrap();
+        assert_eq!(contents(&db.get_message_tree("c").unwrap()), ["hi", "meow", "pet"]);
+        assert_eq!(contents(&db.get_messages_by_conversation("c").unwrap()), ["hi"]);
+    }
+
+    #[test]
+    fn delete_reattaches_replies_and_clear_resets_the_branch() {
+        let db = db_with_conversation();
+        say(&db, "user", "a");
+        let b = say(&db, "assistant", "b");
+        say(&db, "user", "c");
+        assert!(db.delete_message(&b.id).unwrap());
+        assert_eq!(contents(&db.get_messages_by_conversation("c").unwrap()), ["a", "c"]);
+
+        db.clear_conversation_messages("c").unwrap();
+        say(&db, "user", "fresh");
+        let path = db.get_messages_by_conversation("c").unwrap();
+        assert_eq!(contents(&path), ["fresh"]);
+        assert_eq!(path[0].parent_id, None);
+    }
+
+    #[test]
+    fn parents_from_another_conversation_are_rejected() {
+        let db = db_with_conversation();
+        db.conn.lock().unwrap().execute(
+            "INSERT INTO conversations (id, pet_id, title, created_at, updated_at) VALUES ('other', 'p', 't', '', '')",
+            [],
+        ).unwrap();
+        let foreign = say(&db, "user", "a");
+
+        let result = db.create_message(CreateMessageData {
+            conversation_id: "other".to_string(),
+            role: "user".to_string(),
+            content: "b".to_string(),
+            tool_call_history: None,
+            parent_id: Some(foreign.id.clone()),
+        });
+        assert!(result.is_err());
+
+        let imported = Message {
+            id: "imported".to_string(),
+            conversation_id: "other".to_string(),
+            parent_id: Some(foreign.id.clone()),
+            ..foreign.clone()
+        };
+        assert!(db.insert_message(&imported).is_err());
+        assert!(db.get_messages_by_conversation("other").unwrap().is_empty());
+    }
+}
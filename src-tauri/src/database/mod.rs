@@ -3,9 +3,14 @@ pub mod conversations;
 pub mod messages;
 pub mod settings;
 pub mod mcp_servers;
+pub mod mcp_config;
+pub mod mcp_permissions;
+pub mod mcp_result_limits;
 pub mod api_providers;
 pub mod skins;
 pub mod chat_history;
+pub mod open_tabs;
+pub mod memory_candidates;
 
 use rusqlite::{Connection, Result};
 use std::sync::Mutex;
@@ -69,6 +74,10 @@ impl Database {
                 title TEXT,
                 created_at TEXT NOT NULL,
                 updated_at TEXT NOT NULL,
+                active_leaf_id TEXT,
+                summary TEXT,
+                summary_until_id TEXT,
+                memory_enabled INTEGER,
                 FOREIGN KEY (pet_id) REFERENCES pets(id)
             )",
             [],
@@ -79,6 +88,7 @@ impl Database {
             "CREATE TABLE IF NOT EXISTS messages (
                 id TEXT PRIMARY KEY,
                 conversation_id TEXT NOT NULL,
+                parent_id TEXT,
                 role TEXT NOT NULL,
                 content TEXT NOT NULL,
                 tool_call_history TEXT,
@@ -113,6 +123,8 @@ impl Database {
                 show_in_toolbar INTEGER DEFAULT 1,
                 toolbar_order INTEGER DEFAULT 0,
                 max_iterations INTEGER,
+                restart_policy TEXT,
+                sandbox TEXT,
                 created_at TEXT NOT NULL,
                 updated_at TEXT NOT NULL
             )",
@@ -124,6 +136,83 @@ impl Database {
         let _ = conn.execute("ALTER TABLE mcp_servers ADD COLUMN url TEXT", []);
         let _ = conn.execute("ALTER TABLE mcp_servers ADD COLUMN api_key TEXT", []);
         let _ = conn.execute("ALTER TABLE mcp_servers ADD COLUMN max_iterations INTEGER", []);
+        let _ = conn.execute("ALTER TABLE mcp_servers ADD COLUMN restart_policy TEXT", []);
+        let _ = conn.execute("ALTER TABLE mcp_servers ADD COLUMN sandbox TEXT", []);
+
+        // OAuth credentials for HTTP MCP servers (JSON, one row per server)
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS mcp_oauth_credentials (
+                server_id TEXT PRIMARY KEY,
+                credentials TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        // Per-tool permission rules for MCP calls (see mcp::permissions)
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS mcp_tool_policies (
+                id TEXT PRIMARY KEY,
+                server_id TEXT NOT NULL,
+                tool_name TEXT NOT NULL DEFAULT '*',
+                pet_id TEXT,
+                action TEXT NOT NULL,
+                arg_pattern TEXT,
+                remembered INTEGER DEFAULT 0,
+                created_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        // Audit log of every MCP tool call, including denied ones
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS mcp_tool_audit_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                server_id TEXT NOT NULL,
+                server_name TEXT,
+                tool_name TEXT NOT NULL,
+                pet_id TEXT,
+                arguments TEXT,
+                decision TEXT NOT NULL,
+                success INTEGER NOT NULL DEFAULT 0,
+                result_summary TEXT,
+                error TEXT,
+                duration_ms INTEGER NOT NULL DEFAULT 0,
+                created_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+        let _ = conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_mcp_tool_audit_log_server ON mcp_tool_audit_log(server_id)",
+            [],
+        );
+
+        // Per-tool size limits for tool results; tool_name '*' = server default
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS mcp_tool_result_limits (
+                server_id TEXT NOT NULL,
+                tool_name TEXT NOT NULL DEFAULT '*',
+                max_text_chars INTEGER NOT NULL,
+                max_image_dimension INTEGER NOT NULL,
+                max_image_bytes INTEGER NOT NULL,
+                spill INTEGER NOT NULL DEFAULT 1,
+                updated_at TEXT NOT NULL,
+                PRIMARY KEY (server_id, tool_name)
+            )",
+            [],
+        )?;
+
+        // Tools a pet should not see; everything else stays enabled
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS pet_disabled_tools (
+                pet_id TEXT NOT NULL,
+                server_id TEXT NOT NULL,
+                tool_name TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                PRIMARY KEY (pet_id, server_id, tool_name)
+            )",
+            [],
+        )?;
 
         // Native QQ connector accounts. Runtime binaries and login data live in
         // app-data; this table only maps a QQ identity to its managed MCP server.
@@ -189,11 +278,75 @@ impl Database {
         // Migration: add is_builtin and is_hidden columns if not exists
         let _ = conn.execute("ALTER TABLE skins ADD COLUMN is_builtin INTEGER DEFAULT 0", []);
 
+        // Open chat tabs, written behind from TabState so they survive a restart
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS open_tabs (
+                conversation_id TEXT PRIMARY KEY,
+                position INTEGER NOT NULL DEFAULT 0,
+                is_active INTEGER DEFAULT 0,
+                draft TEXT,
+                messages TEXT,
+                is_thinking INTEGER DEFAULT 0,
+                partial_reply TEXT,
+                updated_at TEXT NOT NULL,
+                FOREIGN KEY (conversation_id) REFERENCES conversations(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+
+        // Facts extracted by the memory consolidator, waiting for review
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS memory_candidates (
+                id TEXT PRIMARY KEY,
+                pet_id TEXT NOT NULL,
+                file TEXT NOT NULL,
+                section TEXT,
+                fact TEXT NOT NULL,
+                replaces TEXT,
+                source_kind TEXT NOT NULL,
+                source_id TEXT NOT NULL,
+                source_message_id TEXT,
+                source_time TEXT NOT NULL,
+                status TEXT NOT NULL DEFAULT 'pending',
+                created_at TEXT NOT NULL,
+                reviewed_at TEXT
+            )",
+            [],
+        )?;
+        let _ = conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_memory_candidates_pet ON memory_candidates(pet_id, status)",
+            [],
+        );
+
         // Index: speed up COUNT(*) and message lookups by conversation_id
         let _ = conn.execute(
             "CREATE INDEX IF NOT EXISTS idx_messages_conversation_id ON messages(conversation_id)",
             [],
         );
+        // Migration: messages form a tree via parent_id; chain existing flat histories in order
+        if conn.execute("ALTER TABLE messages ADD COLUMN parent_id TEXT", []).is_ok() {
+            conn.execute(
+                "UPDATE messages SET parent_id = (
+                    SELECT p.id FROM messages p
+                    WHERE p.conversation_id = messages.conversation_id
+                      AND (p.created_at < messages.created_at
+                           OR (p.created_at = messages.created_at AND p.rowid < messages.rowid))
+                    ORDER BY p.created_at DESC, p.rowid DESC LIMIT 1
+                )",
+                [],
+            )?;
+        }
+        // Migration: last message of the branch currently shown (NULL = newest message)
+        let _ = conn.execute("ALTER TABLE conversations ADD COLUMN active_leaf_id TEXT", []);
+        let _ = conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_messages_parent_id ON messages(parent_id)",
+            [],
+        );
+        // Migration: rolling summary of older messages, up to and including summary_until_id
+        let _ = conn.execute("ALTER TABLE conversations ADD COLUMN summary TEXT", []);
+        let _ = conn.execute("ALTER TABLE conversations ADD COLUMN summary_until_id TEXT", []);
+        // Migration: per-conversation memory switch (NULL = follow memoryEnabledByDefault)
+        let _ = conn.execute("ALTER TABLE conversations ADD COLUMN memory_enabled INTEGER", []);
         let _ = conn.execute("ALTER TABLE skins ADD COLUMN is_hidden INTEGER DEFAULT 0", []);
         // Migration: add moods column for dynamic mood/expression support
         // 固定表情系统: ["normal", "smile", "sad", "shocked", "thinking"]
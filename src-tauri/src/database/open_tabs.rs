@@ -0,0 +1,176 @@
+// Open chat tabs and the last in-memory state `tab_state::TabState` held for them.
+// Tab order comes from the frontend; drafts and message snapshots are written behind.
+
+use rusqlite::{params, Result, Row};
+use serde::{Deserialize, Serialize};
+use chrono::Utc;
+use super::Database;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenTab {
+    pub conversation_id: String,
+    #[serde(default)]
+    pub position: i64,
+    #[serde(default)]
+    pub is_active: bool,
+    /// Unsent composer text; ignored by `set_open_tabs`, see `save_tab_snapshot`
+    #[serde(default)]
+    pub draft: Option<String>,
+}
+
+/// What TabState held for one open tab at the last flush
+#[derive(Debug, Clone, PartialEq)]
+pub struct TabSnapshot {
+    pub conversation_id: String,
+    /// JSON array of `tab_state::Message`
+    pub messages: String,
+    pub is_thinking: bool,
+    /// Text streamed so far for a reply that had not finished
+    pub partial_reply: Option<String>,
+    pub draft: Option<String>,
+}
+
+fn row_to_snapshot(row: &Row) -> Result<TabSnapshot> {
+    Ok(TabSnapshot {
+        conversation_id: row.get(0)?,
+        messages: row.get::<_, Option<String>>(1)?.unwrap_or_else(|| "[]".to_string()),
+        is_thinking: row.get::<_, i32>(2)? != 0,
+        partial_reply: row.get(3)?,
+        draft: row.get(4)?,
+    })
+}
+
+impl Database {
+    /// Open tabs in display order; tabs whose conversation was deleted are skipped
+    pub fn get_open_tabs(&self) -> Result<Vec<OpenTab>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT t.conversation_id, t.position, t.is_active, t.draft
+             FROM open_tabs t JOIN conversations c ON c.id = t.conversation_id
+             ORDER BY t.position, t.rowid"
+        )?;
+        let tabs = stmt.query_map([], |row| {
+            Ok(OpenTab {
+                conversation_id: row.get(0)?,
+                position: row.get(1)?,
+                is_active: row.get::<_, i32>(2)? != 0,
+                draft: row.get(3)?,
+            })
+        })?.collect::<Result<Vec<_>>>()?;
+        Ok(tabs)
+    }
+
+    /// Replace the tab list. Tabs that stay open keep their draft and snapshot.
+    pub fn set_open_tabs(&self, tabs: &[OpenTab]) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        {
+            let mut existing = tx.prepare("SELECT conversation_id FROM open_tabs")?;
+            let closed: Vec<String> = existing
+                .query_map([], |row| row.get::<_, String>(0))?
+                .collect::<Result<Vec<_>>>()?
+                .into_iter()
+                .filter(|id| !tabs.iter().any(|t| &t.conversation_id == id))
+                .collect();
+            for id in closed {
+                tx.execute("DELETE FROM open_tabs WHERE conversation_id = ?", params![id])?;
+            }
+            for (index, tab) in tabs.iter().enumerate() {
+                tx.execute(
+                    "INSERT INTO open_tabs (conversation_id, position, is_active, updated_at)
+                     VALUES (?1, ?2, ?3, ?4)
+                     ON CONFLICT(conversation_id) DO UPDATE SET
+                        position = excluded.position,
+                        is_active = excluded.is_active,
+                        updated_at = excluded.updated_at",
+                    params![tab.conversation_id, index as i64, tab.is_active as i32, now],
+                )?;
+            }
+        }
+        tx.commit()
+    }
+
+    /// Snapshots of every open tab that has been flushed at least once
+    pub fn get_tab_snapshots(&self) -> Result<Vec<TabSnapshot>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT conversation_id, messages, is_thinking, partial_reply, draft
+             FROM open_tabs
+             WHERE messages IS NOT NULL OR draft IS NOT NULL
+             ORDER BY position"
+        )?;
+        let snapshots = stmt.query_map([], row_to_snapshot)?
+            .collect::<Result<Vec<_>>>()?;
+        Ok(snapshots)
+    }
+
+    /// Store a tab's in-memory state. Returns false when the tab is not open.
+    pub fn save_tab_snapshot(&self, snapshot: &TabSnapshot) -> Result<bool> {
+        let now = Utc::now().to_rfc3339();
+        let conn = self.conn.lock().unwrap();
+        let rows = conn.execute(
+            "UPDATE open_tabs
+             SET messages = ?1, is_thinking = ?2, partial_reply = ?3, draft = ?4, updated_at = ?5
+             WHERE conversation_id = ?6",
+            params![
+                snapshot.messages,
+                snapshot.is_thinking as i32,
+                snapshot.partial_reply,
+                snapshot.draft,
+                now,
+                snapshot.conversation_id,
+            ],
+        )?;
+        Ok(rows > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tab(id: &str, is_active: bool) -> OpenTab {
+        OpenTab { conversation_id: id.to_string(), position: 0, is_active, draft: None }
+    }
+
+    #[test]
+    fn reordering_keeps_snapshots_and_closing_drops_them() {
+        let db = Database::new(std::path::PathBuf::from(":memory:")).unwrap();
+        {
+            let conn = db.conn.lock().unwrap();
+            conn.execute_batch(
+                "INSERT INTO pets (id, name, created_at, updated_at) VALUES ('p', 'Mimi', '', '');
+                 INSERT INTO conversations (id, pet_id, title, created_at, updated_at) VALUES
+                    ('a', 'p', 'A', '', ''), ('b', 'p', 'B', '', ''), ('c', 'p', 'C', '', '');"
+            ).unwrap();
+        }
+        db.set_open_tabs(&[tab("a", false), tab("b", true)]).unwrap();
+        let snapshot = TabSnapshot {
+            conversation_id: "b".to_string(),
+            messages: r#"[{"role":"user","content":"hi"}]"#.to_string(),
+            is_thinking: true,
+            partial_reply: Some("Hel".to_string()),
+            draft: Some("next question".to_string()),
+        };
+        assert!(db.save_tab_snapshot(&snapshot).unwrap());
+        assert!(!db.save_tab_snapshot(&TabSnapshot { conversation_id: "c".to_string(), ..snapshot.clone() }).unwrap());
+
+        db.set_open_tabs(&[tab("c", false), tab("b", true), tab("a", false)]).unwrap();
+        let order: Vec<_> = db.get_open_tabs().unwrap().into_iter()
+            .map(|t| (t.conversation_id, t.position, t.is_active, t.draft))
+            .collect();
+        assert_eq!(order, vec![
+            ("c".to_string(), 0, false, None),
+            ("b".to_string(), 1, true, Some("next question".to_string())),
+            ("a".to_string(), 2, false, None),
+        ]);
+        assert_eq!(db.get_tab_snapshots().unwrap(), vec![snapshot]);
+
+        db.set_open_tabs(&[tab("a", true)]).unwrap();
+        assert!(db.get_tab_snapshots().unwrap().is_empty());
+        assert!(db.delete_conversation("a").unwrap());
+        assert!(db.get_open_tabs().unwrap().is_empty());
+    }
+}
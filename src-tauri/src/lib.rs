@@ -1,6 +1,5 @@
 mod database;
 mod mcp;
-mod message_cache;
 mod tab_state;
 mod llm;
 mod workspace;
@@ -10,13 +9,19 @@ mod platform;
 mod window_layout;
 mod qq_connector;
 mod commands;
+mod summarizer;
+mod memory;
+mod system_prompt;
 #[cfg(target_os = "linux")]
 mod linux_shortcuts;
 
-use database::{Database, pets, conversations, messages, settings, mcp_servers, api_providers, skins};
+use database::{Database, pets, conversations, messages, settings, mcp_servers, mcp_config, mcp_permissions, mcp_result_limits, api_providers, skins};
 use database::chat_history::{InsertChatMessageData, ChatSearchParams, ChatSearchResult, ChatContextResult};
-use mcp::{McpManager, ServerStatus, McpToolInfo, CallToolResponse, ToolContent, SamplingLlmConfig};
-use message_cache::TabMessageCache;
+use mcp::{McpManager, ServerStatus, McpToolInfo, CallToolResponse, ToolContent, SamplingLlmConfig, McpEvent, McpResourceTemplate, ResourceReadResult, RestartPolicy, ResultLimits, SpillTarget};
+use mcp::oauth::{self as mcp_oauth, OAuthCredentials};
+use mcp::permissions::{self as mcp_policy, ApprovalDecision, PermissionAction};
+use mcp::server::{McpHostHandle, PetGptMcpServer};
+use mcp::sandbox::Sandbox;
 use tab_state::TabState;
 use llm::{LlmClient, LlmRequest, LlmResponse, StreamChunk, LlmStreamCancellation, LlmProxy};
 use workspace::WorkspaceEngine;
@@ -43,9 +48,21 @@ type LlmCancelState = Arc<LlmStreamCancellation>;
 // Type alias for LLM proxy state (social agent 用)
 type LlmProxyState = Arc<LlmProxy>;
 
+// Type alias for the background conversation titling / summary service
+type SummarizerState = Arc<summarizer::ConversationSummarizer>;
+
+// Type alias for the background memory consolidation service
+type MemoryState = Arc<memory::MemoryConsolidator>;
+
 // Type alias for MCP manager state
 type McpState = Arc<tokio::sync::RwLock<McpManager>>;
 
+// Type alias for the built-in MCP server (PetGPT exposed to other MCP hosts)
+type McpHostServerState = Arc<PetGptMcpServer>;
+
+// Type alias for the running loopback HTTP endpoint of the built-in MCP server
+type McpHostState = Arc<tokio::sync::Mutex<Option<McpHostHandle>>>;
+
 // Type alias for workspace state
 type WorkspaceFileState = Arc<WorkspaceEngine>;
 
@@ -121,8 +138,10 @@ fn set_vibrancy_enabled(app: AppHandle, enabled: bool) -> Result<(), String> {
 #[tauri::command]
 async fn llm_call(
     llm_client: State<'_, LlmState>,
-    request: LlmRequest,
+    db: State<'_, DbState>,
+    mut request: LlmRequest,
 ) -> Result<LlmResponse, String> {
+    summarizer::compact_request(&db, &mut request);
     llm_client.call(&request).await
 }
 
@@ -131,9 +150,15 @@ async fn llm_call(
 async fn llm_stream(
     app: AppHandle,
     cancellation: State<'_, LlmCancelState>,
-    request: LlmRequest,
+    db: State<'_, DbState>,
+    tab_state: State<'_, TabState>,
+    mut request: LlmRequest,
 ) -> Result<LlmResponse, String> {
-    llm::stream_chat(app, request, cancellation.inner().clone()).await
+    summarizer::compact_request(&db, &mut request);
+    let tab_state = tab_state.inner();
+    llm::stream_chat(app, request, cancellation.inner().clone(), |chunk: &llm::StreamChunk| {
+        tab_state.record_stream_chunk(&chunk.conversation_id, &chunk.full_text, chunk.done);
+    }).await
 }
 
 /// 取消指定会话的 LLM 流
@@ -225,6 +250,16 @@ fn update_conversation_title(db: State<DbState>, id: String, title: String) -> R
     db.update_conversation_title(&id, &title).map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+fn get_conversation_memory_enabled(db: State<DbState>, id: String) -> Result<Option<bool>, String> {
+    db.get_conversation_memory_enabled(&id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn set_conversation_memory_enabled(db: State<DbState>, id: String, enabled: bool) -> Result<bool, String> {
+    db.set_conversation_memory_enabled(&id, enabled).map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 fn delete_conversation(db: State<DbState>, id: String) -> Result<bool, String> {
     db.delete_conversation(&id).map_err(|e| e.to_string())
@@ -261,11 +296,21 @@ fn get_messages(db: State<DbState>, conversationId: String) -> Result<Vec<messag
 }
 
 #[tauri::command]
-fn create_message(db: State<DbState>, data: messages::CreateMessageData) -> Result<messages::Message, String> {
+fn create_message(
+    app: AppHandle,
+    db: State<DbState>,
+    summarizer: State<SummarizerState>,
+    data: messages::CreateMessageData,
+) -> Result<messages::Message, String> {
     println!("[Rust create_message] ★ convId={}, role={}, content_len={}", data.conversation_id, data.role, data.content.len());
     let result = db.create_message(data);
     match &result {
-        Ok(msg) => println!("[Rust create_message] ✅ saved msgId={} to convId={}", msg.id, msg.conversation_id),
+        Ok(msg) => {
+            println!("[Rust create_message] ✅ saved msgId={} to convId={}", msg.id, msg.conversation_id);
+            if msg.role == "assistant" {
+                summarizer.schedule(&msg.conversation_id, &app);
+            }
+        }
         Err(e) => println!("[Rust create_message] ❌ ERROR: {:?}", e),
     }
     result.map_err(|e| e.to_string())
@@ -283,6 +328,94 @@ fn clear_conversation_messages(db: State<DbState>, conversationId: String) -> Re
     result.map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+#[allow(non_snake_case)]
+fn get_message_tree(db: State<DbState>, conversationId: String) -> Result<Vec<messages::Message>, String> {
+    db.get_message_tree(&conversationId).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+fn get_message_branches(db: State<DbState>, conversationId: String) -> Result<Vec<messages::BranchPoint>, String> {
+    db.get_message_branches(&conversationId).map_err(|e| e.to_string())
+}
+
+/// Save a full history as the active branch; `keepBranches` keeps a divergent old tail as a sibling branch
+#[tauri::command]
+#[allow(non_snake_case)]
+fn save_conversation_branch(
+    app: AppHandle,
+    db: State<DbState>,
+    tab_state: State<TabState>,
+    summarizer: State<SummarizerState>,
+    conversationId: String,
+    history: Vec<messages::HistoryMessage>,
+    keepBranches: Option<bool>,
+) -> Result<Vec<messages::Message>, String> {
+    let branch = db.save_active_branch(&conversationId, history, keepBranches.unwrap_or(false))
+        .map_err(|e| e.to_string())?;
+    tab_state.replace_with_branch(&conversationId, &branch, &app);
+    if branch.last().is_some_and(|m| m.role == "assistant") {
+        summarizer.schedule(&conversationId, &app);
+    }
+    Ok(branch)
+}
+
+/// Edit a message as a new sibling branch and make it active
+#[tauri::command]
+fn edit_message(
+    app: AppHandle,
+    db: State<DbState>,
+    tab_state: State<TabState>,
+    id: String,
+    content: String,
+) -> Result<messages::Message, String> {
+    let message = db.edit_message(&id, &content)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Message not found: {}", id))?;
+    let branch = db.get_messages_by_conversation(&message.conversation_id).map_err(|e| e.to_string())?;
+    tab_state.replace_with_branch(&message.conversation_id, &branch, &app);
+    Ok(message)
+}
+
+/// Rewind the active branch to before an assistant reply; the next reply is saved as its sibling
+#[tauri::command]
+fn regenerate_message(
+    app: AppHandle,
+    db: State<DbState>,
+    tab_state: State<TabState>,
+    id: String,
+) -> Result<Vec<messages::Message>, String> {
+    let message = db.get_message_by_id(&id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Message not found: {}", id))?;
+    if message.role != "assistant" {
+        return Err(format!("Only assistant replies can be regenerated (got {})", message.role));
+    }
+    let branch = db.rewind_to_parent(&id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Nothing to regenerate from: the reply has no preceding message".to_string())?;
+    tab_state.replace_with_branch(&message.conversation_id, &branch, &app);
+    Ok(branch)
+}
+
+/// Activate the branch containing a message, following its newest replies
+#[tauri::command]
+fn switch_message_branch(
+    app: AppHandle,
+    db: State<DbState>,
+    tab_state: State<TabState>,
+    id: String,
+) -> Result<Vec<messages::Message>, String> {
+    let branch = db.switch_message_branch(&id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Message not found: {}", id))?;
+    if let Some(first) = branch.first() {
+        tab_state.replace_with_branch(&first.conversation_id, &branch, &app);
+    }
+    Ok(branch)
+}
+
 // ============ Chat History Commands (QQ 群聊存档) ============
 
 #[tauri::command]
@@ -884,12 +1017,178 @@ fn delete_mcp_server(db: State<DbState>, id: String) -> Result<bool, String> {
     db.delete_mcp_server(&id).map_err(|e| e.to_string())
 }
 
+/// Import servers from an `mcpServers` JSON config; `dry_run` only previews the result
+#[tauri::command]
+fn import_mcp_servers(
+    db: State<DbState>,
+    json: String,
+    on_conflict: Option<mcp_config::McpImportConflict>,
+    dry_run: Option<bool>,
+) -> Result<mcp_config::McpImportReport, String> {
+    let servers = mcp_config::parse_mcp_config(&json)?;
+    db.import_mcp_servers(servers, on_conflict.unwrap_or_default(), dry_run.unwrap_or(false))
+        .map_err(|e| e.to_string())
+}
+
+/// Export servers (all, or the given ids) as an `mcpServers` JSON config
+#[tauri::command]
+fn export_mcp_servers(db: State<DbState>, ids: Option<Vec<String>>) -> Result<String, String> {
+    let servers = db.get_all_mcp_servers().map_err(|e| e.to_string())?;
+    let servers: Vec<_> = match ids {
+        Some(ids) => servers.into_iter().filter(|s| ids.contains(&s.id)).collect(),
+        None => servers,
+    };
+    Ok(mcp_config::export_mcp_config(&servers))
+}
+
+// ============ MCP Tool Permission Commands ============
+
+#[tauri::command]
+fn get_mcp_tool_policies(
+    db: State<DbState>,
+    server_id: Option<String>,
+) -> Result<Vec<mcp_permissions::ToolPolicy>, String> {
+    db.get_tool_policies(server_id.as_deref()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn create_mcp_tool_policy(
+    db: State<DbState>,
+    data: mcp_permissions::CreateToolPolicyData,
+) -> Result<mcp_permissions::ToolPolicy, String> {
+    if PermissionAction::parse(&data.action).is_none() {
+        return Err(format!("Unknown permission action '{}' (expected allow, ask or deny)", data.action));
+    }
+    db.create_tool_policy(data).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn delete_mcp_tool_policy(db: State<DbState>, id: String) -> Result<bool, String> {
+    db.delete_tool_policy(&id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_mcp_tool_audit_log(
+    db: State<DbState>,
+    server_id: Option<String>,
+    pet_id: Option<String>,
+    limit: Option<i64>,
+) -> Result<Vec<mcp_permissions::ToolAuditEntry>, String> {
+    db.get_tool_audit_log(server_id.as_deref(), pet_id.as_deref(), limit.unwrap_or(200))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn clear_mcp_tool_audit_log(db: State<DbState>) -> Result<usize, String> {
+    db.clear_tool_audit_log().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_mcp_tool_result_limits(
+    db: State<DbState>,
+    server_id: Option<String>,
+) -> Result<Vec<mcp_result_limits::ToolResultLimits>, String> {
+    db.get_tool_result_limits(server_id.as_deref()).map_err(|e| e.to_string())
+}
+
+/// `tool_name` "*" (or empty) sets the default for every tool on the server
+#[tauri::command]
+fn set_mcp_tool_result_limits(
+    db: State<DbState>,
+    server_id: String,
+    tool_name: String,
+    limits: ResultLimits,
+) -> Result<mcp_result_limits::ToolResultLimits, String> {
+    db.set_tool_result_limits(&server_id, &tool_name, &limits).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn delete_mcp_tool_result_limits(db: State<DbState>, server_id: String, tool_name: String) -> Result<bool, String> {
+    db.delete_tool_result_limits(&server_id, &tool_name).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_pet_disabled_tools(
+    db: State<DbState>,
+    pet_id: String,
+) -> Result<Vec<mcp_permissions::PetToolRef>, String> {
+    db.get_pet_disabled_tools(&pet_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn set_pet_tool_enabled(
+    db: State<DbState>,
+    pet_id: String,
+    server_id: String,
+    tool_name: String,
+    enabled: bool,
+) -> Result<(), String> {
+    db.set_pet_tool_enabled(&pet_id, &server_id, &tool_name, enabled).map_err(|e| e.to_string())
+}
+
+/// Answer an `mcp-tool-approval-requested` prompt
+#[tauri::command]
+async fn mcp_respond_tool_approval(
+    mcp: State<'_, McpState>,
+    request_id: String,
+    approved: bool,
+    remember: Option<bool>,
+) -> Result<bool, String> {
+    let manager = mcp.read().await;
+    Ok(manager.approvals().respond(&request_id, ApprovalDecision {
+        approved,
+        remember: remember.unwrap_or(false),
+    }))
+}
+
 // ============ MCP Runtime Commands ============
 
+/// Stored OAuth credentials for an HTTP server, if it has been authorized for its current URL.
+/// Tokens issued for another resource are never sent, so editing the URL can't leak them to a new host.
+fn load_mcp_oauth_credentials(db: &Database, server_id: &str, server_url: &str) -> Option<OAuthCredentials> {
+    let json = db.get_mcp_oauth_credentials(server_id).ok().flatten()?;
+    let credentials: OAuthCredentials = match serde_json::from_str(&json) {
+        Ok(credentials) => credentials,
+        Err(e) => {
+            log::warn!("[MCP-OAuth] Ignoring unreadable credentials for {}: {}", server_id, e);
+            return None;
+        }
+    };
+    if credentials.resource != mcp_oauth::canonical_resource(server_url) {
+        log::warn!("[MCP-OAuth] Ignoring credentials for {}: issued for {}, server now points at {}",
+            server_id, credentials.resource, server_url);
+        return None;
+    }
+    Some(credentials)
+}
+
+/// Resolve a server's sandbox profile: the pet workspace when one is pinned, else a
+/// per-server scratch directory under app data.
+fn resolve_mcp_sandbox(
+    app: &AppHandle,
+    workspace: &WorkspaceEngine,
+    server: &mcp_servers::McpServer,
+) -> Result<Option<Sandbox>, String> {
+    let profile = match &server.sandbox {
+        Some(profile) if profile.enabled => profile.clone(),
+        _ => return Ok(None),
+    };
+    let work_dir = match profile.workspace_pet_id.as_deref() {
+        Some(pet_id) => workspace.get_full_path(pet_id, ".").map_err(|e| e.to_string())?,
+        None => app.path().app_data_dir()
+            .map_err(|e| format!("Failed to get app data dir: {}", e))?
+            .join("mcp-sandbox")
+            .join(&server.id),
+    };
+    Ok(Some(Sandbox { profile, work_dir }))
+}
+
 #[tauri::command]
 async fn mcp_start_server(
+    app: AppHandle,
     db: State<'_, DbState>,
     mcp: State<'_, McpState>,
+    workspace: State<'_, WorkspaceFileState>,
     server_id: String,
 ) -> Result<ServerStatus, String> {
     // Get server config from database
@@ -898,16 +1197,23 @@ async fn mcp_start_server(
         .ok_or_else(|| format!("Server not found: {}", server_id))?;
 
     let manager = mcp.read().await;
+    manager.set_restart_policy(&server.id, RestartPolicy::effective(
+        server.restart_policy.as_deref(),
+        server.auto_start,
+    ));
+    manager.set_sandbox(&server.id, resolve_mcp_sandbox(&app, &workspace, &server)?);
     
     // Start based on transport type
     match server.transport {
         mcp_servers::TransportType::Http => {
             let url = server.url.ok_or_else(|| "HTTP server requires a URL".to_string())?;
+            let oauth = load_mcp_oauth_credentials(&db, &server.id, &url);
             manager.start_http_server(
                 &server.id,
                 &server.name,
                 &url,
                 server.api_key,
+                oauth,
             ).await
         }
         mcp_servers::TransportType::Stdio => {
@@ -933,8 +1239,10 @@ async fn mcp_stop_server(
 
 #[tauri::command]
 async fn mcp_restart_server(
+    app: AppHandle,
     db: State<'_, DbState>,
     mcp: State<'_, McpState>,
+    workspace: State<'_, WorkspaceFileState>,
     server_id: String,
 ) -> Result<ServerStatus, String> {
     // Stop first
@@ -952,15 +1260,22 @@ async fn mcp_restart_server(
         .ok_or_else(|| format!("Server not found: {}", server_id))?;
 
     let manager = mcp.read().await;
+    manager.set_restart_policy(&server.id, RestartPolicy::effective(
+        server.restart_policy.as_deref(),
+        server.auto_start,
+    ));
+    manager.set_sandbox(&server.id, resolve_mcp_sandbox(&app, &workspace, &server)?);
     
     match server.transport {
         mcp_servers::TransportType::Http => {
             let url = server.url.ok_or_else(|| "HTTP server requires a URL".to_string())?;
+            let oauth = load_mcp_oauth_credentials(&db, &server.id, &url);
             manager.start_http_server(
                 &server.id,
                 &server.name,
                 &url,
                 server.api_key,
+                oauth,
             ).await
         }
         mcp_servers::TransportType::Stdio => {
@@ -994,21 +1309,83 @@ async fn mcp_get_all_statuses(
 
 #[tauri::command]
 async fn mcp_get_all_tools(
+    db: State<'_, DbState>,
     mcp: State<'_, McpState>,
+    pet_id: Option<String>,
 ) -> Result<Vec<McpToolInfo>, String> {
     let manager = mcp.read().await;
-    Ok(manager.get_all_tools().await)
+    let mut tools = manager.get_all_tools().await;
+    // Drop the tools this pet has switched off so they never reach the model
+    if let Some(pet_id) = pet_id {
+        let disabled = db.get_pet_disabled_tools(&pet_id).map_err(|e| e.to_string())?;
+        tools.retain(|t| !disabled.iter().any(|d| d.server_id == t.server_id && d.tool_name == t.tool.name));
+    }
+    Ok(tools)
+}
+
+/// Apply the tool's result size limits. Oversized content spills into the pet's
+/// workspace (`tool-results/`) or, without a pet, into the uploads dir.
+fn limit_tool_response(
+    app: &AppHandle,
+    db: &Database,
+    workspace: &WorkspaceEngine,
+    server_id: &str,
+    tool_name: &str,
+    pet_id: Option<&str>,
+    mut response: CallToolResponse,
+) -> CallToolResponse {
+    let limits = db.resolve_tool_result_limits(server_id, tool_name).unwrap_or_default();
+    let spill = match pet_id {
+        Some(pet_id) => Some(SpillTarget::Workspace { engine: workspace, pet_id }),
+        None => get_uploads_dir(app).ok().map(|dir| SpillTarget::Dir {
+            display_prefix: format!("{}{}", dir.to_string_lossy(), std::path::MAIN_SEPARATOR),
+            dir,
+        }),
+    };
+    response.content = mcp::limit_tool_result(std::mem::take(&mut response.content), tool_name, &limits, spill.as_ref());
+    response
 }
 
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 async fn mcp_call_tool(
+    app: AppHandle,
+    db: State<'_, DbState>,
     mcp: State<'_, McpState>,
+    workspace: State<'_, WorkspaceFileState>,
     server_id: String,
     tool_name: String,
     arguments: Option<serde_json::Value>,
+    pet_id: Option<String>,
+    interactive: Option<bool>,
 ) -> Result<CallToolResponse, String> {
     let manager = mcp.read().await;
-    manager.call_tool(&server_id, &tool_name, arguments).await
+    let response = mcp_policy::call_tool_with_policy(
+        &db, &manager, &server_id, &tool_name, arguments, pet_id.as_deref(), interactive.unwrap_or(false),
+    ).await?;
+    Ok(limit_tool_response(&app, &db, &workspace, &server_id, &tool_name, pet_id.as_deref(), response))
+}
+
+/// Call a tool by the namespaced `toolId` from `mcp_get_all_tools`
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn mcp_call_tool_by_id(
+    app: AppHandle,
+    db: State<'_, DbState>,
+    mcp: State<'_, McpState>,
+    workspace: State<'_, WorkspaceFileState>,
+    tool_id: String,
+    arguments: Option<serde_json::Value>,
+    pet_id: Option<String>,
+    interactive: Option<bool>,
+) -> Result<CallToolResponse, String> {
+    let manager = mcp.read().await;
+    let target = manager.resolve_tool_id(&tool_id).await
+        .ok_or_else(|| format!("Unknown MCP tool '{}'", tool_id))?;
+    let response = mcp_policy::call_tool_with_policy(
+        &db, &manager, &target.server_id, &target.tool_name, arguments, pet_id.as_deref(), interactive.unwrap_or(false),
+    ).await?;
+    Ok(limit_tool_response(&app, &db, &workspace, &target.server_id, &target.tool_name, pet_id.as_deref(), response))
 }
 
 #[tauri::command]
@@ -1048,6 +1425,223 @@ async fn mcp_set_sampling_config(
     manager.set_sampling_config(&server_id, config).await
 }
 
+#[tauri::command]
+async fn mcp_get_resource_templates(
+    mcp: State<'_, McpState>,
+    server_id: String,
+) -> Result<Vec<McpResourceTemplate>, String> {
+    let manager = mcp.read().await;
+    manager.get_resource_templates(&server_id).await
+}
+
+#[tauri::command]
+async fn mcp_read_resource(
+    mcp: State<'_, McpState>,
+    server_id: String,
+    uri: String,
+) -> Result<ResourceReadResult, String> {
+    let manager = mcp.read().await;
+    manager.read_resource(&server_id, &uri).await
+}
+
+#[tauri::command]
+async fn mcp_read_resource_template(
+    mcp: State<'_, McpState>,
+    server_id: String,
+    uri_template: String,
+    variables: HashMap<String, String>,
+) -> Result<ResourceReadResult, String> {
+    let manager = mcp.read().await;
+    manager.read_resource_template(&server_id, &uri_template, variables).await
+}
+
+/// Read a resource and turn it into chat content parts; blobs are saved into the uploads dir
+#[tauri::command]
+async fn mcp_read_resource_as_attachments(
+    app: AppHandle,
+    mcp: State<'_, McpState>,
+    server_id: String,
+    uri: String,
+) -> Result<Vec<llm::ContentPart>, String> {
+    let result = {
+        let manager = mcp.read().await;
+        manager.read_resource(&server_id, &uri).await?
+    };
+    let uploads_dir = get_uploads_dir(&app)?;
+    mcp::resources::resource_contents_to_parts(&result, &uploads_dir)
+}
+
+/// Run the OAuth authorization flow for an HTTP server and store the tokens.
+/// The authorization URL is sent to the frontend as `mcp-oauth-authorize-url` to open in the browser.
+#[tauri::command]
+async fn mcp_oauth_authorize(
+    app: AppHandle,
+    db: State<'_, DbState>,
+    server_id: String,
+) -> Result<(), String> {
+    let server = db.get_mcp_server_by_id(&server_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Server not found: {}", server_id))?;
+    let url = server.url.ok_or_else(|| "HTTP server requires a URL".to_string())?;
+
+    let credentials = mcp_oauth::authorize(&url, |auth_url| {
+        app.emit("mcp-oauth-authorize-url", serde_json::json!({
+            "serverId": server_id,
+            "url": auth_url,
+        })).map_err(|e| e.to_string())
+    }).await?;
+
+    let json = serde_json::to_string(&credentials).map_err(|e| e.to_string())?;
+    db.set_mcp_oauth_credentials(&server_id, &json).map_err(|e| e.to_string())
+}
+
+/// Forget stored OAuth tokens for a server
+#[tauri::command]
+fn mcp_oauth_logout(
+    db: State<'_, DbState>,
+    server_id: String,
+) -> Result<bool, String> {
+    db.delete_mcp_oauth_credentials(&server_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn mcp_oauth_is_authorized(
+    db: State<'_, DbState>,
+    server_id: String,
+) -> Result<bool, String> {
+    let server = db.get_mcp_server_by_id(&server_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Server not found: {}", server_id))?;
+    let Some(url) = server.url else { return Ok(false) };
+    Ok(load_mcp_oauth_credentials(&db, &server_id, &url).is_some())
+}
+
+// ============ Built-in MCP Server Commands ============
+
+const DEFAULT_MCP_HOST_PORT: u16 = 7436;
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct McpHostStatus {
+    running: bool,
+    url: Option<String>,
+    token: String,
+    /// Command line for hosts that launch PetGPT over stdio
+    stdio_command: String,
+}
+
+/// Bearer token required by the HTTP endpoint, generated on first use
+fn mcp_host_token(db: &Database) -> Result<String, String> {
+    if let Some(token) = db.get_setting("mcpHostToken").map_err(|e| e.to_string())?.filter(|t| !t.is_empty()) {
+        return Ok(token);
+    }
+    let token = uuid::Uuid::new_v4().simple().to_string();
+    db.set_setting("mcpHostToken", &token).map_err(|e| e.to_string())?;
+    Ok(token)
+}
+
+fn mcp_host_status_of(db: &Database, handle: Option<&McpHostHandle>) -> Result<McpHostStatus, String> {
+    let exe = std::env::current_exe().map(|p| p.to_string_lossy().to_string()).unwrap_or_else(|_| "petgpt".to_string());
+    Ok(McpHostStatus {
+        running: handle.is_some(),
+        url: handle.map(|h| h.url()),
+        token: mcp_host_token(db)?,
+        stdio_command: format!("\"{}\" --mcp-stdio", exe),
+    })
+}
+
+async fn start_mcp_host(
+    db: &Database,
+    server: McpHostServerState,
+    host: &McpHostState,
+    port: Option<u16>,
+) -> Result<McpHostStatus, String> {
+    let mut guard = host.lock().await;
+    if let Some(handle) = guard.take() {
+        handle.stop();
+    }
+    let port = match port {
+        Some(port) => port,
+        None => db.get_setting("mcpHostPort").map_err(|e| e.to_string())?
+            .and_then(|p| p.parse().ok())
+            .unwrap_or(DEFAULT_MCP_HOST_PORT),
+    };
+    let handle = mcp::server::serve_http(server, port, mcp_host_token(db)?).await?;
+    db.set_setting("mcpHostPort", &handle.addr.port().to_string()).map_err(|e| e.to_string())?;
+    db.set_setting("mcpHostEnabled", "true").map_err(|e| e.to_string())?;
+    *guard = Some(handle);
+    mcp_host_status_of(db, guard.as_ref())
+}
+
+#[tauri::command]
+async fn mcp_host_start(
+    db: State<'_, DbState>,
+    server: State<'_, McpHostServerState>,
+    host: State<'_, McpHostState>,
+    port: Option<u16>,
+) -> Result<McpHostStatus, String> {
+    start_mcp_host(&db, server.inner().clone(), &host, port).await
+}
+
+#[tauri::command]
+async fn mcp_host_stop(
+    db: State<'_, DbState>,
+    host: State<'_, McpHostState>,
+) -> Result<McpHostStatus, String> {
+    let mut guard = host.lock().await;
+    if let Some(handle) = guard.take() {
+        handle.stop();
+        log::info!("[MCP-Host] Stopped");
+    }
+    db.set_setting("mcpHostEnabled", "false").map_err(|e| e.to_string())?;
+    mcp_host_status_of(&db, None)
+}
+
+#[tauri::command]
+async fn mcp_host_status(
+    db: State<'_, DbState>,
+    host: State<'_, McpHostState>,
+) -> Result<McpHostStatus, String> {
+    let guard = host.lock().await;
+    mcp_host_status_of(&db, guard.as_ref())
+}
+
+/// Generate a new bearer token; clients have to be reconfigured
+#[tauri::command]
+async fn mcp_host_reset_token(
+    db: State<'_, DbState>,
+    server: State<'_, McpHostServerState>,
+    host: State<'_, McpHostState>,
+) -> Result<McpHostStatus, String> {
+    db.set_setting("mcpHostToken", "").map_err(|e| e.to_string())?;
+    let running = host.lock().await.is_some();
+    if running {
+        start_mcp_host(&db, server.inner().clone(), &host, None).await
+    } else {
+        mcp_host_status_of(&db, None)
+    }
+}
+
+#[tauri::command]
+async fn mcp_subscribe_resource(
+    mcp: State<'_, McpState>,
+    server_id: String,
+    uri: String,
+) -> Result<(), String> {
+    let manager = mcp.read().await;
+    manager.subscribe_resource(&server_id, &uri).await
+}
+
+#[tauri::command]
+async fn mcp_unsubscribe_resource(
+    mcp: State<'_, McpState>,
+    server_id: String,
+    uri: String,
+) -> Result<(), String> {
+    let manager = mcp.read().await;
+    manager.unsubscribe_resource(&server_id, &uri).await
+}
+
 #[tauri::command]
 async fn mcp_test_server(
     transport: Option<String>,
@@ -1069,6 +1663,7 @@ async fn mcp_test_server(
                 "Test Server",
                 &url,
                 api_key,
+                None,
             ).await
         }
         _ => {
@@ -2929,6 +3524,28 @@ fn update_shortcuts(app: AppHandle, shortcut1: String, shortcut2: String, shortc
     }))
 }
 
+/// Tauri bundle identifier; the app data directory is named after it (see tauri.conf.json)
+const APP_IDENTIFIER: &str = "com.petgpt.app";
+
+/// `petgpt --mcp-stdio`: serve the built-in MCP server on stdin/stdout without
+/// starting the GUI. Uses the same database and workspace as the app.
+pub fn run_mcp_stdio() {
+    let app_data_dir = dirs::data_dir()
+        .expect("Failed to get app data dir")
+        .join(APP_IDENTIFIER);
+    std::fs::create_dir_all(&app_data_dir).expect("Failed to create app data dir");
+    let db = Arc::new(Database::new(app_data_dir.join("petgpt.db")).expect("Failed to initialize database"));
+    let workspace_dir = app_data_dir.join("workspace");
+    let workspace = Arc::new(WorkspaceEngine::new(workspace_dir.clone()));
+    let skills = Arc::new(SkillEngine::new(workspace_dir, app_data_dir.join("skills")));
+    let server = Arc::new(PetGptMcpServer::new(db, workspace, skills));
+
+    let runtime = tokio::runtime::Runtime::new().expect("Failed to start tokio runtime");
+    if let Err(e) = runtime.block_on(mcp::server::serve_stdio(server)) {
+        eprintln!("[MCP-Host] stdio server failed: {}", e);
+    }
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
@@ -2950,11 +3567,32 @@ pub fn run() {
             // Initialize built-in skins if they don't exist
             initialize_builtin_skins(&db);
             
-            app.manage(Arc::new(db));
+            let db: DbState = Arc::new(db);
+            app.manage(db.clone());
 
-            // Initialize MCP manager
-            let mcp_manager = Arc::new(tokio::sync::RwLock::new(McpManager::new()));
-            app.manage(mcp_manager);
+            // Initialize MCP manager; server-initiated events are forwarded to all windows
+            let mut mcp_manager = McpManager::new();
+            {
+                let db = db.clone();
+                mcp_manager.set_token_store(Arc::new(move |server_id: &str, credentials: &OAuthCredentials| {
+                    let result = serde_json::to_string(credentials)
+                        .map_err(|e| e.to_string())
+                        .and_then(|json| db.set_mcp_oauth_credentials(server_id, &json).map_err(|e| e.to_string()));
+                    if let Err(e) = result {
+                        log::error!("[MCP-OAuth] Failed to persist refreshed tokens for {}: {}", server_id, e);
+                    }
+                }));
+            }
+            {
+                let app_handle = app.handle().clone();
+                mcp_manager.set_event_sink(Arc::new(move |event: McpEvent| {
+                    if let Err(e) = app_handle.emit(event.event_name(), &event) {
+                        log::error!("[MCP] Failed to emit {}: {}", event.event_name(), e);
+                    }
+                }));
+            }
+            let mcp_state: McpState = Arc::new(tokio::sync::RwLock::new(mcp_manager));
+            app.manage(mcp_state);
 
             // Initialize the optional, on-demand native QQ connector. It never
             // downloads or starts anything until the user explicitly asks.
@@ -2963,7 +3601,11 @@ pub fn run() {
 
             // Initialize LLM client
             let llm_client: LlmState = Arc::new(LlmClient::new());
-            app.manage(llm_client);
+            app.manage(llm_client.clone());
+
+            // Background titling / rolling summaries (off until configured)
+            let summarizer: SummarizerState = Arc::new(summarizer::ConversationSummarizer::new(db.clone(), llm_client.clone()));
+            app.manage(summarizer);
 
             // Initialize LLM proxy (social agent 用：带超时 + 并发控制)
             let llm_proxy: LlmProxyState = Arc::new(LlmProxy::new());
@@ -2973,25 +3615,78 @@ pub fn run() {
             let llm_cancellation: LlmCancelState = Arc::new(LlmStreamCancellation::new());
             app.manage(llm_cancellation);
 
-            // Initialize tab message cache for in-memory message management (legacy)
-            app.manage(TabMessageCache::new());
-            
-            // Initialize new tab state manager (Rust-owned state)
-            app.manage(TabState::new());
+            // Initialize new tab state manager (Rust-owned state), restoring tabs open at last exit
+            let tab_state = TabState::new();
+            tab_state.restore(&db);
+            app.manage(tab_state);
+
+            // Write tab state behind to SQLite so a crash loses at most a second of it
+            {
+                let app_handle = app.handle().clone();
+                let db = db.clone();
+                std::thread::spawn(move || loop {
+                    std::thread::sleep(std::time::Duration::from_secs(1));
+                    app_handle.state::<TabState>().flush(&db);
+                });
+            }
 
             // Initialize workspace engine for file-based personality/memory
             let workspace_dir = app_data_dir.join("workspace");
             let workspace_engine: WorkspaceFileState = Arc::new(WorkspaceEngine::new(workspace_dir.clone()));
-            app.manage(workspace_engine);
+            app.manage(workspace_engine.clone());
+
+            // Periodic fact extraction into the USER.md / MEMORY.md review queue (off until configured)
+            let memory_consolidator: MemoryState = Arc::new(memory::MemoryConsolidator::new(
+                db.clone(),
+                llm_client,
+                workspace_engine.clone(),
+            ));
+            memory_consolidator.start(app.handle());
+            app.manage(memory_consolidator);
 
             // Initialize the inherited global Skill library plus per-assistant
             // private libraries. Skill commands apply stricter path checks.
             let global_skills_dir = app_data_dir.join("skills");
             let skill_engine: SkillFileState = Arc::new(SkillEngine::new(
-                workspace_dir,
-                global_skills_dir,
+                workspace_dir.clone(),
+                global_skills_dir.clone(),
             ));
-            app.manage(skill_engine);
+            app.manage(skill_engine.clone());
+
+            // Pick up SOUL.md / USER.md / MEMORY.md and Skill edits made outside the app
+            {
+                let app_handle = app.handle().clone();
+                match workspace::WorkspaceWatcher::start(workspace_dir, global_skills_dir, move |change| {
+                    let _ = app_handle.emit(workspace::watcher::FILES_CHANGED_EVENT, &change);
+                }) {
+                    Ok(watcher) => {
+                        app.manage(watcher);
+                    }
+                    Err(e) => log::warn!("[Workspace] {}", e),
+                }
+            }
+
+            // Built-in MCP server; the HTTP endpoint comes back if it was running last time
+            {
+                let mut server = PetGptMcpServer::new(db.clone(), workspace_engine, skill_engine);
+                let app_handle = app.handle().clone();
+                server.set_message_added_hook(Arc::new(move |message: &messages::Message| {
+                    let _ = app_handle.emit("conversation-message-added", message);
+                }));
+                let server: McpHostServerState = Arc::new(server);
+                let host: McpHostState = Arc::new(tokio::sync::Mutex::new(None));
+                app.manage(server.clone());
+                app.manage(host.clone());
+
+                if db.get_setting("mcpHostEnabled").ok().flatten().as_deref() == Some("true") {
+                    let db = db.clone();
+                    tauri::async_runtime::spawn(async move {
+                        if let Err(e) = start_mcp_host(&db, server, &host, None).await {
+                            log::error!("[MCP-Host] Failed to start: {}", e);
+                        }
+                    });
+                }
+            }
 
             let subagent_pool: SubagentPoolState = Arc::new(subagent::SubagentPool::new());
             app.manage(subagent_pool);
@@ -3317,6 +4012,8 @@ pub fn run() {
             get_conversation_with_history,
             create_conversation,
             update_conversation_title,
+            get_conversation_memory_enabled,
+            set_conversation_memory_enabled,
             delete_conversation,
             get_orphan_conversations,
             transfer_conversation,
@@ -3326,6 +4023,12 @@ pub fn run() {
             get_messages,
             create_message,
             clear_conversation_messages,
+            get_message_tree,
+            get_message_branches,
+            save_conversation_branch,
+            edit_message,
+            regenerate_message,
+            switch_message_branch,
             // Chat history commands (QQ 群聊存档)
             chat_history_insert,
             chat_history_insert_batch,
@@ -3363,6 +4066,20 @@ pub fn run() {
             create_mcp_server,
             update_mcp_server,
             delete_mcp_server,
+            import_mcp_servers,
+            export_mcp_servers,
+            // MCP tool permissions
+            get_mcp_tool_policies,
+            create_mcp_tool_policy,
+            delete_mcp_tool_policy,
+            get_mcp_tool_audit_log,
+            clear_mcp_tool_audit_log,
+            mcp_respond_tool_approval,
+            get_pet_disabled_tools,
+            set_pet_tool_enabled,
+            get_mcp_tool_result_limits,
+            set_mcp_tool_result_limits,
+            delete_mcp_tool_result_limits,
             // MCP Runtime commands
             mcp_start_server,
             mcp_stop_server,
@@ -3371,11 +4088,25 @@ pub fn run() {
             mcp_get_all_statuses,
             mcp_get_all_tools,
             mcp_call_tool,
+            mcp_call_tool_by_id,
             mcp_is_server_running,
             mcp_test_server,
             mcp_cancel_all_tool_calls,
             mcp_reset_cancellation,
             mcp_set_sampling_config,
+            mcp_get_resource_templates,
+            mcp_read_resource,
+            mcp_read_resource_template,
+            mcp_read_resource_as_attachments,
+            mcp_subscribe_resource,
+            mcp_unsubscribe_resource,
+            mcp_oauth_authorize,
+            mcp_oauth_logout,
+            mcp_oauth_is_authorized,
+            mcp_host_start,
+            mcp_host_stop,
+            mcp_host_status,
+            mcp_host_reset_token,
             // Managed native QQ connector (no Docker)
             qq_connector::qq_connector_status,
             qq_connector::qq_connector_install_mcp,
@@ -3444,13 +4175,6 @@ pub fn run() {
             get_pending_character_id,
             set_vibrancy_enabled,
             // Tab message cache commands (legacy)
-            message_cache::get_tab_messages,
-            message_cache::set_tab_messages,
-            message_cache::add_tab_message,
-            message_cache::update_tab_message,
-            message_cache::delete_tab_message,
-            message_cache::clear_tab_messages,
-            message_cache::get_tab_messages_count,
             // New Tab State commands (Rust-owned)
             tab_state::get_tab_state,
             tab_state::init_tab_messages,
@@ -3460,6 +4184,10 @@ pub fn run() {
             tab_state::delete_tab_state_message,
             tab_state::set_tab_thinking,
             tab_state::clear_tab_state,
+            tab_state::get_open_tabs,
+            tab_state::set_open_tabs,
+            tab_state::get_tab_draft,
+            tab_state::set_tab_draft,
             // LLM commands
             llm_call,
             llm_stream,
@@ -3475,12 +4203,27 @@ pub fn run() {
             workspace::workspace_write,
             workspace::workspace_append,
             workspace::workspace_edit,
+            workspace::workspace_edit_batch,
+            workspace::workspace_apply_patch,
             workspace::workspace_ensure_default_files,
             workspace::workspace_list_dir,
             workspace::workspace_delete_file,
             workspace::workspace_rename_file,
             workspace::workspace_write_binary,
             workspace::workspace_read_binary,
+            workspace::workspace_search,
+            workspace::workspace_file_history,
+            workspace::workspace_diff_versions,
+            workspace::workspace_restore_version,
+            workspace::workspace_get_history_retention,
+            workspace::workspace_set_history_retention,
+            workspace::workspace_usage,
+            workspace::workspace_get_quota,
+            workspace::workspace_set_quota,
+            workspace::workspace_file_info,
+            workspace::workspace_thumbnail,
+            workspace::workspace_cleanup_scan,
+            workspace::workspace_cleanup_delete,
             workspace::workspace_file_exists,
             workspace::workspace_get_path,
             workspace::workspace_delete_folder,
@@ -3502,6 +4245,22 @@ pub fn run() {
             subagent::subagent_set_max_concurrent,
             commands::training_export::run_training_export,
             commands::training_export::get_home_dir,
+            commands::conversation_export::export_conversation,
+            commands::conversation_export::export_pet_conversations,
+            commands::conversation_export::import_conversation,
+            commands::chat_import::import_chat_archive,
+            summarizer::get_summarizer_config,
+            summarizer::set_summarizer_config,
+            summarizer::get_conversation_summary,
+            memory::get_memory_config,
+            memory::set_memory_config,
+            memory::memory_consolidate,
+            memory::memory_list_candidates,
+            memory::memory_approve_candidate,
+            memory::memory_reject_candidate,
+            system_prompt::get_system_prompt,
+            system_prompt::get_prompt_budget,
+            system_prompt::set_prompt_budget,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
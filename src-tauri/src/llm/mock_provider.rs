@@ -0,0 +1,65 @@
+//! 测试用的假 LLM 服务
+//!
+//! 回放 tests/fixtures/llm 下录制的 OpenAI / Gemini / Anthropic SSE 流（含畸形块），
+//! 按固定字节数切块写出，模拟网络把一行、甚至一个多字节字符切开的情况。
+
+use std::time::Duration;
+use crate::llm::types::*;
+use crate::mcp::mock_server::{MockHttpServer, MockResponse};
+
+pub const OPENAI_STREAM: &str = include_str!("../../tests/fixtures/llm/openai_stream.sse");
+pub const OPENAI_MALFORMED: &str = include_str!("../../tests/fixtures/llm/openai_malformed.sse");
+pub const GEMINI_STREAM: &str = include_str!("../../tests/fixtures/llm/gemini_stream.sse");
+pub const ANTHROPIC_STREAM: &str = include_str!("../../tests/fixtures/llm/anthropic_stream.sse");
+
+/// 两次写之间的间隔，保证客户端分多次读到
+const CHUNK_DELAY_MS: u64 = 1;
+
+/// 每 `chunk_size` 字节写一次地回放 fixture
+pub async fn replay(fixture: &'static str, chunk_size: usize) -> MockHttpServer {
+    MockHttpServer::start(move |_| {
+        let chunks = fixture.as_bytes().chunks(chunk_size).map(<[u8]>::to_vec).collect();
+        MockResponse::sse_bytes(chunks).with_delay(Duration::from_millis(CHUNK_DELAY_MS))
+    }).await
+}
+
+/// 非流式 OpenAI chat completion 响应体
+pub fn openai_completion(text: &str) -> MockResponse {
+    MockResponse::json(serde_json::json!({
+        "id": "chatcmpl-mock",
+        "object": "chat.completion",
+        "model": "gpt-4o-mini",
+        "choices": [{
+            "index": 0,
+            "message": { "role": "assistant", "content": text },
+            "finish_reason": "stop"
+        }]
+    }).to_string())
+}
+
+/// 指向假服务的最小请求
+pub fn request(api_format: ApiFormat, base_url: &str) -> LlmRequest {
+    LlmRequest {
+        conversation_id: "conv-test".to_string(),
+        messages: vec![
+            ChatMessage {
+                role: Role::System,
+                content: MessageContent::Text("You are a cat.".to_string()),
+                tool_call_history: None,
+            },
+            ChatMessage {
+                role: Role::User,
+                content: MessageContent::Text("Hi".to_string()),
+                tool_call_history: None,
+            },
+        ],
+        api_format,
+        api_key: "test-key".to_string(),
+        model: "test-model".to_string(),
+        base_url: Some(base_url.to_string()),
+        temperature: None,
+        max_tokens: None,
+        stream: true,
+        response_format: None,
+    }
+}
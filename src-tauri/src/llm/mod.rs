@@ -8,6 +8,8 @@ pub mod client;
 pub mod types;
 pub mod stream;
 pub mod proxy;
+#[cfg(test)]
+pub(crate) mod mock_provider;
 
 pub use client::LlmClient;
 pub use types::*;
@@ -60,6 +60,134 @@ impl LlmProxy {
     }
 }
 
+impl LlmProxy {
+    /// 构建带 headers 的 POST 请求（Content-Type 固定为 JSON）
+    fn post_request(
+        &self,
+        endpoint: &str,
+        headers: &HashMap<String, String>,
+        body: &serde_json::Value,
+    ) -> reqwest::RequestBuilder {
+        let mut req = self.http_client
+            .post(endpoint)
+            .header("Content-Type", "application/json");
+
+        for (key, value) in headers {
+            // Content-Type 已设过，跳过重复
+            if key.to_lowercase() == "content-type" {
+                continue;
+            }
+            req = req.header(key.as_str(), value.as_str());
+        }
+
+        req.json(body)
+    }
+
+    /// 发送 POST 并返回 JSON 响应（llm_proxy_call 的实现）
+    pub async fn post_json(
+        &self,
+        endpoint: &str,
+        headers: &HashMap<String, String>,
+        body: &serde_json::Value,
+    ) -> Result<serde_json::Value, String> {
+        // 获取并发许可（若已满则等待，不会无限等——受前面 timeout 保护）
+        let _permit = self.semaphore
+            .acquire()
+            .await
+            .map_err(|e| format!("Semaphore closed: {}", e))?;
+
+        let response = self.post_request(endpoint, headers, body)
+            .send()
+            .await
+            .map_err(|e| {
+                if e.is_timeout() {
+                    format!("LLM request timed out after {}s", REQUEST_TIMEOUT_SECS)
+                } else {
+                    format!("HTTP error: {}", e)
+                }
+            })?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(format!("API error {}: {}", status.as_u16(), error_text));
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|e| format!("JSON parse error: {}", e))
+    }
+
+    /// 发送 POST 并把响应体按文本块交给 `on_chunk`（llm_proxy_stream 的实现）。
+    /// 被网络分块截断的多字节字符会留到下一块再解码。
+    pub async fn post_stream<F>(
+        &self,
+        endpoint: &str,
+        headers: &HashMap<String, String>,
+        body: &serde_json::Value,
+        mut on_chunk: F,
+    ) -> Result<(), String>
+    where
+        F: FnMut(String) -> Result<(), String>,
+    {
+        let _permit = self.semaphore
+            .acquire()
+            .await
+            .map_err(|e| format!("Semaphore closed: {}", e))?;
+
+        let response = self.post_request(endpoint, headers, body)
+            .send()
+            .await
+            .map_err(|e| {
+                if e.is_timeout() {
+                    format!("LLM stream request timed out after {}s", REQUEST_TIMEOUT_SECS)
+                } else {
+                    format!("HTTP error: {}", e)
+                }
+            })?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(format!("API error {}: {}", status.as_u16(), error_text));
+        }
+
+        let mut stream = response.bytes_stream();
+        let mut pending: Vec<u8> = Vec::new();
+
+        while let Some(chunk_result) = stream.next().await {
+            let chunk = chunk_result.map_err(|e| format!("Stream error: {}", e))?;
+            pending.extend_from_slice(&chunk);
+            let text = take_utf8(&mut pending);
+            if !text.is_empty() {
+                on_chunk(text)?;
+            }
+        }
+        if !pending.is_empty() {
+            on_chunk(String::from_utf8_lossy(&pending).to_string())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// 取出 `pending` 开头最长的完整 UTF-8 文本；末尾不完整的字符留在 `pending` 里。
+/// 遇到真正非法的字节时整体按 lossy 解码，不让坏数据卡住后面的输出。
+fn take_utf8(pending: &mut Vec<u8>) -> String {
+    let valid = match std::str::from_utf8(pending) {
+        Ok(text) => text.len(),
+        Err(e) if e.error_len().is_none() => e.valid_up_to(),
+        Err(_) => {
+            let text = String::from_utf8_lossy(pending).to_string();
+            pending.clear();
+            return text;
+        }
+    };
+    let rest = pending.split_off(valid);
+    String::from_utf8(std::mem::replace(pending, rest)).unwrap_or_default()
+}
+
 impl Default for LlmProxy {
     fn default() -> Self {
         Self::new()
@@ -88,48 +216,7 @@ pub async fn llm_proxy_call(
     let body_value: serde_json::Value = serde_json::from_str(&body_str)
         .map_err(|e| format!("Body JSON parse error: {}", e))?;
 
-    // 获取并发许可（若已满则等待，不会无限等——受前面 timeout 保护）
-    let _permit = proxy.semaphore
-        .acquire()
-        .await
-        .map_err(|e| format!("Semaphore closed: {}", e))?;
-
-    let mut req = proxy.http_client
-        .post(&endpoint)
-        .header("Content-Type", "application/json");
-
-    for (key, value) in &headers {
-        // Content-Type 已设过，跳过重复
-        if key.to_lowercase() == "content-type" {
-            continue;
-        }
-        req = req.header(key.as_str(), value.as_str());
-    }
-
-    let response = req
-        .json(&body_value)
-        .send()
-        .await
-        .map_err(|e| {
-            if e.is_timeout() {
-                format!("LLM request timed out after {}s", REQUEST_TIMEOUT_SECS)
-            } else {
-                format!("HTTP error: {}", e)
-            }
-        })?;
-
-    let status = response.status();
-    if !status.is_success() {
-        let error_text = response.text().await.unwrap_or_default();
-        return Err(format!("API error {}: {}", status.as_u16(), error_text));
-    }
-
-    let data: serde_json::Value = response
-        .json()
-        .await
-        .map_err(|e| format!("JSON parse error: {}", e))?;
-
-    Ok(data)
+    proxy.post_json(&endpoint, &headers, &body_value).await
 }
 
 /// 代理 LLM HTTP GET 请求（用于 /models 等浏览器 fetch 可能被 CORS/ATS 拦截的端点）
@@ -196,53 +283,16 @@ pub async fn llm_proxy_stream(
     let body_value: serde_json::Value = serde_json::from_str(&body_str)
         .map_err(|e| format!("Body JSON parse error: {}", e))?;
 
-    let _permit = proxy.semaphore
-        .acquire()
-        .await
-        .map_err(|e| format!("Semaphore closed: {}", e))?;
-
-    let mut req = proxy.http_client
-        .post(&endpoint)
-        .header("Content-Type", "application/json");
-
-    for (key, value) in &headers {
-        if key.to_lowercase() == "content-type" {
-            continue;
-        }
-        req = req.header(key.as_str(), value.as_str());
-    }
-
-    let response = req
-        .json(&body_value)
-        .send()
-        .await
-        .map_err(|e| {
-            if e.is_timeout() {
-                format!("LLM stream request timed out after {}s", REQUEST_TIMEOUT_SECS)
-            } else {
-                format!("HTTP error: {}", e)
-            }
-        })?;
-
-    let status = response.status();
-    if !status.is_success() {
-        let error_text = response.text().await.unwrap_or_default();
-        return Err(format!("API error {}: {}", status.as_u16(), error_text));
-    }
-
     let event_name = format!("llm-proxy-chunk:{}", request_id);
-    let mut stream = response.bytes_stream();
-
-    while let Some(chunk_result) = stream.next().await {
-        let chunk = chunk_result.map_err(|e| format!("Stream error: {}", e))?;
+    proxy.post_stream(&endpoint, &headers, &body_value, |chunk| {
         let payload = ProxyStreamChunk {
             request_id: request_id.clone(),
-            chunk: String::from_utf8_lossy(&chunk).to_string(),
+            chunk,
             done: false,
         };
         app.emit(&event_name, &payload)
-            .map_err(|e| format!("Event emit error: {}", e))?;
-    }
+            .map_err(|e| format!("Event emit error: {}", e))
+    }).await?;
 
     let done_payload = ProxyStreamChunk {
         request_id,
@@ -317,3 +367,65 @@ pub async fn image_gen_proxy_call(
 
     Ok(data)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::mock_provider::{self, ANTHROPIC_STREAM};
+    use crate::mcp::mock_server::{MockHttpServer, MockResponse};
+
+    #[test]
+    fn take_utf8_keeps_incomplete_characters() {
+        let mut pending = "猫".as_bytes()[..2].to_vec();
+        assert_eq!(take_utf8(&mut pending), "");
+        pending.push("猫".as_bytes()[2]);
+        pending.extend_from_slice(b"!");
+        assert_eq!(take_utf8(&mut pending), "猫!");
+        assert!(pending.is_empty());
+    }
+
+    #[tokio::test]
+    async fn post_json_forwards_headers_and_body() {
+        let server = MockHttpServer::start(|_| mock_provider::openai_completion("meow")).await;
+        let proxy = LlmProxy::new();
+        let headers = HashMap::from([
+            ("Authorization".to_string(), "Bearer k".to_string()),
+            ("Content-Type".to_string(), "text/plain".to_string()),
+        ]);
+        let body = serde_json::json!({ "model": "m", "messages": [] });
+
+        let data = proxy.post_json(&format!("{}/v1/chat/completions", server.base_url()), &headers, &body).await.unwrap();
+
+        assert_eq!(data["choices"][0]["message"]["content"], "meow");
+        let sent = &server.requests()[0];
+        assert_eq!(sent.header("authorization"), Some("Bearer k"));
+        assert_eq!(sent.header("content-type"), Some("application/json"));
+        assert_eq!(sent.json(), body);
+    }
+
+    #[tokio::test]
+    async fn post_json_reports_api_errors() {
+        let server = MockHttpServer::start(|_| MockResponse::status(429)).await;
+        let err = LlmProxy::new()
+            .post_json(&server.base_url(), &HashMap::new(), &serde_json::json!({}))
+            .await
+            .unwrap_err();
+        assert!(err.starts_with("API error 429"));
+    }
+
+    #[tokio::test]
+    async fn post_stream_never_splits_characters() {
+        let server = mock_provider::replay(ANTHROPIC_STREAM, 5).await;
+        let mut chunks = Vec::new();
+        LlmProxy::new()
+            .post_stream(&server.base_url(), &HashMap::new(), &serde_json::json!({}), |chunk| {
+                chunks.push(chunk);
+                Ok(())
+            })
+            .await
+            .unwrap();
+
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks.concat(), ANTHROPIC_STREAM);
+    }
+}
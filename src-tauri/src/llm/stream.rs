@@ -15,6 +15,26 @@ const STREAM_CONNECT_TIMEOUT_SECS: u64 = 20;
 /// 流式请求两次读之间的最大空闲时间（不是总时长，长回答不受影响）
 const STREAM_READ_TIMEOUT_SECS: u64 = 120;
 
+/// 把 SSE 字节流切成整行。整行到齐后才做 UTF-8 解码，
+/// 避免网络分块把多字节字符（中文、emoji）截成两半变成乱码。
+#[derive(Default)]
+pub(crate) struct SseLineBuffer {
+    pending: Vec<u8>,
+}
+
+impl SseLineBuffer {
+    /// 追加一块数据，返回其中已完整的行（不含换行符）；最后半行留到下一块
+    pub(crate) fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.pending.extend_from_slice(chunk);
+        let mut lines = Vec::new();
+        while let Some(pos) = self.pending.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=pos).collect();
+            lines.push(String::from_utf8_lossy(&line[..pos]).to_string());
+        }
+        lines
+    }
+}
+
 /// LLM 流取消管理器
 pub struct LlmStreamCancellation {
     /// 每个 conversation_id 的取消状态
@@ -93,12 +113,34 @@ impl Default for LlmStreamCancellation {
     }
 }
 
-/// 流式调用 LLM 并通过 Tauri 事件推送块
-pub async fn stream_chat(
+/// 流式调用 LLM 并通过 Tauri 事件推送块；推送前先交给 `observe`（例如记录到 TabState）
+pub async fn stream_chat<F>(
     app: AppHandle,
     request: LlmRequest,
     cancellation: Arc<LlmStreamCancellation>,
-) -> Result<LlmResponse, String> {
+    observe: F,
+) -> Result<LlmResponse, String>
+where
+    F: Fn(&StreamChunk) + Send + Sync,
+{
+    stream_chat_with(request, cancellation, move |chunk: &StreamChunk| {
+        observe(chunk);
+        let event_name = format!("llm-chunk:{}", chunk.conversation_id);
+        if let Err(e) = app.emit(&event_name, chunk) {
+            eprintln!("[LLM Stream] Failed to emit chunk: {:?}", e);
+        }
+    }).await
+}
+
+/// 流式调用 LLM，每个块（包括最后 done=true 的块）交给 `on_chunk`
+pub async fn stream_chat_with<F>(
+    request: LlmRequest,
+    cancellation: Arc<LlmStreamCancellation>,
+    on_chunk: F,
+) -> Result<LlmResponse, String>
+where
+    F: Fn(&StreamChunk) + Send + Sync,
+{
     // 流式请求不能设总超时（长回答本来就慢），但必须设建连超时 + 单次读超时：
     // 否则半开连接会让 SSE 读取永久挂起，前端的 await 永不返回。
     let client = Client::builder()
@@ -118,15 +160,15 @@ pub async fn stream_chat(
     let cancel_token = cancellation.get_token(&conversation_id);
     
     match request.api_format {
-        ApiFormat::OpenaiCompatible => stream_openai(app, client, request, cancel_token).await,
-        ApiFormat::GeminiOfficial => stream_gemini(app, client, request, cancel_token).await,
-        ApiFormat::AnthropicNative => stream_anthropic(app, client, request, cancel_token).await,
+        ApiFormat::OpenaiCompatible => stream_openai(&on_chunk, client, request, cancel_token).await,
+        ApiFormat::GeminiOfficial => stream_gemini(&on_chunk, client, request, cancel_token).await,
+        ApiFormat::AnthropicNative => stream_anthropic(&on_chunk, client, request, cancel_token).await,
     }
 }
 
 /// OpenAI 兼容 API 流式调用
-async fn stream_openai(
-    app: AppHandle,
+async fn stream_openai<F: Fn(&StreamChunk) + Sync>(
+    on_chunk: &F,
     client: Client,
     request: LlmRequest,
     cancel_token: Arc<AtomicBool>,
@@ -214,7 +256,7 @@ async fn stream_openai(
     }
 
     let mut stream = response.bytes_stream();
-    let mut buffer = String::new();
+    let mut buffer = SseLineBuffer::default();
     let mut full_text = String::new();
     let conversation_id = request.conversation_id.clone();
     let mut cancelled = false;
@@ -228,14 +270,9 @@ async fn stream_openai(
         }
         
         let chunk = chunk_result.map_err(|e| format!("Stream error: {}", e))?;
-        let chunk_str = String::from_utf8_lossy(&chunk);
-        buffer.push_str(&chunk_str);
 
         // 处理 SSE 格式
-        let lines: Vec<&str> = buffer.split('\n').collect();
-        let remaining = lines.last().cloned().unwrap_or("");
-        
-        for line in &lines[..lines.len().saturating_sub(1)] {
+        for line in buffer.push(&chunk) {
             if line.starts_with("data: ") {
                 let json_str = line[6..].trim();
                 if json_str == "[DONE]" {
@@ -255,17 +292,12 @@ async fn stream_openai(
                                 done: false,
                             };
                             
-                            let event_name = format!("llm-chunk:{}", conversation_id);
-                            if let Err(e) = app.emit(&event_name, &stream_chunk) {
-                                eprintln!("[LLM Stream] Failed to emit chunk: {:?}", e);
-                            }
+                            on_chunk(&stream_chunk);
                         }
                     }
                 }
             }
         }
-        
-        buffer = remaining.to_string();
     }
 
     // 发送完成/取消事件
@@ -276,8 +308,7 @@ async fn stream_openai(
         done: true,
     };
     
-    let event_name = format!("llm-chunk:{}", conversation_id);
-    let _ = app.emit(&event_name, &done_chunk);
+    on_chunk(&done_chunk);
 
     if cancelled {
         Ok(LlmResponse {
@@ -385,8 +416,8 @@ fn message_content_to_gemini_parts(content: &MessageContent) -> Vec<serde_json::
 }
 
 /// Gemini 官方 API 流式调用
-async fn stream_gemini(
-    app: AppHandle,
+async fn stream_gemini<F: Fn(&StreamChunk) + Sync>(
+    on_chunk: &F,
     client: Client,
     request: LlmRequest,
     cancel_token: Arc<AtomicBool>,
@@ -462,7 +493,7 @@ async fn stream_gemini(
     }
 
     let mut stream = response.bytes_stream();
-    let mut buffer = String::new();
+    let mut buffer = SseLineBuffer::default();
     let mut full_text = String::new();
     let conversation_id = request.conversation_id.clone();
     let mut cancelled = false;
@@ -476,14 +507,9 @@ async fn stream_gemini(
         }
         
         let chunk = chunk_result.map_err(|e| format!("Stream error: {}", e))?;
-        let chunk_str = String::from_utf8_lossy(&chunk);
-        buffer.push_str(&chunk_str);
 
         // Gemini SSE 格式处理
-        let lines: Vec<&str> = buffer.split('\n').collect();
-        let remaining = lines.last().cloned().unwrap_or("");
-        
-        for line in &lines[..lines.len().saturating_sub(1)] {
+        for line in buffer.push(&chunk) {
             if line.starts_with("data: ") {
                 let json_str = line[6..].trim();
                 
@@ -498,16 +524,11 @@ async fn stream_gemini(
                             done: false,
                         };
                         
-                        let event_name = format!("llm-chunk:{}", conversation_id);
-                        if let Err(e) = app.emit(&event_name, &stream_chunk) {
-                            eprintln!("[LLM Stream] Failed to emit chunk: {:?}", e);
-                        }
+                        on_chunk(&stream_chunk);
                     }
                 }
             }
         }
-        
-        buffer = remaining.to_string();
     }
 
     // 发送完成/取消事件
@@ -518,8 +539,7 @@ async fn stream_gemini(
         done: true,
     };
     
-    let event_name = format!("llm-chunk:{}", conversation_id);
-    let _ = app.emit(&event_name, &done_chunk);
+    on_chunk(&done_chunk);
 
     if cancelled {
         Ok(LlmResponse {
@@ -539,8 +559,8 @@ async fn stream_gemini(
 }
 
 /// Anthropic Messages API 流式调用
-async fn stream_anthropic(
-    app: AppHandle,
+async fn stream_anthropic<F: Fn(&StreamChunk) + Sync>(
+    on_chunk: &F,
     client: Client,
     request: LlmRequest,
     cancel_token: Arc<AtomicBool>,
@@ -647,7 +667,7 @@ async fn stream_anthropic(
     }
 
     let mut stream = response.bytes_stream();
-    let mut buffer = String::new();
+    let mut buffer = SseLineBuffer::default();
     let mut full_text = String::new();
     let conversation_id = request.conversation_id.clone();
     let mut cancelled = false;
@@ -660,15 +680,10 @@ async fn stream_anthropic(
         }
 
         let chunk = chunk_result.map_err(|e| format!("Stream error: {}", e))?;
-        let chunk_str = String::from_utf8_lossy(&chunk);
-        buffer.push_str(&chunk_str);
 
         // Anthropic SSE 事件：
         //   message_start | content_block_start | content_block_delta | content_block_stop | message_delta | message_stop | ping
-        let lines: Vec<&str> = buffer.split('\n').collect();
-        let remaining = lines.last().cloned().unwrap_or("");
-
-        for line in &lines[..lines.len().saturating_sub(1)] {
+        for line in buffer.push(&chunk) {
             let line = line.trim();
             if !line.starts_with("data: ") {
                 continue;
@@ -695,17 +710,12 @@ async fn stream_anthropic(
                                 full_text: full_text.clone(),
                                 done: false,
                             };
-                            let event_name = format!("llm-chunk:{}", conversation_id);
-                            if let Err(e) = app.emit(&event_name, &stream_chunk) {
-                                eprintln!("[LLM Stream] Failed to emit chunk: {:?}", e);
-                            }
+                            on_chunk(&stream_chunk);
                         }
                     }
                 }
             }
         }
-
-        buffer = remaining.to_string();
     }
 
     // 完成事件
@@ -715,8 +725,7 @@ async fn stream_anthropic(
         full_text: full_text.clone(),
         done: true,
     };
-    let event_name = format!("llm-chunk:{}", conversation_id);
-    let _ = app.emit(&event_name, &done_chunk);
+    on_chunk(&done_chunk);
 
     if cancelled {
         Ok(LlmResponse {
@@ -734,3 +743,88 @@ async fn stream_anthropic(
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::mock_provider::{self, ANTHROPIC_STREAM, GEMINI_STREAM, OPENAI_MALFORMED, OPENAI_STREAM};
+    use crate::mcp::mock_server::{MockHttpServer, MockResponse};
+
+    async fn collect(request: LlmRequest) -> (Result<LlmResponse, String>, Vec<StreamChunk>) {
+        let chunks = Mutex::new(Vec::new());
+        let result = stream_chat_with(request, Arc::new(LlmStreamCancellation::new()), |chunk: &StreamChunk| {
+            chunks.lock().unwrap().push(chunk.clone());
+        }).await;
+        (result, chunks.into_inner().unwrap())
+    }
+
+    fn assert_stream(chunks: &[StreamChunk], expected: &str) {
+        let (last, deltas) = chunks.split_last().unwrap();
+        assert!(last.done && last.full_text == expected);
+        assert!(deltas.iter().all(|c| !c.done));
+        assert_eq!(deltas.iter().map(|c| c.delta.as_str()).collect::<String>(), expected);
+    }
+
+    #[test]
+    fn line_buffer_waits_for_split_characters() {
+        let mut buffer = SseLineBuffer::default();
+        let bytes = "data: 世界\n".as_bytes();
+        assert!(buffer.push(&bytes[..7]).is_empty());
+        assert_eq!(buffer.push(&bytes[7..]), vec!["data: 世界".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn openai_stream_is_reassembled() {
+        let server = mock_provider::replay(OPENAI_STREAM, 5).await;
+        let (result, chunks) = collect(mock_provider::request(ApiFormat::OpenaiCompatible, &server.base_url())).await;
+
+        assert_eq!(result.unwrap().content, "Hello, 世界 🐱");
+        assert_stream(&chunks, "Hello, 世界 🐱");
+        let sent = &server.requests()[0];
+        assert_eq!(sent.path, "/v1/chat/completions");
+        assert_eq!(sent.header("authorization"), Some("Bearer test-key"));
+        assert_eq!(sent.json()["stream"], true);
+    }
+
+    #[tokio::test]
+    async fn malformed_openai_chunks_are_skipped() {
+        let server = mock_provider::replay(OPENAI_MALFORMED, 3).await;
+        let (result, chunks) = collect(mock_provider::request(ApiFormat::OpenaiCompatible, &server.base_url())).await;
+
+        assert_eq!(result.unwrap().content, "Hello world");
+        assert_stream(&chunks, "Hello world");
+    }
+
+    #[tokio::test]
+    async fn gemini_stream_is_reassembled() {
+        let server = mock_provider::replay(GEMINI_STREAM, 4).await;
+        let (result, chunks) = collect(mock_provider::request(ApiFormat::GeminiOfficial, &server.base_url())).await;
+
+        assert_eq!(result.unwrap().content, "猫咪在睡觉。");
+        assert_stream(&chunks, "猫咪在睡觉。");
+        let sent = &server.requests()[0];
+        assert!(sent.path.starts_with("/v1beta/models/test-model:streamGenerateContent?"));
+        assert_eq!(sent.json()["systemInstruction"]["parts"][0]["text"], "You are a cat.");
+    }
+
+    #[tokio::test]
+    async fn anthropic_stream_is_reassembled() {
+        let server = mock_provider::replay(ANTHROPIC_STREAM, 7).await;
+        let (result, chunks) = collect(mock_provider::request(ApiFormat::AnthropicNative, &server.base_url())).await;
+
+        assert_eq!(result.unwrap().content, "Purr… ☀️");
+        assert_stream(&chunks, "Purr… ☀️");
+        let sent = &server.requests()[0];
+        assert_eq!(sent.path, "/v1/messages");
+        assert_eq!(sent.header("x-api-key"), Some("test-key"));
+    }
+
+    #[tokio::test]
+    async fn http_errors_are_returned() {
+        let server = MockHttpServer::start(|_| MockResponse::status(401)).await;
+        let (result, chunks) = collect(mock_provider::request(ApiFormat::OpenaiCompatible, &server.base_url())).await;
+
+        assert!(result.unwrap_err().starts_with("API error 401"));
+        assert!(chunks.is_empty());
+    }
+}
@@ -13,5 +13,11 @@ fn main() {
     }
   }
 
+  // `petgpt --mcp-stdio` runs PetGPT as a headless MCP server for other hosts
+  if std::env::args().any(|arg| arg == "--mcp-stdio") {
+    app_lib::run_mcp_stdio();
+    return;
+  }
+
   app_lib::run();
 }
@@ -1,20 +1,24 @@
 // MCP Client - JSON-RPC 2.0 over stdio
 // Manages communication with a single MCP server
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::io::{BufRead, BufReader, Write};
 use std::process::{Child, Command, Stdio};
-use std::sync::atomic::{AtomicU64, AtomicBool, Ordering};
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use tokio::sync::{mpsc, oneshot};
 
+use super::sandbox::Sandbox;
 use super::types::*;
 use crate::llm::{LlmClient, LlmRequest, ChatMessage, MessageContent, Role, ApiFormat};
 
 const PROTOCOL_VERSION: &str = "2024-11-05";
 const REQUEST_TIMEOUT_MS: u64 = 60000; // Increased to 60s for long tool calls
 const TOOL_CALL_TIMEOUT_MS: u64 = 300000; // 5 minutes for tool calls
+const PING_TIMEOUT_MS: u64 = 10000;
+/// Number of stderr lines kept for the crash log
+pub const STDERR_TAIL_LINES: usize = 50;
 
 /// Incoming server→client request that needs async processing
 struct SamplingJob {
@@ -28,6 +32,8 @@ pub struct McpClient {
     command: String,
     args: Vec<String>,
     env: HashMap<String, String>,
+    // Opt-in isolation applied when the process is spawned
+    sandbox: Mutex<Option<Sandbox>>,
     
     // Process management
     process: Arc<Mutex<Option<Child>>>,
@@ -43,14 +49,22 @@ pub struct McpClient {
     server_info: Arc<Mutex<Option<ServerInfo>>>,
     tools: Arc<Mutex<Vec<McpTool>>>,
     resources: Arc<Mutex<Vec<McpResource>>>,
+    resource_templates: Arc<Mutex<Vec<McpResourceTemplate>>>,
+    subscriptions: Arc<Mutex<HashSet<String>>>,
     
     // Cancellation support
     cancelled: Arc<AtomicBool>,
     // Error state for propagating process failures
     last_error: Arc<Mutex<Option<String>>>,
+    // Recent stderr output, kept across restarts for the crash log
+    stderr_tail: Arc<Mutex<VecDeque<String>>>,
+    restart_count: AtomicU32,
     
     // Sampling support — LLM config for responding to server sampling requests
     sampling_config: Arc<Mutex<Option<SamplingLlmConfig>>>,
+    
+    // Receiver for server-initiated events (resource updates)
+    event_sink: Arc<Mutex<Option<McpEventSink>>>,
 }
 
 impl McpClient {
@@ -67,6 +81,7 @@ impl McpClient {
             command,
             args,
             env,
+            sandbox: Mutex::new(None),
             process: Arc::new(Mutex::new(None)),
             stdin_tx: Arc::new(Mutex::new(None)),
             request_id: AtomicU64::new(0),
@@ -76,9 +91,14 @@ impl McpClient {
             server_info: Arc::new(Mutex::new(None)),
             tools: Arc::new(Mutex::new(Vec::new())),
             resources: Arc::new(Mutex::new(Vec::new())),
+            resource_templates: Arc::new(Mutex::new(Vec::new())),
+            subscriptions: Arc::new(Mutex::new(HashSet::new())),
             cancelled: Arc::new(AtomicBool::new(false)),
             last_error: Arc::new(Mutex::new(None)),
+            stderr_tail: Arc::new(Mutex::new(VecDeque::with_capacity(STDERR_TAIL_LINES))),
+            restart_count: AtomicU32::new(0),
             sampling_config: Arc::new(Mutex::new(None)),
+            event_sink: Arc::new(Mutex::new(None)),
         }
     }
     
@@ -89,6 +109,16 @@ impl McpClient {
             if self.sampling_config.lock().unwrap().is_some() { "set" } else { "cleared" });
     }
     
+    /// Run the server process sandboxed from the next `connect` on
+    pub fn set_sandbox(&self, sandbox: Option<Sandbox>) {
+        *self.sandbox.lock().unwrap() = sandbox;
+    }
+    
+    /// Set the receiver for server-initiated events
+    pub fn set_event_sink(&self, sink: Option<McpEventSink>) {
+        *self.event_sink.lock().unwrap() = sink;
+    }
+    
     /// Cancel pending operations
     pub fn cancel(&self) {
         log::info!("[MCP][{}] Cancelling operations", self.server_name);
@@ -135,12 +165,20 @@ impl McpClient {
         log::info!("[MCP][{}] Starting server: {} {:?}", self.server_name, self.command, self.args);
 
         // Spawn the process
-        let mut cmd = Command::new(&self.command);
-        cmd.args(&self.args)
-            .stdin(Stdio::piped())
+        let mut cmd = match self.sandbox.lock().unwrap().as_ref() {
+            Some(sandbox) => {
+                log::info!("[MCP][{}] Sandboxed in {:?}", self.server_name, sandbox.work_dir);
+                sandbox.command(&self.command, &self.args, &self.env)
+            }
+            None => {
+                let mut cmd = Command::new(&self.command);
+                cmd.args(&self.args).envs(&self.env);
+                cmd
+            }
+        };
+        cmd.stdin(Stdio::piped())
             .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .envs(&self.env);
+            .stderr(Stdio::piped());
 
         let mut child = cmd.spawn().map_err(|e| format!("Failed to spawn process: {}", e))?;
 
@@ -207,6 +245,8 @@ impl McpClient {
         let last_error_stdout = self.last_error.clone();
         let sampling_config_clone = self.sampling_config.clone();
         let stdin_tx_for_sampling = self.stdin_tx.clone();
+        let event_sink_stdout = self.event_sink.clone();
+        let server_id_stdout = self.server_id.clone();
         
         thread::spawn(move || {
             let reader = BufReader::new(stdout);
@@ -304,6 +344,20 @@ impl McpClient {
                                         "notifications/resources/list_changed" => {
                                             log::info!("[MCP][{}] Resources list changed", server_name_stdout);
                                         }
+                                        "notifications/resources/updated" => {
+                                            let uri = notif.params.as_ref()
+                                                .and_then(|p| p.get("uri"))
+                                                .and_then(|u| u.as_str())
+                                                .unwrap_or_default()
+                                                .to_string();
+                                            log::info!("[MCP][{}] Resource updated: {}", server_name_stdout, uri);
+                                            if let Some(sink) = event_sink_stdout.lock().unwrap().clone() {
+                                                sink(McpEvent::ResourceUpdated {
+                                                    server_id: server_id_stdout.clone(),
+                                                    uri,
+                                                });
+                                            }
+                                        }
                                         _ => {
                                             log::debug!("[MCP][{}] Notification: {}", server_name_stdout, notif.method);
                                         }
@@ -346,14 +400,16 @@ impl McpClient {
             }
         });
 
-        // Spawn stderr reader thread (for logging)
+        // Spawn stderr reader thread (for logging and the crash log)
         let server_name_stderr = self.server_name.clone();
+        let stderr_tail = self.stderr_tail.clone();
         thread::spawn(move || {
             let reader = BufReader::new(stderr);
             for line in reader.lines() {
                 match line {
                     Ok(line) => {
                         log::debug!("[MCP][{}][stderr] {}", server_name_stderr, line);
+                        push_stderr_line(&mut stderr_tail.lock().unwrap(), line);
                     }
                     Err(_) => break,
                 }
@@ -406,6 +462,11 @@ impl McpClient {
         // Fetch tools and resources
         self.refresh_tools().await?;
         self.refresh_resources().await?;
+        
+        // Templates are optional - older servers answer with method-not-found
+        if let Err(e) = self.refresh_resource_templates().await {
+            log::warn!("[MCP][{}] resources/templates/list failed: {}", self.server_name, e);
+        }
 
         Ok(())
     }
@@ -448,6 +509,54 @@ impl McpClient {
         Ok(())
     }
 
+    /// Refresh the resource templates list from the server
+    pub async fn refresh_resource_templates(&self) -> Result<(), String> {
+        let caps = self.server_capabilities.lock().unwrap().clone();
+        if caps.resources.is_none() {
+            *self.resource_templates.lock().unwrap() = Vec::new();
+            return Ok(());
+        }
+
+        let result: ResourceTemplatesListResult = self
+            .send_request("resources/templates/list", None)
+            .await
+            .and_then(|v| serde_json::from_value(v).map_err(|e| e.to_string()))?;
+
+        log::info!("[MCP][{}] Resource templates: {:?}", self.server_name, result.resource_templates.iter().map(|t| &t.uri_template).collect::<Vec<_>>());
+        *self.resource_templates.lock().unwrap() = result.resource_templates;
+
+        Ok(())
+    }
+
+    /// Subscribe to notifications/resources/updated for a URI
+    pub async fn subscribe_resource(&self, uri: &str) -> Result<(), String> {
+        let supports_subscribe = self.server_capabilities.lock().unwrap()
+            .resources.as_ref().map(|r| r.subscribe).unwrap_or(false);
+        if !supports_subscribe {
+            return Err("Server does not support resource subscriptions".to_string());
+        }
+
+        self.send_request("resources/subscribe", Some(serde_json::json!({ "uri": uri }))).await?;
+        self.subscriptions.lock().unwrap().insert(uri.to_string());
+        log::info!("[MCP][{}] Subscribed to resource: {}", self.server_name, uri);
+
+        Ok(())
+    }
+
+    /// Unsubscribe from updates for a URI
+    pub async fn unsubscribe_resource(&self, uri: &str) -> Result<(), String> {
+        if !self.subscriptions.lock().unwrap().contains(uri) {
+            return Ok(());
+        }
+
+        // Keep the subscription until the server confirms, so a failed call leaves both sides in sync
+        self.send_request("resources/unsubscribe", Some(serde_json::json!({ "uri": uri }))).await?;
+        self.subscriptions.lock().unwrap().remove(uri);
+        log::info!("[MCP][{}] Unsubscribed from resource: {}", self.server_name, uri);
+
+        Ok(())
+    }
+
     /// Call a tool on the server with cancellation support
     pub async fn call_tool(&self, name: &str, arguments: Option<serde_json::Value>) -> Result<ToolCallResult, String> {
         // Check for errors from previous operations
@@ -668,6 +777,42 @@ impl McpClient {
 
         *self.tools.lock().unwrap() = Vec::new();
         *self.resources.lock().unwrap() = Vec::new();
+        *self.resource_templates.lock().unwrap() = Vec::new();
+        self.subscriptions.lock().unwrap().clear();
+    }
+
+    /// Health check: `ping` must be answered within PING_TIMEOUT_MS
+    pub async fn ping(&self) -> Result<(), String> {
+        self.send_request_with_timeout("ping", None, PING_TIMEOUT_MS, true)
+            .await
+            .map(|_| ())
+    }
+
+    /// Exit code if the process has terminated (`Some(-1)` when killed by a signal)
+    pub fn exit_code(&self) -> Option<i32> {
+        let mut guard = self.process.lock().unwrap();
+        let process = guard.as_mut()?;
+        match process.try_wait() {
+            Ok(Some(status)) => Some(status.code().unwrap_or(-1)),
+            _ => None,
+        }
+    }
+
+    /// Kill the process (if any) and start it again; used by the supervisor
+    pub async fn reconnect(&self) -> Result<(), String> {
+        self.disconnect();
+        self.reset_cancellation();
+        self.restart_count.fetch_add(1, Ordering::SeqCst);
+        self.connect().await
+    }
+
+    pub fn restart_count(&self) -> u32 {
+        self.restart_count.load(Ordering::SeqCst)
+    }
+
+    /// Last stderr lines, oldest first
+    pub fn get_stderr_tail(&self) -> Vec<String> {
+        self.stderr_tail.lock().unwrap().iter().cloned().collect()
     }
 
     /// Get current connection status
@@ -685,6 +830,18 @@ impl McpClient {
         self.resources.lock().unwrap().clone()
     }
 
+    /// Get available resource templates
+    pub fn get_resource_templates(&self) -> Vec<McpResourceTemplate> {
+        self.resource_templates.lock().unwrap().clone()
+    }
+
+    /// Get URIs with active update subscriptions
+    pub fn get_subscriptions(&self) -> Vec<String> {
+        let mut uris: Vec<String> = self.subscriptions.lock().unwrap().iter().cloned().collect();
+        uris.sort();
+        uris
+    }
+
     /// Get server info
     pub fn get_server_info(&self) -> Option<ServerInfo> {
         self.server_info.lock().unwrap().clone()
@@ -698,12 +855,24 @@ impl McpClient {
             is_running: self.is_connected(),
             tools: self.get_tools(),
             resources: self.get_resources(),
+            resource_templates: self.get_resource_templates(),
+            subscriptions: self.get_subscriptions(),
             server_info: self.get_server_info(),
             error: self.get_last_error(),
+            stderr_tail: self.get_stderr_tail(),
+            restart_count: self.restart_count(),
         }
     }
 }
 
+/// Append a line to the stderr ring buffer, dropping the oldest beyond STDERR_TAIL_LINES
+fn push_stderr_line(tail: &mut VecDeque<String>, line: String) {
+    if tail.len() >= STDERR_TAIL_LINES {
+        tail.pop_front();
+    }
+    tail.push_back(line);
+}
+
 /// Send a JSON-RPC error response synchronously (from the stdout reader thread context)
 fn send_jsonrpc_error_sync(
     stdin_tx: &Arc<Mutex<Option<mpsc::Sender<String>>>>,
@@ -736,11 +905,44 @@ async fn handle_sampling_job(
     log::info!("[MCP][{}] Handling sampling request (id={:?}, {} messages)", 
         server_name, job.request_id, job.params.messages.len());
     
+    let response = match create_sampling_message(server_name, &job.params, config).await {
+        Ok(result) => serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": job.request_id,
+            "result": result,
+        }),
+        Err(e) => {
+            log::error!("[MCP][{}] Sampling LLM call failed: {}", server_name, e);
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": job.request_id,
+                "error": {
+                    "code": -32603,
+                    "message": format!("LLM call failed: {}", e),
+                }
+            })
+        }
+    };
+    
+    if let Ok(msg) = serde_json::to_string(&response) {
+        if let Err(e) = stdin_tx.send(msg + "\n").await {
+            log::error!("[MCP][{}] Failed to send sampling response: {}", server_name, e);
+        }
+    }
+}
+
+/// Run a sampling/createMessage request against the configured LLM.
+/// Shared by the stdio and Streamable HTTP transports.
+pub(crate) async fn create_sampling_message(
+    server_name: &str,
+    params: &SamplingCreateMessageParams,
+    config: &SamplingLlmConfig,
+) -> Result<SamplingCreateMessageResult, String> {
     // Convert MCP sampling messages to LLM messages
     let mut llm_messages: Vec<ChatMessage> = Vec::new();
     
     // Add system prompt if provided
-    if let Some(ref system_prompt) = job.params.system_prompt {
+    if let Some(ref system_prompt) = params.system_prompt {
         llm_messages.push(ChatMessage {
             role: Role::System,
             content: MessageContent::Text(system_prompt.clone()),
@@ -749,7 +951,7 @@ async fn handle_sampling_job(
     }
     
     // Convert sampling messages
-    for msg in &job.params.messages {
+    for msg in &params.messages {
         let role = match msg.role.as_str() {
             "assistant" => Role::Assistant,
             _ => Role::User,
@@ -777,58 +979,24 @@ async fn handle_sampling_job(
         api_key: config.api_key.clone(),
         model: config.model.clone(),
         base_url: config.base_url.clone(),
-        temperature: job.params.temperature.map(|t| t as f32),
-        max_tokens: job.params.max_tokens.or(Some(4096)),
+        temperature: params.temperature.map(|t| t as f32),
+        max_tokens: params.max_tokens.or(Some(4096)),
         stream: false,
         response_format: None,
     };
     
     // Call LLM
     let llm_client = LlmClient::new();
-    match llm_client.call(&llm_request).await {
-        Ok(llm_response) => {
-            log::info!("[MCP][{}] Sampling LLM response: {} chars", 
-                server_name, llm_response.content.len());
-            
-            // Build MCP sampling response
-            let result = SamplingCreateMessageResult {
-                role: "assistant".to_string(),
-                content: SamplingContent::Text { text: llm_response.content },
-                model: config.model.clone(),
-                stop_reason: Some("endTurn".to_string()),
-            };
-            
-            let response = serde_json::json!({
-                "jsonrpc": "2.0",
-                "id": job.request_id,
-                "result": result,
-            });
-            
-            if let Ok(msg) = serde_json::to_string(&response) {
-                if let Err(e) = stdin_tx.send(msg + "\n").await {
-                    log::error!("[MCP][{}] Failed to send sampling response: {}", server_name, e);
-                }
-            }
-        }
-        Err(e) => {
-            log::error!("[MCP][{}] Sampling LLM call failed: {}", server_name, e);
-            
-            let response = serde_json::json!({
-                "jsonrpc": "2.0",
-                "id": job.request_id,
-                "error": {
-                    "code": -32603,
-                    "message": format!("LLM call failed: {}", e),
-                }
-            });
-            
-            if let Ok(msg) = serde_json::to_string(&response) {
-                if let Err(e) = stdin_tx.send(msg + "\n").await {
-                    log::error!("[MCP][{}] Failed to send sampling error: {}", server_name, e);
-                }
-            }
-        }
-    }
+    let llm_response = llm_client.call(&llm_request).await?;
+    log::info!("[MCP][{}] Sampling LLM response: {} chars", 
+        server_name, llm_response.content.len());
+    
+    Ok(SamplingCreateMessageResult {
+        role: "assistant".to_string(),
+        content: SamplingContent::Text { text: llm_response.content },
+        model: config.model.clone(),
+        stop_reason: Some("endTurn".to_string()),
+    })
 }
 
 impl Drop for McpClient {
@@ -836,3 +1004,99 @@ impl Drop for McpClient {
         self.disconnect();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::mock_provider;
+    use crate::mcp::mock_server::{self, MockHttpServer};
+    use serde_json::json;
+
+    fn mock_client(script: serde_json::Value) -> McpClient {
+        let (command, args, env) = mock_server::stdio_command(script);
+        McpClient::new("mock-id".into(), "mock".into(), command, args, env)
+    }
+
+    fn text_of(result: &ToolCallResult) -> &str {
+        match &result.content[0] {
+            ToolContent::Text { text } => text,
+            other => panic!("unexpected content {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn stdio_mock_serves_tools_resources_and_progress() {
+        let client = mock_client(json!({
+            "tools": [
+                { "name": "slow", "progress": [0.5, 1.0], "result": [{ "type": "text", "text": "done" }] },
+                { "name": "echo" }
+            ],
+            "resources": [{ "uri": "mem://notes", "mimeType": "text/plain", "text": "hello" }]
+        }));
+        client.connect().await.unwrap();
+
+        let tools: Vec<String> = client.get_tools().into_iter().map(|t| t.name).collect();
+        assert_eq!(tools, ["slow", "echo"]);
+        assert_eq!(text_of(&client.call_tool("slow", None).await.unwrap()), "done");
+        assert_eq!(text_of(&client.call_tool("echo", Some(json!({ "a": 1 }))).await.unwrap()), r#"{"a":1}"#);
+        assert!(client.call_tool("missing", None).await.unwrap_err().contains("Unknown tool"));
+
+        let read = client.read_resource("mem://notes").await.unwrap();
+        assert_eq!(read.contents[0].text.as_deref(), Some("hello"));
+        client.disconnect();
+    }
+
+    #[tokio::test]
+    async fn stdio_mock_sampling_goes_through_the_llm() {
+        let llm = MockHttpServer::start(|_| mock_provider::openai_completion("a sleepy cat")).await;
+        let client = mock_client(json!({ "tools": [{ "name": "describe", "sampling": "Describe the pet" }] }));
+        client.set_sampling_config(Some(SamplingLlmConfig {
+            api_key: "test-key".into(),
+            model: "test-model".into(),
+            base_url: Some(llm.base_url()),
+            api_format: "openai_compatible".into(),
+        }));
+        client.connect().await.unwrap();
+
+        let result = client.call_tool("describe", None).await.unwrap();
+
+        assert!(!result.is_error);
+        assert_eq!(text_of(&result), "a sleepy cat");
+        let sent = &llm.requests()[0];
+        assert_eq!(sent.path, "/v1/chat/completions");
+        assert_eq!(sent.json()["messages"][0]["content"], "Describe the pet");
+        client.disconnect();
+    }
+
+    #[tokio::test]
+    async fn stdio_mock_crash_is_reported() {
+        let client = mock_client(json!({
+            "tools": [{ "name": "boom", "crash": true }],
+            "stderr": ["mock booting"],
+            "exitCode": 3
+        }));
+        client.connect().await.unwrap();
+
+        assert!(client.call_tool("boom", None).await.is_err());
+        for _ in 0..100 {
+            if client.exit_code().is_some() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert_eq!(client.exit_code(), Some(3));
+        assert!(!client.is_connected());
+        assert!(client.get_stderr_tail().iter().any(|l| l == "mock booting"));
+    }
+
+    #[test]
+    fn stderr_tail_keeps_last_lines() {
+        let mut tail = VecDeque::new();
+        for i in 0..STDERR_TAIL_LINES + 5 {
+            push_stderr_line(&mut tail, format!("line {}", i));
+        }
+        assert_eq!(tail.len(), STDERR_TAIL_LINES);
+        assert_eq!(tail.front().map(String::as_str), Some("line 5"));
+        assert_eq!(tail.back().map(String::as_str), Some(format!("line {}", STDERR_TAIL_LINES + 4).as_str()));
+    }
+}
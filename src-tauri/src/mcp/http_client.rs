@@ -1,20 +1,334 @@
 // MCP Streamable HTTP Client
 // Implements MCP protocol over Streamable HTTP (2025-03-26 spec)
 // See: https://modelcontextprotocol.io/specification/2025-03-26/basic/transports#streamable-http
-
-use std::sync::atomic::{AtomicU64, AtomicBool, Ordering};
+//
+// Session lifecycle:
+//   - The Mcp-Session-Id returned by `initialize` is sent on every later request
+//   - HTTP 404 for a request carrying a session id means the session expired:
+//     the client re-initializes and retries the request once
+//   - A GET stream carries server-initiated requests/notifications and is
+//     reconnected with Last-Event-ID after a drop
+//   - A POST SSE stream that drops before the response arrives is resumed
+//     via GET with Last-Event-ID
+//   - disconnect() sends DELETE to terminate the session
+//   - With OAuth credentials, expired tokens are refreshed before sending and
+//     a 401 triggers one refresh + retry
+
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
-use std::collections::HashMap;
-use tokio::sync::{oneshot, RwLock};
+use std::collections::{HashMap, HashSet};
+use tokio::sync::oneshot;
 use futures::StreamExt;
 use tokio::time::{timeout, Duration};
 
+use super::client::create_sampling_message;
+use super::oauth::{self, OAuthCredentials, OAuthTokenStore};
 use super::types::*;
 
-const PROTOCOL_VERSION: &str = "2024-11-05";
+const PROTOCOL_VERSION: &str = "2025-03-26";
 const REQUEST_TIMEOUT_SECS: u64 = 60;
+const CONNECT_TIMEOUT_SECS: u64 = 15;
 const SSE_STREAM_TIMEOUT_SECS: u64 = 300; // 5 minutes for long-running tool calls
 const SSE_CHUNK_TIMEOUT_SECS: u64 = 30; // Max time between chunks
+const MAX_RESUME_ATTEMPTS: u32 = 3; // POST stream resumptions via Last-Event-ID
+const MAX_SEND_ATTEMPTS: u32 = 3; // Retries when the TCP connection cannot be established
+const RECONNECT_INITIAL_MS: u64 = 1000;
+const RECONNECT_MAX_MS: u64 = 30000;
+const PING_TIMEOUT_SECS: u64 = 10;
+
+/// Error from a single POST round-trip
+enum HttpRequestError {
+    /// Server answered 404 to a request carrying our Mcp-Session-Id
+    SessionExpired,
+    /// Server answered 401 - the access token is missing, expired or revoked
+    Unauthorized,
+    Other(String),
+}
+
+impl HttpRequestError {
+    fn into_message(self) -> String {
+        match self {
+            HttpRequestError::SessionExpired => "HTTP 404 - session expired".to_string(),
+            HttpRequestError::Unauthorized => "HTTP 401 - authorization required".to_string(),
+            HttpRequestError::Other(e) => e,
+        }
+    }
+}
+
+impl From<String> for HttpRequestError {
+    fn from(e: String) -> Self {
+        HttpRequestError::Other(e)
+    }
+}
+
+/// One parsed SSE event
+#[derive(Debug, Default, Clone, PartialEq)]
+struct SseEvent {
+    event: Option<String>,
+    data: Option<String>,
+    id: Option<String>,
+    retry: Option<u64>,
+}
+
+/// Incremental SSE decoder — feed it chunks, get back complete events
+#[derive(Default)]
+struct SseDecoder {
+    buffer: String,
+}
+
+impl SseDecoder {
+    fn push(&mut self, chunk: &str) -> Vec<SseEvent> {
+        self.buffer.push_str(chunk);
+        let mut events = Vec::new();
+
+        // SSE events are separated by blank lines, handle both \r\n\r\n and \n\n
+        loop {
+            // Find event boundary - try \r\n\r\n first (Windows/HTTP style), then \n\n (Unix style)
+            let (pos, skip_len) = if let Some(p) = self.buffer.find("\r\n\r\n") {
+                (p, 4)
+            } else if let Some(p) = self.buffer.find("\n\n") {
+                (p, 2)
+            } else {
+                break; // No complete event yet
+            };
+
+            let event_block = self.buffer[..pos].to_string();
+            self.buffer = self.buffer[pos + skip_len..].to_string();
+            events.push(McpHttpClient::parse_sse_event_full(&event_block));
+        }
+
+        events
+    }
+
+    /// Flush remaining buffer — some servers don't send trailing \n\n before closing the stream
+    fn finish(&mut self) -> Option<SseEvent> {
+        let remaining = std::mem::take(&mut self.buffer);
+        let remaining = remaining.trim();
+        if remaining.is_empty() {
+            None
+        } else {
+            Some(McpHttpClient::parse_sse_event_full(remaining))
+        }
+    }
+}
+
+/// Shared handles needed by background stream tasks (GET stream, server-request handlers)
+#[derive(Clone)]
+struct StreamContext {
+    server_id: String,
+    server_name: String,
+    endpoint_url: String,
+    api_key: Option<String>,
+    oauth: Arc<Mutex<Option<OAuthCredentials>>>,
+    client: reqwest::Client,
+    session_id: Arc<Mutex<Option<String>>>,
+    protocol_version: Arc<Mutex<Option<String>>>,
+    event_sink: Arc<Mutex<Option<McpEventSink>>>,
+    sampling_config: Arc<Mutex<Option<SamplingLlmConfig>>>,
+}
+
+impl StreamContext {
+    /// Attach auth, session and protocol-version headers
+    fn with_headers(&self, mut req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        // OAuth access token takes precedence over a static API key
+        let oauth_token = self.oauth.lock().unwrap().as_ref().map(|c| c.access_token.clone());
+        if let Some(token) = oauth_token {
+            req = req.header("Authorization", format!("Bearer {}", token));
+        } else if let Some(key) = &self.api_key {
+            // Add API key if provided (as query param is already in URL, but also try Bearer token)
+            req = req.header("Authorization", format!("Bearer {}", key));
+        }
+        if let Some(session_id) = self.session_id.lock().unwrap().clone() {
+            req = req.header("Mcp-Session-Id", session_id);
+        }
+        if let Some(version) = self.protocol_version.lock().unwrap().clone() {
+            req = req.header("Mcp-Protocol-Version", version);
+        }
+        req
+    }
+
+    /// Route one SSE data payload. Returns it if it is a JSON-RPC response.
+    fn dispatch(&self, data: &str) -> Option<JsonRpcResponse> {
+        // Server → client request (has both "method" and "id")
+        if let Ok(incoming) = serde_json::from_str::<JsonRpcIncomingRequest>(data) {
+            log::info!("[MCP-HTTP][{}] Incoming server request: {} (id={:?})",
+                self.server_name, incoming.method, incoming.id);
+            let ctx = self.clone();
+            tokio::spawn(async move {
+                ctx.handle_server_request(incoming).await;
+            });
+            return None;
+        }
+        if let Ok(resp) = serde_json::from_str::<JsonRpcResponse>(data) {
+            return Some(resp);
+        }
+        if let Ok(notif) = serde_json::from_str::<JsonRpcNotification>(data) {
+            log::info!("[MCP-HTTP][{}] Server notification: {}", self.server_name, notif.method);
+            self.handle_notification(&notif);
+            return None;
+        }
+        // Could be a partial or malformed message
+        log::debug!("[MCP-HTTP][{}] Non-JSON SSE data: {}", self.server_name, data);
+        None
+    }
+
+    /// Dispatch a server notification received on any stream
+    fn handle_notification(&self, notif: &JsonRpcNotification) {
+        if notif.method == "notifications/resources/updated" {
+            let uri = notif.params.as_ref()
+                .and_then(|p| p.get("uri"))
+                .and_then(|u| u.as_str())
+                .unwrap_or_default()
+                .to_string();
+            log::info!("[MCP-HTTP][{}] Resource updated: {}", self.server_name, uri);
+            if let Some(sink) = self.event_sink.lock().unwrap().clone() {
+                sink(McpEvent::ResourceUpdated {
+                    server_id: self.server_id.clone(),
+                    uri,
+                });
+            }
+        }
+    }
+
+    /// Answer a server-initiated request by POSTing the JSON-RPC response back
+    async fn handle_server_request(&self, incoming: JsonRpcIncomingRequest) {
+        let outcome: Result<serde_json::Value, (i32, String)> = match incoming.method.as_str() {
+            "ping" => Ok(serde_json::json!({})),
+            "roots/list" => Ok(serde_json::json!({ "roots": [] })),
+            "sampling/createMessage" => {
+                let config = self.sampling_config.lock().unwrap().clone();
+                match (config, incoming.params) {
+                    (None, _) => {
+                        log::warn!("[MCP-HTTP][{}] Sampling requested but no LLM config set", self.server_name);
+                        Err((-32603, "Sampling not configured: no LLM config available".to_string()))
+                    }
+                    (Some(_), None) => Err((-32602, "Missing params in sampling request".to_string())),
+                    (Some(config), Some(params_val)) => {
+                        match serde_json::from_value::<SamplingCreateMessageParams>(params_val) {
+                            Ok(params) => create_sampling_message(&self.server_name, &params, &config)
+                                .await
+                                .map(|result| serde_json::to_value(result).unwrap_or_default())
+                                .map_err(|e| (-32603, format!("LLM call failed: {}", e))),
+                            Err(e) => Err((-32602, format!("Invalid sampling params: {}", e))),
+                        }
+                    }
+                }
+            }
+            other => {
+                log::warn!("[MCP-HTTP][{}] Unhandled server request: {}", self.server_name, other);
+                Err((-32601, format!("Method not found: {}", other)))
+            }
+        };
+
+        let body = match outcome {
+            Ok(result) => serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": incoming.id,
+                "result": result,
+            }),
+            Err((code, message)) => serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": incoming.id,
+                "error": { "code": code, "message": message },
+            }),
+        };
+
+        let req = self.with_headers(
+            self.client.post(&self.endpoint_url)
+                .header("Content-Type", "application/json")
+                .header("Accept", "application/json, text/event-stream")
+                .json(&body),
+        );
+        match req.send().await {
+            Ok(resp) if resp.status().is_success() => {}
+            Ok(resp) => log::warn!("[MCP-HTTP][{}] Server rejected response to {}: HTTP {}",
+                self.server_name, incoming.method, resp.status()),
+            Err(e) => log::error!("[MCP-HTTP][{}] Failed to send response to {}: {}",
+                self.server_name, incoming.method, e),
+        }
+    }
+}
+
+/// Long-lived GET stream for server-initiated messages. Reconnects with Last-Event-ID.
+async fn run_get_stream(
+    ctx: StreamContext,
+    stream_client: reqwest::Client,
+    is_connected: Arc<Mutex<bool>>,
+    last_event_id: Arc<Mutex<Option<String>>>,
+) {
+    let mut backoff_ms = RECONNECT_INITIAL_MS;
+
+    loop {
+        if !*is_connected.lock().unwrap() {
+            break;
+        }
+
+        let mut req = ctx.with_headers(
+            stream_client.get(&ctx.endpoint_url).header("Accept", "text/event-stream"),
+        );
+        let resume_from = last_event_id.lock().unwrap().clone();
+        if let Some(id) = &resume_from {
+            req = req.header("Last-Event-ID", id.as_str());
+        }
+
+        match req.send().await {
+            Ok(resp) if resp.status() == reqwest::StatusCode::METHOD_NOT_ALLOWED => {
+                log::info!("[MCP-HTTP][{}] Server does not offer a GET stream", ctx.server_name);
+                return;
+            }
+            Ok(resp) if resp.status() == reqwest::StatusCode::NOT_FOUND => {
+                // Session expired; the next POST re-initializes and restarts this stream
+                log::warn!("[MCP-HTTP][{}] GET stream rejected: session expired", ctx.server_name);
+                return;
+            }
+            Ok(resp) if resp.status() == reqwest::StatusCode::UNAUTHORIZED => {
+                // Token expired; the next POST refreshes it and restarts this stream
+                log::warn!("[MCP-HTTP][{}] GET stream rejected: unauthorized", ctx.server_name);
+                return;
+            }
+            Ok(resp) if resp.status().is_success() => {
+                log::info!("[MCP-HTTP][{}] GET stream open (resume from {:?})", ctx.server_name, resume_from);
+                backoff_ms = RECONNECT_INITIAL_MS;
+
+                let mut stream = resp.bytes_stream();
+                let mut decoder = SseDecoder::default();
+                while let Some(chunk) = stream.next().await {
+                    let bytes = match chunk {
+                        Ok(bytes) => bytes,
+                        Err(e) => {
+                            log::warn!("[MCP-HTTP][{}] GET stream error: {}", ctx.server_name, e);
+                            break;
+                        }
+                    };
+                    for event in decoder.push(&String::from_utf8_lossy(&bytes)) {
+                        if let Some(id) = event.id {
+                            *last_event_id.lock().unwrap() = Some(id);
+                        }
+                        if let Some(retry) = event.retry {
+                            backoff_ms = retry;
+                        }
+                        if let Some(data) = event.data {
+                            if let Some(resp) = ctx.dispatch(&data) {
+                                log::debug!("[MCP-HTTP][{}] Ignoring response on GET stream (id={})",
+                                    ctx.server_name, resp.id);
+                            }
+                        }
+                    }
+                }
+                log::info!("[MCP-HTTP][{}] GET stream closed", ctx.server_name);
+            }
+            Ok(resp) => {
+                log::warn!("[MCP-HTTP][{}] GET stream failed: HTTP {}", ctx.server_name, resp.status());
+            }
+            Err(e) => {
+                log::warn!("[MCP-HTTP][{}] GET stream connect failed: {}", ctx.server_name, e);
+            }
+        }
+
+        tokio::time::sleep(Duration::from_millis(backoff_ms)).await;
+        backoff_ms = (backoff_ms * 2).min(RECONNECT_MAX_MS);
+    }
+}
 
 pub struct McpHttpClient {
     server_id: String,
@@ -22,26 +336,45 @@ pub struct McpHttpClient {
     /// The MCP endpoint URL (e.g., https://mcp.tavily.com/mcp/)
     endpoint_url: String,
     api_key: Option<String>,
-    
-    // HTTP client
+
+    // OAuth 2.1 credentials and the callback that persists refreshed tokens
+    oauth: Arc<Mutex<Option<OAuthCredentials>>>,
+    token_store: Mutex<Option<OAuthTokenStore>>,
+
+    // HTTP clients: `client` for request/response, `stream_client` (no total timeout) for long-lived streams
     client: reqwest::Client,
-    
-    // Session management (Mcp-Session-Id header)
-    session_id: Arc<RwLock<Option<String>>>,
-    
+    stream_client: reqwest::Client,
+
+    // Session management (Mcp-Session-Id header) and negotiated protocol version
+    session_id: Arc<Mutex<Option<String>>>,
+    protocol_version: Arc<Mutex<Option<String>>>,
+
+    // GET stream for server-initiated messages
+    get_stream_task: Mutex<Option<tokio::task::JoinHandle<()>>>,
+    last_event_id: Arc<Mutex<Option<String>>>,
+
     // Request management
     request_id: AtomicU64,
     pending_requests: Arc<Mutex<HashMap<u64, oneshot::Sender<Result<serde_json::Value, String>>>>>,
-    
+
     // State
     is_connected: Arc<Mutex<bool>>,
     server_capabilities: Arc<Mutex<ServerCapabilities>>,
     server_info: Arc<Mutex<Option<ServerInfo>>>,
     tools: Arc<Mutex<Vec<McpTool>>>,
     resources: Arc<Mutex<Vec<McpResource>>>,
-    
+    resource_templates: Arc<Mutex<Vec<McpResourceTemplate>>>,
+    subscriptions: Arc<Mutex<HashSet<String>>>,
+
     // Cancellation support
     cancelled: Arc<AtomicBool>,
+    restart_count: AtomicU32,
+
+    // Receiver for server-initiated events (resource updates)
+    event_sink: Arc<Mutex<Option<McpEventSink>>>,
+
+    // Sampling support — LLM config for responding to server sampling requests
+    sampling_config: Arc<Mutex<Option<SamplingLlmConfig>>>,
 }
 
 impl McpHttpClient {
@@ -55,14 +388,24 @@ impl McpHttpClient {
             .timeout(std::time::Duration::from_secs(REQUEST_TIMEOUT_SECS))
             .build()
             .unwrap();
+        let stream_client = reqwest::Client::builder()
+            .connect_timeout(std::time::Duration::from_secs(CONNECT_TIMEOUT_SECS))
+            .build()
+            .unwrap();
 
         Self {
             server_id,
             server_name,
             endpoint_url: endpoint_url.trim_end_matches('/').to_string(),
             api_key,
+            oauth: Arc::new(Mutex::new(None)),
+            token_store: Mutex::new(None),
             client,
-            session_id: Arc::new(RwLock::new(None)),
+            stream_client,
+            session_id: Arc::new(Mutex::new(None)),
+            protocol_version: Arc::new(Mutex::new(None)),
+            get_stream_task: Mutex::new(None),
+            last_event_id: Arc::new(Mutex::new(None)),
             request_id: AtomicU64::new(0),
             pending_requests: Arc::new(Mutex::new(HashMap::new())),
             is_connected: Arc::new(Mutex::new(false)),
@@ -70,26 +413,70 @@ impl McpHttpClient {
             server_info: Arc::new(Mutex::new(None)),
             tools: Arc::new(Mutex::new(Vec::new())),
             resources: Arc::new(Mutex::new(Vec::new())),
+            resource_templates: Arc::new(Mutex::new(Vec::new())),
+            subscriptions: Arc::new(Mutex::new(HashSet::new())),
             cancelled: Arc::new(AtomicBool::new(false)),
+            restart_count: AtomicU32::new(0),
+            event_sink: Arc::new(Mutex::new(None)),
+            sampling_config: Arc::new(Mutex::new(None)),
         }
     }
-    
+
+    /// Set the receiver for server-initiated events
+    pub fn set_event_sink(&self, sink: Option<McpEventSink>) {
+        *self.event_sink.lock().unwrap() = sink;
+    }
+
+    /// Use OAuth credentials instead of the static API key.
+    /// `store` is called whenever the tokens are refreshed.
+    pub fn set_oauth(&self, credentials: Option<OAuthCredentials>, store: Option<OAuthTokenStore>) {
+        *self.oauth.lock().unwrap() = credentials;
+        *self.token_store.lock().unwrap() = store;
+    }
+
+    /// Set the LLM configuration used for MCP Sampling responses
+    pub fn set_sampling_config(&self, config: Option<SamplingLlmConfig>) {
+        log::info!("[MCP-HTTP][{}] Sampling config {}", self.server_name,
+            if config.is_some() { "set" } else { "cleared" });
+        *self.sampling_config.lock().unwrap() = config;
+    }
+
     /// Cancel pending operations
     pub fn cancel(&self) {
         log::info!("[MCP-HTTP][{}] Cancelling operations", self.server_name);
         self.cancelled.store(true, Ordering::SeqCst);
     }
-    
+
     /// Reset cancellation flag
     pub fn reset_cancellation(&self) {
         self.cancelled.store(false, Ordering::SeqCst);
     }
-    
+
     /// Check if cancelled
     pub fn is_cancelled(&self) -> bool {
         self.cancelled.load(Ordering::SeqCst)
     }
 
+    /// Current Mcp-Session-Id, if the server assigned one
+    pub fn session_id(&self) -> Option<String> {
+        self.session_id.lock().unwrap().clone()
+    }
+
+    fn stream_context(&self) -> StreamContext {
+        StreamContext {
+            server_id: self.server_id.clone(),
+            server_name: self.server_name.clone(),
+            endpoint_url: self.endpoint_url.clone(),
+            api_key: self.api_key.clone(),
+            oauth: self.oauth.clone(),
+            client: self.client.clone(),
+            session_id: self.session_id.clone(),
+            protocol_version: self.protocol_version.clone(),
+            event_sink: self.event_sink.clone(),
+            sampling_config: self.sampling_config.clone(),
+        }
+    }
+
     /// Connect to the MCP server via Streamable HTTP
     /// In Streamable HTTP, we POST requests directly to the endpoint
     pub async fn connect(&self) -> Result<(), String> {
@@ -104,18 +491,83 @@ impl McpHttpClient {
         self.initialize().await?;
 
         *self.is_connected.lock().unwrap() = true;
+        self.start_get_stream();
         log::info!("[MCP-HTTP][{}] Connected successfully", self.server_name);
 
         Ok(())
     }
 
-    /// Parse SSE stream response and extract JSON-RPC messages
+    /// (Re)start the background GET stream for server-initiated messages
+    fn start_get_stream(&self) {
+        let task = tokio::spawn(run_get_stream(
+            self.stream_context(),
+            self.stream_client.clone(),
+            self.is_connected.clone(),
+            self.last_event_id.clone(),
+        ));
+        if let Some(previous) = self.get_stream_task.lock().unwrap().replace(task) {
+            previous.abort();
+        }
+    }
+
+    /// Parse SSE stream response and extract the JSON-RPC response for `request_id`.
+    /// If the stream drops before the response arrives and the server assigned
+    /// event ids, the stream is resumed via GET with Last-Event-ID.
+    async fn parse_sse_response(&self, response: reqwest::Response, request_id: u64) -> Result<serde_json::Value, String> {
+        let ctx = self.stream_context();
+        let mut response = response;
+        let mut last_event_id: Option<String> = None;
+        let mut retry_ms = RECONNECT_INITIAL_MS;
+        let mut attempts = 0;
+
+        loop {
+            if let Some(result) = self.read_sse_stream(&ctx, response, request_id, &mut last_event_id, &mut retry_ms).await? {
+                return Ok(result);
+            }
+
+            loop {
+                let Some(event_id) = last_event_id.clone() else {
+                    return Err("No response received from SSE stream".to_string());
+                };
+                if attempts >= MAX_RESUME_ATTEMPTS {
+                    return Err(format!("SSE stream dropped; gave up after {} resume attempts", attempts));
+                }
+                attempts += 1;
+
+                log::info!("[MCP-HTTP][{}] Resuming SSE stream after event {} (attempt {})",
+                    self.server_name, event_id, attempts);
+                tokio::time::sleep(Duration::from_millis(retry_ms)).await;
+
+                let resumed = ctx.with_headers(
+                    self.stream_client.get(&self.endpoint_url)
+                        .header("Accept", "text/event-stream")
+                        .header("Last-Event-ID", event_id.as_str()),
+                ).send().await;
+
+                match resumed {
+                    Ok(resp) if resp.status().is_success() => {
+                        response = resp;
+                        break;
+                    }
+                    Ok(resp) => log::warn!("[MCP-HTTP][{}] Resume failed: HTTP {}", self.server_name, resp.status()),
+                    Err(e) => log::warn!("[MCP-HTTP][{}] Resume failed: {}", self.server_name, e),
+                }
+            }
+        }
+    }
+
+    /// Read one SSE stream until the response for `request_id` arrives (Some) or the stream ends (None)
     /// Implements proper SSE parsing with timeout and cancellation support
-    async fn parse_sse_response(&self, response: reqwest::Response) -> Result<serde_json::Value, String> {
+    async fn read_sse_stream(
+        &self,
+        ctx: &StreamContext,
+        response: reqwest::Response,
+        request_id: u64,
+        last_event_id: &mut Option<String>,
+        retry_ms: &mut u64,
+    ) -> Result<Option<serde_json::Value>, String> {
         let mut stream = response.bytes_stream();
-        let mut buffer = String::new();
-        let mut result: Option<serde_json::Value> = None;
-        let mut last_event_type: Option<String> = None;
+        let mut decoder = SseDecoder::default();
         let start_time = std::time::Instant::now();
 
         loop {
@@ -123,18 +575,18 @@ impl McpHttpClient {
             if self.is_cancelled() {
                 return Err("Operation cancelled".to_string());
             }
-            
+
             // Check overall timeout
             if start_time.elapsed().as_secs() > SSE_STREAM_TIMEOUT_SECS {
                 return Err("SSE stream timeout".to_string());
             }
-            
+
             // Read next chunk with timeout
             let chunk_result = timeout(
                 Duration::from_secs(SSE_CHUNK_TIMEOUT_SECS),
                 stream.next()
             ).await;
-            
+
             let chunk = match chunk_result {
                 Ok(Some(Ok(bytes))) => bytes,
                 Ok(Some(Err(e))) => {
@@ -152,156 +604,123 @@ impl McpHttpClient {
                     break;
                 }
             };
-            
+
             let text = String::from_utf8_lossy(&chunk);
-            buffer.push_str(&text);
             log::info!("[MCP-HTTP][{}] SSE chunk ({} bytes): {:?}", self.server_name, chunk.len(), &text[..text.len().min(300)]);
 
-            // Process complete events
-            // SSE events are separated by blank lines, handle both \r\n\r\n and \n\n
-            loop {
-                // Find event boundary - try \r\n\r\n first (Windows/HTTP style), then \n\n (Unix style)
-                let (pos, skip_len) = if let Some(p) = buffer.find("\r\n\r\n") {
-                    (p, 4)
-                } else if let Some(p) = buffer.find("\n\n") {
-                    (p, 2)
-                } else {
-                    break; // No complete event yet
-                };
+            for event in decoder.push(&text) {
+                if let Some(result) = self.handle_sse_event(ctx, event, request_id, last_event_id, retry_ms)? {
+                    return Ok(Some(result));
+                }
+            }
+        }
 
-                let event_block = buffer[..pos].to_string();
-                buffer = buffer[pos + skip_len..].to_string();
+        if let Some(event) = decoder.finish() {
+            log::info!("[MCP-HTTP][{}] Flushing remaining SSE buffer", self.server_name);
+            return self.handle_sse_event(ctx, event, request_id, last_event_id, retry_ms);
+        }
 
-                log::debug!("[MCP-HTTP][{}] SSE event block: {:?}", self.server_name, event_block);
+        Ok(None)
+    }
 
-                // Parse SSE event (extract event type and data)
-                let (event_type, data) = Self::parse_sse_event_full(&event_block);
-                
-                if let Some(et) = event_type {
-                    last_event_type = Some(et);
-                }
-                
-                if let Some(data) = data {
-                    log::debug!("[MCP-HTTP][{}] SSE data (event={:?}): {}", 
-                        self.server_name, last_event_type, data);
-                    
-                    // Handle different event types per MCP spec
-                    match last_event_type.as_deref() {
-                        Some("error") => {
-                            // Server sent an error event
-                            return Err(format!("SSE error event: {}", data));
-                        }
-                        Some("endpoint") => {
-                            // Server is redirecting to a new endpoint (rare)
-                            log::info!("[MCP-HTTP][{}] Server sent endpoint redirect: {}", 
-                                self.server_name, data);
-                        }
-                        _ => {
-                            // Default: "message" event or no event type
-                            // Try to parse as JSON-RPC response
-                            if let Ok(resp) = serde_json::from_str::<JsonRpcResponse>(&data) {
-                                if let Some(error) = resp.error {
-                                    return Err(format!("JSON-RPC error {}: {}", error.code, error.message));
-                                }
-                                result = Some(resp.result.unwrap_or(serde_json::Value::Null));
-                                // Continue processing in case there are more events
-                            }
-                            // Handle notifications (log them but continue)
-                            else if let Ok(notif) = serde_json::from_str::<JsonRpcNotification>(&data) {
-                                log::info!("[MCP-HTTP][{}] Server notification: {}", 
-                                    self.server_name, notif.method);
-                            }
-                            // Could be a partial or malformed message
-                            else {
-                                log::debug!("[MCP-HTTP][{}] Non-JSON SSE data: {}", 
-                                    self.server_name, data);
-                            }
-                        }
-                    }
-                    
-                    // Reset event type after processing
-                    last_event_type = None;
-                }
-            }
-            
-            // If we already have a result and the buffer is empty, we can return early
-            if result.is_some() && buffer.trim().is_empty() {
-                break;
-            }
+    /// Apply one SSE event; returns the result if it is the awaited response
+    fn handle_sse_event(
+        &self,
+        ctx: &StreamContext,
+        event: SseEvent,
+        request_id: u64,
+        last_event_id: &mut Option<String>,
+        retry_ms: &mut u64,
+    ) -> Result<Option<serde_json::Value>, String> {
+        log::debug!("[MCP-HTTP][{}] SSE event: {:?}", self.server_name, event);
+
+        if let Some(id) = event.id {
+            *last_event_id = Some(id);
+        }
+        if let Some(retry) = event.retry {
+            *retry_ms = retry;
         }
+        let Some(data) = event.data else { return Ok(None) };
 
-        // Flush remaining buffer — some servers don't send trailing \n\n before closing the stream
-        let remaining = buffer.trim().to_string();
-        if !remaining.is_empty() && result.is_none() {
-            log::info!("[MCP-HTTP][{}] Flushing remaining SSE buffer ({} bytes)", self.server_name, remaining.len());
-            let (event_type, data) = Self::parse_sse_event_full(&remaining);
-            if let Some(et) = event_type {
-                last_event_type = Some(et);
+        // Handle different event types per MCP spec
+        match event.event.as_deref() {
+            Some("error") => {
+                // Server sent an error event
+                Err(format!("SSE error event: {}", data))
             }
-            if let Some(data) = data {
-                match last_event_type.as_deref() {
-                    Some("error") => {
-                        return Err(format!("SSE error event: {}", data));
-                    }
-                    _ => {
-                        if let Ok(resp) = serde_json::from_str::<JsonRpcResponse>(&data) {
-                            if let Some(error) = resp.error {
-                                return Err(format!("JSON-RPC error {}: {}", error.code, error.message));
-                            }
-                            result = Some(resp.result.unwrap_or(serde_json::Value::Null));
+            Some("endpoint") => {
+                // Server is redirecting to a new endpoint (rare)
+                log::info!("[MCP-HTTP][{}] Server sent endpoint redirect: {}", self.server_name, data);
+                Ok(None)
+            }
+            _ => {
+                // Default: "message" event or no event type
+                match ctx.dispatch(&data) {
+                    Some(resp) if resp.id == request_id => {
+                        if let Some(error) = resp.error {
+                            return Err(format!("JSON-RPC error {}: {}", error.code, error.message));
                         }
+                        Ok(Some(resp.result.unwrap_or(serde_json::Value::Null)))
                     }
+                    Some(resp) => {
+                        log::warn!("[MCP-HTTP][{}] Received response for unexpected request id: {}",
+                            self.server_name, resp.id);
+                        Ok(None)
+                    }
+                    None => Ok(None),
                 }
             }
         }
-
-        result.ok_or_else(|| "No response received from SSE stream".to_string())
     }
 
-    /// Parse SSE event block and extract event type and data
-    /// Returns (event_type, data) tuple
-    fn parse_sse_event_full(event_str: &str) -> (Option<String>, Option<String>) {
-        let mut event_type: Option<String> = None;
+    /// Parse SSE event block and extract event type, data, id and retry fields
+    fn parse_sse_event_full(event_str: &str) -> SseEvent {
+        let mut event = SseEvent::default();
         let mut data_lines = Vec::new();
-        
+
         for line in event_str.lines() {
             let line = line.trim_start(); // SSE spec says leading spaces should be ignored
-            
+
             if let Some(et) = line.strip_prefix("event:") {
-                event_type = Some(et.trim().to_string());
+                event.event = Some(et.trim().to_string());
             } else if let Some(data) = line.strip_prefix("data:") {
                 // Handle "data: value" or "data:value"
                 // Per SSE spec, a single space after colon should be removed
-                let data = if data.starts_with(' ') {
-                    &data[1..]
-                } else {
-                    data
-                };
+                let data = data.strip_prefix(' ').unwrap_or(data);
                 data_lines.push(data.to_string());
-            } else if line.starts_with("id:") || line.starts_with("retry:") {
-                // Ignore id and retry fields for now
-            } else if line.starts_with(':') {
-                // Comment line, ignore
+            } else if let Some(id) = line.strip_prefix("id:") {
+                event.id = Some(id.trim().to_string());
+            } else if let Some(retry) = line.strip_prefix("retry:") {
+                event.retry = retry.trim().parse().ok();
             }
-            // Empty lines within an event block are ignored
+            // Comment lines (":") and empty lines within an event block are ignored
         }
-        
-        let data = if data_lines.is_empty() {
-            None
-        } else {
-            Some(data_lines.join("\n"))
-        };
-        
-        (event_type, data)
-    }
-    
-    /// Legacy parse function for backward compatibility
-    fn parse_sse_event(event_str: &str) -> Option<String> {
-        Self::parse_sse_event_full(event_str).1
+
+        if !data_lines.is_empty() {
+            event.data = Some(data_lines.join("\n"));
+        }
+
+        event
     }
 
     /// Send initialize request
     async fn initialize(&self) -> Result<(), String> {
+        self.handshake().await?;
+
+        // Fetch tools and resources
+        self.refresh_tools().await?;
+        self.refresh_resources().await?;
+
+        // Templates are optional - older servers answer with method-not-found
+        if let Err(e) = self.refresh_resource_templates().await {
+            log::warn!("[MCP-HTTP][{}] resources/templates/list failed: {}", self.server_name, e);
+        }
+
+        Ok(())
+    }
+
+    /// initialize + notifications/initialized; establishes a fresh session
+    async fn handshake(&self) -> Result<(), String> {
         let params = InitializeParams {
             protocol_version: PROTOCOL_VERSION.to_string(),
             capabilities: ClientCapabilities {
@@ -314,21 +733,66 @@ impl McpHttpClient {
             },
         };
 
-        let result: InitializeResult = self
-            .send_request("initialize", Some(serde_json::to_value(params).unwrap()))
-            .await
+        // A new InitializeRequest must not carry the old session id
+        *self.session_id.lock().unwrap() = None;
+        *self.protocol_version.lock().unwrap() = None;
+        *self.last_event_id.lock().unwrap() = None;
+
+        let params = Some(serde_json::to_value(params).unwrap());
+        let mut response = self.send_request_once("initialize", params.clone()).await;
+        if let Err(HttpRequestError::Unauthorized) = response {
+            self.refresh_oauth().await?;
+            response = self.send_request_once("initialize", params).await;
+        }
+        let result: InitializeResult = response
+            .map_err(HttpRequestError::into_message)
             .and_then(|v| serde_json::from_value(v).map_err(|e| e.to_string()))?;
 
+        if !result.protocol_version.is_empty() {
+            *self.protocol_version.lock().unwrap() = Some(result.protocol_version.clone());
+        }
         *self.server_capabilities.lock().unwrap() = result.capabilities;
         *self.server_info.lock().unwrap() = result.server_info;
 
         // Send initialized notification
         self.send_notification("notifications/initialized", None).await?;
 
-        // Fetch tools and resources
-        self.refresh_tools().await?;
-        self.refresh_resources().await?;
+        Ok(())
+    }
+
+    /// Start a new session after the server expired ours
+    async fn reinitialize_session(&self) -> Result<(), String> {
+        log::warn!("[MCP-HTTP][{}] Session expired, re-initializing", self.server_name);
+        self.handshake().await?;
+
+        // Subscriptions belong to the old session
+        let uris: Vec<String> = self.subscriptions.lock().unwrap().iter().cloned().collect();
+        for uri in uris {
+            if let Err(HttpRequestError::Other(e)) = self
+                .send_request_once("resources/subscribe", Some(serde_json::json!({ "uri": uri })))
+                .await
+            {
+                log::warn!("[MCP-HTTP][{}] Failed to restore subscription {}: {}", self.server_name, uri, e);
+            }
+        }
+
+        if *self.is_connected.lock().unwrap() {
+            self.start_get_stream();
+        }
+        Ok(())
+    }
 
+    /// Refresh the OAuth access token and persist it; fails if there is nothing to refresh
+    async fn refresh_oauth(&self) -> Result<(), String> {
+        let Some(credentials) = self.oauth.lock().unwrap().clone() else {
+            return Err("HTTP 401 - authorization required".to_string());
+        };
+        let refreshed = oauth::refresh(&credentials).await
+            .map_err(|e| format!("HTTP 401 - authorization required ({})", e))?;
+        *self.oauth.lock().unwrap() = Some(refreshed.clone());
+        if let Some(store) = self.token_store.lock().unwrap().clone() {
+            store(&self.server_id, &refreshed);
+        }
         Ok(())
     }
 
@@ -370,12 +834,60 @@ impl McpHttpClient {
         Ok(())
     }
 
+    /// Refresh resource templates list
+    pub async fn refresh_resource_templates(&self) -> Result<(), String> {
+        let caps = self.server_capabilities.lock().unwrap().clone();
+        if caps.resources.is_none() {
+            *self.resource_templates.lock().unwrap() = Vec::new();
+            return Ok(());
+        }
+
+        let result: ResourceTemplatesListResult = self
+            .send_request("resources/templates/list", None)
+            .await
+            .and_then(|v| serde_json::from_value(v).map_err(|e| e.to_string()))?;
+
+        log::info!("[MCP-HTTP][{}] Resource templates: {:?}", self.server_name, result.resource_templates.iter().map(|t| &t.uri_template).collect::<Vec<_>>());
+        *self.resource_templates.lock().unwrap() = result.resource_templates;
+
+        Ok(())
+    }
+
+    /// Subscribe to notifications/resources/updated for a URI
+    pub async fn subscribe_resource(&self, uri: &str) -> Result<(), String> {
+        let supports_subscribe = self.server_capabilities.lock().unwrap()
+            .resources.as_ref().map(|r| r.subscribe).unwrap_or(false);
+        if !supports_subscribe {
+            return Err("Server does not support resource subscriptions".to_string());
+        }
+
+        self.send_request("resources/subscribe", Some(serde_json::json!({ "uri": uri }))).await?;
+        self.subscriptions.lock().unwrap().insert(uri.to_string());
+        log::info!("[MCP-HTTP][{}] Subscribed to resource: {}", self.server_name, uri);
+
+        Ok(())
+    }
+
+    /// Unsubscribe from updates for a URI
+    pub async fn unsubscribe_resource(&self, uri: &str) -> Result<(), String> {
+        if !self.subscriptions.lock().unwrap().contains(uri) {
+            return Ok(());
+        }
+
+        // Keep the subscription until the server confirms, so a failed call leaves both sides in sync
+        self.send_request("resources/unsubscribe", Some(serde_json::json!({ "uri": uri }))).await?;
+        self.subscriptions.lock().unwrap().remove(uri);
+        log::info!("[MCP-HTTP][{}] Unsubscribed from resource: {}", self.server_name, uri);
+
+        Ok(())
+    }
+
     /// Call a tool with cancellation support
     pub async fn call_tool(&self, name: &str, arguments: Option<serde_json::Value>) -> Result<ToolCallResult, String> {
         if !*self.is_connected.lock().unwrap() {
             return Err("Not connected".to_string());
         }
-        
+
         // Check cancellation before starting
         if self.is_cancelled() {
             return Err("Operation cancelled".to_string());
@@ -393,7 +905,7 @@ impl McpHttpClient {
             .send_request("tools/call", Some(serde_json::to_value(params).unwrap()))
             .await
             .and_then(|v| serde_json::from_value(v).map_err(|e| e.to_string()))?;
-        
+
         // Check cancellation after completion
         if self.is_cancelled() {
             return Err("Operation cancelled".to_string());
@@ -422,8 +934,26 @@ impl McpHttpClient {
     }
 
     /// Send JSON-RPC request via HTTP POST (Streamable HTTP)
-    /// The server may respond with application/json or text/event-stream
+    /// Re-initializes once if the server reports the session as expired
+    /// Refreshes the OAuth token once on 401
     async fn send_request(&self, method: &str, params: Option<serde_json::Value>) -> Result<serde_json::Value, String> {
+        match self.send_request_once(method, params.clone()).await {
+            Ok(result) => Ok(result),
+            Err(HttpRequestError::SessionExpired) => {
+                self.reinitialize_session().await?;
+                self.send_request_once(method, params).await.map_err(HttpRequestError::into_message)
+            }
+            Err(HttpRequestError::Unauthorized) => {
+                self.refresh_oauth().await?;
+                self.send_request_once(method, params).await.map_err(HttpRequestError::into_message)
+            }
+            Err(HttpRequestError::Other(e)) => Err(e),
+        }
+    }
+
+    /// Single POST round-trip
+    /// The server may respond with application/json or text/event-stream
+    async fn send_request_once(&self, method: &str, params: Option<serde_json::Value>) -> Result<serde_json::Value, HttpRequestError> {
         let id = self.request_id.fetch_add(1, Ordering::SeqCst);
 
         let request = JsonRpcRequest {
@@ -436,40 +966,59 @@ impl McpHttpClient {
         log::info!("[MCP-HTTP][{}] Sending request: {} (id={})", self.server_name, method, id);
         log::debug!("[MCP-HTTP][{}] Request body: {:?}", self.server_name, request);
 
-        // Build request - POST to the MCP endpoint
-        let mut req = self.client.post(&self.endpoint_url)
-            .header("Content-Type", "application/json")
-            // Accept both JSON and SSE responses as per spec
-            .header("Accept", "application/json, text/event-stream")
-            .json(&request);
-
-        // Add API key if provided (as query param is already in URL, but also try Bearer token)
-        if let Some(key) = &self.api_key {
-            req = req.header("Authorization", format!("Bearer {}", key));
-        }
-
-        // Add session ID if we have one
-        if let Some(session_id) = &*self.session_id.read().await {
-            req = req.header("Mcp-Session-Id", session_id);
+        // Refresh proactively rather than waiting for a 401
+        let expired = self.oauth.lock().unwrap().as_ref().map(|c| c.is_expired() && c.refresh_token.is_some()).unwrap_or(false);
+        if expired {
+            if let Err(e) = self.refresh_oauth().await {
+                log::warn!("[MCP-HTTP][{}] Token refresh failed: {}", self.server_name, e);
+            }
         }
 
-        // Send request
-        let response = req.send().await.map_err(|e| {
-            format!("HTTP request failed: {}", e)
-        })?;
+        let ctx = self.stream_context();
+        let had_session = self.session_id().is_some();
+
+        // Send request, retrying only when the connection could not be established
+        let mut attempt = 0;
+        let response = loop {
+            attempt += 1;
+            // Build request - POST to the MCP endpoint
+            let req = ctx.with_headers(
+                self.client.post(&self.endpoint_url)
+                    .header("Content-Type", "application/json")
+                    // Accept both JSON and SSE responses as per spec
+                    .header("Accept", "application/json, text/event-stream")
+                    .json(&request),
+            );
+            match req.send().await {
+                Ok(resp) => break resp,
+                Err(e) if e.is_connect() && attempt < MAX_SEND_ATTEMPTS && !self.is_cancelled() => {
+                    log::warn!("[MCP-HTTP][{}] Connect failed (attempt {}): {}", self.server_name, attempt, e);
+                    tokio::time::sleep(Duration::from_millis(RECONNECT_INITIAL_MS * attempt as u64)).await;
+                }
+                Err(e) => return Err(format!("HTTP request failed: {}", e).into()),
+            }
+        };
 
         // Check for session ID in response (set by server during initialization)
         if let Some(session_id) = response.headers().get("mcp-session-id") {
-            if let Ok(id) = session_id.to_str() {
-                log::info!("[MCP-HTTP][{}] Got session ID: {}", self.server_name, id);
-                *self.session_id.write().await = Some(id.to_string());
+            if let Ok(sid) = session_id.to_str() {
+                if self.session_id().as_deref() != Some(sid) {
+                    log::info!("[MCP-HTTP][{}] Got session ID: {}", self.server_name, sid);
+                    *self.session_id.lock().unwrap() = Some(sid.to_string());
+                }
             }
         }
 
         let status = response.status();
+        if status == reqwest::StatusCode::NOT_FOUND && had_session && method != "initialize" {
+            return Err(HttpRequestError::SessionExpired);
+        }
+        if status == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(HttpRequestError::Unauthorized);
+        }
         if !status.is_success() {
             let body = response.text().await.unwrap_or_default();
-            return Err(format!("HTTP {} - {}", status, body));
+            return Err(format!("HTTP {} - {}", status, body).into());
         }
 
         // Check Content-Type to determine how to parse response
@@ -484,23 +1033,23 @@ impl McpHttpClient {
         if content_type.contains("text/event-stream") {
             // Parse SSE stream response
             log::debug!("[MCP-HTTP][{}] Parsing SSE response", self.server_name);
-            self.parse_sse_response(response).await
+            Ok(self.parse_sse_response(response, id).await?)
         } else {
             // Parse JSON response
             let body = response.text().await.map_err(|e| e.to_string())?;
             log::debug!("[MCP-HTTP][{}] JSON response: {}", self.server_name, body);
-            
+
             if body.is_empty() {
-                return Err("Empty response body".to_string());
+                return Err("Empty response body".to_string().into());
             }
 
             let resp: JsonRpcResponse = serde_json::from_str(&body)
                 .map_err(|e| format!("Failed to parse JSON response: {} - body: {}", e, body))?;
-            
+
             if let Some(error) = resp.error {
-                return Err(error.message);
+                return Err(error.message.into());
             }
-            
+
             Ok(resp.result.unwrap_or(serde_json::Value::Null))
         }
     }
@@ -516,21 +1065,15 @@ impl McpHttpClient {
 
         log::debug!("[MCP-HTTP][{}] Sending notification: {}", self.server_name, method);
 
-        let mut req = self.client.post(&self.endpoint_url)
-            .header("Content-Type", "application/json")
-            .header("Accept", "application/json, text/event-stream")
-            .json(&notification);
-
-        if let Some(key) = &self.api_key {
-            req = req.header("Authorization", format!("Bearer {}", key));
-        }
-
-        if let Some(session_id) = &*self.session_id.read().await {
-            req = req.header("Mcp-Session-Id", session_id);
-        }
+        let req = self.stream_context().with_headers(
+            self.client.post(&self.endpoint_url)
+                .header("Content-Type", "application/json")
+                .header("Accept", "application/json, text/event-stream")
+                .json(&notification),
+        );
 
         let response = req.send().await.map_err(|e| format!("Failed to send notification: {}", e))?;
-        
+
         // Per spec: server should return 202 Accepted for notifications
         // But we accept any 2xx status
         if !response.status().is_success() {
@@ -543,13 +1086,41 @@ impl McpHttpClient {
     }
 
     /// Disconnect from the server
+    /// Terminates the session with DELETE (fire-and-forget when a runtime is available)
     pub fn disconnect(&self) {
         log::info!("[MCP-HTTP][{}] Disconnecting", self.server_name);
-        
+
         // Set cancelled to interrupt any ongoing operations
         self.cancelled.store(true, Ordering::SeqCst);
         *self.is_connected.lock().unwrap() = false;
 
+        if let Some(task) = self.get_stream_task.lock().unwrap().take() {
+            task.abort();
+        }
+
+        // Build the DELETE before clearing the session so it carries the id
+        let delete_req = self.session_id().map(|sid| {
+            (sid, self.stream_context().with_headers(self.client.delete(&self.endpoint_url)))
+        });
+        *self.session_id.lock().unwrap() = None;
+        *self.last_event_id.lock().unwrap() = None;
+        if let Some((sid, req)) = delete_req {
+            match tokio::runtime::Handle::try_current() {
+                Ok(handle) => {
+                    let server_name = self.server_name.clone();
+                    handle.spawn(async move {
+                        match req.send().await {
+                            // 405 means the server does not let clients terminate sessions
+                            Ok(resp) => log::info!("[MCP-HTTP][{}] Session {} terminated: HTTP {}",
+                                server_name, sid, resp.status()),
+                            Err(e) => log::warn!("[MCP-HTTP][{}] Session DELETE failed: {}", server_name, e),
+                        }
+                    });
+                }
+                Err(_) => log::debug!("[MCP-HTTP][{}] No runtime, skipping session DELETE", self.server_name),
+            }
+        }
+
         // Clear pending requests
         let mut pending = self.pending_requests.lock().unwrap();
         for (_, tx) in pending.drain() {
@@ -558,6 +1129,31 @@ impl McpHttpClient {
 
         *self.tools.lock().unwrap() = Vec::new();
         *self.resources.lock().unwrap() = Vec::new();
+        *self.resource_templates.lock().unwrap() = Vec::new();
+        self.subscriptions.lock().unwrap().clear();
+    }
+
+    /// Health check: `ping` must be answered within PING_TIMEOUT_SECS
+    pub async fn ping(&self) -> Result<(), String> {
+        if !self.is_connected() {
+            return Err("Not connected".to_string());
+        }
+        match timeout(Duration::from_secs(PING_TIMEOUT_SECS), self.send_request("ping", None)).await {
+            Ok(result) => result.map(|_| ()),
+            Err(_) => Err(format!("Request timeout after {}s: ping", PING_TIMEOUT_SECS)),
+        }
+    }
+
+    /// Tear down the session and initialize a new one; used by the supervisor
+    pub async fn reconnect(&self) -> Result<(), String> {
+        self.disconnect();
+        self.reset_cancellation();
+        self.restart_count.fetch_add(1, Ordering::SeqCst);
+        self.connect().await
+    }
+
+    pub fn restart_count(&self) -> u32 {
+        self.restart_count.load(Ordering::SeqCst)
     }
 
     /// Get connection status
@@ -575,6 +1171,18 @@ impl McpHttpClient {
         self.resources.lock().unwrap().clone()
     }
 
+    /// Get available resource templates
+    pub fn get_resource_templates(&self) -> Vec<McpResourceTemplate> {
+        self.resource_templates.lock().unwrap().clone()
+    }
+
+    /// Get URIs with active update subscriptions
+    pub fn get_subscriptions(&self) -> Vec<String> {
+        let mut uris: Vec<String> = self.subscriptions.lock().unwrap().iter().cloned().collect();
+        uris.sort();
+        uris
+    }
+
     /// Get server info
     pub fn get_server_info(&self) -> Option<ServerInfo> {
         self.server_info.lock().unwrap().clone()
@@ -588,8 +1196,12 @@ impl McpHttpClient {
             is_running: self.is_connected(),
             tools: self.get_tools(),
             resources: self.get_resources(),
+            resource_templates: self.get_resource_templates(),
+            subscriptions: self.get_subscriptions(),
             server_info: self.get_server_info(),
             error: None,
+            stderr_tail: Vec::new(),
+            restart_count: self.restart_count(),
         }
     }
 }
@@ -599,3 +1211,291 @@ impl Drop for McpHttpClient {
         self.disconnect();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::mock_provider;
+    use crate::mcp::mock_server::{self, MockHttpServer, MockResponse};
+    use serde_json::json;
+
+    fn rpc_result(id: &serde_json::Value, result: serde_json::Value) -> String {
+        json!({ "jsonrpc": "2.0", "id": id, "result": result }).to_string()
+    }
+
+    /// Minimal MCP server: one `echo` tool, answers notifications with 202
+    fn basic_reply(body: &serde_json::Value, session: &str) -> MockResponse {
+        let id = body["id"].clone();
+        match body["method"].as_str().unwrap_or("") {
+            "initialize" => MockResponse::json(rpc_result(&id, json!({
+                "protocolVersion": "2025-03-26",
+                "capabilities": { "tools": {}, "resources": { "subscribe": true } },
+                "serverInfo": { "name": "mock", "version": "1.0" }
+            }))).with_header("Mcp-Session-Id", session),
+            "tools/list" => MockResponse::json(rpc_result(&id, json!({ "tools": [{ "name": "echo", "inputSchema": {} }] }))),
+            "resources/list" => MockResponse::json(rpc_result(&id, json!({ "resources": [] }))),
+            "resources/templates/list" => MockResponse::json(rpc_result(&id, json!({ "resourceTemplates": [] }))),
+            "tools/call" => MockResponse::json(rpc_result(&id, json!({ "content": [{ "type": "text", "text": "pong" }] }))),
+            _ => MockResponse::status(202),
+        }
+    }
+
+    #[tokio::test]
+    async fn session_id_is_sent_and_deleted_on_disconnect() {
+        let server = MockHttpServer::start(|req| match req.method.as_str() {
+            "GET" => MockResponse::status(405),
+            "DELETE" => MockResponse::status(200),
+            _ => basic_reply(&req.json(), "sess-1"),
+        }).await;
+
+        let client = McpHttpClient::new("id".into(), "mock".into(), server.url(), None);
+        client.connect().await.unwrap();
+        assert_eq!(client.session_id().as_deref(), Some("sess-1"));
+        client.call_tool("echo", None).await.unwrap();
+
+        client.disconnect();
+        server.wait_for(|reqs| reqs.iter().any(|r| r.method == "DELETE")).await;
+
+        let requests = server.requests();
+        assert!(requests[0].header("mcp-session-id").is_none());
+        assert!(requests.iter().skip(1).all(|r| r.header("mcp-session-id") == Some("sess-1")));
+        let tools_list = requests.iter().find(|r| r.json()["method"] == "tools/list").unwrap();
+        assert_eq!(tools_list.header("mcp-protocol-version"), Some("2025-03-26"));
+    }
+
+    #[tokio::test]
+    async fn failed_unsubscribe_keeps_the_subscription() {
+        let server = MockHttpServer::start(|req| {
+            if req.method != "POST" {
+                return MockResponse::status(405);
+            }
+            let body = req.json();
+            match body["method"].as_str().unwrap_or("") {
+                "resources/subscribe" => MockResponse::json(rpc_result(&body["id"], json!({}))),
+                "resources/unsubscribe" => MockResponse::json(json!({
+                    "jsonrpc": "2.0", "id": body["id"], "error": { "code": -32603, "message": "busy" }
+                }).to_string()),
+                _ => basic_reply(&body, "sess-1"),
+            }
+        }).await;
+
+        let client = McpHttpClient::new("id".into(), "mock".into(), server.url(), None);
+        client.connect().await.unwrap();
+        client.subscribe_resource("mem://a").await.unwrap();
+
+        assert!(client.unsubscribe_resource("mem://a").await.is_err());
+        assert_eq!(client.get_subscriptions(), ["mem://a"]);
+    }
+
+    #[tokio::test]
+    async fn expired_session_is_reinitialized() {
+        let inits = Arc::new(Mutex::new(0));
+        let inits_clone = inits.clone();
+        let server = MockHttpServer::start(move |req| {
+            if req.method != "POST" {
+                return MockResponse::status(405);
+            }
+            let body = req.json();
+            if body["method"] == "initialize" {
+                *inits_clone.lock().unwrap() += 1;
+            }
+            if body["method"] == "tools/call" && req.header("mcp-session-id") == Some("sess-1") {
+                return MockResponse::status(404);
+            }
+            let session = format!("sess-{}", *inits_clone.lock().unwrap());
+            basic_reply(&body, &session)
+        }).await;
+
+        let client = McpHttpClient::new("id".into(), "mock".into(), server.url(), None);
+        client.connect().await.unwrap();
+        let result = client.call_tool("echo", None).await.unwrap();
+
+        assert!(!result.is_error);
+        assert_eq!(*inits.lock().unwrap(), 2);
+        assert_eq!(client.session_id().as_deref(), Some("sess-2"));
+    }
+
+    #[tokio::test]
+    async fn dropped_post_stream_resumes_with_last_event_id() {
+        let call_id = Arc::new(Mutex::new(serde_json::Value::Null));
+        let call_id_clone = call_id.clone();
+        let server = MockHttpServer::start(move |req| {
+            if req.method == "GET" {
+                return match req.header("last-event-id") {
+                    Some("evt-1") => {
+                        let id = call_id_clone.lock().unwrap().clone();
+                        let result = json!({ "content": [{ "type": "text", "text": "resumed" }] });
+                        MockResponse::sse(vec![format!("id: evt-2\ndata: {}\n\n", rpc_result(&id, result))])
+                    }
+                    _ => MockResponse::status(405),
+                };
+            }
+            let body = req.json();
+            if body["method"] == "tools/call" {
+                // Priming event, then the connection drops before the response
+                *call_id_clone.lock().unwrap() = body["id"].clone();
+                return MockResponse::sse(vec!["id: evt-1\nretry: 10\ndata: \n\n".to_string()]);
+            }
+            basic_reply(&body, "sess-1")
+        }).await;
+
+        let client = McpHttpClient::new("id".into(), "mock".into(), server.url(), None);
+        client.connect().await.unwrap();
+        let result = client.call_tool("echo", None).await.unwrap();
+
+        match &result.content[0] {
+            ToolContent::Text { text } => assert_eq!(text, "resumed"),
+            other => panic!("unexpected content {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn get_stream_delivers_server_requests_and_notifications() {
+        let server = MockHttpServer::start(|req| {
+            if req.method == "GET" {
+                return MockResponse::sse(vec![
+                    "id: 1\ndata: {\"jsonrpc\":\"2.0\",\"id\":\"srv-1\",\"method\":\"ping\"}\n\n".to_string(),
+                    "id: 2\ndata: {\"jsonrpc\":\"2.0\",\"method\":\"notifications/resources/updated\",\"params\":{\"uri\":\"file:///a\"}}\n\n".to_string(),
+                ]).hold_open();
+            }
+            basic_reply(&req.json(), "sess-1")
+        }).await;
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let events_clone = events.clone();
+        let client = McpHttpClient::new("srv".into(), "mock".into(), server.url(), None);
+        client.set_event_sink(Some(Arc::new(move |e: McpEvent| events_clone.lock().unwrap().push(e))));
+        client.connect().await.unwrap();
+
+        server.wait_for(|reqs| reqs.iter().any(|r| r.json()["id"] == "srv-1")).await;
+        let pong = server.requests().into_iter().find(|r| r.json()["id"] == "srv-1").unwrap();
+        assert_eq!(pong.json()["result"], json!({}));
+        assert_eq!(pong.header("mcp-session-id"), Some("sess-1"));
+
+        for _ in 0..50 {
+            if !events.lock().unwrap().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        match events.lock().unwrap().first() {
+            Some(McpEvent::ResourceUpdated { server_id, uri }) => {
+                assert_eq!(server_id, "srv");
+                assert_eq!(uri, "file:///a");
+            }
+            other => panic!("expected resource update, got {:?}", other),
+        }
+        client.disconnect();
+    }
+
+    #[tokio::test]
+    async fn unauthorized_request_refreshes_oauth_token() {
+        let server = MockHttpServer::start(|req| {
+            if req.path == "/token" {
+                return MockResponse::json(json!({
+                    "access_token": "new-token",
+                    "refresh_token": "refresh-2",
+                    "expires_in": 3600,
+                }).to_string());
+            }
+            if req.method == "GET" {
+                return MockResponse::status(405);
+            }
+            if req.header("authorization") != Some("Bearer new-token") {
+                return MockResponse::status(401);
+            }
+            basic_reply(&req.json(), "sess-1")
+        }).await;
+
+        let stored = Arc::new(Mutex::new(Vec::new()));
+        let stored_clone = stored.clone();
+        let client = McpHttpClient::new("srv".into(), "mock".into(), server.url(), None);
+        client.set_oauth(
+            Some(OAuthCredentials {
+                resource: server.url(),
+                token_endpoint: server.url().replace("/mcp", "/token"),
+                client_id: "client-1".into(),
+                client_secret: None,
+                access_token: "old-token".into(),
+                refresh_token: Some("refresh-1".into()),
+                expires_at: None,
+                scope: None,
+            }),
+            Some(Arc::new(move |server_id: &str, creds: &OAuthCredentials| {
+                stored_clone.lock().unwrap().push((server_id.to_string(), creds.clone()));
+            })),
+        );
+        client.connect().await.unwrap();
+
+        let token_request = server.requests().into_iter().find(|r| r.path == "/token").unwrap();
+        assert!(token_request.body.contains("grant_type=refresh_token"));
+        assert!(token_request.body.contains("refresh_token=refresh-1"));
+
+        let stored = stored.lock().unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].0, "srv");
+        assert_eq!(stored[0].1.access_token, "new-token");
+        assert_eq!(stored[0].1.refresh_token.as_deref(), Some("refresh-2"));
+        assert!(stored[0].1.expires_at.is_some());
+    }
+
+    #[test]
+    fn sse_decoder_handles_split_chunks_and_fields() {
+        let mut decoder = SseDecoder::default();
+        assert!(decoder.push("id: 7\nretry: 250\ndata: {\"a\":").is_empty());
+        let events = decoder.push("1}\r\n\r\n: comment\nevent: message\ndata: x\n");
+        assert_eq!(events, vec![SseEvent {
+            event: None,
+            data: Some("{\"a\":1}".to_string()),
+            id: Some("7".to_string()),
+            retry: Some(250),
+        }]);
+        let tail = decoder.finish().unwrap();
+        assert_eq!(tail.event.as_deref(), Some("message"));
+        assert_eq!(tail.data.as_deref(), Some("x"));
+    }
+
+    #[tokio::test]
+    async fn scripted_server_streams_progress_and_sampling() {
+        let llm = MockHttpServer::start(|_| mock_provider::openai_completion("sampled")).await;
+        let server = mock_server::start_mcp(json!({
+            "tools": [
+                { "name": "slow", "progress": [0.25, 1.0], "result": [{ "type": "text", "text": "done" }] },
+                { "name": "ask", "sampling": "Say something" }
+            ],
+            "resources": [{ "uri": "mem://a", "text": "alpha" }]
+        })).await;
+
+        let client = McpHttpClient::new("srv".into(), "mock".into(), server.url(), None);
+        client.set_sampling_config(Some(SamplingLlmConfig {
+            api_key: "k".into(),
+            model: "m".into(),
+            base_url: Some(llm.base_url()),
+            api_format: "openai_compatible".into(),
+        }));
+        client.connect().await.unwrap();
+        assert_eq!(client.session_id().as_deref(), Some("mock-session"));
+
+        let slow = client.call_tool("slow", None).await.unwrap();
+        assert!(matches!(&slow.content[0], ToolContent::Text { text } if text == "done"));
+        let ask = client.call_tool("ask", None).await.unwrap();
+        assert!(matches!(&ask.content[0], ToolContent::Text { text } if text == "sampled"));
+        assert_eq!(llm.requests()[0].json()["messages"][0]["content"], "Say something");
+
+        let read = client.read_resource("mem://a").await.unwrap();
+        assert_eq!(read.contents[0].text.as_deref(), Some("alpha"));
+        client.disconnect();
+    }
+
+    #[tokio::test]
+    async fn scripted_server_crash_fails_the_call() {
+        let server = mock_server::start_mcp(json!({ "tools": [{ "name": "boom", "crash": true }] })).await;
+
+        let client = McpHttpClient::new("srv".into(), "mock".into(), server.url(), None);
+        client.connect().await.unwrap();
+
+        assert!(client.call_tool("boom", None).await.is_err());
+        assert!(client.call_tool("boom", None).await.is_err());
+        client.disconnect();
+    }
+}
@@ -2,18 +2,33 @@
 // Manages lifecycle of all MCP servers (both stdio and HTTP transports)
 
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::sync::RwLock;
-use tokio::time::{timeout, Duration};
+use tokio::task::JoinHandle;
+use tokio::time::{timeout, Duration, Instant};
 
 use super::client::McpClient;
 use super::http_client::McpHttpClient;
+use super::oauth::{OAuthCredentials, OAuthTokenStore};
+use super::permissions::ApprovalBroker;
+use super::resources::{expand_uri_template, template_variables};
+use super::sandbox::Sandbox;
+use super::tool_ids::{build_tool_index, split_legacy_name, ServerTools, ToolTarget};
 use super::types::*;
 
 // Default timeout for tool calls via manager
 const MANAGER_TOOL_TIMEOUT_SECS: u64 = 300; // 5 minutes
 
+// Supervisor tuning
+const SUPERVISOR_POLL_SECS: u64 = 1; // How often process liveness is checked
+const HEALTH_CHECK_INTERVAL_SECS: u64 = 30; // How often `ping` is sent
+const UNHEALTHY_PING_FAILURES: u32 = 2; // Consecutive failed pings treated as a crash
+const STABLE_RUN_SECS: u64 = 60; // Uptime after which the backoff is reset
+const MAX_FAILURE_RESTARTS: u32 = 5; // on-failure gives up after this many attempts
+const RESTART_BACKOFF_INITIAL_MS: u64 = 1000;
+const RESTART_BACKOFF_MAX_MS: u64 = 60000;
+
 /// Unified client wrapper for both transport types
 #[derive(Clone)]
 pub enum McpClientWrapper {
@@ -81,22 +96,221 @@ impl McpClientWrapper {
         }
     }
 
+    pub async fn subscribe_resource(&self, uri: &str) -> Result<(), String> {
+        match self {
+            McpClientWrapper::Stdio(c) => c.subscribe_resource(uri).await,
+            McpClientWrapper::Http(c) => c.subscribe_resource(uri).await,
+        }
+    }
+
+    pub async fn unsubscribe_resource(&self, uri: &str) -> Result<(), String> {
+        match self {
+            McpClientWrapper::Stdio(c) => c.unsubscribe_resource(uri).await,
+            McpClientWrapper::Http(c) => c.unsubscribe_resource(uri).await,
+        }
+    }
+
+    pub fn get_resource_templates(&self) -> Vec<McpResourceTemplate> {
+        match self {
+            McpClientWrapper::Stdio(c) => c.get_resource_templates(),
+            McpClientWrapper::Http(c) => c.get_resource_templates(),
+        }
+    }
+
     /// Set LLM config for MCP Sampling (server→client LLM calls)
     pub fn set_sampling_config(&self, config: Option<SamplingLlmConfig>) {
         match self {
             McpClientWrapper::Stdio(c) => c.set_sampling_config(config),
-            McpClientWrapper::Http(_) => {
-                // HTTP transport doesn't support sampling yet
-                log::debug!("[McpClientWrapper] set_sampling_config ignored for HTTP client");
+            McpClientWrapper::Http(c) => c.set_sampling_config(config),
+        }
+    }
+
+    pub async fn ping(&self) -> Result<(), String> {
+        match self {
+            McpClientWrapper::Stdio(c) => c.ping().await,
+            McpClientWrapper::Http(c) => c.ping().await,
+        }
+    }
+
+    pub async fn reconnect(&self) -> Result<(), String> {
+        match self {
+            McpClientWrapper::Stdio(c) => c.reconnect().await,
+            McpClientWrapper::Http(c) => c.reconnect().await,
+        }
+    }
+
+    /// Exit code of the server process, once it has exited (stdio only)
+    pub fn exit_code(&self) -> Option<i32> {
+        match self {
+            McpClientWrapper::Stdio(c) => c.exit_code(),
+            McpClientWrapper::Http(_) => None,
+        }
+    }
+
+    pub fn last_error(&self) -> Option<String> {
+        match self {
+            McpClientWrapper::Stdio(c) => c.get_last_error(),
+            McpClientWrapper::Http(_) => None,
+        }
+    }
+
+    pub fn restart_count(&self) -> u32 {
+        match self {
+            McpClientWrapper::Stdio(c) => c.restart_count(),
+            McpClientWrapper::Http(c) => c.restart_count(),
+        }
+    }
+}
+
+/// Delay before restart attempt number `attempt` (0-based), or None to leave the server down.
+/// `exit_code` is None when the failure was not a process exit (failed health checks, HTTP).
+pub fn restart_delay(policy: RestartPolicy, exit_code: Option<i32>, attempt: u32) -> Option<Duration> {
+    match policy {
+        RestartPolicy::Never => return None,
+        RestartPolicy::OnFailure => {
+            if exit_code == Some(0) || attempt >= MAX_FAILURE_RESTARTS {
+                return None;
+            }
+        }
+        RestartPolicy::Always => {}
+    }
+    let ms = RESTART_BACKOFF_INITIAL_MS
+        .saturating_mul(1u64 << attempt.min(16))
+        .min(RESTART_BACKOFF_MAX_MS);
+    Some(Duration::from_millis(ms))
+}
+
+/// Watches one server: detects process exits and failed pings, reports
+/// lifecycle changes and restarts according to `policy`.
+/// Runs until aborted by stop_server/stop_all or until the policy gives up.
+async fn supervise(
+    server_id: String,
+    client: McpClientWrapper,
+    policy: RestartPolicy,
+    event_sink: Option<McpEventSink>,
+) {
+    let emit = |state: ServerLifecycle, error: Option<String>| {
+        if let Some(sink) = &event_sink {
+            sink(McpEvent::StatusChanged {
+                server_id: server_id.clone(),
+                state,
+                error,
+                restart_count: client.restart_count(),
+            });
+        }
+    };
+
+    let mut attempt = 0u32;
+    let mut running_since = Instant::now();
+    let mut last_ping = Instant::now();
+    let mut ping_failures = 0u32;
+
+    loop {
+        tokio::time::sleep(Duration::from_secs(SUPERVISOR_POLL_SECS)).await;
+
+        let mut exit_code = client.exit_code();
+        let mut failure = if exit_code.is_some() || !client.is_connected() {
+            Some(client.last_error().unwrap_or_else(|| match exit_code {
+                Some(code) => format!("Process exited with code {}", code),
+                None => "Connection lost".to_string(),
+            }))
+        } else {
+            None
+        };
+
+        // A cancelled client rejects every request, so skip pings until it is reset
+        if failure.is_none()
+            && !client.is_cancelled()
+            && last_ping.elapsed() >= Duration::from_secs(HEALTH_CHECK_INTERVAL_SECS)
+        {
+            last_ping = Instant::now();
+            match client.ping().await {
+                Ok(()) => {
+                    if ping_failures > 0 {
+                        log::info!("[MCPManager] Server {} healthy again", server_id);
+                        emit(ServerLifecycle::Running, None);
+                    }
+                    ping_failures = 0;
+                }
+                Err(e) => {
+                    ping_failures += 1;
+                    log::warn!("[MCPManager] Health check {} failed for {}: {}", ping_failures, server_id, e);
+                    if ping_failures >= UNHEALTHY_PING_FAILURES {
+                        failure = Some(format!("Health check failed: {}", e));
+                    } else {
+                        emit(ServerLifecycle::Unhealthy, Some(e));
+                    }
+                }
+            }
+        }
+
+        let Some(error) = failure else {
+            if attempt > 0 && running_since.elapsed() >= Duration::from_secs(STABLE_RUN_SECS) {
+                attempt = 0;
+            }
+            continue;
+        };
+
+        log::warn!("[MCPManager] Server {} crashed: {}", server_id, error);
+        emit(ServerLifecycle::Crashed, Some(error.clone()));
+
+        loop {
+            let Some(delay) = restart_delay(policy, exit_code, attempt) else {
+                log::warn!("[MCPManager] Not restarting {} (policy {:?}, attempt {})", server_id, policy, attempt);
+                emit(ServerLifecycle::Failed, Some(error));
+                return;
+            };
+            attempt += 1;
+            log::info!("[MCPManager] Restarting {} in {:?} (attempt {})", server_id, delay, attempt);
+            emit(ServerLifecycle::Restarting, None);
+            tokio::time::sleep(delay).await;
+
+            match client.reconnect().await {
+                Ok(()) => {
+                    log::info!("[MCPManager] Server {} restarted", server_id);
+                    emit(ServerLifecycle::Running, None);
+                    running_since = Instant::now();
+                    last_ping = Instant::now();
+                    ping_failures = 0;
+                    break;
+                }
+                Err(e) => {
+                    log::warn!("[MCPManager] Restart of {} failed: {}", server_id, e);
+                    exit_code = client.exit_code();
+                }
             }
         }
     }
 }
 
+fn tool_index_for(statuses: &[ServerStatus]) -> HashMap<String, ToolTarget> {
+    let servers: Vec<ServerTools> = statuses
+        .iter()
+        .map(|s| ServerTools {
+            server_id: &s.server_id,
+            server_name: &s.name,
+            tool_names: s.tools.iter().map(|t| t.name.as_str()).collect(),
+        })
+        .collect();
+    build_tool_index(&servers)
+}
+
 pub struct McpManager {
     clients: Arc<RwLock<HashMap<String, McpClientWrapper>>>,
     /// Global cancellation flag for all tool calls
     cancelled: Arc<AtomicBool>,
+    /// Receiver for server-initiated events, handed to every client on start
+    event_sink: Option<McpEventSink>,
+    /// Persists refreshed OAuth tokens for HTTP servers
+    token_store: Option<OAuthTokenStore>,
+    /// Restart policy per server id (servers without an entry use `never`)
+    restart_policies: Mutex<HashMap<String, RestartPolicy>>,
+    /// Supervisor task per running server
+    supervisors: Mutex<HashMap<String, JoinHandle<()>>>,
+    /// Sandbox per stdio server id (servers without an entry run unsandboxed)
+    sandboxes: Mutex<HashMap<String, Sandbox>>,
+    /// Tool calls waiting for the user's approval
+    approvals: ApprovalBroker,
 }
 
 impl McpManager {
@@ -104,14 +318,83 @@ impl McpManager {
         Self {
             clients: Arc::new(RwLock::new(HashMap::new())),
             cancelled: Arc::new(AtomicBool::new(false)),
+            event_sink: None,
+            token_store: None,
+            restart_policies: Mutex::new(HashMap::new()),
+            supervisors: Mutex::new(HashMap::new()),
+            sandboxes: Mutex::new(HashMap::new()),
+            approvals: ApprovalBroker::new(),
         }
     }
     
+    /// Forward an event to the installed sink, if any
+    pub fn emit_event(&self, event: McpEvent) {
+        if let Some(sink) = &self.event_sink {
+            sink(event);
+        }
+    }
+
+    /// Pending tool-call approval prompts
+    pub fn approvals(&self) -> &ApprovalBroker {
+        &self.approvals
+    }
+
+    /// Install the event receiver used by subsequently started clients
+    pub fn set_event_sink(&mut self, sink: McpEventSink) {
+        self.event_sink = Some(sink);
+    }
+
+    /// Install the callback that persists refreshed OAuth tokens
+    pub fn set_token_store(&mut self, store: OAuthTokenStore) {
+        self.token_store = Some(store);
+    }
+    
+    /// Set the restart policy used by the supervisor of this server (applies on next start)
+    pub fn set_restart_policy(&self, server_id: &str, policy: RestartPolicy) {
+        self.restart_policies.lock().unwrap().insert(server_id.to_string(), policy);
+    }
+
+    /// Sandbox used the next time this stdio server starts (None = unsandboxed)
+    pub fn set_sandbox(&self, server_id: &str, sandbox: Option<Sandbox>) {
+        let mut sandboxes = self.sandboxes.lock().unwrap();
+        match sandbox {
+            Some(sandbox) => sandboxes.insert(server_id.to_string(), sandbox),
+            None => sandboxes.remove(server_id),
+        };
+    }
+
+    /// Start (or replace) the supervisor task for a freshly connected client
+    fn spawn_supervisor(&self, server_id: &str, client: McpClientWrapper) {
+        let policy = self
+            .restart_policies
+            .lock()
+            .unwrap()
+            .get(server_id)
+            .copied()
+            .unwrap_or_default();
+        let task = tokio::spawn(supervise(
+            server_id.to_string(),
+            client,
+            policy,
+            self.event_sink.clone(),
+        ));
+        if let Some(old) = self.supervisors.lock().unwrap().insert(server_id.to_string(), task) {
+            old.abort();
+        }
+    }
+
+    fn stop_supervisor(&self, server_id: &str) {
+        if let Some(task) = self.supervisors.lock().unwrap().remove(server_id) {
+            task.abort();
+        }
+    }
+
     /// Cancel all pending tool calls across all clients
     /// This will physically interrupt ongoing operations
     pub async fn cancel_all_tool_calls(&self) {
         log::info!("[MCPManager] Cancelling all tool calls");
         self.cancelled.store(true, Ordering::SeqCst);
+        self.approvals.cancel_all();
         
         // Cancel on all connected clients
         let clients = self.clients.read().await;
@@ -169,6 +452,8 @@ impl McpManager {
             args,
             env,
         ));
+        client.set_event_sink(self.event_sink.clone());
+        client.set_sandbox(self.sandboxes.lock().unwrap().get(server_id).cloned());
 
         client.connect().await?;
 
@@ -177,6 +462,7 @@ impl McpManager {
             let mut clients = self.clients.write().await;
             clients.insert(server_id.to_string(), McpClientWrapper::Stdio(client.clone()));
         }
+        self.spawn_supervisor(server_id, McpClientWrapper::Stdio(client.clone()));
 
         Ok(client.get_status())
     }
@@ -188,6 +474,7 @@ impl McpManager {
         server_name: &str,
         url: &str,
         api_key: Option<String>,
+        oauth: Option<OAuthCredentials>,
     ) -> Result<ServerStatus, String> {
         // Check if already running
         {
@@ -209,6 +496,10 @@ impl McpManager {
             url.to_string(),
             api_key,
         ));
+        client.set_event_sink(self.event_sink.clone());
+        if oauth.is_some() {
+            client.set_oauth(oauth, self.token_store.clone());
+        }
 
         client.connect().await?;
 
@@ -217,12 +508,15 @@ impl McpManager {
             let mut clients = self.clients.write().await;
             clients.insert(server_id.to_string(), McpClientWrapper::Http(client.clone()));
         }
+        self.spawn_supervisor(server_id, McpClientWrapper::Http(client.clone()));
 
         Ok(client.get_status())
     }
 
     /// Stop a running server
     pub async fn stop_server(&self, server_id: &str) -> Result<(), String> {
+        // Stop supervising first so the disconnect is not treated as a crash
+        self.stop_supervisor(server_id);
         let client = {
             let mut clients = self.clients.write().await;
             clients.remove(server_id)
@@ -255,6 +549,9 @@ impl McpManager {
     /// Stop all servers
     pub async fn stop_all(&self) {
         log::info!("[MCPManager] Stopping all servers");
+        for (_, task) in self.supervisors.lock().unwrap().drain() {
+            task.abort();
+        }
         let mut clients = self.clients.write().await;
         for (id, client) in clients.drain() {
             log::info!("[MCPManager] Stopping server: {}", id);
@@ -274,28 +571,68 @@ impl McpManager {
         clients.values().map(|c| c.get_status()).collect()
     }
 
-    /// Get all tools from all connected servers
+    /// Get all tools from all connected servers, each with its namespaced `tool_id`
     pub async fn get_all_tools(&self) -> Vec<McpToolInfo> {
-        let clients = self.clients.read().await;
+        let statuses = self.connected_statuses().await;
+        let mut ids: HashMap<(String, String), String> = tool_index_for(&statuses)
+            .into_iter()
+            .map(|(id, target)| ((target.server_id, target.tool_name), id))
+            .collect();
+
         let mut tools = Vec::new();
-        
-        for client in clients.values() {
-            if client.is_connected() {
-                let status = client.get_status();
-                for tool in status.tools {
-                    tools.push(McpToolInfo {
-                        server_id: status.server_id.clone(),
-                        server_name: status.name.clone(),
-                        tool,
-                    });
-                }
+        for status in statuses {
+            for tool in status.tools {
+                let tool_id = ids
+                    .remove(&(status.server_id.clone(), tool.name.clone()))
+                    .unwrap_or_default();
+                tools.push(McpToolInfo {
+                    tool_id,
+                    server_id: status.server_id.clone(),
+                    server_name: status.name.clone(),
+                    tool,
+                });
             }
         }
         
         tools
     }
 
-    /// Call a tool on a specific server with timeout and cancellation support
+    /// Look up a tool advertised by a running server
+    pub async fn find_tool(&self, server_id: &str, tool_name: &str) -> Option<McpToolInfo> {
+        self.get_all_tools()
+            .await
+            .into_iter()
+            .find(|t| t.server_id == server_id && t.tool.name == tool_name)
+    }
+
+    /// Reverse lookup of a namespaced tool ID. Also accepts the older `ServerName__tool`
+    /// form for servers whose name is not a valid slug.
+    pub async fn resolve_tool_id(&self, tool_id: &str) -> Option<ToolTarget> {
+        let statuses = self.connected_statuses().await;
+        if let Some(target) = tool_index_for(&statuses).remove(tool_id) {
+            return Some(target);
+        }
+        let (server_name, tool_name) = split_legacy_name(tool_id)?;
+        statuses
+            .into_iter()
+            .find(|s| s.name == server_name && s.tools.iter().any(|t| t.name == tool_name))
+            .map(|s| ToolTarget {
+                server_id: s.server_id,
+                tool_name: tool_name.to_string(),
+            })
+    }
+
+    async fn connected_statuses(&self) -> Vec<ServerStatus> {
+        let clients = self.clients.read().await;
+        clients
+            .values()
+            .filter(|c| c.is_connected())
+            .map(|c| c.get_status())
+            .collect()
+    }
+
+    /// Call a tool on a specific server with timeout and cancellation support.
+    /// Does not check permissions; app-facing callers go through `permissions::call_tool_with_policy`.
     pub async fn call_tool(
         &self,
         server_id: &str,
@@ -371,6 +708,55 @@ impl McpManager {
         client.read_resource(uri).await
     }
 
+    /// Get resource templates advertised by a specific server
+    pub async fn get_resource_templates(&self, server_id: &str) -> Result<Vec<McpResourceTemplate>, String> {
+        let clients = self.clients.read().await;
+        let client = clients.get(server_id)
+            .ok_or_else(|| format!("Server {} not found or not running", server_id))?;
+        Ok(client.get_resource_templates())
+    }
+
+    /// Expand a resource template with the given variables and read the result
+    pub async fn read_resource_template(
+        &self,
+        server_id: &str,
+        uri_template: &str,
+        variables: HashMap<String, String>,
+    ) -> Result<ResourceReadResult, String> {
+        let missing: Vec<String> = template_variables(uri_template)
+            .into_iter()
+            .filter(|name| !variables.contains_key(name))
+            .collect();
+        if !missing.is_empty() {
+            log::warn!("[MCPManager] Template {} missing variables: {:?}", uri_template, missing);
+        }
+        let uri = expand_uri_template(uri_template, &variables)?;
+        log::info!("[MCPManager] Expanded template {} -> {}", uri_template, uri);
+        self.read_resource(server_id, &uri).await
+    }
+
+    /// Subscribe to update notifications for a resource on a specific server
+    pub async fn subscribe_resource(&self, server_id: &str, uri: &str) -> Result<(), String> {
+        let client = {
+            let clients = self.clients.read().await;
+            clients.get(server_id)
+                .ok_or_else(|| format!("Server {} not found or not running", server_id))?
+                .clone()
+        };
+        client.subscribe_resource(uri).await
+    }
+
+    /// Unsubscribe from update notifications for a resource
+    pub async fn unsubscribe_resource(&self, server_id: &str, uri: &str) -> Result<(), String> {
+        let client = {
+            let clients = self.clients.read().await;
+            clients.get(server_id)
+                .ok_or_else(|| format!("Server {} not found or not running", server_id))?
+                .clone()
+        };
+        client.unsubscribe_resource(uri).await
+    }
+
     /// Check if a server is running
     pub async fn is_server_running(&self, server_id: &str) -> bool {
         let clients = self.clients.read().await;
@@ -403,3 +789,40 @@ impl Default for McpManager {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn never_policy_does_not_restart() {
+        assert_eq!(restart_delay(RestartPolicy::Never, Some(1), 0), None);
+        assert_eq!(restart_delay(RestartPolicy::Never, None, 0), None);
+    }
+
+    #[test]
+    fn on_failure_skips_clean_exit_and_gives_up() {
+        assert_eq!(restart_delay(RestartPolicy::OnFailure, Some(0), 0), None);
+        assert_eq!(restart_delay(RestartPolicy::OnFailure, Some(1), 0), Some(Duration::from_secs(1)));
+        assert_eq!(restart_delay(RestartPolicy::OnFailure, None, 2), Some(Duration::from_secs(4)));
+        assert_eq!(restart_delay(RestartPolicy::OnFailure, Some(1), MAX_FAILURE_RESTARTS), None);
+    }
+
+    #[test]
+    fn always_policy_backs_off_up_to_cap() {
+        assert_eq!(restart_delay(RestartPolicy::Always, Some(0), 0), Some(Duration::from_secs(1)));
+        assert_eq!(restart_delay(RestartPolicy::Always, Some(0), 3), Some(Duration::from_secs(8)));
+        assert_eq!(
+            restart_delay(RestartPolicy::Always, None, 40),
+            Some(Duration::from_millis(RESTART_BACKOFF_MAX_MS))
+        );
+    }
+
+    #[test]
+    fn effective_policy_defaults_by_auto_start() {
+        assert_eq!(RestartPolicy::effective(None, true), RestartPolicy::OnFailure);
+        assert_eq!(RestartPolicy::effective(None, false), RestartPolicy::Never);
+        assert_eq!(RestartPolicy::effective(Some("always"), false), RestartPolicy::Always);
+        assert_eq!(RestartPolicy::effective(Some("bogus"), true), RestartPolicy::OnFailure);
+    }
+}
@@ -0,0 +1,204 @@
+// Scripted MCP server logic shared by the in-process HTTP mock (`mock_server`) and the
+// stdio mock binary (`examples/mock_mcp_server.rs`, which includes this file by path).
+// Only depends on std, serde and serde_json so it compiles in both places.
+//
+// A script is plain JSON, e.g.
+//   { "tools": [{ "name": "slow", "progress": [0.5, 1.0], "result": [{ "type": "text", "text": "ok" }] }],
+//     "resources": [{ "uri": "mem://a", "text": "hello" }],
+//     "stderr": ["booting"], "crashAfter": 5, "exitCode": 3 }
+
+use std::collections::HashMap;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// What the mock server does
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MockScript {
+    pub tools: Vec<MockTool>,
+    pub resources: Vec<MockResource>,
+    /// Lines written to stderr on startup (stdio only)
+    #[allow(dead_code)]
+    pub stderr: Vec<String>,
+    /// Exit after answering this many requests
+    pub crash_after: Option<usize>,
+    pub exit_code: i32,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MockTool {
+    pub name: String,
+    pub description: Option<String>,
+    pub input_schema: Option<Value>,
+    /// Result content; empty echoes the call arguments as text
+    pub result: Vec<Value>,
+    pub is_error: bool,
+    /// `notifications/progress` values sent before the result
+    pub progress: Vec<f64>,
+    /// Ask the client for a completion with this prompt and return its text
+    pub sampling: Option<String>,
+    /// Exit instead of answering
+    pub crash: bool,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MockResource {
+    pub uri: String,
+    pub name: Option<String>,
+    pub mime_type: Option<String>,
+    pub text: String,
+}
+
+impl MockScript {
+    pub fn from_value(value: Value) -> Self {
+        serde_json::from_value(value).expect("invalid mock MCP script")
+    }
+}
+
+/// Something the transport has to do in response to a message
+#[derive(Debug, Clone, PartialEq)]
+pub enum MockAction {
+    Send(Value),
+    Exit(i32),
+}
+
+/// Server state machine: feed it client messages, perform the returned actions in order
+pub struct MockMcp {
+    script: MockScript,
+    answered: usize,
+    next_request: u64,
+    // Outstanding sampling request id -> id of the tools/call waiting on it
+    sampling: HashMap<String, Value>,
+}
+
+impl MockMcp {
+    pub fn new(script: MockScript) -> Self {
+        Self { script, answered: 0, next_request: 0, sampling: HashMap::new() }
+    }
+
+    pub fn handle(&mut self, message: &Value) -> Vec<MockAction> {
+        let id = message.get("id").cloned();
+        match (message.get("method").and_then(Value::as_str), id) {
+            (Some(method), Some(id)) => self.handle_request(method, id, message.get("params")),
+            (None, Some(id)) => self.handle_response(&id, message),
+            // Notifications (initialized, cancelled, ...) need no answer
+            _ => Vec::new(),
+        }
+    }
+
+    fn handle_request(&mut self, method: &str, id: Value, params: Option<&Value>) -> Vec<MockAction> {
+        let params = params.cloned().unwrap_or(Value::Null);
+        let outcome = match method {
+            "initialize" => Ok(json!({
+                "protocolVersion": params["protocolVersion"].as_str().unwrap_or("2024-11-05"),
+                "capabilities": { "tools": {}, "resources": { "subscribe": true } },
+                "serverInfo": { "name": "mock-mcp", "version": "1.0.0" }
+            })),
+            "ping" | "resources/subscribe" | "resources/unsubscribe" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": self.script.tools.iter().map(|t| json!({
+                "name": t.name,
+                "description": t.description,
+                "inputSchema": t.input_schema.clone().unwrap_or_else(|| json!({ "type": "object" })),
+            })).collect::<Vec<_>>() })),
+            "resources/list" => Ok(json!({ "resources": self.script.resources.iter().map(|r| json!({
+                "uri": r.uri,
+                "name": r.name.clone().unwrap_or_else(|| r.uri.clone()),
+                "mimeType": r.mime_type,
+            })).collect::<Vec<_>>() })),
+            "resources/templates/list" => Ok(json!({ "resourceTemplates": [] })),
+            "resources/read" => {
+                let uri = params["uri"].as_str().unwrap_or_default();
+                match self.script.resources.iter().find(|r| r.uri == uri) {
+                    Some(r) => Ok(json!({ "contents": [{
+                        "uri": r.uri,
+                        "mimeType": r.mime_type.clone().unwrap_or_else(|| "text/plain".to_string()),
+                        "text": r.text,
+                    }] })),
+                    None => Err((-32002, format!("Resource not found: {}", uri))),
+                }
+            }
+            "tools/call" => return self.call_tool(id, &params),
+            other => Err((-32601, format!("Method not found: {}", other))),
+        };
+        let mut actions = vec![MockAction::Send(match outcome {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } }),
+        })];
+        self.answered(&mut actions);
+        actions
+    }
+
+    fn call_tool(&mut self, id: Value, params: &Value) -> Vec<MockAction> {
+        let name = params["name"].as_str().unwrap_or_default();
+        let Some(tool) = self.script.tools.iter().find(|t| t.name == name).cloned() else {
+            return vec![MockAction::Send(json!({
+                "jsonrpc": "2.0", "id": id,
+                "error": { "code": -32602, "message": format!("Unknown tool: {}", name) }
+            }))];
+        };
+        if tool.crash {
+            return vec![MockAction::Exit(self.script.exit_code)];
+        }
+
+        let token = params["_meta"]["progressToken"].clone();
+        let token = if token.is_null() { id.clone() } else { token };
+        let mut actions: Vec<MockAction> = tool.progress.iter().map(|p| MockAction::Send(json!({
+            "jsonrpc": "2.0",
+            "method": "notifications/progress",
+            "params": { "progressToken": token, "progress": p, "total": 1.0 }
+        }))).collect();
+
+        if let Some(prompt) = &tool.sampling {
+            self.next_request += 1;
+            let request_id = format!("sampling-{}", self.next_request);
+            self.sampling.insert(request_id.clone(), id);
+            actions.push(MockAction::Send(json!({
+                "jsonrpc": "2.0",
+                "id": request_id,
+                "method": "sampling/createMessage",
+                "params": {
+                    "messages": [{ "role": "user", "content": { "type": "text", "text": prompt } }],
+                    "maxTokens": 100
+                }
+            })));
+            return actions;
+        }
+
+        let content = if tool.result.is_empty() {
+            vec![json!({ "type": "text", "text": params["arguments"].to_string() })]
+        } else {
+            tool.result.clone()
+        };
+        actions.push(MockAction::Send(json!({
+            "jsonrpc": "2.0", "id": id,
+            "result": { "content": content, "isError": tool.is_error }
+        })));
+        self.answered(&mut actions);
+        actions
+    }
+
+    // The client answered one of our sampling requests: finish the tool call that asked
+    fn handle_response(&mut self, id: &Value, message: &Value) -> Vec<MockAction> {
+        let key = id.as_str().map(str::to_string).unwrap_or_else(|| id.to_string());
+        let Some(call_id) = self.sampling.remove(&key) else { return Vec::new() };
+        let (text, is_error) = match message.get("error") {
+            Some(error) => (error["message"].as_str().unwrap_or("sampling failed").to_string(), true),
+            None => (message["result"]["content"]["text"].as_str().unwrap_or_default().to_string(), false),
+        };
+        let mut actions = vec![MockAction::Send(json!({
+            "jsonrpc": "2.0", "id": call_id,
+            "result": { "content": [{ "type": "text", "text": text }], "isError": is_error }
+        }))];
+        self.answered(&mut actions);
+        actions
+    }
+
+    fn answered(&mut self, actions: &mut Vec<MockAction>) {
+        self.answered += 1;
+        if self.script.crash_after == Some(self.answered) {
+            actions.push(MockAction::Exit(self.script.exit_code));
+        }
+    }
+}
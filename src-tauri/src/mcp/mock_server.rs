@@ -0,0 +1,345 @@
+// Minimal HTTP/1.1 server for exercising the Streamable HTTP client and the LLM
+// streamers in tests. One request per connection (`Connection: close`); responses come
+// from a handler closure. `start_mcp` wraps a scripted MCP server (see `mock_script`),
+// `stdio_command` launches the same script as a stdio process.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use super::mock_script::{MockAction, MockMcp, MockScript};
+
+/// A request received by the mock server
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl MockRequest {
+    /// Header value by case-insensitive name
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Body parsed as JSON (Null if empty or invalid)
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_str(&self.body).unwrap_or(serde_json::Value::Null)
+    }
+}
+
+enum MockBody {
+    Empty,
+    Json(String),
+    Sse { events: Vec<Vec<u8>>, hold_open: bool, delay: Option<Duration> },
+    // SSE body fed by a channel; the response ends when every sender is dropped
+    Channel(mpsc::UnboundedReceiver<Vec<u8>>),
+    // Close the connection without answering
+    Disconnect,
+}
+
+/// Response produced by the handler
+pub struct MockResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: MockBody,
+}
+
+impl MockResponse {
+    pub fn status(status: u16) -> Self {
+        Self { status, headers: Vec::new(), body: MockBody::Empty }
+    }
+
+    pub fn json(body: String) -> Self {
+        Self { status: 200, headers: Vec::new(), body: MockBody::Json(body) }
+    }
+
+    /// SSE response; each entry is written verbatim (include the trailing blank line)
+    pub fn sse(events: Vec<String>) -> Self {
+        Self::sse_bytes(events.into_iter().map(String::into_bytes).collect())
+    }
+
+    /// SSE response from raw writes, which may split events or UTF-8 sequences anywhere
+    pub fn sse_bytes(events: Vec<Vec<u8>>) -> Self {
+        Self { status: 200, headers: Vec::new(), body: MockBody::Sse { events, hold_open: false, delay: None } }
+    }
+
+    /// SSE response streamed from a channel
+    pub fn channel(rx: mpsc::UnboundedReceiver<Vec<u8>>) -> Self {
+        Self { status: 200, headers: Vec::new(), body: MockBody::Channel(rx) }
+    }
+
+    pub fn disconnect() -> Self {
+        Self { status: 0, headers: Vec::new(), body: MockBody::Disconnect }
+    }
+
+    /// Pause between SSE writes so the client sees them as separate reads
+    pub fn with_delay(mut self, pause: Duration) -> Self {
+        if let MockBody::Sse { delay, .. } = &mut self.body {
+            *delay = Some(pause);
+        }
+        self
+    }
+
+    /// Keep an SSE response open after the events instead of closing it
+    pub fn hold_open(mut self) -> Self {
+        if let MockBody::Sse { hold_open, .. } = &mut self.body {
+            *hold_open = true;
+        }
+        self
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+type Handler = Arc<dyn Fn(&MockRequest) -> MockResponse + Send + Sync>;
+
+pub struct MockHttpServer {
+    addr: std::net::SocketAddr,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl MockHttpServer {
+    pub async fn start<F>(handler: F) -> Self
+    where
+        F: Fn(&MockRequest) -> MockResponse + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let handler: Handler = Arc::new(handler);
+
+        let requests_clone = requests.clone();
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let handler = handler.clone();
+                let requests = requests_clone.clone();
+                tokio::spawn(async move {
+                    let _ = serve_connection(stream, handler, requests).await;
+                });
+            }
+        });
+
+        Self { addr, requests, task }
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}/mcp", self.addr)
+    }
+
+    /// Root URL, for clients that append their own paths (LLM providers)
+    pub fn base_url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Wait (up to 5s) until the recorded requests satisfy `pred`
+    pub async fn wait_for(&self, pred: impl Fn(&[MockRequest]) -> bool) {
+        for _ in 0..250 {
+            if pred(&self.requests.lock().unwrap()) {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        panic!("timed out waiting for requests: {:#?}", self.requests());
+    }
+}
+
+impl Drop for MockHttpServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn serve_connection(
+    mut stream: TcpStream,
+    handler: Handler,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+) -> std::io::Result<()> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    let header_end = loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or("").split_whitespace();
+    let method = request_line.next().unwrap_or("").to_string();
+    let path = request_line.next().unwrap_or("").to_string();
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|l| l.split_once(':'))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .collect();
+
+    let content_length = headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, v)| v.parse::<usize>().ok())
+        .unwrap_or(0);
+    while buf.len() < header_end + content_length {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    let body = String::from_utf8_lossy(&buf[header_end..]).to_string();
+
+    let request = MockRequest { method, path, headers, body };
+    requests.lock().unwrap().push(request.clone());
+    let response = handler(&request);
+    if let MockBody::Disconnect = response.body {
+        return Ok(());
+    }
+
+    let mut head = format!("HTTP/1.1 {} Mock\r\nConnection: close\r\n", response.status);
+    for (k, v) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", k, v));
+    }
+
+    match response.body {
+        MockBody::Empty => {
+            head.push_str("Content-Length: 0\r\n\r\n");
+            stream.write_all(head.as_bytes()).await?;
+        }
+        MockBody::Json(json) => {
+            head.push_str(&format!("Content-Type: application/json\r\nContent-Length: {}\r\n\r\n", json.len()));
+            stream.write_all(head.as_bytes()).await?;
+            stream.write_all(json.as_bytes()).await?;
+        }
+        MockBody::Sse { events, hold_open, delay } => {
+            head.push_str("Content-Type: text/event-stream\r\nCache-Control: no-cache\r\n\r\n");
+            stream.write_all(head.as_bytes()).await?;
+            for event in events {
+                stream.write_all(&event).await?;
+                stream.flush().await?;
+                if let Some(delay) = delay {
+                    tokio::time::sleep(delay).await;
+                }
+            }
+            if hold_open {
+                std::future::pending::<()>().await;
+            }
+        }
+        MockBody::Channel(mut rx) => {
+            head.push_str("Content-Type: text/event-stream\r\nCache-Control: no-cache\r\n\r\n");
+            stream.write_all(head.as_bytes()).await?;
+            while let Some(bytes) = rx.recv().await {
+                stream.write_all(&bytes).await?;
+                stream.flush().await?;
+            }
+        }
+        MockBody::Disconnect => {}
+    }
+
+    stream.shutdown().await
+}
+
+/// Serve a scripted MCP server over Streamable HTTP.
+///
+/// Each POST gets an SSE response carrying whatever the script sends while handling it.
+/// A tools/call that asks for sampling keeps its stream open until the client POSTs the
+/// sampling result, then finishes on that same stream. After the script exits, every
+/// connection is dropped unanswered.
+pub async fn start_mcp(script: serde_json::Value) -> MockHttpServer {
+    let mcp = Arc::new(Mutex::new(MockMcp::new(MockScript::from_value(script))));
+    let waiting: Arc<Mutex<HashMap<String, mpsc::UnboundedSender<Vec<u8>>>>> = Arc::default();
+    let crashed = Arc::new(Mutex::new(false));
+
+    MockHttpServer::start(move |req| {
+        if *crashed.lock().unwrap() {
+            return MockResponse::disconnect();
+        }
+        match req.method.as_str() {
+            "POST" => {}
+            "DELETE" => return MockResponse::status(200),
+            _ => return MockResponse::status(405),
+        }
+
+        let message = req.json();
+        let actions = mcp.lock().unwrap().handle(&message);
+        if message.get("method").is_none() {
+            // Answer to a sampling request: the result belongs to the waiting tools/call stream
+            let key = message["id"].as_str().unwrap_or_default().to_string();
+            if let Some(tx) = waiting.lock().unwrap().remove(&key) {
+                for action in actions {
+                    match action {
+                        MockAction::Send(value) => { let _ = tx.send(sse_event(&value)); }
+                        MockAction::Exit(_) => *crashed.lock().unwrap() = true,
+                    }
+                }
+            }
+            return MockResponse::status(202);
+        }
+        if message.get("id").is_none() {
+            return MockResponse::status(202);
+        }
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        for action in actions {
+            match action {
+                MockAction::Send(value) => {
+                    if value["method"] == "sampling/createMessage" {
+                        let key = value["id"].as_str().unwrap_or_default().to_string();
+                        waiting.lock().unwrap().insert(key, tx.clone());
+                    }
+                    let _ = tx.send(sse_event(&value));
+                }
+                MockAction::Exit(_) => {
+                    *crashed.lock().unwrap() = true;
+                    return MockResponse::disconnect();
+                }
+            }
+        }
+        let response = MockResponse::channel(rx);
+        if message["method"] == "initialize" {
+            return response.with_header("Mcp-Session-Id", "mock-session");
+        }
+        response
+    }).await
+}
+
+fn sse_event(value: &serde_json::Value) -> Vec<u8> {
+    format!("data: {}\n\n", value).into_bytes()
+}
+
+/// Command, args and env that run `script` with the stdio mock binary
+/// (`examples/mock_mcp_server.rs`, built by `cargo test` next to the test binary)
+pub fn stdio_command(script: serde_json::Value) -> (String, Vec<String>, HashMap<String, String>) {
+    let exe = std::env::current_exe().unwrap();
+    let target_dir = exe.parent().and_then(|deps| deps.parent()).unwrap();
+    let binary: PathBuf = target_dir
+        .join("examples")
+        .join(format!("mock_mcp_server{}", std::env::consts::EXE_SUFFIX));
+    assert!(
+        binary.exists(),
+        "{:?} is missing; run `cargo build --example mock_mcp_server`",
+        binary
+    );
+    let env = HashMap::from([("MOCK_MCP_SCRIPT".to_string(), script.to_string())]);
+    (binary.to_string_lossy().to_string(), Vec::new(), env)
+}
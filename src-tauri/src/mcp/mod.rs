@@ -4,6 +4,16 @@
 pub mod client;
 pub mod http_client;
 pub mod manager;
+#[cfg(test)]
+mod mock_script;
+#[cfg(test)]
+pub(crate) mod mock_server;
+pub mod oauth;
+pub mod permissions;
+pub mod resources;
+pub mod sandbox;
+pub mod server;
+pub mod tool_ids;
 pub mod types;
 
 pub use client::McpClient;
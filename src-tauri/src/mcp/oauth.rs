@@ -0,0 +1,526 @@
+// MCP Authorization (OAuth 2.1) for remote HTTP servers
+// See: https://modelcontextprotocol.io/specification/2025-06-18/basic/authorization
+//
+// Flow:
+//   1. Probe the MCP endpoint; a 401 may carry `resource_metadata` in WWW-Authenticate
+//   2. Protected resource metadata (RFC 9728) names the authorization server
+//   3. Authorization server metadata (RFC 8414 / OIDC discovery)
+//   4. Dynamic client registration (RFC 7591)
+//   5. Authorization code + PKCE (S256) with a loopback redirect listener
+//   6. Tokens are stored per server and refreshed on expiry or 401
+
+use std::sync::Arc;
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::time::{timeout, Duration};
+
+const HTTP_TIMEOUT_SECS: u64 = 30;
+const CALLBACK_TIMEOUT_SECS: u64 = 300; // User has 5 minutes to finish in the browser
+const EXPIRY_MARGIN_SECS: i64 = 60; // Refresh a little before the token actually expires
+
+/// Persisted OAuth state for one MCP server
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OAuthCredentials {
+    /// Canonical MCP server URI, sent as the RFC 8707 `resource` parameter
+    pub resource: String,
+    pub token_endpoint: String,
+    pub client_id: String,
+    #[serde(default)]
+    pub client_secret: Option<String>,
+    pub access_token: String,
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+    /// Unix timestamp (seconds)
+    #[serde(default)]
+    pub expires_at: Option<i64>,
+    #[serde(default)]
+    pub scope: Option<String>,
+}
+
+impl OAuthCredentials {
+    /// Whether the access token is expired or about to expire
+    pub fn is_expired(&self) -> bool {
+        match self.expires_at {
+            Some(expires_at) => chrono::Utc::now().timestamp() + EXPIRY_MARGIN_SECS >= expires_at,
+            None => false,
+        }
+    }
+
+    fn apply(&mut self, token: TokenResponse) {
+        self.access_token = token.access_token;
+        // Servers may rotate the refresh token; keep the old one if they don't
+        if token.refresh_token.is_some() {
+            self.refresh_token = token.refresh_token;
+        }
+        self.expires_at = token.expires_in.map(|secs| chrono::Utc::now().timestamp() + secs);
+        if token.scope.is_some() {
+            self.scope = token.scope;
+        }
+    }
+}
+
+/// Callback used to persist refreshed tokens (server_id, credentials)
+pub type OAuthTokenStore = Arc<dyn Fn(&str, &OAuthCredentials) + Send + Sync>;
+
+/// RFC 9728 protected resource metadata
+#[derive(Debug, Deserialize, Clone)]
+pub struct ProtectedResourceMetadata {
+    #[serde(default)]
+    pub resource: Option<String>,
+    #[serde(default)]
+    pub authorization_servers: Vec<String>,
+    #[serde(default)]
+    pub scopes_supported: Vec<String>,
+}
+
+/// RFC 8414 authorization server metadata
+#[derive(Debug, Deserialize, Clone)]
+pub struct AuthorizationServerMetadata {
+    #[serde(default)]
+    pub issuer: Option<String>,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    #[serde(default)]
+    pub registration_endpoint: Option<String>,
+    #[serde(default)]
+    pub scopes_supported: Vec<String>,
+    #[serde(default)]
+    pub code_challenge_methods_supported: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ClientRegistration {
+    client_id: String,
+    #[serde(default)]
+    client_secret: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    refresh_token: Option<String>,
+    #[serde(default)]
+    expires_in: Option<i64>,
+    #[serde(default)]
+    scope: Option<String>,
+}
+
+/// PKCE verifier/challenge pair (S256)
+pub struct Pkce {
+    pub verifier: String,
+    pub challenge: String,
+}
+
+impl Pkce {
+    pub fn generate() -> Self {
+        // 64 hex chars from two v4 UUIDs (~244 bits of randomness), within RFC 7636's 43-128 range
+        let verifier = format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+        let challenge = Self::challenge_for(&verifier);
+        Self { verifier, challenge }
+    }
+
+    pub fn challenge_for(verifier: &str) -> String {
+        URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+    }
+}
+
+fn http_client() -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(HTTP_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// Extract `resource_metadata="..."` from a WWW-Authenticate header
+pub fn parse_resource_metadata_url(www_authenticate: &str) -> Option<String> {
+    let idx = www_authenticate.find("resource_metadata=")?;
+    let rest = &www_authenticate[idx + "resource_metadata=".len()..];
+    let value = match rest.strip_prefix('"') {
+        Some(quoted) => quoted.split('"').next()?,
+        None => rest.split([',', ' ']).next()?,
+    };
+    if value.is_empty() { None } else { Some(value.to_string()) }
+}
+
+/// Well-known URLs to try, in order, for a given base URL.
+/// Path-aware form first (`/.well-known/{suffix}/path`), then the root form.
+pub fn well_known_urls(base: &str, suffix: &str) -> Result<Vec<String>, String> {
+    let url = url::Url::parse(base).map_err(|e| format!("Invalid URL {}: {}", base, e))?;
+    let origin = url.origin().ascii_serialization();
+    let path = url.path().trim_end_matches('/');
+
+    let mut urls = Vec::new();
+    if !path.is_empty() {
+        urls.push(format!("{}/.well-known/{}{}", origin, suffix, path));
+    }
+    urls.push(format!("{}/.well-known/{}", origin, suffix));
+    Ok(urls)
+}
+
+/// Canonical resource URI for RFC 8707: no fragment, no trailing slash
+pub fn canonical_resource(server_url: &str) -> String {
+    let without_fragment = server_url.split('#').next().unwrap_or(server_url);
+    without_fragment.trim_end_matches('/').to_string()
+}
+
+async fn fetch_json<T: serde::de::DeserializeOwned>(client: &reqwest::Client, url: &str) -> Option<T> {
+    let resp = client.get(url)
+        .header("Accept", "application/json")
+        .header("MCP-Protocol-Version", "2025-06-18")
+        .send().await.ok()?;
+    if !resp.status().is_success() {
+        return None;
+    }
+    resp.json::<T>().await.ok()
+}
+
+/// Probe the MCP endpoint unauthenticated and return the WWW-Authenticate header from a 401
+async fn probe_www_authenticate(client: &reqwest::Client, server_url: &str) -> Option<String> {
+    let resp = client.post(server_url)
+        .header("Content-Type", "application/json")
+        .header("Accept", "application/json, text/event-stream")
+        .body(r#"{"jsonrpc":"2.0","id":0,"method":"ping"}"#)
+        .send().await.ok()?;
+    if resp.status() != reqwest::StatusCode::UNAUTHORIZED {
+        return None;
+    }
+    resp.headers().get("www-authenticate")
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string())
+}
+
+/// Discover the protected resource metadata for an MCP server, if it publishes one
+pub async fn discover_protected_resource(client: &reqwest::Client, server_url: &str) -> Option<ProtectedResourceMetadata> {
+    if let Some(header) = probe_www_authenticate(client, server_url).await {
+        if let Some(url) = parse_resource_metadata_url(&header) {
+            if let Some(meta) = fetch_json(client, &url).await {
+                return Some(meta);
+            }
+        }
+    }
+    for url in well_known_urls(server_url, "oauth-protected-resource").ok()? {
+        if let Some(meta) = fetch_json(client, &url).await {
+            return Some(meta);
+        }
+    }
+    None
+}
+
+/// Discover authorization server metadata (RFC 8414, then OpenID Connect discovery).
+/// Servers without metadata fall back to the default /authorize, /token, /register endpoints.
+pub async fn discover_authorization_server(client: &reqwest::Client, issuer: &str) -> Result<AuthorizationServerMetadata, String> {
+    let mut candidates = well_known_urls(issuer, "oauth-authorization-server")?;
+    candidates.extend(well_known_urls(issuer, "openid-configuration")?);
+    let issuer_trimmed = issuer.trim_end_matches('/');
+    candidates.push(format!("{}/.well-known/openid-configuration", issuer_trimmed));
+
+    for url in &candidates {
+        if let Some(meta) = fetch_json::<AuthorizationServerMetadata>(client, url).await {
+            log::info!("[MCP-OAuth] Authorization server metadata from {}", url);
+            return Ok(meta);
+        }
+    }
+
+    let origin = url::Url::parse(issuer).map_err(|e| e.to_string())?.origin().ascii_serialization();
+    log::warn!("[MCP-OAuth] No metadata for {}, using default endpoints", issuer);
+    Ok(AuthorizationServerMetadata {
+        issuer: Some(origin.clone()),
+        authorization_endpoint: format!("{}/authorize", origin),
+        token_endpoint: format!("{}/token", origin),
+        registration_endpoint: Some(format!("{}/register", origin)),
+        scopes_supported: Vec::new(),
+        code_challenge_methods_supported: Vec::new(),
+    })
+}
+
+async fn register_client(
+    client: &reqwest::Client,
+    registration_endpoint: &str,
+    redirect_uri: &str,
+) -> Result<ClientRegistration, String> {
+    let body = serde_json::json!({
+        "client_name": "PetGPT",
+        "redirect_uris": [redirect_uri],
+        "grant_types": ["authorization_code", "refresh_token"],
+        "response_types": ["code"],
+        "token_endpoint_auth_method": "none",
+    });
+    let resp = client.post(registration_endpoint)
+        .json(&body)
+        .send().await
+        .map_err(|e| format!("Client registration failed: {}", e))?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_default();
+        return Err(format!("Client registration failed: HTTP {} - {}", status, text));
+    }
+    resp.json().await.map_err(|e| format!("Invalid registration response: {}", e))
+}
+
+async fn token_request(
+    client: &reqwest::Client,
+    token_endpoint: &str,
+    form: &[(&str, &str)],
+) -> Result<TokenResponse, String> {
+    let resp = client.post(token_endpoint)
+        .header("Accept", "application/json")
+        .form(form)
+        .send().await
+        .map_err(|e| format!("Token request failed: {}", e))?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_default();
+        return Err(format!("Token request failed: HTTP {} - {}", status, text));
+    }
+    resp.json().await.map_err(|e| format!("Invalid token response: {}", e))
+}
+
+/// Use the refresh token to obtain a new access token
+pub async fn refresh(credentials: &OAuthCredentials) -> Result<OAuthCredentials, String> {
+    let refresh_token = credentials.refresh_token.as_deref()
+        .ok_or_else(|| "No refresh token; re-authorization required".to_string())?;
+    let client = http_client()?;
+
+    let mut form = vec![
+        ("grant_type", "refresh_token"),
+        ("refresh_token", refresh_token),
+        ("client_id", credentials.client_id.as_str()),
+        ("resource", credentials.resource.as_str()),
+    ];
+    if let Some(secret) = &credentials.client_secret {
+        form.push(("client_secret", secret.as_str()));
+    }
+
+    let token = token_request(&client, &credentials.token_endpoint, &form).await?;
+    let mut updated = credentials.clone();
+    updated.apply(token);
+    log::info!("[MCP-OAuth] Refreshed access token for {}", credentials.resource);
+    Ok(updated)
+}
+
+/// Write a small HTML page back to the browser and close the connection
+async fn respond(stream: &mut tokio::net::TcpStream, status: &str, message: &str) {
+    let body = format!("<!DOCTYPE html><html><body><p>{}</p></body></html>", message);
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, body.len(), body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+/// Wait for the browser redirect on the loopback listener and return the authorization code.
+/// Requests without the expected `state` are rejected individually so a stray local
+/// connection cannot abort the flow; the caller's timeout bounds the wait.
+async fn wait_for_callback(listener: TcpListener, expected_state: &str) -> Result<String, String> {
+    loop {
+        let (mut stream, _) = listener.accept().await.map_err(|e| e.to_string())?;
+
+        let mut buf = vec![0u8; 8192];
+        let n = match stream.read(&mut buf).await {
+            Ok(n) => n,
+            Err(_) => continue,
+        };
+        let request = String::from_utf8_lossy(&buf[..n]).to_string();
+        let target = request.lines().next()
+            .and_then(|line| line.split_whitespace().nth(1))
+            .unwrap_or("")
+            .to_string();
+
+        // Browsers also ask for /favicon.ico — ignore anything but the callback
+        if !target.starts_with("/callback") {
+            let _ = stream.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n").await;
+            continue;
+        }
+
+        let parsed = match url::Url::parse(&format!("http://127.0.0.1{}", target)) {
+            Ok(parsed) => parsed,
+            Err(_) => {
+                respond(&mut stream, "400 Bad Request", "Malformed authorization callback.").await;
+                continue;
+            }
+        };
+        let param = |name: &str| parsed.query_pairs().find(|(k, _)| k == name).map(|(_, v)| v.to_string());
+
+        if param("state").as_deref() != Some(expected_state) {
+            log::warn!("[MCP-OAuth] Ignoring callback with mismatched state");
+            respond(&mut stream, "400 Bad Request", "Authorization state mismatch.").await;
+            continue;
+        }
+
+        let outcome = if let Some(error) = param("error") {
+            let description = param("error_description").unwrap_or_default();
+            Err(format!("Authorization denied: {} {}", error, description).trim().to_string())
+        } else {
+            param("code").ok_or_else(|| "Authorization failed: no code in redirect".to_string())
+        };
+
+        let message = match &outcome {
+            Ok(_) => "Authorization complete. You can close this window and return to PetGPT.",
+            Err(_) => "Authorization failed. You can close this window and return to PetGPT.",
+        };
+        respond(&mut stream, "200 OK", message).await;
+
+        return outcome;
+    }
+}
+
+/// Run the full authorization flow for an MCP server.
+/// `open_url` is called with the authorization URL (typically opens the system browser).
+pub async fn authorize<F>(server_url: &str, open_url: F) -> Result<OAuthCredentials, String>
+where
+    F: FnOnce(&str) -> Result<(), String>,
+{
+    let client = http_client()?;
+    let resource = canonical_resource(server_url);
+
+    // 1-2. Which authorization server protects this resource?
+    let resource_meta = discover_protected_resource(&client, server_url).await;
+    let issuer = match resource_meta.as_ref().and_then(|m| m.authorization_servers.first()) {
+        Some(issuer) => issuer.clone(),
+        // 2025-03-26 servers: the MCP server's origin is the authorization server
+        None => url::Url::parse(server_url).map_err(|e| e.to_string())?.origin().ascii_serialization(),
+    };
+    log::info!("[MCP-OAuth] Authorization server for {}: {}", resource, issuer);
+
+    // 3. Authorization server metadata
+    let as_meta = discover_authorization_server(&client, &issuer).await?;
+    if !as_meta.code_challenge_methods_supported.is_empty()
+        && !as_meta.code_challenge_methods_supported.iter().any(|m| m == "S256")
+    {
+        return Err("Authorization server does not support PKCE S256".to_string());
+    }
+
+    // Loopback redirect listener on an ephemeral port
+    let listener = TcpListener::bind("127.0.0.1:0").await.map_err(|e| format!("Failed to bind callback listener: {}", e))?;
+    let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+    let redirect_uri = format!("http://127.0.0.1:{}/callback", port);
+
+    // 4. Dynamic client registration
+    let registration_endpoint = as_meta.registration_endpoint.as_deref()
+        .ok_or_else(|| "Authorization server does not support dynamic client registration".to_string())?;
+    let registration = register_client(&client, registration_endpoint, &redirect_uri).await?;
+    log::info!("[MCP-OAuth] Registered client {}", registration.client_id);
+
+    // 5. Authorization code + PKCE
+    let pkce = Pkce::generate();
+    let state = uuid::Uuid::new_v4().simple().to_string();
+    let scope = resource_meta.as_ref()
+        .map(|m| m.scopes_supported.join(" "))
+        .filter(|s| !s.is_empty());
+
+    let mut auth_url = url::Url::parse(&as_meta.authorization_endpoint).map_err(|e| e.to_string())?;
+    {
+        let mut query = auth_url.query_pairs_mut();
+        query.append_pair("response_type", "code")
+            .append_pair("client_id", &registration.client_id)
+            .append_pair("redirect_uri", &redirect_uri)
+            .append_pair("code_challenge", &pkce.challenge)
+            .append_pair("code_challenge_method", "S256")
+            .append_pair("state", &state)
+            .append_pair("resource", &resource);
+        if let Some(scope) = &scope {
+            query.append_pair("scope", scope);
+        }
+    }
+
+    open_url(auth_url.as_str())?;
+
+    let code = timeout(Duration::from_secs(CALLBACK_TIMEOUT_SECS), wait_for_callback(listener, &state))
+        .await
+        .map_err(|_| "Authorization timed out".to_string())??;
+
+    // 6. Exchange the code for tokens
+    let mut form = vec![
+        ("grant_type", "authorization_code"),
+        ("code", code.as_str()),
+        ("redirect_uri", redirect_uri.as_str()),
+        ("client_id", registration.client_id.as_str()),
+        ("code_verifier", pkce.verifier.as_str()),
+        ("resource", resource.as_str()),
+    ];
+    if let Some(secret) = &registration.client_secret {
+        form.push(("client_secret", secret.as_str()));
+    }
+    let token = token_request(&client, &as_meta.token_endpoint, &form).await?;
+
+    let mut credentials = OAuthCredentials {
+        resource,
+        token_endpoint: as_meta.token_endpoint.clone(),
+        client_id: registration.client_id,
+        client_secret: registration.client_secret,
+        access_token: String::new(),
+        refresh_token: None,
+        expires_at: None,
+        scope,
+    };
+    credentials.apply(token);
+    log::info!("[MCP-OAuth] Authorized {}", credentials.resource);
+
+    Ok(credentials)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pkce_challenge_is_unpadded_base64url_sha256() {
+        assert_eq!(
+            Pkce::challenge_for("dBjftJeZ4CVP-mJ92IZQsuzpMU0Ypnc1g5cvRy6Tz5c"),
+            "ktN-hHs6JGNfe5LQmhG2efac8JdeAe4bOg1Gk3ElUcs"
+        );
+        let pkce = Pkce::generate();
+        assert!((43..=128).contains(&pkce.verifier.len()));
+    }
+
+    #[test]
+    fn parses_resource_metadata_from_www_authenticate() {
+        let header = r#"Bearer realm="mcp", resource_metadata="https://mcp.example.com/.well-known/oauth-protected-resource""#;
+        assert_eq!(
+            parse_resource_metadata_url(header).as_deref(),
+            Some("https://mcp.example.com/.well-known/oauth-protected-resource")
+        );
+        assert_eq!(parse_resource_metadata_url("Bearer error=\"invalid_token\""), None);
+    }
+
+    #[test]
+    fn builds_well_known_urls() {
+        assert_eq!(
+            well_known_urls("https://auth.example.com/tenant1", "oauth-authorization-server").unwrap(),
+            vec![
+                "https://auth.example.com/.well-known/oauth-authorization-server/tenant1",
+                "https://auth.example.com/.well-known/oauth-authorization-server",
+            ]
+        );
+        assert_eq!(
+            well_known_urls("https://auth.example.com", "openid-configuration").unwrap(),
+            vec!["https://auth.example.com/.well-known/openid-configuration"]
+        );
+        assert_eq!(canonical_resource("https://mcp.example.com/mcp/#x"), "https://mcp.example.com/mcp");
+    }
+
+    #[tokio::test]
+    async fn callback_with_wrong_state_does_not_abort_the_flow() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let waiter = tokio::spawn(async move { wait_for_callback(listener, "expected").await });
+
+        let send = |path: &'static str| async move {
+            let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            stream.write_all(format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        };
+
+        assert!(send("/callback?error=access_denied&state=forged").await.starts_with("HTTP/1.1 400"));
+        assert!(send("/callback?code=abc&state=expected").await.starts_with("HTTP/1.1 200"));
+        assert_eq!(waiter.await.unwrap().unwrap(), "abc");
+    }
+}
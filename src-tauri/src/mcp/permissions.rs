@@ -0,0 +1,484 @@
+// Permission checks for MCP tool calls.
+//
+// Every call goes through `call_tool_with_policy`: the most specific matching rule
+// from `mcp_tool_policies` decides allow / ask / deny, "ask" blocks on an approval
+// event answered by the user (denied on timeout, or right away for unattended calls
+// such as the social loop), and the outcome is written to the audit log together
+// with `format_tool_result` and the call duration.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+use tokio::time::{timeout, Duration, Instant};
+use uuid::Uuid;
+
+use crate::database::mcp_permissions::{NewToolAuditEntry, ToolPolicy};
+use crate::database::Database;
+use super::manager::McpManager;
+use super::types::*;
+
+/// How long an approval prompt waits before the call is denied
+pub const APPROVAL_TIMEOUT_SECS: u64 = 120;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PermissionAction {
+    Allow,
+    Ask,
+    Deny,
+}
+
+impl PermissionAction {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "allow" => Some(PermissionAction::Allow),
+            "ask" => Some(PermissionAction::Ask),
+            "deny" => Some(PermissionAction::Deny),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PermissionAction::Allow => "allow",
+            PermissionAction::Ask => "ask",
+            PermissionAction::Deny => "deny",
+        }
+    }
+
+    // Ties between equally specific rules go to the more restrictive one
+    fn restrictiveness(&self) -> u8 {
+        match self {
+            PermissionAction::Allow => 0,
+            PermissionAction::Ask => 1,
+            PermissionAction::Deny => 2,
+        }
+    }
+}
+
+/// The user's answer to an approval prompt
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ApprovalDecision {
+    pub approved: bool,
+    /// Store the answer for this pet so the tool is not asked about again
+    #[serde(default)]
+    pub remember: bool,
+}
+
+/// `*` matches any run of characters, `?` a single one
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = text.chars().collect();
+    let (mut pi, mut ti) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            backtrack = Some((pi, ti));
+            pi += 1;
+        } else if let Some((star, matched)) = backtrack {
+            pi = star + 1;
+            ti = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|c| *c == '*')
+}
+
+fn is_glob(s: &str) -> bool {
+    s.contains('*') || s.contains('?')
+}
+
+/// `key=glob` tests one top-level argument (strings compared raw, other values as JSON);
+/// anything else is a glob over the whole JSON-encoded argument object.
+fn arg_pattern_matches(pattern: &str, arguments: Option<&serde_json::Value>) -> bool {
+    if let Some((key, glob)) = pattern.split_once('=') {
+        let key = key.trim();
+        if !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.') {
+            return match arguments.and_then(|a| a.get(key)) {
+                Some(serde_json::Value::String(s)) => glob_match(glob.trim(), s),
+                Some(value) => glob_match(glob.trim(), &value.to_string()),
+                None => false,
+            };
+        }
+    }
+    let text = arguments.map(|a| a.to_string()).unwrap_or_else(|| "{}".to_string());
+    glob_match(pattern, &text)
+}
+
+fn policy_matches(
+    policy: &ToolPolicy,
+    server_id: &str,
+    tool_name: &str,
+    pet_id: Option<&str>,
+    arguments: Option<&serde_json::Value>,
+) -> bool {
+    policy.server_id == server_id
+        && glob_match(&policy.tool_name, tool_name)
+        && policy.pet_id.as_deref().map_or(true, |p| Some(p) == pet_id)
+        && policy.arg_pattern.as_deref().map_or(true, |p| arg_pattern_matches(p, arguments))
+}
+
+// An argument rule beats none, pet-specific beats global, exact tool name beats a glob.
+// Argument rules rank first so a remembered per-pet allow (never argument-scoped) cannot
+// override a configured rule such as `write_file` deny for `path=/etc/*`.
+fn specificity(policy: &ToolPolicy) -> u8 {
+    let mut score = 0;
+    if policy.arg_pattern.is_some() {
+        score += 4;
+    }
+    if policy.pet_id.is_some() {
+        score += 2;
+    }
+    if !is_glob(&policy.tool_name) {
+        score += 1;
+    }
+    score
+}
+
+/// The rule that governs this call, if any
+pub fn resolve_policy<'a>(
+    policies: &'a [ToolPolicy],
+    server_id: &str,
+    tool_name: &str,
+    pet_id: Option<&str>,
+    arguments: Option<&serde_json::Value>,
+) -> Option<(&'a ToolPolicy, PermissionAction)> {
+    policies
+        .iter()
+        .filter(|p| policy_matches(p, server_id, tool_name, pet_id, arguments))
+        .filter_map(|p| PermissionAction::parse(&p.action).map(|action| (p, action)))
+        .max_by_key(|(p, action)| (specificity(p), action.restrictiveness()))
+}
+
+/// Whether the server says the tool may destroy data (hints are never used to skip a prompt
+/// the user configured, only to choose the default). Unannotated tools are not counted:
+/// nearly every third-party tool is, and asking before all of them makes MCP unusable
+pub fn is_destructive(tool: Option<&McpTool>) -> bool {
+    tool.and_then(|t| t.annotations.as_ref())
+        .is_some_and(|a| a.read_only_hint != Some(true) && a.destructive_hint == Some(true))
+}
+
+/// Action used when no rule matches: ask before destructive tools, allow everything else
+pub fn default_action(tool: Option<&McpTool>) -> PermissionAction {
+    if is_destructive(tool) {
+        PermissionAction::Ask
+    } else {
+        PermissionAction::Allow
+    }
+}
+
+/// Pending approval prompts, keyed by request id
+#[derive(Default)]
+pub struct ApprovalBroker {
+    pending: Mutex<HashMap<String, oneshot::Sender<ApprovalDecision>>>,
+}
+
+impl ApprovalBroker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a prompt, let `notify` publish it, and wait for the answer.
+    /// Returns None on timeout or when the prompt is cancelled.
+    pub async fn request(&self, wait: Duration, notify: impl FnOnce(&str)) -> Option<ApprovalDecision> {
+        let request_id = Uuid::new_v4().to_string();
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(request_id.clone(), tx);
+        notify(&request_id);
+
+        let result = timeout(wait, rx).await;
+        self.pending.lock().unwrap().remove(&request_id);
+        match result {
+            Ok(Ok(decision)) => Some(decision),
+            _ => None,
+        }
+    }
+
+    /// Answer a prompt; false if it already timed out or never existed
+    pub fn respond(&self, request_id: &str, decision: ApprovalDecision) -> bool {
+        match self.pending.lock().unwrap().remove(request_id) {
+            Some(tx) => tx.send(decision).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Drop every open prompt; the waiting calls are denied
+    pub fn cancel_all(&self) {
+        self.pending.lock().unwrap().clear();
+    }
+
+    pub fn pending_count(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+}
+
+fn denied_response(message: String) -> CallToolResponse {
+    CallToolResponse {
+        success: false,
+        content: vec![],
+        error: Some(message),
+    }
+}
+
+/// Check the permission policy, prompt if needed, run the tool and record the audit entry.
+/// `interactive` is false for calls nobody watches (e.g. the social loop); those are denied
+/// at once when a prompt would be needed instead of waiting out the timeout
+pub async fn call_tool_with_policy(
+    db: &Database,
+    manager: &McpManager,
+    server_id: &str,
+    tool_name: &str,
+    arguments: Option<serde_json::Value>,
+    pet_id: Option<&str>,
+    interactive: bool,
+) -> Result<CallToolResponse, String> {
+    let tool_info = manager.find_tool(server_id, tool_name).await;
+    let tool = tool_info.as_ref().map(|t| &t.tool);
+    let server_name = match &tool_info {
+        Some(info) => Some(info.server_name.clone()),
+        None => manager.get_server_status(server_id).await.map(|s| s.name),
+    };
+
+    let disabled_for_pet = match pet_id {
+        Some(pet_id) => db.get_pet_disabled_tools(pet_id).map_err(|e| e.to_string())?
+            .iter()
+            .any(|d| d.server_id == server_id && d.tool_name == tool_name),
+        None => false,
+    };
+    let policies = db.get_tool_policies(Some(server_id)).map_err(|e| e.to_string())?;
+    let action = if disabled_for_pet {
+        PermissionAction::Deny
+    } else {
+        resolve_policy(&policies, server_id, tool_name, pet_id, arguments.as_ref())
+            .map(|(_, action)| action)
+            .unwrap_or_else(|| default_action(tool))
+    };
+
+    let mut audit = NewToolAuditEntry {
+        server_id: server_id.to_string(),
+        server_name: server_name.clone(),
+        tool_name: tool_name.to_string(),
+        pet_id: pet_id.map(|p| p.to_string()),
+        arguments: arguments.clone(),
+        decision: action.as_str().to_string(),
+        ..Default::default()
+    };
+    let record = |audit: &NewToolAuditEntry| {
+        if let Err(e) = db.insert_tool_audit(audit) {
+            log::warn!("[MCP] Failed to write tool audit entry: {}", e);
+        }
+    };
+
+    match action {
+        PermissionAction::Allow => {}
+        PermissionAction::Deny => {
+            log::info!("[MCP] Tool {} on {} denied by policy", tool_name, server_id);
+            let response = if disabled_for_pet {
+                audit.decision = "disabled".to_string();
+                denied_response(format!("Tool '{}' is disabled for this pet", tool_name))
+            } else {
+                denied_response(format!("Tool '{}' is denied by the permission policy", tool_name))
+            };
+            audit.error = response.error.clone();
+            record(&audit);
+            return Ok(response);
+        }
+        PermissionAction::Ask if !interactive => {
+            log::info!("[MCP] Tool {} on {} needs approval but the call is unattended", tool_name, server_id);
+            audit.decision = "unattended".to_string();
+            let response = denied_response(format!(
+                "Tool '{}' needs the user's approval, which this call cannot ask for; add an allow rule to run it unattended",
+                tool_name
+            ));
+            audit.error = response.error.clone();
+            record(&audit);
+            return Ok(response);
+        }
+        PermissionAction::Ask => {
+            let decision = manager.approvals().request(Duration::from_secs(APPROVAL_TIMEOUT_SECS), |request_id| {
+                manager.emit_event(McpEvent::ToolApprovalRequested {
+                    request_id: request_id.to_string(),
+                    server_id: server_id.to_string(),
+                    server_name: server_name.clone().unwrap_or_else(|| server_id.to_string()),
+                    tool_name: tool_name.to_string(),
+                    arguments: arguments.clone(),
+                    pet_id: pet_id.map(|p| p.to_string()),
+                    destructive: is_destructive(tool),
+                    timeout_secs: APPROVAL_TIMEOUT_SECS,
+                });
+            }).await;
+
+            if let (Some(decision), Some(pet_id)) = (decision, pet_id) {
+                if decision.remember {
+                    let remembered = if decision.approved { "allow" } else { "deny" };
+                    if let Err(e) = db.remember_tool_decision(pet_id, server_id, tool_name, remembered) {
+                        log::warn!("[MCP] Failed to remember tool decision: {}", e);
+                    }
+                }
+            }
+
+            let message = match decision {
+                Some(d) if d.approved => None,
+                Some(_) => {
+                    audit.decision = "rejected".to_string();
+                    Some(format!("The user rejected the call to tool '{}'", tool_name))
+                }
+                None => {
+                    audit.decision = "timeout".to_string();
+                    Some(format!("No approval for tool '{}' within {}s", tool_name, APPROVAL_TIMEOUT_SECS))
+                }
+            };
+            if let Some(message) = message {
+                audit.error = Some(message.clone());
+                record(&audit);
+                return Ok(denied_response(message));
+            }
+            audit.decision = "approved".to_string();
+        }
+    }
+
+    let started = Instant::now();
+    let result = manager.call_tool(server_id, tool_name, arguments).await;
+    audit.duration_ms = started.elapsed().as_millis() as i64;
+    match &result {
+        Ok(response) => {
+            audit.success = response.success;
+            audit.error = response.error.clone();
+            audit.result_summary = Some(format_tool_result(&ToolCallResult {
+                content: response.content.clone(),
+                is_error: !response.success,
+            }));
+        }
+        Err(e) => audit.error = Some(e.clone()),
+    }
+    record(&audit);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(tool_name: &str, pet_id: Option<&str>, action: &str, arg_pattern: Option<&str>) -> ToolPolicy {
+        ToolPolicy {
+            id: format!("{}-{:?}-{}", tool_name, pet_id, action),
+            server_id: "fs".to_string(),
+            tool_name: tool_name.to_string(),
+            pet_id: pet_id.map(|p| p.to_string()),
+            action: action.to_string(),
+            arg_pattern: arg_pattern.map(|p| p.to_string()),
+            remembered: false,
+            created_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn glob_matching() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("delete_*", "delete_file"));
+        assert!(glob_match("/etc/*", "/etc/passwd"));
+        assert!(glob_match("a?c*z", "abcxyz"));
+        assert!(!glob_match("delete_*", "read_file"));
+        assert!(!glob_match("a?c", "ac"));
+    }
+
+    #[test]
+    fn most_specific_rule_wins() {
+        let policies = vec![
+            policy("*", None, "allow", None),
+            policy("write_*", None, "ask", None),
+            policy("write_file", None, "allow", None),
+            policy("write_file", None, "deny", Some("path=/etc/*")),
+            policy("write_file", Some("pet-1"), "allow", None),
+        ];
+        let args = |path: &str| serde_json::json!({ "path": path, "content": "x" });
+        let resolve = |tool: &str, pet: Option<&str>, a: &serde_json::Value| {
+            resolve_policy(&policies, "fs", tool, pet, Some(a)).map(|(_, action)| action)
+        };
+
+        assert_eq!(resolve("read_file", None, &args("/tmp/a")), Some(PermissionAction::Allow));
+        assert_eq!(resolve("write_dir", None, &args("/tmp/a")), Some(PermissionAction::Ask));
+        assert_eq!(resolve("write_file", None, &args("/tmp/a")), Some(PermissionAction::Allow));
+        assert_eq!(resolve("write_file", None, &args("/etc/hosts")), Some(PermissionAction::Deny));
+        assert_eq!(resolve("write_file", Some("pet-1"), &args("/tmp/a")), Some(PermissionAction::Allow));
+        assert_eq!(resolve("write_file", Some("pet-1"), &args("/etc/hosts")), Some(PermissionAction::Deny));
+        assert_eq!(resolve_policy(&policies, "other", "read_file", None, None).map(|(_, a)| a), None);
+    }
+
+    #[test]
+    fn ties_go_to_the_more_restrictive_rule() {
+        let policies = vec![
+            policy("rm", None, "allow", None),
+            policy("rm", None, "deny", None),
+            policy("rm", None, "bogus", Some("*")),
+        ];
+        assert_eq!(
+            resolve_policy(&policies, "fs", "rm", None, None).map(|(_, a)| a),
+            Some(PermissionAction::Deny)
+        );
+        assert!(arg_pattern_matches("*\"force\":true*", Some(&serde_json::json!({ "force": true }))));
+        assert!(!arg_pattern_matches("force=false", Some(&serde_json::json!({ "force": true }))));
+    }
+
+    #[test]
+    fn only_declared_destructive_tools_ask_by_default() {
+        let tool: McpTool = serde_json::from_value(serde_json::json!({
+            "name": "drop_table",
+            "annotations": { "destructiveHint": true }
+        })).unwrap();
+        assert_eq!(default_action(Some(&tool)), PermissionAction::Ask);
+
+        let read_only: McpTool = serde_json::from_value(serde_json::json!({
+            "name": "query",
+            "annotations": { "readOnlyHint": true, "destructiveHint": true }
+        })).unwrap();
+        assert_eq!(default_action(Some(&read_only)), PermissionAction::Allow);
+
+        let unannotated: McpTool = serde_json::from_value(serde_json::json!({ "name": "run" })).unwrap();
+        assert_eq!(default_action(Some(&unannotated)), PermissionAction::Allow);
+        assert_eq!(default_action(None), PermissionAction::Allow);
+    }
+
+    #[tokio::test]
+    async fn unattended_calls_that_need_approval_are_denied_at_once() {
+        let db = Database::new(std::path::PathBuf::from(":memory:")).unwrap();
+        db.create_tool_policy(crate::database::mcp_permissions::CreateToolPolicyData {
+            server_id: "qq".to_string(),
+            tool_name: Some("send_message".to_string()),
+            pet_id: None,
+            action: "ask".to_string(),
+            arg_pattern: None,
+        }).unwrap();
+        let manager = McpManager::new();
+
+        let call = call_tool_with_policy(&db, &manager, "qq", "send_message", None, None, false);
+        let response = timeout(Duration::from_secs(1), call).await.unwrap().unwrap();
+        assert!(!response.success);
+        assert_eq!(manager.approvals().pending_count(), 0);
+        let audit = db.get_tool_audit_log(Some("qq"), None, 10).unwrap();
+        assert_eq!(audit[0].decision, "unattended");
+    }
+
+    #[tokio::test]
+    async fn approval_broker_answers_and_times_out() {
+        let broker = std::sync::Arc::new(ApprovalBroker::new());
+        let responder = broker.clone();
+        let decision = broker.request(Duration::from_secs(5), move |id| {
+            assert!(responder.respond(id, ApprovalDecision { approved: true, remember: true }));
+        }).await;
+        assert_eq!(decision, Some(ApprovalDecision { approved: true, remember: true }));
+        assert_eq!(broker.pending_count(), 0);
+
+        let timed_out = broker.request(Duration::from_millis(20), |_| {}).await;
+        assert_eq!(timed_out, None);
+        assert!(!broker.respond("missing", ApprovalDecision { approved: true, remember: false }));
+    }
+}
@@ -0,0 +1,292 @@
+// MCP Resource helpers
+// URI template expansion (resources/templates/list) and blob decoding for attachments
+
+use std::collections::HashMap;
+use std::path::Path;
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+
+use super::types::ResourceReadResult;
+use crate::llm::{ContentPart, FileUrl, ImageUrl};
+
+// ============================================
+// URI Templates (RFC 6570, levels 1-3)
+// ============================================
+
+/// Expand an RFC 6570 URI template with string variables.
+///
+/// Supports the operators used by MCP servers in practice: simple `{var}`,
+/// reserved `{+var}`, fragment `{#var}`, label `{.var}`, path `{/var}`,
+/// path-style `{;var}`, query `{?a,b}` and continuation `{&a}`, plus the
+/// `:n` prefix modifier. Undefined variables are skipped, as the RFC requires.
+pub fn expand_uri_template(template: &str, vars: &HashMap<String, String>) -> Result<String, String> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .map(|e| start + e)
+            .ok_or_else(|| format!("Unclosed expression in URI template: {}", template))?;
+        out.push_str(&expand_expression(&rest[start + 1..end], vars)?);
+        rest = &rest[end + 1..];
+    }
+    if rest.contains('}') {
+        return Err(format!("Unmatched '}}' in URI template: {}", template));
+    }
+    out.push_str(rest);
+
+    Ok(out)
+}
+
+/// List the variable names referenced by a URI template (for building input forms)
+pub fn template_variables(template: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}').map(|e| start + e) else { break };
+        let expr = &rest[start + 1..end];
+        let expr = expr.trim_start_matches(['+', '#', '.', '/', ';', '?', '&']);
+        for spec in expr.split(',') {
+            let name = spec.split(':').next().unwrap_or("").trim_end_matches('*');
+            if !name.is_empty() && !names.iter().any(|n| n == name) {
+                names.push(name.to_string());
+            }
+        }
+        rest = &rest[end + 1..];
+    }
+    names
+}
+
+fn expand_expression(expr: &str, vars: &HashMap<String, String>) -> Result<String, String> {
+    let (op, body) = match expr.chars().next() {
+        Some(c @ ('+' | '#' | '.' | '/' | ';' | '?' | '&')) => (Some(c), &expr[1..]),
+        Some(_) => (None, expr),
+        None => return Err("Empty expression in URI template".to_string()),
+    };
+
+    // (first prefix, separator, named, if-empty suffix, allow reserved)
+    let (first, sep, named, ifemp, reserved) = match op {
+        None => ("", ",", false, "", false),
+        Some('+') => ("", ",", false, "", true),
+        Some('#') => ("#", ",", false, "", true),
+        Some('.') => (".", ".", false, "", false),
+        Some('/') => ("/", "/", false, "", false),
+        Some(';') => (";", ";", true, "", false),
+        Some('?') => ("?", "&", true, "=", false),
+        Some('&') => ("&", "&", true, "=", false),
+        Some(_) => unreachable!(),
+    };
+
+    let mut parts = Vec::new();
+    for spec in body.split(',') {
+        let (name, prefix_len) = match spec.split_once(':') {
+            Some((name, len)) => {
+                let len = len.parse::<usize>()
+                    .map_err(|_| format!("Invalid prefix modifier in URI template: {}", spec))?;
+                (name, Some(len))
+            }
+            None => (spec.trim_end_matches('*'), None),
+        };
+        let Some(value) = vars.get(name) else { continue };
+
+        let value: String = match prefix_len {
+            Some(len) => value.chars().take(len).collect(),
+            None => value.clone(),
+        };
+        let encoded = percent_encode(&value, reserved);
+
+        if named {
+            if encoded.is_empty() {
+                parts.push(format!("{}{}", name, ifemp));
+            } else {
+                parts.push(format!("{}={}", name, encoded));
+            }
+        } else {
+            parts.push(encoded);
+        }
+    }
+
+    if parts.is_empty() {
+        return Ok(String::new());
+    }
+    Ok(format!("{}{}", first, parts.join(sep)))
+}
+
+fn percent_encode(value: &str, allow_reserved: bool) -> String {
+    const RESERVED: &str = ":/?#[]@!$&'()*+,;=";
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        let unreserved = c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | '~');
+        if unreserved || (allow_reserved && (RESERVED.contains(c) || c == '%')) {
+            out.push(c);
+        } else {
+            let mut buf = [0u8; 4];
+            for b in c.encode_utf8(&mut buf).bytes() {
+                out.push_str(&format!("%{:02X}", b));
+            }
+        }
+    }
+    out
+}
+
+// ============================================
+// Blob contents → chat attachments
+// ============================================
+
+/// Convert a resources/read result into chat content parts.
+///
+/// Text contents become `ContentPart::Text`. Blob contents are base64-decoded
+/// and written into `uploads_dir` using the same `{timestamp}_{name}` naming as
+/// `save_file`, then referenced by path as `ImageUrl` (image/*) or `FileUrl`.
+pub fn resource_contents_to_parts(
+    result: &ResourceReadResult,
+    uploads_dir: &Path,
+) -> Result<Vec<ContentPart>, String> {
+    let mut parts = Vec::new();
+
+    for content in &result.contents {
+        if let Some(text) = &content.text {
+            parts.push(ContentPart::Text { text: text.clone() });
+            continue;
+        }
+        let Some(blob) = &content.blob else { continue };
+
+        let data = BASE64.decode(blob.trim())
+            .map_err(|e| format!("Failed to decode resource blob {}: {}", content.uri, e))?;
+        let mime_type = content.mime_type.clone()
+            .unwrap_or_else(|| "application/octet-stream".to_string());
+        let file_name = attachment_file_name(&content.uri, &mime_type);
+
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_err(|e| format!("Time error: {}", e))?
+            .as_millis();
+        let unique_name = format!("{}_{}", timestamp, file_name);
+        let file_path = uploads_dir.join(&unique_name);
+
+        std::fs::write(&file_path, &data)
+            .map_err(|e| format!("Failed to write resource blob: {}", e))?;
+        log::info!("[MCP] Saved resource blob {} ({} bytes) to {:?}", content.uri, data.len(), file_path);
+
+        let url = file_path.to_string_lossy().to_string();
+        if mime_type.starts_with("image/") {
+            parts.push(ContentPart::ImageUrl {
+                image_url: ImageUrl { url, mime_type: Some(mime_type) },
+            });
+        } else {
+            parts.push(ContentPart::FileUrl {
+                file_url: FileUrl { url, mime_type: Some(mime_type), name: Some(file_name) },
+            });
+        }
+    }
+
+    Ok(parts)
+}
+
+/// Derive a safe file name from the resource URI, adding an extension from the MIME type if missing
+fn attachment_file_name(uri: &str, mime_type: &str) -> String {
+    let last = uri
+        .split(['?', '#']).next().unwrap_or(uri)
+        .trim_end_matches('/')
+        .rsplit(['/', ':']).next().unwrap_or("");
+    let mut name: String = last
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') { c } else { '_' })
+        .collect();
+    name = name.trim_matches('.').to_string();
+    if name.is_empty() {
+        name = "resource".to_string();
+    }
+    if !name.contains('.') {
+        if let Some(ext) = extension_for_mime(mime_type) {
+            name = format!("{}.{}", name, ext);
+        }
+    }
+    name
+}
+
+pub(crate) fn extension_for_mime(mime_type: &str) -> Option<&'static str> {
+    match mime_type.split(';').next().unwrap_or("").trim() {
+        "image/png" => Some("png"),
+        "image/jpeg" => Some("jpg"),
+        "image/gif" => Some("gif"),
+        "image/webp" => Some("webp"),
+        "image/svg+xml" => Some("svg"),
+        "video/mp4" => Some("mp4"),
+        "video/webm" => Some("webm"),
+        "audio/mpeg" => Some("mp3"),
+        "audio/wav" => Some("wav"),
+        "audio/ogg" => Some("ogg"),
+        "application/pdf" => Some("pdf"),
+        "application/json" => Some("json"),
+        "text/plain" => Some("txt"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mcp::types::ResourceContent;
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn expands_rfc6570_operators() {
+        let v = vars(&[("var", "value"), ("hello", "Hello World!"), ("path", "/foo/bar"), ("x", "1024"), ("y", "768")]);
+        assert_eq!(expand_uri_template("{var}", &v).unwrap(), "value");
+        assert_eq!(expand_uri_template("{hello}", &v).unwrap(), "Hello%20World%21");
+        assert_eq!(expand_uri_template("{+path}/here", &v).unwrap(), "/foo/bar/here");
+        assert_eq!(expand_uri_template("map{?x,y}", &v).unwrap(), "map?x=1024&y=768");
+        assert_eq!(expand_uri_template("{/var,x}/here", &v).unwrap(), "/value/1024/here");
+        assert_eq!(expand_uri_template("X{.var}", &v).unwrap(), "X.value");
+        assert_eq!(expand_uri_template("{;x,y}", &v).unwrap(), ";x=1024;y=768");
+        assert_eq!(expand_uri_template("{#path}", &v).unwrap(), "#/foo/bar");
+        assert_eq!(expand_uri_template("{var:3}", &v).unwrap(), "val");
+        assert_eq!(expand_uri_template("?fixed=yes{&x}", &v).unwrap(), "?fixed=yes&x=1024");
+    }
+
+    #[test]
+    fn undefined_variables_are_skipped() {
+        let v = vars(&[("owner", "jules")]);
+        assert_eq!(expand_uri_template("repo://{owner}/{repo}", &v).unwrap(), "repo://jules/");
+        assert_eq!(expand_uri_template("search{?q,page}", &v).unwrap(), "search");
+        assert!(expand_uri_template("broken{var", &v).is_err());
+    }
+
+    #[test]
+    fn lists_template_variables() {
+        assert_eq!(template_variables("file:///{+path}{?rev,limit:2}"), vec!["path", "rev", "limit"]);
+    }
+
+    #[test]
+    fn blobs_are_saved_as_attachments() {
+        let dir = std::env::temp_dir().join(format!("petgpt_test_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let result = ResourceReadResult {
+            contents: vec![
+                ResourceContent { uri: "note://a".into(), mime_type: None, text: Some("hi".into()), blob: None },
+                ResourceContent {
+                    uri: "screenshot://desktop/main".into(),
+                    mime_type: Some("image/png".into()),
+                    text: None,
+                    blob: Some(BASE64.encode(b"\x89PNG")),
+                },
+            ],
+        };
+
+        let parts = resource_contents_to_parts(&result, &dir).unwrap();
+        assert_eq!(parts.len(), 2);
+        match &parts[1] {
+            ContentPart::ImageUrl { image_url } => {
+                assert!(image_url.url.ends_with("_main.png"));
+                assert_eq!(std::fs::read(&image_url.url).unwrap(), b"\x89PNG");
+            }
+            other => panic!("expected image part, got {:?}", other),
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,416 @@
+// Opt-in sandbox for stdio MCP servers.
+//
+// A sandboxed server starts with a small environment allowlist instead of PetGPT's
+// full environment, runs inside a pinned working directory (normally a pet workspace)
+// and, on Linux, can have its filesystem confined with bubblewrap (when `bwrap` is on
+// PATH) or Landlock, plus rlimits on memory and CPU time. Anything the platform cannot
+// do is skipped with a warning rather than failing the start.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use serde::{Deserialize, Serialize};
+
+/// Variables passed through from PetGPT's own environment to every sandboxed server
+const BASE_ENV_ALLOWLIST: &[&str] = &[
+    "PATH", "LANG", "LC_ALL", "LC_CTYPE", "TERM", "TZ",
+    // Windows needs these to start most programs
+    "SYSTEMROOT", "WINDIR", "PATHEXT", "COMSPEC",
+];
+
+/// System locations a confined server may read and execute
+#[cfg(target_os = "linux")]
+const SYSTEM_READ_PATHS: &[&str] = &[
+    "/usr", "/bin", "/sbin", "/lib", "/lib32", "/lib64", "/etc", "/opt", "/nix/store",
+    "/run/systemd/resolve",
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SandboxProfile {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Extra variable names passed through from PetGPT's environment
+    #[serde(default)]
+    pub env_allowlist: Vec<String>,
+    /// Pet whose workspace becomes the working directory (None = a private directory per server)
+    #[serde(default)]
+    pub workspace_pet_id: Option<String>,
+    /// Confine the filesystem with bubblewrap or Landlock (Linux only)
+    #[serde(default)]
+    pub restrict_filesystem: bool,
+    /// Extra paths the server may read when the filesystem is confined
+    #[serde(default)]
+    pub read_only_paths: Vec<String>,
+    /// Extra paths the server may write when the filesystem is confined
+    #[serde(default)]
+    pub writable_paths: Vec<String>,
+    /// Keep network access when confined with bubblewrap (Landlock cannot restrict it)
+    #[serde(default = "default_true")]
+    pub allow_network: bool,
+    /// Virtual memory limit in MiB (RLIMIT_AS); Node.js servers need a few GiB
+    #[serde(default)]
+    pub max_memory_mb: Option<u64>,
+    /// CPU time limit in seconds (RLIMIT_CPU)
+    #[serde(default)]
+    pub max_cpu_secs: Option<u64>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for SandboxProfile {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            env_allowlist: Vec::new(),
+            workspace_pet_id: None,
+            restrict_filesystem: false,
+            read_only_paths: Vec::new(),
+            writable_paths: Vec::new(),
+            allow_network: true,
+            max_memory_mb: None,
+            max_cpu_secs: None,
+        }
+    }
+}
+
+/// A profile with its working directory resolved
+#[derive(Debug, Clone)]
+pub struct Sandbox {
+    pub profile: SandboxProfile,
+    pub work_dir: PathBuf,
+}
+
+impl Sandbox {
+    /// Build the command that runs `program args` inside the sandbox
+    pub fn command(&self, program: &str, args: &[String], env: &HashMap<String, String>) -> Command {
+        if let Err(e) = std::fs::create_dir_all(&self.work_dir) {
+            log::warn!("[MCP][sandbox] Failed to create {:?}: {}", self.work_dir, e);
+        }
+        let vars = self.environment(env);
+
+        let mut cmd = self.base_command(program, args, &vars);
+        cmd.env_clear().envs(&vars).current_dir(&self.work_dir);
+        cmd
+    }
+
+    /// The allowlisted environment, with HOME pointed at the working directory so
+    /// caches and dotfiles stay out of the real home. Configured server env always wins.
+    pub fn environment(&self, configured: &HashMap<String, String>) -> HashMap<String, String> {
+        let mut vars = HashMap::new();
+        let names = BASE_ENV_ALLOWLIST
+            .iter()
+            .copied()
+            .chain(self.profile.env_allowlist.iter().map(|s| s.as_str()));
+        for name in names {
+            if let Ok(value) = std::env::var(name) {
+                vars.insert(name.to_string(), value);
+            }
+        }
+        let home = self.work_dir.to_string_lossy().to_string();
+        if cfg!(windows) {
+            vars.insert("USERPROFILE".to_string(), home);
+        } else {
+            vars.insert("HOME".to_string(), home);
+        }
+        vars.extend(configured.iter().map(|(k, v)| (k.clone(), v.clone())));
+        vars
+    }
+
+    #[cfg(target_os = "linux")]
+    fn base_command(&self, program: &str, args: &[String], vars: &HashMap<String, String>) -> Command {
+        let program_prefix = resolve_program(program, vars.get("PATH").map(|s| s.as_str()))
+            .and_then(|p| install_prefix(&p));
+
+        let mut landlock_rules = Vec::new();
+        let mut cmd = if !self.profile.restrict_filesystem {
+            plain_command(program, args)
+        } else if let Some(bwrap) = resolve_program("bwrap", std::env::var("PATH").ok().as_deref()) {
+            log::info!("[MCP][sandbox] Confining {} with bubblewrap", program);
+            let mut cmd = Command::new(bwrap);
+            cmd.args(self.bwrap_args(program_prefix.as_deref())).arg("--").arg(program).args(args);
+            cmd
+        } else if linux::landlock_abi() > 0 {
+            log::info!("[MCP][sandbox] Confining {} with Landlock", program);
+            landlock_rules = self.landlock_rules(program_prefix.as_deref());
+            plain_command(program, args)
+        } else {
+            log::warn!("[MCP][sandbox] Neither bubblewrap nor Landlock is available; {} runs without filesystem restrictions", program);
+            plain_command(program, args)
+        };
+
+        linux::install_pre_exec(&mut cmd, &self.profile, landlock_rules);
+        cmd
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn base_command(&self, program: &str, args: &[String], _vars: &HashMap<String, String>) -> Command {
+        let p = &self.profile;
+        if p.restrict_filesystem || p.max_memory_mb.is_some() || p.max_cpu_secs.is_some() {
+            log::warn!("[MCP][sandbox] Filesystem restrictions and rlimits are only supported on Linux; starting {} with env and working directory isolation only", program);
+        }
+        plain_command(program, args)
+    }
+
+    /// bubblewrap arguments: read-only system dirs, private /tmp, the working
+    /// directory and configured paths bound at their own location
+    #[cfg(target_os = "linux")]
+    fn bwrap_args(&self, program_prefix: Option<&Path>) -> Vec<String> {
+        let mut args: Vec<String> = vec![
+            "--die-with-parent".into(),
+            "--new-session".into(),
+            "--unshare-all".into(),
+        ];
+        if self.profile.allow_network {
+            args.push("--share-net".into());
+        }
+        let read_only = SYSTEM_READ_PATHS
+            .iter()
+            .map(PathBuf::from)
+            .chain(program_prefix.map(Path::to_path_buf))
+            .chain(self.profile.read_only_paths.iter().map(PathBuf::from));
+        for path in read_only {
+            let path = path.to_string_lossy().to_string();
+            args.extend(["--ro-bind-try".to_string(), path.clone(), path]);
+        }
+        args.extend(["--proc", "/proc", "--dev", "/dev", "--tmpfs", "/tmp"].map(String::from));
+        let work_dir = self.work_dir.to_string_lossy().to_string();
+        args.extend(["--bind".to_string(), work_dir.clone(), work_dir.clone()]);
+        for path in &self.profile.writable_paths {
+            args.extend(["--bind-try".to_string(), path.clone(), path.clone()]);
+        }
+        args.extend(["--chdir".to_string(), work_dir]);
+        args
+    }
+
+    #[cfg(target_os = "linux")]
+    fn landlock_rules(&self, program_prefix: Option<&Path>) -> Vec<(PathBuf, bool)> {
+        let read_only = SYSTEM_READ_PATHS
+            .iter()
+            .map(PathBuf::from)
+            .chain(["/proc", "/sys"].map(PathBuf::from))
+            .chain(program_prefix.map(Path::to_path_buf))
+            .chain(self.profile.read_only_paths.iter().map(PathBuf::from))
+            .map(|p| (p, false));
+        let writable = [self.work_dir.clone(), PathBuf::from("/tmp"), PathBuf::from("/dev")]
+            .into_iter()
+            .chain(self.profile.writable_paths.iter().map(PathBuf::from))
+            .map(|p| (p, true));
+        read_only.chain(writable).filter(|(p, _)| p.exists()).collect()
+    }
+}
+
+fn plain_command(program: &str, args: &[String]) -> Command {
+    let mut cmd = Command::new(program);
+    cmd.args(args);
+    cmd
+}
+
+/// Find `program` the way the shell would, using the given PATH
+pub fn resolve_program(program: &str, path_var: Option<&str>) -> Option<PathBuf> {
+    let candidate = Path::new(program);
+    if candidate.components().count() > 1 {
+        return candidate.is_file().then(|| candidate.to_path_buf());
+    }
+    std::env::split_paths(path_var?)
+        .map(|dir| dir.join(program))
+        .find(|p| p.is_file())
+}
+
+/// `/opt/node/bin/node` -> `/opt/node`, so the runtime's lib dir is readable too
+fn install_prefix(program: &Path) -> Option<PathBuf> {
+    let program = program.canonicalize().ok()?;
+    let bin_dir = program.parent()?;
+    Some(bin_dir.parent().unwrap_or(bin_dir).to_path_buf())
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::process::CommandExt;
+    use std::path::PathBuf;
+    use std::process::Command;
+    use super::SandboxProfile;
+
+    // Landlock ABI v1 (linux/landlock.h)
+    const LANDLOCK_CREATE_RULESET_VERSION: u32 = 1;
+    const LANDLOCK_RULE_PATH_BENEATH: u32 = 1;
+    const ACCESS_EXECUTE: u64 = 1 << 0;
+    const ACCESS_WRITE_FILE: u64 = 1 << 1;
+    const ACCESS_READ_FILE: u64 = 1 << 2;
+    const ACCESS_READ_DIR: u64 = 1 << 3;
+    const ACCESS_ALL_V1: u64 = (1 << 13) - 1;
+    const ACCESS_FILE_ONLY: u64 = ACCESS_EXECUTE | ACCESS_WRITE_FILE | ACCESS_READ_FILE;
+
+    #[repr(C)]
+    struct RulesetAttr {
+        handled_access_fs: u64,
+    }
+
+    #[repr(C, packed)]
+    struct PathBeneathAttr {
+        allowed_access: u64,
+        parent_fd: i32,
+    }
+
+    /// Landlock ABI version supported by the running kernel (0 = unavailable)
+    pub fn landlock_abi() -> i64 {
+        let abi = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                std::ptr::null::<RulesetAttr>(),
+                0usize,
+                LANDLOCK_CREATE_RULESET_VERSION,
+            )
+        };
+        abi.max(0)
+    }
+
+    /// Apply rlimits and Landlock rules in the child between fork and exec.
+    /// Everything is prepared up front so the hook itself only makes syscalls.
+    pub fn install_pre_exec(cmd: &mut Command, profile: &SandboxProfile, landlock_rules: Vec<(PathBuf, bool)>) {
+        let memory = profile.max_memory_mb.map(|mb| mb.saturating_mul(1024 * 1024));
+        let cpu = profile.max_cpu_secs;
+        let rules: Vec<(CString, u64)> = landlock_rules
+            .into_iter()
+            .filter_map(|(path, writable)| {
+                let mut access = if writable {
+                    ACCESS_ALL_V1
+                } else {
+                    ACCESS_EXECUTE | ACCESS_READ_FILE | ACCESS_READ_DIR
+                };
+                if !path.is_dir() {
+                    access &= ACCESS_FILE_ONLY;
+                }
+                CString::new(path.as_os_str().as_bytes()).ok().map(|c| (c, access))
+            })
+            .collect();
+        if memory.is_none() && cpu.is_none() && rules.is_empty() {
+            return;
+        }
+
+        unsafe {
+            cmd.pre_exec(move || {
+                if let Some(bytes) = memory {
+                    set_rlimit(libc::RLIMIT_AS, bytes)?;
+                }
+                if let Some(secs) = cpu {
+                    set_rlimit(libc::RLIMIT_CPU, secs)?;
+                }
+                if !rules.is_empty() {
+                    restrict_filesystem(&rules)?;
+                }
+                Ok(())
+            });
+        }
+    }
+
+    fn set_rlimit(resource: libc::__rlimit_resource_t, value: u64) -> std::io::Result<()> {
+        let limit = libc::rlimit { rlim_cur: value as libc::rlim_t, rlim_max: value as libc::rlim_t };
+        if unsafe { libc::setrlimit(resource, &limit) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    fn restrict_filesystem(rules: &[(CString, u64)]) -> std::io::Result<()> {
+        let attr = RulesetAttr { handled_access_fs: ACCESS_ALL_V1 };
+        let ruleset = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                &attr as *const RulesetAttr,
+                std::mem::size_of::<RulesetAttr>(),
+                0u32,
+            )
+        };
+        if ruleset < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let ruleset = ruleset as libc::c_int;
+
+        for (path, access) in rules {
+            let fd = unsafe { libc::open(path.as_ptr(), libc::O_PATH | libc::O_CLOEXEC) };
+            if fd < 0 {
+                continue;
+            }
+            let rule = PathBeneathAttr { allowed_access: *access, parent_fd: fd };
+            unsafe {
+                libc::syscall(
+                    libc::SYS_landlock_add_rule,
+                    ruleset,
+                    LANDLOCK_RULE_PATH_BENEATH,
+                    &rule as *const PathBeneathAttr,
+                    0u32,
+                );
+                libc::close(fd);
+            }
+        }
+
+        let result = unsafe {
+            if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+                -1
+            } else {
+                libc::syscall(libc::SYS_landlock_restrict_self, ruleset, 0u32)
+            }
+        };
+        unsafe { libc::close(ruleset) };
+        if result != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sandbox(profile: SandboxProfile) -> Sandbox {
+        let work_dir = std::env::temp_dir().join(format!("petgpt-sandbox-{}", uuid::Uuid::new_v4()));
+        Sandbox { profile: SandboxProfile { enabled: true, ..profile }, work_dir }
+    }
+
+    #[test]
+    fn environment_is_allowlisted() {
+        std::env::set_var("PETGPT_SANDBOX_SECRET", "hunter2");
+        std::env::set_var("PETGPT_SANDBOX_ALLOWED", "yes");
+        let sb = sandbox(SandboxProfile {
+            env_allowlist: vec!["PETGPT_SANDBOX_ALLOWED".into()],
+            ..Default::default()
+        });
+        let configured = HashMap::from([("API_KEY".to_string(), "k".to_string())]);
+        let vars = sb.environment(&configured);
+
+        assert!(!vars.contains_key("PETGPT_SANDBOX_SECRET"));
+        assert_eq!(vars.get("PETGPT_SANDBOX_ALLOWED").map(|s| s.as_str()), Some("yes"));
+        assert_eq!(vars.get("API_KEY").map(|s| s.as_str()), Some("k"));
+        assert_eq!(vars.get("PATH"), std::env::var("PATH").ok().as_ref());
+    }
+
+    #[test]
+    fn profile_defaults_keep_network() {
+        let profile: SandboxProfile = serde_json::from_str(r#"{"enabled":true,"maxMemoryMb":512}"#).unwrap();
+        assert!(profile.allow_network);
+        assert_eq!(profile.max_memory_mb, Some(512));
+        assert!(!SandboxProfile::default().enabled);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn sandboxed_process_runs_in_work_dir_with_clean_env() {
+        std::env::set_var("PETGPT_SANDBOX_LEAK", "1");
+        let sb = sandbox(SandboxProfile { max_cpu_secs: Some(10), ..Default::default() });
+        let output = sb
+            .command("sh", &["-c".into(), "pwd; echo leak=$PETGPT_SANDBOX_LEAK".into()], &HashMap::new())
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let work_dir = sb.work_dir.canonicalize().unwrap();
+        assert_eq!(stdout.lines().next().map(PathBuf::from), Some(work_dir));
+        assert!(stdout.contains("leak=\n"));
+        let _ = std::fs::remove_dir_all(&sb.work_dir);
+    }
+}
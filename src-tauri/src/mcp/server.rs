@@ -0,0 +1,821 @@
+// PetGPT as an MCP server
+// Exposes pets, conversations, QQ chat history, workspace files and skills to
+// other MCP hosts (agents, IDEs).
+//
+// Transports:
+//   - stdio: `petgpt --mcp-stdio` (newline-delimited JSON-RPC, see `serve_stdio`)
+//   - Streamable HTTP on 127.0.0.1 (see `serve_http`); every request must carry
+//     `Authorization: Bearer <token>` and browser origins other than localhost
+//     are rejected. Responses are plain JSON — the server never initiates
+//     messages, so GET streams are answered with 405.
+
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::database::chat_history::ChatSearchParams;
+use crate::database::messages::{CreateMessageData, Message};
+use crate::database::Database;
+use crate::skills::SkillEngine;
+use crate::workspace::{ChangeSource, SearchOptions, WorkspaceEngine};
+
+const SUPPORTED_PROTOCOL_VERSIONS: [&str; 2] = ["2025-03-26", "2024-11-05"];
+const MAX_HTTP_BODY_BYTES: usize = 8 * 1024 * 1024;
+/// An idle or trickling client must not hold a connection handler forever
+const HTTP_READ_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+const DEFAULT_MESSAGE_LIMIT: usize = 50;
+const RESOURCE_SCHEME: &str = "petgpt://";
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Called after a message was added to a conversation through the `add_message` tool
+pub type MessageAddedHook = Arc<dyn Fn(&Message) + Send + Sync>;
+
+pub struct PetGptMcpServer {
+    db: Arc<Database>,
+    workspace: Arc<WorkspaceEngine>,
+    skills: Arc<SkillEngine>,
+    on_message_added: Option<MessageAddedHook>,
+}
+
+impl PetGptMcpServer {
+    pub fn new(db: Arc<Database>, workspace: Arc<WorkspaceEngine>, skills: Arc<SkillEngine>) -> Self {
+        Self { db, workspace, skills, on_message_added: None }
+    }
+
+    pub fn set_message_added_hook(&mut self, hook: MessageAddedHook) {
+        self.on_message_added = Some(hook);
+    }
+
+    /// Handle one JSON-RPC message (or batch); returns the response, if any
+    pub fn handle_message(&self, message: &Value) -> Option<Value> {
+        if let Some(batch) = message.as_array() {
+            let responses: Vec<Value> = batch.iter().filter_map(|m| self.handle_single(m)).collect();
+            return if responses.is_empty() { None } else { Some(Value::Array(responses)) };
+        }
+        self.handle_single(message)
+    }
+
+    fn handle_single(&self, message: &Value) -> Option<Value> {
+        let Some(method) = message.get("method").and_then(|m| m.as_str()) else {
+            // Responses from the client (we never send requests) are ignored
+            if message.get("result").is_some() || message.get("error").is_some() {
+                return None;
+            }
+            return Some(error_response(message.get("id").cloned().unwrap_or(Value::Null), INVALID_REQUEST, "Invalid request"));
+        };
+        // Notifications (no id) get no response
+        let id = message.get("id")?.clone();
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+        let result = match method {
+            "initialize" => Ok(self.initialize(&params)),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": tool_definitions() })),
+            "tools/call" => return Some(success_response(id, self.call_tool(&params))),
+            "resources/list" => self.list_resources(),
+            "resources/templates/list" => Ok(json!({ "resourceTemplates": resource_templates() })),
+            "resources/read" => self.read_resource(&params),
+            _ => Err((METHOD_NOT_FOUND, format!("Method not found: {}", method))),
+        };
+
+        Some(match result {
+            Ok(result) => success_response(id, result),
+            Err((code, message)) => error_response(id, code, &message),
+        })
+    }
+
+    fn initialize(&self, params: &Value) -> Value {
+        let requested = params.get("protocolVersion").and_then(|v| v.as_str()).unwrap_or("");
+        let version = SUPPORTED_PROTOCOL_VERSIONS
+            .iter()
+            .find(|v| **v == requested)
+            .unwrap_or(&SUPPORTED_PROTOCOL_VERSIONS[0]);
+        json!({
+            "protocolVersion": version,
+            "capabilities": {
+                "tools": { "listChanged": false },
+                "resources": { "subscribe": false, "listChanged": false },
+            },
+            "serverInfo": { "name": "PetGPT", "version": env!("CARGO_PKG_VERSION") },
+            "instructions": "Access PetGPT assistants (pets): their conversations, workspace files (SOUL.md, USER.md, MEMORY.md), skills and archived QQ chat history. Use list_pets first to get pet ids.",
+        })
+    }
+
+    // ============ Tools ============
+
+    /// tools/call — failures are reported as tool results with isError so the caller's model sees them
+    fn call_tool(&self, params: &Value) -> Value {
+        let name = params.get("name").and_then(|v| v.as_str()).unwrap_or("");
+        let args = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+        match self.run_tool(name, &args) {
+            Ok(value) => {
+                let text = match value {
+                    Value::String(s) => s,
+                    other => serde_json::to_string_pretty(&other).unwrap_or_default(),
+                };
+                json!({ "content": [{ "type": "text", "text": text }], "isError": false })
+            }
+            Err(e) => json!({ "content": [{ "type": "text", "text": e }], "isError": true }),
+        }
+    }
+
+    fn run_tool(&self, name: &str, args: &Value) -> Result<Value, String> {
+        match name {
+            "list_pets" => {
+                let pets = self.db.get_all_pets().map_err(|e| e.to_string())?;
+                Ok(Value::Array(pets.iter().map(|p| json!({
+                    "id": p.id,
+                    "name": p.name,
+                    "type": p.pet_type,
+                    "model": p.model_name,
+                })).collect()))
+            }
+            "list_conversations" => {
+                let conversations = match opt_str(args, "petId") {
+                    Some(pet_id) => self.db.get_conversations_by_pet(pet_id).map(serde_json::to_value),
+                    None => self.db.get_all_conversations().map(serde_json::to_value),
+                };
+                conversations.map_err(|e| e.to_string())?.map_err(|e| e.to_string())
+            }
+            "get_conversation" => {
+                let conversation_id = req_str(args, "conversationId")?;
+                let limit = args.get("limit").and_then(|v| v.as_u64()).map(|v| v as usize).unwrap_or(DEFAULT_MESSAGE_LIMIT);
+                let conversation = self.db.get_conversation_by_id(conversation_id)
+                    .map_err(|e| e.to_string())?
+                    .ok_or_else(|| format!("Conversation not found: {}", conversation_id))?;
+                let messages = self.db.get_messages_by_conversation(conversation_id).map_err(|e| e.to_string())?;
+                let skip = messages.len().saturating_sub(limit);
+                Ok(json!({
+                    "conversation": conversation,
+                    "totalMessages": messages.len(),
+                    "messages": messages.into_iter().skip(skip).collect::<Vec<_>>(),
+                }))
+            }
+            "search_conversations" => {
+                let query = req_str(args, "query")?;
+                serde_json::to_value(self.db.search_conversations(query).map_err(|e| e.to_string())?)
+                    .map_err(|e| e.to_string())
+            }
+            "add_message" => {
+                let conversation_id = req_str(args, "conversationId")?;
+                let content = req_str(args, "content")?;
+                let role = opt_str(args, "role").unwrap_or("user");
+                if !matches!(role, "user" | "assistant" | "system") {
+                    return Err(format!("Invalid role: {} (expected user, assistant or system)", role));
+                }
+                self.db.get_conversation_by_id(conversation_id)
+                    .map_err(|e| e.to_string())?
+                    .ok_or_else(|| format!("Conversation not found: {}", conversation_id))?;
+                let message = self.db.create_message(CreateMessageData {
+                    conversation_id: conversation_id.to_string(),
+                    role: role.to_string(),
+                    content: content.to_string(),
+                    tool_call_history: None,
+                    parent_id: None,
+                }).map_err(|e| e.to_string())?;
+                if let Some(hook) = &self.on_message_added {
+                    hook(&message);
+                }
+                serde_json::to_value(message).map_err(|e| e.to_string())
+            }
+            "chat_history_search" => {
+                let params: ChatSearchParams = serde_json::from_value(args.clone()).map_err(|e| format!("Invalid arguments: {}", e))?;
+                serde_json::to_value(self.db.chat_search(&params).map_err(|e| e.to_string())?)
+                    .map_err(|e| e.to_string())
+            }
+            "chat_history_context" => {
+                let message_id = req_str(args, "messageId")?;
+                let before = args.get("before").and_then(|v| v.as_i64()).unwrap_or(10);
+                let after = args.get("after").and_then(|v| v.as_i64()).unwrap_or(10);
+                serde_json::to_value(self.db.chat_context(message_id, before, after).map_err(|e| e.to_string())?)
+                    .map_err(|e| e.to_string())
+            }
+            "workspace_list" => {
+                let pet_id = self.existing_pet(args)?;
+                let path = opt_str(args, "path").unwrap_or("");
+                let entries = self.workspace.list_dir(pet_id, path).map_err(|e| e.to_string())?;
+                Ok(json!(entries))
+            }
+            "workspace_read" => {
+                let pet_id = self.existing_pet(args)?;
+                let path = req_str(args, "path")?;
+                self.workspace.read(pet_id, path).map(Value::String).map_err(|e| e.to_string())
+            }
+            "workspace_write" => {
+                let pet_id = self.existing_pet(args)?;
+                let path = req_str(args, "path")?;
+                let content = req_str(args, "content")?;
+                self.workspace.write(pet_id, path, content, ChangeSource::Tool).map(Value::String).map_err(|e| e.to_string())
+            }
+            "workspace_search" => {
+                let pet_id = self.existing_pet(args)?;
+                let options: SearchOptions = serde_json::from_value(args.clone()).map_err(|e| format!("Invalid arguments: {}", e))?;
+                let result = self.workspace.search(pet_id, &options).map_err(|e| e.to_string())?;
+                Ok(Value::String(result.to_text()))
+            }
+            "skills_list" => {
+                let pet_id = self.existing_pet(args)?;
+                serde_json::to_value(self.skills.list(pet_id)?).map_err(|e| e.to_string())
+            }
+            "skills_read" => {
+                let pet_id = self.existing_pet(args)?;
+                let skill_id = req_str(args, "skillId")?;
+                serde_json::to_value(self.skills.read(pet_id, skill_id)?).map_err(|e| e.to_string())
+            }
+            _ => Err(format!("Unknown tool: {}", name)),
+        }
+    }
+
+    /// `petId` argument, checked against the database so tools cannot create stray workspaces
+    fn existing_pet<'a>(&self, args: &'a Value) -> Result<&'a str, String> {
+        let pet_id = req_str(args, "petId")?;
+        self.db.get_pet_by_id(pet_id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Pet not found: {}", pet_id))?;
+        Ok(pet_id)
+    }
+
+    // ============ Resources ============
+
+    /// Workspace files of every pet
+    fn list_resources(&self) -> Result<Value, (i64, String)> {
+        let pets = self.db.get_all_pets().map_err(|e| (INVALID_REQUEST, e.to_string()))?;
+        let mut resources = Vec::new();
+        for pet in pets {
+            // Pets without a workspace directory yet have nothing to list
+            let Ok(entries) = self.workspace.list_dir(&pet.id, "") else { continue };
+            for path in entries.into_iter().filter(|p| !p.ends_with('/')) {
+                resources.push(json!({
+                    "uri": format!("{}pets/{}/workspace/{}", RESOURCE_SCHEME, pet.id, path),
+                    "name": format!("{}/{}", pet.name, path),
+                    "mimeType": mime_for_path(&path),
+                }));
+            }
+        }
+        Ok(json!({ "resources": resources }))
+    }
+
+    fn read_resource(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let uri = params.get("uri").and_then(|v| v.as_str())
+            .ok_or_else(|| (INVALID_PARAMS, "Missing uri".to_string()))?;
+        let (mime_type, text) = match parse_resource_uri(uri) {
+            Some(PetResource::WorkspaceFile { pet_id, path }) => {
+                let text = self.workspace.read(pet_id, path).map_err(|e| (INVALID_PARAMS, e.to_string()))?;
+                (mime_for_path(path), text)
+            }
+            Some(PetResource::Conversation { conversation_id }) => {
+                let conversation = self.db.get_conversation_with_history(conversation_id)
+                    .map_err(|e| (INVALID_PARAMS, e.to_string()))?
+                    .ok_or_else(|| (INVALID_PARAMS, format!("Conversation not found: {}", conversation_id)))?;
+                ("application/json", serde_json::to_string_pretty(&conversation).unwrap_or_default())
+            }
+            Some(PetResource::Skill { pet_id, skill_id }) => {
+                let skill = self.skills.read(pet_id, skill_id).map_err(|e| (INVALID_PARAMS, e))?;
+                ("text/markdown", skill.content)
+            }
+            None => return Err((INVALID_PARAMS, format!("Unknown resource: {}", uri))),
+        };
+        Ok(json!({ "contents": [{ "uri": uri, "mimeType": mime_type, "text": text }] }))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum PetResource<'a> {
+    WorkspaceFile { pet_id: &'a str, path: &'a str },
+    Conversation { conversation_id: &'a str },
+    Skill { pet_id: &'a str, skill_id: &'a str },
+}
+
+fn parse_resource_uri(uri: &str) -> Option<PetResource<'_>> {
+    let rest = uri.strip_prefix(RESOURCE_SCHEME)?;
+    if let Some(conversation_id) = rest.strip_prefix("conversations/") {
+        return (!conversation_id.is_empty()).then_some(PetResource::Conversation { conversation_id });
+    }
+    let (pet_id, rest) = rest.strip_prefix("pets/")?.split_once('/')?;
+    if let Some(path) = rest.strip_prefix("workspace/") {
+        return (!path.is_empty()).then_some(PetResource::WorkspaceFile { pet_id, path });
+    }
+    let skill_id = rest.strip_prefix("skills/")?;
+    (!skill_id.is_empty()).then_some(PetResource::Skill { pet_id, skill_id })
+}
+
+fn mime_for_path(path: &str) -> &'static str {
+    match path.rsplit('.').next().map(str::to_lowercase).as_deref() {
+        Some("md") => "text/markdown",
+        Some("json") => "application/json",
+        _ => "text/plain",
+    }
+}
+
+fn resource_templates() -> Value {
+    json!([
+        {
+            "uriTemplate": "petgpt://pets/{petId}/workspace/{+path}",
+            "name": "Workspace file",
+            "description": "A file in a pet's workspace (SOUL.md, USER.md, MEMORY.md, ...)",
+        },
+        {
+            "uriTemplate": "petgpt://conversations/{conversationId}",
+            "name": "Conversation",
+            "description": "A conversation with its full message history",
+            "mimeType": "application/json",
+        },
+        {
+            "uriTemplate": "petgpt://pets/{petId}/skills/{skillId}",
+            "name": "Skill",
+            "description": "SKILL.md of a skill available to a pet",
+            "mimeType": "text/markdown",
+        },
+    ])
+}
+
+fn tool(name: &str, description: &str, properties: Value, required: &[&str]) -> Value {
+    json!({
+        "name": name,
+        "description": description,
+        "inputSchema": { "type": "object", "properties": properties, "required": required },
+    })
+}
+
+fn tool_definitions() -> Vec<Value> {
+    let pet_id = json!({ "type": "string", "description": "Pet id from list_pets" });
+    vec![
+        tool("list_pets", "List the PetGPT assistants (pets) with their ids.", json!({}), &[]),
+        tool("list_conversations", "List conversations, optionally only those of one pet.",
+            json!({ "petId": pet_id }), &[]),
+        tool("get_conversation", "Get a conversation and its most recent messages.",
+            json!({
+                "conversationId": { "type": "string" },
+                "limit": { "type": "integer", "description": "Number of most recent messages (default 50)" },
+            }), &["conversationId"]),
+        tool("search_conversations", "Full-text search over conversation titles and messages.",
+            json!({ "query": { "type": "string" } }), &["query"]),
+        tool("add_message", "Append a message to a conversation. It shows up in PetGPT's chat window.",
+            json!({
+                "conversationId": { "type": "string" },
+                "content": { "type": "string" },
+                "role": { "type": "string", "enum": ["user", "assistant", "system"], "description": "Default: user" },
+            }), &["conversationId", "content"]),
+        tool("chat_history_search", "Search archived QQ group/private chat history.",
+            json!({
+                "keywords": { "type": "string" },
+                "sender": { "type": "string" },
+                "target": { "type": "string", "description": "Group or friend id" },
+                "startTs": { "type": "integer", "description": "Milliseconds since epoch" },
+                "endTs": { "type": "integer", "description": "Milliseconds since epoch" },
+                "sort": { "type": "string", "enum": ["relevance", "newest", "oldest"] },
+                "limit": { "type": "integer" },
+            }), &["keywords"]),
+        tool("chat_history_context", "Get the chat messages around an archived QQ message.",
+            json!({
+                "messageId": { "type": "string" },
+                "before": { "type": "integer", "description": "Default 10" },
+                "after": { "type": "integer", "description": "Default 10" },
+            }), &["messageId"]),
+        tool("workspace_list", "List files in a pet's workspace.",
+            json!({ "petId": pet_id, "path": { "type": "string", "description": "Directory (default: root)" } }), &["petId"]),
+        tool("workspace_read", "Read a file from a pet's workspace, e.g. MEMORY.md.",
+            json!({ "petId": pet_id, "path": { "type": "string" } }), &["petId", "path"]),
+        tool("workspace_write", "Create or overwrite a file in a pet's workspace.",
+            json!({ "petId": pet_id, "path": { "type": "string" }, "content": { "type": "string" } }), &["petId", "path", "content"]),
+        tool("workspace_search", "Search file contents in a pet's workspace (grep-style output with line numbers).",
+            json!({
+                "petId": pet_id,
+                "query": { "type": "string" },
+                "regex": { "type": "boolean", "description": "Treat query as a regular expression (default: literal substring)" },
+                "caseSensitive": { "type": "boolean" },
+                "path": { "type": "string", "description": "Directory to search (default: root)" },
+                "include": { "type": "array", "items": { "type": "string" }, "description": "Globs such as \"social/**/*.md\"" },
+                "exclude": { "type": "array", "items": { "type": "string" } },
+                "contextLines": { "type": "integer", "description": "Default 2" },
+                "maxResults": { "type": "integer", "description": "Default 100" },
+            }), &["petId", "query"]),
+        tool("skills_list", "List the skills available to a pet.", json!({ "petId": pet_id }), &["petId"]),
+        tool("skills_read", "Read the SKILL.md of a skill.",
+            json!({ "petId": pet_id, "skillId": { "type": "string" } }), &["petId", "skillId"]),
+    ]
+}
+
+fn req_str<'a>(args: &'a Value, key: &str) -> Result<&'a str, String> {
+    opt_str(args, key).ok_or_else(|| format!("Missing required argument: {}", key))
+}
+
+fn opt_str<'a>(args: &'a Value, key: &str) -> Option<&'a str> {
+    args.get(key).and_then(|v| v.as_str()).filter(|s| !s.is_empty())
+}
+
+fn success_response(id: Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+// ============ stdio transport ============
+
+/// Serve newline-delimited JSON-RPC on stdin/stdout until stdin closes
+pub async fn serve_stdio(server: Arc<PetGptMcpServer>) -> std::io::Result<()> {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Value>(&line) {
+            Ok(message) => server.handle_message(&message),
+            Err(e) => Some(error_response(Value::Null, PARSE_ERROR, &format!("Parse error: {}", e))),
+        };
+        if let Some(response) = response {
+            stdout.write_all(format!("{}\n", response).as_bytes()).await?;
+            stdout.flush().await?;
+        }
+    }
+    Ok(())
+}
+
+// ============ Streamable HTTP transport ============
+
+/// A running loopback HTTP endpoint; dropping it does not stop the server, call `stop`
+pub struct McpHostHandle {
+    pub addr: SocketAddr,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl McpHostHandle {
+    pub fn url(&self) -> String {
+        format!("http://{}/mcp", self.addr)
+    }
+
+    pub fn stop(self) {
+        self.task.abort();
+    }
+}
+
+/// Listen on 127.0.0.1:`port` (0 = any free port)
+pub async fn serve_http(server: Arc<PetGptMcpServer>, port: u16, token: String) -> Result<McpHostHandle, String> {
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .await
+        .map_err(|e| format!("Failed to bind 127.0.0.1:{}: {}", port, e))?;
+    let addr = listener.local_addr().map_err(|e| e.to_string())?;
+    let sessions = Arc::new(Mutex::new(HashSet::new()));
+    log::info!("[MCP-Host] Listening on http://{}/mcp", addr);
+
+    let task = tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let server = server.clone();
+            let token = token.clone();
+            let sessions = sessions.clone();
+            tokio::spawn(async move {
+                if let Err(e) = serve_connection(stream, &server, &token, &sessions).await {
+                    log::debug!("[MCP-Host] Connection error: {}", e);
+                }
+            });
+        }
+    });
+    Ok(McpHostHandle { addr, task })
+}
+
+struct HttpRequest {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl HttpRequest {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(k, _)| k.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+    }
+}
+
+struct HttpResponse {
+    status: u16,
+    headers: Vec<(&'static str, String)>,
+    body: Option<String>,
+}
+
+impl HttpResponse {
+    fn status(status: u16) -> Self {
+        Self { status, headers: Vec::new(), body: None }
+    }
+
+    fn json(value: &Value) -> Self {
+        Self { status: 200, headers: Vec::new(), body: Some(value.to_string()) }
+    }
+
+    fn with_header(mut self, name: &'static str, value: String) -> Self {
+        self.headers.push((name, value));
+        self
+    }
+}
+
+async fn read_http_request(stream: &mut TcpStream) -> std::io::Result<Option<HttpRequest>> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 8192];
+    let header_end = loop {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        if buf.len() > 64 * 1024 {
+            return Ok(None);
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or("").split_whitespace();
+    let method = request_line.next().unwrap_or("").to_string();
+    let path = request_line.next().unwrap_or("").to_string();
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|l| l.split_once(':'))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .collect();
+
+    let content_length = headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, v)| v.parse::<usize>().ok())
+        .unwrap_or(0);
+    if content_length > MAX_HTTP_BODY_BYTES {
+        return Ok(None);
+    }
+    while buf.len() < header_end + content_length {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+    let body = buf[header_end..].to_vec();
+    Ok(Some(HttpRequest { method, path, headers, body }))
+}
+
+async fn serve_connection(
+    mut stream: TcpStream,
+    server: &PetGptMcpServer,
+    token: &str,
+    sessions: &Mutex<HashSet<String>>,
+) -> std::io::Result<()> {
+    let response = match tokio::time::timeout(HTTP_READ_TIMEOUT, read_http_request(&mut stream)).await {
+        Ok(Ok(Some(request))) => route_http(&request, server, token, sessions),
+        Ok(Ok(None)) => HttpResponse::status(400),
+        Ok(Err(e)) => return Err(e),
+        Err(_) => HttpResponse::status(408),
+    };
+
+    let mut head = format!("HTTP/1.1 {} {}\r\nConnection: close\r\n", response.status, reason(response.status));
+    for (k, v) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", k, v));
+    }
+    match response.body {
+        Some(body) => {
+            head.push_str(&format!("Content-Type: application/json\r\nContent-Length: {}\r\n\r\n", body.len()));
+            stream.write_all(head.as_bytes()).await?;
+            stream.write_all(body.as_bytes()).await?;
+        }
+        None => {
+            head.push_str("Content-Length: 0\r\n\r\n");
+            stream.write_all(head.as_bytes()).await?;
+        }
+    }
+    stream.shutdown().await
+}
+
+fn route_http(
+    request: &HttpRequest,
+    server: &PetGptMcpServer,
+    token: &str,
+    sessions: &Mutex<HashSet<String>>,
+) -> HttpResponse {
+    if request.path.split('?').next() != Some("/mcp") {
+        return HttpResponse::status(404);
+    }
+    // DNS-rebinding protection: only local pages may talk to us from a browser
+    if let Some(origin) = request.header("Origin") {
+        if !is_local_origin(origin) {
+            return HttpResponse::status(403);
+        }
+    }
+    let authorized = request
+        .header("Authorization")
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .is_some_and(|t| constant_time_eq(t.trim().as_bytes(), token.as_bytes()));
+    if !authorized {
+        return HttpResponse::status(401).with_header("WWW-Authenticate", "Bearer".to_string());
+    }
+
+    let session_id = request.header("Mcp-Session-Id");
+    if let Some(sid) = session_id {
+        if !sessions.lock().unwrap().contains(sid) {
+            return HttpResponse::status(404);
+        }
+    }
+
+    match request.method.as_str() {
+        "POST" => {
+            let message: Value = match serde_json::from_slice(&request.body) {
+                Ok(message) => message,
+                Err(e) => {
+                    let error = error_response(Value::Null, PARSE_ERROR, &format!("Parse error: {}", e));
+                    return HttpResponse { status: 400, ..HttpResponse::json(&error) };
+                }
+            };
+            let is_initialize = message.get("method").and_then(|m| m.as_str()) == Some("initialize");
+            match server.handle_message(&message) {
+                Some(response) if is_initialize => {
+                    let sid = uuid::Uuid::new_v4().to_string();
+                    sessions.lock().unwrap().insert(sid.clone());
+                    HttpResponse::json(&response).with_header("Mcp-Session-Id", sid)
+                }
+                Some(response) => HttpResponse::json(&response),
+                // Only notifications/responses
+                None => HttpResponse::status(202),
+            }
+        }
+        "DELETE" => match session_id {
+            Some(sid) => {
+                sessions.lock().unwrap().remove(sid);
+                HttpResponse::status(200)
+            }
+            None => HttpResponse::status(400),
+        },
+        // No server-initiated messages, so no GET stream
+        _ => HttpResponse::status(405).with_header("Allow", "POST, DELETE".to_string()),
+    }
+}
+
+/// Compares without an early exit so response timing doesn't reveal how much of the token matched
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn is_local_origin(origin: &str) -> bool {
+    let host = origin
+        .split("://")
+        .nth(1)
+        .unwrap_or(origin)
+        .split('/')
+        .next()
+        .unwrap_or("");
+    let host = host.rsplit_once(':').map(|(h, _)| h).unwrap_or(host);
+    matches!(host, "localhost" | "127.0.0.1" | "[::1]" | "tauri.localhost") || origin.starts_with("tauri://")
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_server() -> (PetGptMcpServer, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!("petgpt-mcp-host-{}", uuid::Uuid::new_v4()));
+        let db = Arc::new(Database::new(std::path::PathBuf::from(":memory:")).unwrap());
+        let workspace = Arc::new(WorkspaceEngine::new(dir.join("workspace")));
+        let skills = Arc::new(SkillEngine::new(dir.join("workspace"), dir.join("skills")));
+        (PetGptMcpServer::new(db, workspace, skills), dir)
+    }
+
+    fn call(server: &PetGptMcpServer, name: &str, arguments: Value) -> Value {
+        let response = server
+            .handle_message(&json!({ "jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": { "name": name, "arguments": arguments } }))
+            .unwrap();
+        response["result"].clone()
+    }
+
+    #[test]
+    fn initialize_negotiates_version_and_lists_tools() {
+        let (server, _) = test_server();
+        let response = server
+            .handle_message(&json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "protocolVersion": "2024-11-05" } }))
+            .unwrap();
+        assert_eq!(response["result"]["protocolVersion"], "2024-11-05");
+
+        assert!(server.handle_message(&json!({ "jsonrpc": "2.0", "method": "notifications/initialized" })).is_none());
+
+        let tools = server.handle_message(&json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" })).unwrap();
+        let names: Vec<&str> = tools["result"]["tools"].as_array().unwrap().iter().map(|t| t["name"].as_str().unwrap()).collect();
+        assert!(names.contains(&"chat_history_search"));
+        assert!(names.contains(&"workspace_read"));
+
+        let unknown = server.handle_message(&json!({ "jsonrpc": "2.0", "id": 3, "method": "sampling/createMessage" })).unwrap();
+        assert_eq!(unknown["error"]["code"], METHOD_NOT_FOUND);
+    }
+
+    #[test]
+    fn add_message_and_workspace_round_trip() {
+        let (mut server, dir) = test_server();
+        let added = Arc::new(Mutex::new(Vec::new()));
+        let added_clone = added.clone();
+        server.set_message_added_hook(Arc::new(move |m: &Message| added_clone.lock().unwrap().push(m.id.clone())));
+
+        let pet = server.db.create_pet(serde_json::from_value(json!({ "name": "Momo" })).unwrap()).unwrap();
+        let conversation = server.db.create_conversation(serde_json::from_value(json!({ "petId": pet.id })).unwrap()).unwrap();
+
+        let result = call(&server, "add_message", json!({ "conversationId": conversation.id, "content": "hello from the IDE" }));
+        assert_eq!(result["isError"], false);
+        assert_eq!(added.lock().unwrap().len(), 1);
+        let history = call(&server, "get_conversation", json!({ "conversationId": conversation.id }));
+        assert!(history["content"][0]["text"].as_str().unwrap().contains("hello from the IDE"));
+
+        let result = call(&server, "workspace_write", json!({ "petId": pet.id, "path": "MEMORY.md", "content": "likes tea" }));
+        assert_eq!(result["isError"], false);
+        let read = server
+            .handle_message(&json!({ "jsonrpc": "2.0", "id": 5, "method": "resources/read", "params": { "uri": format!("petgpt://pets/{}/workspace/MEMORY.md", pet.id) } }))
+            .unwrap();
+        assert_eq!(read["result"]["contents"][0]["text"], "likes tea");
+
+        let found = call(&server, "workspace_search", json!({ "petId": pet.id, "query": "TEA" }));
+        assert_eq!(found["content"][0]["text"].as_str().unwrap().lines().next(), Some("MEMORY.md:1: likes tea"));
+
+        let escape = call(&server, "workspace_read", json!({ "petId": pet.id, "path": "../../etc/passwd" }));
+        assert_eq!(escape["isError"], true);
+        let missing_pet = call(&server, "workspace_read", json!({ "petId": "nope", "path": "MEMORY.md" }));
+        assert_eq!(missing_pet["isError"], true);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn parses_resource_uris() {
+        assert_eq!(
+            parse_resource_uri("petgpt://pets/p1/workspace/notes/a.md"),
+            Some(PetResource::WorkspaceFile { pet_id: "p1", path: "notes/a.md" })
+        );
+        assert_eq!(parse_resource_uri("petgpt://conversations/c1"), Some(PetResource::Conversation { conversation_id: "c1" }));
+        assert_eq!(parse_resource_uri("petgpt://pets/p1/skills/s1"), Some(PetResource::Skill { pet_id: "p1", skill_id: "s1" }));
+        assert_eq!(parse_resource_uri("petgpt://pets/p1/workspace/"), None);
+        assert_eq!(parse_resource_uri("file:///etc/passwd"), None);
+    }
+
+    #[test]
+    fn token_comparison_needs_an_exact_match() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secreT", b"secret"));
+        assert!(!constant_time_eq(b"secret2", b"secret"));
+        assert!(!constant_time_eq(b"", b"secret"));
+    }
+
+    #[tokio::test]
+    async fn http_endpoint_requires_token_and_issues_session() {
+        let (server, _) = test_server();
+        let handle = serve_http(Arc::new(server), 0, "secret".to_string()).await.unwrap();
+        let client = reqwest::Client::new();
+        let init = json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} });
+
+        let resp = client.post(handle.url()).json(&init).send().await.unwrap();
+        assert_eq!(resp.status(), 401);
+
+        let resp = client.post(handle.url()).bearer_auth("secret").header("Origin", "https://evil.example").json(&init).send().await.unwrap();
+        assert_eq!(resp.status(), 403);
+
+        let resp = client.post(handle.url()).bearer_auth("secret").json(&init).send().await.unwrap();
+        assert_eq!(resp.status(), 200);
+        let sid = resp.headers().get("mcp-session-id").unwrap().to_str().unwrap().to_string();
+
+        let resp = client.post(handle.url()).bearer_auth("secret").header("Mcp-Session-Id", &sid)
+            .json(&json!({ "jsonrpc": "2.0", "method": "notifications/initialized" })).send().await.unwrap();
+        assert_eq!(resp.status(), 202);
+
+        let resp = client.post(handle.url()).bearer_auth("secret").header("Mcp-Session-Id", &sid)
+            .json(&json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/list" })).send().await.unwrap();
+        let body: Value = resp.json().await.unwrap();
+        assert!(body["result"]["tools"].as_array().unwrap().len() > 5);
+
+        let resp = client.delete(handle.url()).bearer_auth("secret").header("Mcp-Session-Id", &sid).send().await.unwrap();
+        assert_eq!(resp.status(), 200);
+        let resp = client.post(handle.url()).bearer_auth("secret").header("Mcp-Session-Id", &sid)
+            .json(&json!({ "jsonrpc": "2.0", "id": 3, "method": "ping" })).send().await.unwrap();
+        assert_eq!(resp.status(), 404);
+
+        handle.stop();
+    }
+}
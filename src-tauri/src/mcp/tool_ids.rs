@@ -0,0 +1,181 @@
+// Namespaced tool IDs for MCP tools exposed to the model.
+//
+// A tool ID is `<server slug>__<tool name>`, restricted to `[A-Za-z0-9_-]` and 64
+// characters so it is accepted by every provider's function-name rules. For a server
+// named with plain ASCII the ID equals the older `ServerName__tool` form, so existing
+// callers keep working. Collisions and over-long names get a short hash suffix derived
+// from the server id and tool name, which keeps IDs stable across restarts.
+
+use std::collections::{HashMap, HashSet};
+use sha2::{Digest, Sha256};
+
+pub const TOOL_ID_SEPARATOR: &str = "__";
+
+/// Shortest function-name limit among supported providers (OpenAI, Gemini)
+pub const MAX_TOOL_ID_LEN: usize = 64;
+
+const HASH_LEN: usize = 6;
+
+/// Where a namespaced ID points to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolTarget {
+    pub server_id: String,
+    pub tool_name: String,
+}
+
+/// Tools of one running server, in the order the server advertised them
+pub struct ServerTools<'a> {
+    pub server_id: &'a str,
+    pub server_name: &'a str,
+    pub tool_names: Vec<&'a str>,
+}
+
+/// Replace every character outside `[A-Za-z0-9_-]` with `_`
+pub fn sanitize(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+        .collect();
+    if cleaned.is_empty() { "_".to_string() } else { cleaned }
+}
+
+fn short_hash(parts: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part.as_bytes());
+        hasher.update([0u8]);
+    }
+    hasher.finalize()
+        .iter()
+        .take(HASH_LEN / 2)
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+// Cut to fit the limit, leaving room for `_<hash>`
+fn with_hash(base: &str, hash: &str) -> String {
+    let keep = MAX_TOOL_ID_LEN - hash.len() - 1;
+    let base: String = base.chars().take(keep).collect();
+    format!("{}_{}", base, hash)
+}
+
+/// Build the ID -> target map for a set of running servers.
+///
+/// Servers whose names sanitize to the same slug all get the server id hash appended,
+/// so the result depends only on the servers, not on the order they were started in.
+pub fn build_tool_index(servers: &[ServerTools]) -> HashMap<String, ToolTarget> {
+    let mut slug_counts: HashMap<String, usize> = HashMap::new();
+    for server in servers {
+        *slug_counts.entry(sanitize(server.server_name)).or_default() += 1;
+    }
+
+    let mut sorted: Vec<&ServerTools> = servers.iter().collect();
+    sorted.sort_by(|a, b| a.server_id.cmp(b.server_id));
+
+    let mut index = HashMap::new();
+    let mut taken: HashSet<String> = HashSet::new();
+    for server in sorted {
+        let mut slug = sanitize(server.server_name);
+        if slug_counts[&slug] > 1 {
+            slug = format!("{}-{}", slug, short_hash(&[server.server_id]));
+        }
+        for tool_name in &server.tool_names {
+            let mut id = format!("{}{}{}", slug, TOOL_ID_SEPARATOR, sanitize(tool_name));
+            if id.len() > MAX_TOOL_ID_LEN || taken.contains(&id) {
+                // The hashed form can itself clash with an ID taken earlier; salt until it doesn't
+                let base = id;
+                id = with_hash(&base, &short_hash(&[server.server_id, tool_name]));
+                let mut salt = 0u32;
+                while taken.contains(&id) {
+                    salt += 1;
+                    id = with_hash(&base, &short_hash(&[server.server_id, tool_name, &salt.to_string()]));
+                }
+            }
+            taken.insert(id.clone());
+            index.insert(id, ToolTarget {
+                server_id: server.server_id.to_string(),
+                tool_name: tool_name.to_string(),
+            });
+        }
+    }
+    index
+}
+
+/// Split an older `ServerName__tool` name; the tool part may itself contain `__`
+pub fn split_legacy_name(name: &str) -> Option<(&str, &str)> {
+    name.split_once(TOOL_ID_SEPARATOR)
+        .filter(|(server, tool)| !server.is_empty() && !tool.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server<'a>(id: &'a str, name: &'a str, tools: &[&'a str]) -> ServerTools<'a> {
+        ServerTools { server_id: id, server_name: name, tool_names: tools.to_vec() }
+    }
+
+    fn id_for(index: &HashMap<String, ToolTarget>, server_id: &str, tool: &str) -> String {
+        index.iter()
+            .find(|(_, t)| t.server_id == server_id && t.tool_name == tool)
+            .map(|(id, _)| id.clone())
+            .unwrap()
+    }
+
+    #[test]
+    fn plain_names_keep_the_legacy_form() {
+        let index = build_tool_index(&[
+            server("1", "webshot", &["webshot"]),
+            server("2", "Brave Search", &["search", "web.fetch"]),
+        ]);
+        assert_eq!(id_for(&index, "1", "webshot"), "webshot__webshot");
+        assert_eq!(id_for(&index, "2", "search"), "Brave_Search__search");
+        assert_eq!(id_for(&index, "2", "web.fetch"), "Brave_Search__web_fetch");
+    }
+
+    #[test]
+    fn colliding_slugs_and_tools_get_stable_suffixes() {
+        let servers = [
+            server("a", "my server", &["search"]),
+            server("b", "my_server", &["search", "x.y", "x_y"]),
+        ];
+        let index = build_tool_index(&servers);
+        assert_eq!(index.len(), 4);
+        let a = id_for(&index, "a", "search");
+        let b = id_for(&index, "b", "search");
+        assert_ne!(a, b);
+        assert!(a.starts_with("my_server-") && a.ends_with("__search"));
+        assert_ne!(id_for(&index, "b", "x.y"), id_for(&index, "b", "x_y"));
+
+        let reversed = build_tool_index(&[
+            server("b", "my_server", &["search", "x.y", "x_y"]),
+            server("a", "my server", &["search"]),
+        ]);
+        assert_eq!(index, reversed);
+    }
+
+    #[test]
+    fn hashed_ids_never_reuse_a_taken_id() {
+        // The first tool's plain ID is exactly what the third would hash to
+        let hashed = format!("x_y_{}", short_hash(&["1", "x.y"]));
+        let tools = [hashed.as_str(), "x_y", "x.y", "x y"];
+        let index = build_tool_index(&[server("1", "s", &tools)]);
+        assert_eq!(index.len(), tools.len());
+        assert_eq!(id_for(&index, "1", &hashed), format!("s__{}", hashed));
+        assert_eq!(id_for(&index, "1", "x_y"), "s__x_y");
+        assert_ne!(id_for(&index, "1", "x.y"), format!("s__{}", hashed));
+        assert_ne!(id_for(&index, "1", "x.y"), id_for(&index, "1", "x y"));
+        assert_eq!(index, build_tool_index(&[server("1", "s", &tools)]));
+    }
+
+    #[test]
+    fn long_ids_are_truncated_within_the_limit() {
+        let long_tool = "t".repeat(100);
+        let index = build_tool_index(&[server("1", "Some Very Long Server Name", &[&long_tool])]);
+        let id = id_for(&index, "1", &long_tool);
+        assert_eq!(id.len(), MAX_TOOL_ID_LEN);
+        assert!(id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-'));
+        assert_eq!(split_legacy_name("srv__a__b"), Some(("srv", "a__b")));
+        assert_eq!(split_legacy_name("__tool"), None);
+    }
+}
@@ -2,6 +2,10 @@
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::PathBuf;
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use crate::workspace::{ChangeSource, WorkspaceEngine};
 
 // ============================================
 // Transport Types
@@ -162,6 +166,24 @@ pub struct McpTool {
     pub description: Option<String>,
     #[serde(default, rename = "inputSchema")]
     pub input_schema: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<ToolAnnotations>,
+}
+
+/// Behaviour hints a server attaches to a tool. Untrusted: only used to pick a default permission.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolAnnotations {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_only_hint: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destructive_hint: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idempotent_hint: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_world_hint: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
@@ -232,6 +254,25 @@ pub struct ResourceReadResult {
     pub contents: Vec<ResourceContent>,
 }
 
+/// Parameterized resource advertised via resources/templates/list (RFC 6570 URI template)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct McpResourceTemplate {
+    pub uri_template: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub mime_type: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceTemplatesListResult {
+    #[serde(default)]
+    pub resource_templates: Vec<McpResourceTemplate>,
+}
+
 // ============================================
 // Server Status Types (for frontend)
 // ============================================
@@ -247,11 +288,117 @@ pub struct ServerStatus {
     #[serde(default)]
     pub resources: Vec<McpResource>,
     #[serde(default)]
+    pub resource_templates: Vec<McpResourceTemplate>,
+    #[serde(default)]
+    pub subscriptions: Vec<String>,
+    #[serde(default)]
     pub server_info: Option<ServerInfo>,
     #[serde(default)]
     pub error: Option<String>,
+    /// Last stderr lines from the server process (stdio only), oldest first
+    #[serde(default)]
+    pub stderr_tail: Vec<String>,
+    /// Automatic restarts performed by the supervisor
+    #[serde(default)]
+    pub restart_count: u32,
+}
+
+// ============================================
+// Supervision
+// ============================================
+
+/// What the supervisor does when a server dies or fails its health checks
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    #[default]
+    Never,
+    /// Restart with exponential backoff unless the process exited cleanly; gives up after a few attempts
+    OnFailure,
+    /// Always restart, with backoff
+    Always,
+}
+
+impl RestartPolicy {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "never" => Some(RestartPolicy::Never),
+            "on-failure" | "on_failure" | "onfailure" => Some(RestartPolicy::OnFailure),
+            "always" => Some(RestartPolicy::Always),
+            _ => None,
+        }
+    }
+
+    /// Policy from the stored setting; auto-start servers default to on-failure
+    pub fn effective(configured: Option<&str>, auto_start: bool) -> Self {
+        configured.and_then(Self::parse).unwrap_or(if auto_start {
+            RestartPolicy::OnFailure
+        } else {
+            RestartPolicy::Never
+        })
+    }
+}
+
+/// Lifecycle state reported in status-change events
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ServerLifecycle {
+    Running,
+    /// Connected but a health check failed
+    Unhealthy,
+    Crashed,
+    Restarting,
+    /// Crashed and the restart policy gave up (or is `never`)
+    Failed,
 }
 
+// ============================================
+// Runtime Events (forwarded to the frontend)
+// ============================================
+
+/// Events raised by MCP clients outside of a request/response cycle
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum McpEvent {
+    /// notifications/resources/updated for a subscribed URI
+    #[serde(rename_all = "camelCase")]
+    ResourceUpdated { server_id: String, uri: String },
+    /// Supervisor observed a lifecycle change
+    #[serde(rename_all = "camelCase")]
+    StatusChanged {
+        server_id: String,
+        state: ServerLifecycle,
+        error: Option<String>,
+        restart_count: u32,
+    },
+    /// A tool call is waiting for the user to approve or deny it
+    #[serde(rename_all = "camelCase")]
+    ToolApprovalRequested {
+        request_id: String,
+        server_id: String,
+        server_name: String,
+        tool_name: String,
+        arguments: Option<serde_json::Value>,
+        pet_id: Option<String>,
+        destructive: bool,
+        timeout_secs: u64,
+    },
+}
+
+impl McpEvent {
+    /// Tauri event name used when forwarding this event
+    pub fn event_name(&self) -> &'static str {
+        match self {
+            McpEvent::ResourceUpdated { .. } => "mcp-resource-updated",
+            McpEvent::StatusChanged { .. } => "mcp-server-status",
+            McpEvent::ToolApprovalRequested { .. } => "mcp-tool-approval-requested",
+        }
+    }
+}
+
+/// Callback installed by the app to receive McpEvents (clients never see the AppHandle)
+pub type McpEventSink = std::sync::Arc<dyn Fn(McpEvent) + Send + Sync>;
+
 // ============================================
 // Frontend API Types
 // ============================================
@@ -336,6 +483,9 @@ pub struct JsonRpcIncomingRequest {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct McpToolInfo {
+    /// Namespaced name to expose to the model (see `tool_ids`)
+    #[serde(default)]
+    pub tool_id: String,
     pub server_id: String,
     pub server_name: String,
     pub tool: McpTool,
@@ -412,3 +562,302 @@ pub fn format_tool_result(result: &ToolCallResult) -> String {
 
     format!("{}, {} item{} ({}){}", status, total, if total != 1 { "s" } else { "" }, breakdown, preview)
 }
+
+// ============================================
+// Result Size Management
+// ============================================
+
+/// Size limits applied to a tool result before it goes back to the model
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ResultLimits {
+    /// Text (across all text items) beyond this many characters is cut
+    pub max_text_chars: usize,
+    /// Images with a longer edge are downscaled to it
+    pub max_image_dimension: u32,
+    /// Images still larger than this after downscaling are spilled to a file
+    pub max_image_bytes: usize,
+    /// Keep oversized content in a file the model can reference instead of dropping it
+    pub spill: bool,
+}
+
+impl Default for ResultLimits {
+    fn default() -> Self {
+        Self {
+            max_text_chars: 20_000,
+            max_image_dimension: 1568,
+            max_image_bytes: 1024 * 1024,
+            spill: true,
+        }
+    }
+}
+
+/// Where oversized content is written
+pub enum SpillTarget<'a> {
+    /// The pet's `tool-results/`, written through the engine so quota and history apply
+    Workspace { engine: &'a WorkspaceEngine, pet_id: &'a str },
+    /// A plain directory (the uploads dir when there is no pet)
+    Dir {
+        dir: PathBuf,
+        /// How the model should refer to files in `dir` (absolute prefix)
+        display_prefix: String,
+    },
+}
+
+impl SpillTarget<'_> {
+    fn write(&self, tool_name: &str, ext: &str, data: &[u8]) -> Result<String, String> {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_err(|e| format!("Time error: {}", e))?
+            .as_millis();
+        let tool: String = tool_name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        let file_name = format!("{}_{}.{}", timestamp, tool, ext);
+        match self {
+            SpillTarget::Workspace { engine, pet_id } => {
+                let path = format!("tool-results/{}", file_name);
+                engine.write_binary(pet_id, &path, &BASE64.encode(data), ChangeSource::Tool)
+                    .map_err(|e| format!("Failed to write tool result: {}", e))?;
+                Ok(path)
+            }
+            SpillTarget::Dir { dir, display_prefix } => {
+                std::fs::create_dir_all(dir)
+                    .map_err(|e| format!("Failed to create spill dir: {}", e))?;
+                std::fs::write(dir.join(&file_name), data)
+                    .map_err(|e| format!("Failed to write tool result: {}", e))?;
+                Ok(format!("{}{}", display_prefix, file_name))
+            }
+        }
+    }
+}
+
+/// Shrink a tool result so it fits the limits.
+///
+/// Text past `max_text_chars` is cut and a header saying how much was dropped (and
+/// where the full text was saved) is put in front. Images are downscaled; any that
+/// are still too large are replaced by a text reference to the spilled file.
+pub fn limit_tool_result(
+    content: Vec<ToolContent>,
+    tool_name: &str,
+    limits: &ResultLimits,
+    spill: Option<&SpillTarget>,
+) -> Vec<ToolContent> {
+    let spill = spill.filter(|_| limits.spill);
+    let content: Vec<ToolContent> = content
+        .into_iter()
+        .map(|item| match item {
+            ToolContent::Image { data, mime_type } => limit_image(data, mime_type, tool_name, limits, spill),
+            other => other,
+        })
+        .collect();
+    limit_text(content, tool_name, limits, spill)
+}
+
+fn text_of(item: &ToolContent) -> Option<&str> {
+    match item {
+        ToolContent::Text { text } => Some(text),
+        ToolContent::Resource { resource } => resource.text.as_deref(),
+        ToolContent::Image { .. } => None,
+    }
+}
+
+fn limit_text(
+    mut content: Vec<ToolContent>,
+    tool_name: &str,
+    limits: &ResultLimits,
+    spill: Option<&SpillTarget>,
+) -> Vec<ToolContent> {
+    let texts: Vec<&str> = content.iter().filter_map(text_of).collect();
+    let total: usize = texts.iter().map(|t| t.chars().count()).sum();
+    if total <= limits.max_text_chars {
+        return content;
+    }
+
+    let saved = spill.map(|target| target.write(tool_name, "txt", texts.join("\n\n").as_bytes()));
+    let text_items = texts.len();
+
+    let mut budget = limits.max_text_chars;
+    content.retain_mut(|item| {
+        let text = match item {
+            ToolContent::Text { text } => text,
+            ToolContent::Resource { resource } => match resource.text.as_mut() {
+                Some(text) => text,
+                None => return true,
+            },
+            ToolContent::Image { .. } => return true,
+        };
+        if budget == 0 {
+            return false;
+        }
+        if let Some((cut, _)) = text.char_indices().nth(budget) {
+            text.truncate(cut);
+            budget = 0;
+        } else {
+            budget -= text.chars().count();
+        }
+        true
+    });
+
+    let mut header = format!(
+        "[Tool result truncated: {} text item{}, {} chars in total; showing the first {}.",
+        text_items,
+        if text_items != 1 { "s" } else { "" },
+        total,
+        limits.max_text_chars,
+    );
+    match saved {
+        Some(Ok(path)) => header.push_str(&format!(" Full output saved to {}]", path)),
+        Some(Err(e)) => {
+            log::warn!("[MCP] Failed to spill result of {}: {}", tool_name, e);
+            header.push(']');
+        }
+        None => header.push(']'),
+    }
+    content.insert(0, ToolContent::Text { text: header });
+    content
+}
+
+fn limit_image(
+    data: String,
+    mime_type: String,
+    tool_name: &str,
+    limits: &ResultLimits,
+    spill: Option<&SpillTarget>,
+) -> ToolContent {
+    let Ok(bytes) = BASE64.decode(data.trim()) else {
+        return ToolContent::Image { data, mime_type };
+    };
+    let (bytes, mime_type, dimensions) = match downscale_image(&bytes, &mime_type, limits.max_image_dimension) {
+        Some((scaled, mime, dims)) => (scaled, mime, Some(dims)),
+        None => (bytes, mime_type, None),
+    };
+    if bytes.len() <= limits.max_image_bytes {
+        return ToolContent::Image { data: BASE64.encode(&bytes), mime_type };
+    }
+
+    let size = match dimensions {
+        Some((w, h)) => format!("{}x{}, ", w, h),
+        None => String::new(),
+    };
+    let ext = super::resources::extension_for_mime(&mime_type).unwrap_or("bin");
+    let text = match spill.map(|target| target.write(tool_name, ext, &bytes)) {
+        Some(Ok(path)) => format!("[Image ({}{}, {} bytes) too large to show; saved to {}]", size, mime_type, bytes.len(), path),
+        Some(Err(e)) => {
+            log::warn!("[MCP] Failed to spill image from {}: {}", tool_name, e);
+            format!("[Image ({}{}, {} bytes) omitted: too large]", size, mime_type, bytes.len())
+        }
+        None => format!("[Image ({}{}, {} bytes) omitted: too large]", size, mime_type, bytes.len()),
+    };
+    ToolContent::Text { text }
+}
+
+// Returns the re-encoded image only when it had to be scaled down
+fn downscale_image(bytes: &[u8], mime_type: &str, max_dimension: u32) -> Option<(Vec<u8>, String, (u32, u32))> {
+    let img = image::load_from_memory(bytes).ok()?;
+    if img.width() <= max_dimension && img.height() <= max_dimension {
+        return None;
+    }
+    let scaled = img.resize(max_dimension, max_dimension, image::imageops::FilterType::Triangle);
+
+    // JPEG stays JPEG (smaller for photos); everything else becomes PNG
+    let (format, mime) = if mime_type == "image/jpeg" {
+        (image::ImageFormat::Jpeg, "image/jpeg")
+    } else {
+        (image::ImageFormat::Png, "image/png")
+    };
+    let scaled = if format == image::ImageFormat::Jpeg {
+        image::DynamicImage::ImageRgb8(scaled.to_rgb8())
+    } else {
+        scaled
+    };
+    let mut out = Cursor::new(Vec::new());
+    scaled.write_to(&mut out, format).ok()?;
+    Some((out.into_inner(), mime.to_string(), (scaled.width(), scaled.height())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workspace::StorageQuota;
+
+    fn text(t: &str) -> ToolContent {
+        ToolContent::Text { text: t.to_string() }
+    }
+
+    fn texts(content: &[ToolContent]) -> Vec<String> {
+        content.iter().filter_map(text_of).map(|t| t.to_string()).collect()
+    }
+
+    fn png(width: u32, height: u32) -> String {
+        let img = image::RgbaImage::from_fn(width, height, |x, y| image::Rgba([(x % 256) as u8, (y % 256) as u8, 128, 255]));
+        let mut out = Cursor::new(Vec::new());
+        img.write_to(&mut out, image::ImageFormat::Png).unwrap();
+        BASE64.encode(out.into_inner())
+    }
+
+    #[test]
+    fn small_results_pass_through() {
+        let limits = ResultLimits::default();
+        let result = limit_tool_result(vec![text("hello")], "echo", &limits, None);
+        assert_eq!(texts(&result), ["hello"]);
+    }
+
+    #[test]
+    fn oversized_text_is_truncated_and_spilled() {
+        let root = std::env::temp_dir().join(format!("petgpt-spill-{}", uuid::Uuid::new_v4()));
+        let engine = WorkspaceEngine::new(root.clone());
+        let target = SpillTarget::Workspace { engine: &engine, pet_id: "p" };
+        let limits = ResultLimits { max_text_chars: 10, ..Default::default() };
+
+        let result = limit_tool_result(
+            vec![text("αβγδεζηθικ"), text("second"), text("third")],
+            "web.fetch",
+            &limits,
+            Some(&target),
+        );
+        let out = texts(&result);
+        assert_eq!(out.len(), 2);
+        assert!(out[0].starts_with("[Tool result truncated: 3 text items, 21 chars in total; showing the first 10."));
+        assert!(out[0].contains("saved to tool-results/") && out[0].ends_with("_web_fetch.txt]"));
+        assert_eq!(out[1], "αβγδεζηθικ");
+
+        let file = std::fs::read_dir(engine.get_full_path("p", "tool-results").unwrap()).unwrap().next().unwrap().unwrap().path();
+        assert_eq!(std::fs::read_to_string(file).unwrap(), "αβγδεζηθικ\n\nsecond\n\nthird");
+
+        // Spills count against the workspace quota like any other write
+        engine.set_quota(Some("p"), Some(StorageQuota { max_workspace_bytes: 0, max_file_bytes: 4 })).unwrap();
+        let over_quota = limit_tool_result(vec![text("0123456789abc")], "t", &limits, Some(&target));
+        assert!(texts(&over_quota)[0].ends_with("showing the first 10.]"));
+        std::fs::remove_dir_all(root).unwrap();
+
+        let no_spill = limit_tool_result(vec![text("0123456789abc")], "t", &limits, None);
+        assert_eq!(texts(&no_spill)[1], "0123456789");
+        assert!(texts(&no_spill)[0].ends_with("showing the first 10.]"));
+    }
+
+    #[test]
+    fn large_images_are_downscaled_or_dropped() {
+        let limits = ResultLimits { max_image_dimension: 32, ..Default::default() };
+        let result = limit_tool_result(
+            vec![ToolContent::Image { data: png(128, 64), mime_type: "image/png".into() }],
+            "shot",
+            &limits,
+            None,
+        );
+        let ToolContent::Image { data, mime_type } = &result[0] else { panic!("expected image") };
+        assert_eq!(mime_type, "image/png");
+        let img = image::load_from_memory(&BASE64.decode(data).unwrap()).unwrap();
+        assert_eq!((img.width(), img.height()), (32, 16));
+
+        let tiny = ResultLimits { max_image_dimension: 32, max_image_bytes: 10, ..Default::default() };
+        let result = limit_tool_result(
+            vec![ToolContent::Image { data: png(128, 64), mime_type: "image/png".into() }],
+            "shot",
+            &tiny,
+            None,
+        );
+        assert!(texts(&result)[0].starts_with("[Image (32x16, image/png,"));
+    }
+}
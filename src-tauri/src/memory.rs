@@ -0,0 +1,778 @@
+//! 记忆整理
+//!
+//! 定期读取每个宠物最近的会话消息（messages）和主人在 QQ 里发的消息（chat_history），
+//! 用设置里配置的模型提炼出候选事实，与 USER.md / MEMORY.md 里已有的条目和待审核的候选去重后
+//! 放进 memory_candidates 审核队列。用户批准后通过 `WorkspaceEngine::edit` 写进对应文件，
+//! 每条都带上来源（会话或聊天对象 + 消息时间）。
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+use crate::database::Database;
+use crate::database::memory_candidates::{MemoryCandidate, NewMemoryCandidate};
+use crate::llm::{ApiFormat, ChatMessage, LlmClient, LlmRequest, MessageContent, Role};
+use crate::summarizer::{message_text, truncate_chars};
+use crate::workspace::engine::WorkspaceError;
+use crate::workspace::{ChangeSource, WorkspaceEngine};
+
+/// settings 表中保存配置的 key（JSON）
+pub const CONFIG_KEY: &str = "memoryConsolidator";
+
+/// 有新候选时发出的事件，payload 为 `{ petId, count }`
+pub const CANDIDATES_EVENT: &str = "memory-candidates-updated";
+
+/// 每个宠物的处理进度保存在 settings 的 `memory_cursor_{petId}`
+const CURSOR_KEY_PREFIX: &str = "memory_cursor_";
+
+/// 后台循环检查配置的间隔
+const TICK: Duration = Duration::from_secs(60);
+
+/// 单条消息放进提示词时的最大字符数
+const MAX_PROMPT_CHARS_PER_MESSAGE: usize = 1000;
+/// USER.md / MEMORY.md 现有内容放进提示词时的最大字符数
+const MAX_EXISTING_CHARS: usize = 6000;
+
+const USER_FILE: &str = "USER.md";
+const MEMORY_FILE: &str = "MEMORY.md";
+
+const EXTRACT_PROMPT: &str = "You maintain the long-term memory of an assistant. USER.md describes who the user is \
+(name, preferences, habits, projects); MEMORY.md records what happened between them (events, decisions, promises, notes). \
+Read the numbered new messages and extract facts worth remembering that are not already in the files. \
+Skip small talk, one-off details and anything sensitive (passwords, keys, financial data). \
+Reply with a JSON array only, for example \
+[{\"file\": \"USER.md\", \"section\": \"了解\", \"fact\": \"...\", \"replaces\": null, \"source\": 3}]. \
+`file` is USER.md or MEMORY.md; `section` is the `## ` heading the fact belongs under; \
+`replaces` is the existing line the fact updates, copied exactly, or null; `source` is the number of the message the fact comes from. \
+Write each fact as one short line in the messages' language. Reply [] when there is nothing new.";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MemoryConfig {
+    pub enabled: bool,
+    pub api_format: ApiFormat,
+    pub api_key: String,
+    pub model: String,
+    pub base_url: Option<String>,
+    /// 两次自动整理之间的间隔
+    pub interval_minutes: u64,
+    /// 每个宠物每次最多读取的消息数（会话消息和 QQ 消息各算一份）
+    pub max_messages_per_run: usize,
+    /// 同时读取主人（社交配置里的 ownerQQ）在 QQ 里发的消息
+    pub include_social: bool,
+    /// 跳过审核，提炼出的事实直接写入文件
+    pub auto_apply: bool,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            api_format: ApiFormat::default(),
+            api_key: String::new(),
+            model: String::new(),
+            base_url: None,
+            interval_minutes: 60,
+            max_messages_per_run: 60,
+            include_social: true,
+            auto_apply: false,
+        }
+    }
+}
+
+impl MemoryConfig {
+    pub fn load(db: &Database) -> Self {
+        db.get_setting(CONFIG_KEY)
+            .ok()
+            .flatten()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    fn usable(&self) -> bool {
+        self.enabled && !self.api_key.is_empty() && !self.model.is_empty()
+    }
+}
+
+/// 已经处理到的位置
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct Cursor {
+    /// 最后处理的会话消息的 created_at
+    messages: Option<String>,
+    /// 以及它的 id（同一时刻的多条消息按 id 排序）
+    message_id: String,
+    /// 最后处理的 QQ 消息的 timestamp（毫秒）
+    chat: i64,
+    /// 以及它的 message_id
+    chat_message_id: String,
+}
+
+impl Cursor {
+    fn load(db: &Database, pet_id: &str) -> Self {
+        db.get_setting(&format!("{}{}", CURSOR_KEY_PREFIX, pet_id))
+            .ok()
+            .flatten()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, db: &Database, pet_id: &str) -> Result<(), String> {
+        let json = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        db.set_setting(&format!("{}{}", CURSOR_KEY_PREFIX, pet_id), &json).map_err(|e| e.to_string())
+    }
+}
+
+/// 提示词里的一条编号消息，以及它的出处
+struct SourceMessage {
+    kind: &'static str,
+    source_id: String,
+    message_id: String,
+    time: String,
+    speaker: String,
+    text: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExtractedFact {
+    file: String,
+    #[serde(default)]
+    section: Option<String>,
+    fact: String,
+    #[serde(default)]
+    replaces: Option<String>,
+    #[serde(default)]
+    source: Option<usize>,
+}
+
+/// 社交配置里的主人 QQ 号
+fn owner_qq(db: &Database, pet_id: &str) -> Option<String> {
+    let json = db.get_setting(&format!("social_config_{}", pet_id)).ok().flatten()?;
+    let config: serde_json::Value = serde_json::from_str(&json).ok()?;
+    let owner = match &config["ownerQQ"] {
+        serde_json::Value::String(s) => s.trim().to_string(),
+        serde_json::Value::Number(n) => n.to_string(),
+        _ => return None,
+    };
+    (!owner.is_empty()).then_some(owner)
+}
+
+/// 模型回复中的 JSON 数组；允许包在代码块里，文件名不对的条目丢弃。
+/// 回复里没有合法的 JSON 数组时返回 None
+fn parse_facts(raw: &str) -> Option<Vec<ExtractedFact>> {
+    let (start, end) = (raw.find('[')?, raw.rfind(']')?);
+    if end < start {
+        return None;
+    }
+    let items = serde_json::from_str::<Vec<serde_json::Value>>(&raw[start..=end]).ok()?;
+    let facts = items.into_iter()
+        .filter_map(|item| serde_json::from_value::<ExtractedFact>(item).ok())
+        .filter_map(|mut fact| {
+            fact.file = match fact.file.trim().to_ascii_uppercase().as_str() {
+                "USER.MD" | "USER" => USER_FILE.to_string(),
+                "MEMORY.MD" | "MEMORY" => MEMORY_FILE.to_string(),
+                _ => return None,
+            };
+            fact.fact = fact.fact.lines().map(str::trim).find(|l| !l.is_empty())?.trim_start_matches("- ").to_string();
+            fact.section = fact.section.map(|s| s.trim_start_matches('#').trim().to_string()).filter(|s| !s.is_empty());
+            fact.replaces = fact.replaces.map(|r| r.trim().to_string()).filter(|r| !r.is_empty());
+            Some(fact)
+        })
+        .collect();
+    Some(facts)
+}
+
+/// 去掉来源注释、列表符号、标点和大小写后的文本，用于去重
+fn normalize(text: &str) -> String {
+    let text = match text.find("<!--") {
+        Some(i) => &text[..i],
+        None => text,
+    };
+    text.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+/// 已有条目里包含同样内容时视为重复
+fn is_duplicate(fact: &str, known: &[String]) -> bool {
+    let fact = normalize(fact);
+    fact.is_empty() || known.iter().map(|k| normalize(k)).any(|k| !k.is_empty() && k.contains(&fact))
+}
+
+fn entry_line(candidate: &MemoryCandidate) -> String {
+    format!(
+        "- {} <!-- source: {} {} @ {} -->",
+        candidate.fact, candidate.source_kind, candidate.source_id, candidate.source_time
+    )
+}
+
+fn default_section(file: &str) -> &'static str {
+    if file == USER_FILE { "了解" } else { "笔记" }
+}
+
+fn file_title(file: &str) -> &'static str {
+    if file == USER_FILE { "# 🧑 关于我的主人" } else { "# 🧠 记忆" }
+}
+
+fn heading_text(line: &str) -> Option<&str> {
+    line.trim().strip_prefix("## ").map(str::trim)
+}
+
+/// 把候选写进 `content` 需要的 (oldText, newText)；文件为空时返回 None，由调用方直接写入
+/// - 有 `replaces` 且能找到唯一一行包含它：替换那一行
+/// - 否则追加到对应 `## ` 小节末尾（找不到就用文件的默认小节）
+/// - 都没有：在文件末尾新建小节
+fn plan_edit(content: &str, candidate: &MemoryCandidate) -> Option<(String, String)> {
+    if content.trim().is_empty() {
+        return None;
+    }
+    let entry = entry_line(candidate);
+    let lines: Vec<&str> = content.lines().collect();
+
+    if let Some(replaces) = candidate.replaces.as_deref() {
+        let matches: Vec<&&str> = lines.iter().filter(|l| l.contains(replaces)).collect();
+        if let [line] = matches.as_slice() {
+            return Some((line.to_string(), entry));
+        }
+    }
+
+    let wanted = candidate.section.as_deref().unwrap_or_else(|| default_section(&candidate.file));
+    let heading = lines.iter().position(|l| heading_text(l) == Some(wanted))
+        .or_else(|| lines.iter().position(|l| heading_text(l) == Some(default_section(&candidate.file))));
+    match heading {
+        Some(start) => {
+            let end = lines[start + 1..].iter()
+                .position(|l| l.starts_with('#') || l.trim() == "---")
+                .map_or(lines.len(), |i| start + 1 + i);
+            let last = (start..end).rev().find(|&i| !lines[i].trim().is_empty()).unwrap_or(start);
+            let block = lines[start..=last].join("\n");
+            let separator = if lines[last].trim_start().starts_with("- ") { "\n" } else { "\n\n" };
+            Some((block.clone(), format!("{}{}{}", block, separator, entry)))
+        }
+        None => {
+            let old = content.trim_end().to_string();
+            Some((old.clone(), format!("{}\n\n## {}\n\n{}", old, wanted, entry)))
+        }
+    }
+}
+
+/// 后台记忆整理服务
+pub struct MemoryConsolidator {
+    db: Arc<Database>,
+    llm: Arc<LlmClient>,
+    workspace: Arc<WorkspaceEngine>,
+    /// 正在整理的宠物，避免定时任务和手动触发同时跑
+    running: Mutex<HashSet<String>>,
+}
+
+impl MemoryConsolidator {
+    pub fn new(db: Arc<Database>, llm: Arc<LlmClient>, workspace: Arc<WorkspaceEngine>) -> Self {
+        Self { db, llm, workspace, running: Mutex::new(HashSet::new()) }
+    }
+
+    /// 启动后台循环：配置可用时每隔 `interval_minutes` 整理一次所有宠物
+    pub fn start(self: &Arc<Self>, app: &AppHandle) {
+        let this = self.clone();
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            let mut last_run: Option<Instant> = None;
+            loop {
+                tokio::time::sleep(TICK).await;
+                let config = MemoryConfig::load(&this.db);
+                let interval = Duration::from_secs(config.interval_minutes.max(1) * 60);
+                if !config.usable() || last_run.is_some_and(|t| t.elapsed() < interval) {
+                    continue;
+                }
+                last_run = Some(Instant::now());
+                let pets = match this.db.get_all_pets() {
+                    Ok(pets) => pets,
+                    Err(e) => {
+                        log::warn!("[Memory] Failed to list pets: {}", e);
+                        continue;
+                    }
+                };
+                for pet in pets {
+                    match this.consolidate(&pet.id, &config).await {
+                        Ok(added) if !added.is_empty() => {
+                            let _ = app.emit(CANDIDATES_EVENT, serde_json::json!({
+                                "petId": pet.id,
+                                "count": added.len(),
+                            }));
+                        }
+                        Ok(_) => {}
+                        Err(e) => log::warn!("[Memory] {}: {}", pet.id, e),
+                    }
+                }
+            }
+        });
+    }
+
+    /// 整理一个宠物的新消息，返回新加入队列（或自动写入）的候选
+    pub async fn consolidate(&self, pet_id: &str, config: &MemoryConfig) -> Result<Vec<MemoryCandidate>, String> {
+        if !self.running.lock().unwrap().insert(pet_id.to_string()) {
+            return Ok(Vec::new());
+        }
+        let result = self.process(pet_id, config).await;
+        self.running.lock().unwrap().remove(pet_id);
+        result
+    }
+
+    async fn process(&self, pet_id: &str, config: &MemoryConfig) -> Result<Vec<MemoryCandidate>, String> {
+        let mut cursor = Cursor::load(&self.db, pet_id);
+        let limit = config.max_messages_per_run.max(1);
+        let after = cursor.messages.as_deref().map(|since| (since, cursor.message_id.as_str()));
+        let messages = self.db.get_pet_messages_since(pet_id, after, limit)
+            .map_err(|e| e.to_string())?;
+        let chat = match owner_qq(&self.db, pet_id).filter(|_| config.include_social) {
+            Some(owner) => self.db.chat_messages_from_sender(&owner, cursor.chat, &cursor.chat_message_id, limit as i64)
+                .map_err(|e| e.to_string())?,
+            None => Vec::new(),
+        };
+
+        // 关闭了记忆的会话（及全局默认关闭时的社交消息）不参与整理；游标照常前移，之后重新打开也不会回头读
+        let mut memory_on: HashMap<&str, bool> = HashMap::new();
+        for m in &messages {
+            if !memory_on.contains_key(m.conversation_id.as_str()) {
+                let on = self.db.is_memory_enabled(Some(&m.conversation_id)).map_err(|e| e.to_string())?;
+                memory_on.insert(&m.conversation_id, on);
+            }
+        }
+        let social_on = self.db.is_memory_enabled(None).map_err(|e| e.to_string())?;
+
+        let mut sources: Vec<SourceMessage> = messages.iter().filter(|m| memory_on[m.conversation_id.as_str()]).map(|m| SourceMessage {
+            kind: "conversation",
+            source_id: m.conversation_id.clone(),
+            message_id: m.id.clone(),
+            time: m.created_at.clone(),
+            speaker: m.role.clone(),
+            text: message_text(m),
+        }).collect();
+        sources.extend(chat.iter().filter(|_| social_on).map(|m| SourceMessage {
+            kind: "chat",
+            source_id: m.target_id.clone(),
+            message_id: m.message_id.clone(),
+            time: chrono::DateTime::from_timestamp_millis(m.timestamp).map(|t| t.to_rfc3339()).unwrap_or_default(),
+            speaker: "user (QQ)".to_string(),
+            text: m.content.clone(),
+        }));
+        sources.retain(|s| !s.text.trim().is_empty());
+
+        let mut added = Vec::new();
+        if !sources.is_empty() {
+            let user = self.workspace.read(pet_id, USER_FILE).unwrap_or_default();
+            let memory = self.workspace.read(pet_id, MEMORY_FILE).unwrap_or_default();
+            let numbered = sources.iter().enumerate()
+                .map(|(i, s)| format!("[{}] ({}) {}: {}", i, s.time, s.speaker, truncate_chars(&s.text, MAX_PROMPT_CHARS_PER_MESSAGE)))
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            let prompt = format!(
+                "USER.md:\n{}\n\nMEMORY.md:\n{}\n\nNew messages:\n{}",
+                truncate_chars(&user, MAX_EXISTING_CHARS),
+                truncate_chars(&memory, MAX_EXISTING_CHARS),
+                numbered
+            );
+            let raw = self.complete(config, prompt).await?;
+            // 回复不是 JSON 数组时不移动游标，下次重新处理这批消息
+            let facts = parse_facts(&raw)
+                .ok_or_else(|| format!("Memory model reply is not a JSON array: {}", truncate_chars(&raw, 200)))?;
+
+            let pending = self.db.get_memory_candidates(pet_id, Some("pending")).map_err(|e| e.to_string())?;
+            let mut known: Vec<String> = user.lines().chain(memory.lines()).map(str::to_string).collect();
+            known.extend(pending.into_iter().map(|c| c.fact));
+            for fact in facts {
+                if is_duplicate(&fact.fact, &known) {
+                    continue;
+                }
+                // 没有或越界的编号无法给出可信的出处，丢弃这条
+                let Some(source) = fact.source.and_then(|i| sources.get(i)) else {
+                    log::warn!("[Memory] Dropping fact without a valid source for {}: {}", pet_id, fact.fact);
+                    continue;
+                };
+                let candidate = self.db.insert_memory_candidate(&NewMemoryCandidate {
+                    pet_id: pet_id.to_string(),
+                    file: fact.file,
+                    section: fact.section,
+                    fact: fact.fact.clone(),
+                    replaces: fact.replaces,
+                    source_kind: source.kind.to_string(),
+                    source_id: source.source_id.clone(),
+                    source_message_id: Some(source.message_id.clone()),
+                    source_time: source.time.clone(),
+                }).map_err(|e| e.to_string())?;
+                known.push(fact.fact);
+                let candidate = if config.auto_apply {
+                    self.approve(&candidate.id, None)?
+                } else {
+                    candidate
+                };
+                added.push(candidate);
+            }
+        }
+
+        if let Some(last) = messages.last() {
+            cursor.messages = Some(last.created_at.clone());
+            cursor.message_id = last.id.clone();
+        }
+        if let Some(last) = chat.last() {
+            cursor.chat = last.timestamp;
+            cursor.chat_message_id = last.message_id.clone();
+        }
+        cursor.save(&self.db, pet_id)?;
+        if !added.is_empty() {
+            log::info!("[Memory] {} new memory candidates for {}", added.len(), pet_id);
+        }
+        Ok(added)
+    }
+
+    /// 批准候选并写入文件；`fact` 为用户修改后的文本
+    pub fn approve(&self, id: &str, fact: Option<String>) -> Result<MemoryCandidate, String> {
+        let mut candidate = self.db.get_memory_candidate(id).map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Memory candidate not found: {}", id))?;
+        if candidate.status != "pending" {
+            return Err(format!("Memory candidate was already {}", candidate.status));
+        }
+        let original_fact = candidate.fact.clone();
+        if let Some(fact) = fact.as_deref().map(str::trim).filter(|f| !f.is_empty()) {
+            candidate.fact = fact.to_string();
+        }
+
+        let content = match self.workspace.read(&candidate.pet_id, &candidate.file) {
+            Ok(content) => content,
+            Err(WorkspaceError::FileNotFound(_)) => String::new(),
+            Err(e) => return Err(e.to_string()),
+        };
+
+        // 先在数据库里标记为已批准再写文件：标记失败时文件还没动，不会重复写入同一条
+        self.db.review_memory_candidate(id, "approved", Some(&candidate.fact)).map_err(|e| e.to_string())?;
+        let written = match plan_edit(&content, &candidate) {
+            Some((old, new)) => self.workspace.edit(&candidate.pet_id, &candidate.file, &old, &new, ChangeSource::Tool),
+            None => {
+                let section = candidate.section.as_deref().unwrap_or_else(|| default_section(&candidate.file));
+                let body = format!("{}\n\n## {}\n\n{}\n", file_title(&candidate.file), section, entry_line(&candidate));
+                self.workspace.write(&candidate.pet_id, &candidate.file, &body, ChangeSource::Tool)
+            }
+        };
+        if let Err(e) = written {
+            // 写文件失败：放回待审核队列
+            if let Err(revert) = self.db.review_memory_candidate(id, "pending", Some(&original_fact)) {
+                log::warn!("[Memory] Failed to return candidate {} to the queue: {}", id, revert);
+            }
+            return Err(e.to_string());
+        }
+        candidate.status = "approved".to_string();
+        Ok(candidate)
+    }
+
+    pub fn reject(&self, id: &str) -> Result<bool, String> {
+        self.db.review_memory_candidate(id, "rejected", None).map_err(|e| e.to_string())
+    }
+
+    async fn complete(&self, config: &MemoryConfig, input: String) -> Result<String, String> {
+        let request = LlmRequest {
+            conversation_id: String::new(),
+            messages: vec![
+                ChatMessage { role: Role::System, content: MessageContent::Text(EXTRACT_PROMPT.to_string()), tool_call_history: None },
+                ChatMessage { role: Role::User, content: MessageContent::Text(input), tool_call_history: None },
+            ],
+            api_format: config.api_format.clone(),
+            api_key: config.api_key.clone(),
+            model: config.model.clone(),
+            base_url: config.base_url.clone(),
+            temperature: Some(0.2),
+            max_tokens: Some(1024),
+            stream: false,
+            response_format: None,
+        };
+        let response = self.llm.call(&request).await?;
+        match response.error {
+            Some(error) => Err(error),
+            None => Ok(response.content.trim().to_string()),
+        }
+    }
+}
+
+// ============ Tauri Commands ============
+
+#[tauri::command]
+pub fn get_memory_config(db: tauri::State<'_, Arc<Database>>) -> MemoryConfig {
+    MemoryConfig::load(&db)
+}
+
+#[tauri::command]
+pub fn set_memory_config(db: tauri::State<'_, Arc<Database>>, config: MemoryConfig) -> Result<(), String> {
+    let json = serde_json::to_string(&config).map_err(|e| e.to_string())?;
+    db.set_setting(CONFIG_KEY, &json).map_err(|e| e.to_string())
+}
+
+/// 立即整理一个宠物（不受间隔限制，但仍需配置可用）
+#[tauri::command]
+pub async fn memory_consolidate(
+    consolidator: tauri::State<'_, Arc<MemoryConsolidator>>,
+    pet_id: String,
+) -> Result<Vec<MemoryCandidate>, String> {
+    let config = MemoryConfig::load(&consolidator.db);
+    if !config.usable() {
+        return Err("Memory consolidation is not configured".to_string());
+    }
+    consolidator.consolidate(&pet_id, &config).await
+}
+
+#[tauri::command]
+pub fn memory_list_candidates(
+    db: tauri::State<'_, Arc<Database>>,
+    pet_id: String,
+    status: Option<String>,
+) -> Result<Vec<MemoryCandidate>, String> {
+    db.get_memory_candidates(&pet_id, status.as_deref()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn memory_approve_candidate(
+    consolidator: tauri::State<'_, Arc<MemoryConsolidator>>,
+    id: String,
+    fact: Option<String>,
+) -> Result<MemoryCandidate, String> {
+    consolidator.approve(&id, fact)
+}
+
+#[tauri::command]
+pub fn memory_reject_candidate(
+    consolidator: tauri::State<'_, Arc<MemoryConsolidator>>,
+    id: String,
+) -> Result<bool, String> {
+    consolidator.reject(&id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use crate::database::chat_history::InsertChatMessageData;
+    use crate::database::conversations::CreateConversationData;
+    use crate::database::messages::CreateMessageData;
+    use crate::llm::mock_provider;
+    use crate::mcp::mock_server::MockHttpServer;
+
+    fn candidate(file: &str, section: Option<&str>, fact: &str, replaces: Option<&str>) -> MemoryCandidate {
+        MemoryCandidate {
+            id: "c".to_string(),
+            pet_id: "p".to_string(),
+            file: file.to_string(),
+            section: section.map(str::to_string),
+            fact: fact.to_string(),
+            replaces: replaces.map(str::to_string),
+            source_kind: "conversation".to_string(),
+            source_id: "conv-1".to_string(),
+            source_message_id: None,
+            source_time: "2026-10-01T08:00:00+00:00".to_string(),
+            status: "pending".to_string(),
+            created_at: String::new(),
+            reviewed_at: None,
+        }
+    }
+
+    #[test]
+    fn plans_edits_by_section_and_replaced_line() {
+        let content = "# 🧠 记忆\n\n## 重要事件\n\n<!-- 值得记住的事情 -->\n\n## 偏好与习惯\n\n- 喜欢喝茶\n\n---\n";
+
+        let (old, new) = plan_edit(content, &candidate("MEMORY.md", Some("重要事件"), "周五面试", None)).unwrap();
+        assert_eq!(old, "## 重要事件\n\n<!-- 值得记住的事情 -->");
+        assert_eq!(new, format!("{}\n\n- 周五面试 <!-- source: conversation conv-1 @ 2026-10-01T08:00:00+00:00 -->", old));
+
+        let (old, new) = plan_edit(content, &candidate("MEMORY.md", Some("偏好与习惯"), "喜欢喝咖啡", None)).unwrap();
+        assert_eq!(old, "## 偏好与习惯\n\n- 喜欢喝茶");
+        assert!(new.starts_with("## 偏好与习惯\n\n- 喜欢喝茶\n- 喜欢喝咖啡"));
+
+        let (old, new) = plan_edit(content, &candidate("MEMORY.md", None, "改喝咖啡了", Some("喜欢喝茶"))).unwrap();
+        assert_eq!(old, "- 喜欢喝茶");
+        assert!(new.starts_with("- 改喝咖啡了 <!-- source:"));
+
+        // Unknown section without the default one: a new section at the end
+        let (old, new) = plan_edit("# 🧠 记忆\n", &candidate("MEMORY.md", Some("旅行"), "去过京都", None)).unwrap();
+        assert_eq!(old, "# 🧠 记忆");
+        assert!(new.starts_with("# 🧠 记忆\n\n## 旅行\n\n- 去过京都"));
+
+        assert!(plan_edit("  \n", &candidate("USER.md", None, "叫小明", None)).is_none());
+    }
+
+    #[test]
+    fn parses_facts_and_skips_duplicates() {
+        let raw = "```json\n[{\"file\": \"user.md\", \"section\": \"## 了解\", \"fact\": \"- 在学 Rust\", \"source\": 1},\
+            {\"file\": \"notes.md\", \"fact\": \"x\"}, {\"file\": \"MEMORY.md\", \"fact\": \"  \"}]\n```";
+        let facts = parse_facts(raw).unwrap();
+        assert_eq!(facts.len(), 1);
+        assert_eq!((facts[0].file.as_str(), facts[0].section.as_deref(), facts[0].fact.as_str()), ("USER.md", Some("了解"), "在学 Rust"));
+        assert_eq!(facts[0].source, Some(1));
+        assert!(parse_facts("[]").unwrap().is_empty());
+        assert!(parse_facts("nothing new").is_none());
+        assert!(parse_facts("[oops").is_none());
+
+        let known = vec!["- 主人在学 rust！ <!-- source: chat 123 @ 2026-01-01 -->".to_string()];
+        assert!(is_duplicate("在学Rust", &known));
+        assert!(!is_duplicate("在学 Go", &known));
+        assert!(is_duplicate("…", &known));
+    }
+
+    #[tokio::test]
+    async fn queues_new_facts_and_writes_approved_ones_with_provenance() {
+        let server = MockHttpServer::start(|_| mock_provider::openai_completion(
+            "[{\"file\": \"USER.md\", \"section\": \"了解\", \"fact\": \"喜欢猫\", \"source\": 0},\
+              {\"file\": \"USER.md\", \"fact\": \"主人在学 Rust\", \"source\": 2},\
+              {\"file\": \"MEMORY.md\", \"section\": \"待办与承诺\", \"fact\": \"周五提醒主人面试\", \"source\": 2}]",
+        )).await;
+
+        let db = Arc::new(Database::new(PathBuf::from(":memory:")).unwrap());
+        db.conn.lock().unwrap().execute_batch(
+            "INSERT INTO pets (id, name, created_at, updated_at) VALUES ('p', 'Mimi', '', '');",
+        ).unwrap();
+        db.set_setting("social_config_p", r#"{"ownerQQ": "10001"}"#).unwrap();
+        let conversation = db.create_conversation(CreateConversationData {
+            pet_id: "p".to_string(),
+            title: None,
+        }).unwrap();
+        for (role, content) in [("user", "我家有只猫"), ("assistant", "好可爱！")] {
+            db.create_message(CreateMessageData {
+                conversation_id: conversation.id.clone(),
+                role: role.to_string(),
+                content: content.to_string(),
+                tool_call_history: None,
+                parent_id: None,
+            }).unwrap();
+        }
+        db.insert_chat_message(&InsertChatMessageData {
+            message_id: "qq-1".to_string(),
+            target_id: "group-9".to_string(),
+            target_type: "group".to_string(),
+            sender_id: "10001".to_string(),
+            content: "周五要面试，最近在学 Rust".to_string(),
+            timestamp: 1_790_000_000_000,
+            reply_to_id: None,
+            is_bot: false,
+            raw_json: None,
+        }).unwrap();
+
+        let root = std::env::temp_dir().join(format!("petgpt_memory_{}", uuid::Uuid::new_v4()));
+        let workspace = Arc::new(WorkspaceEngine::new(root.clone()));
+        workspace.ensure_default_files("p", "Mimi").unwrap();
+        workspace.edit("p", "USER.md", "## 了解\n", "## 了解\n\n- 主人在学 Rust\n", ChangeSource::User).unwrap();
+
+        let consolidator = MemoryConsolidator::new(db.clone(), Arc::new(LlmClient::new()), workspace.clone());
+        let config = MemoryConfig {
+            enabled: true,
+            api_key: "test-key".to_string(),
+            model: "cheap-model".to_string(),
+            base_url: Some(server.base_url()),
+            ..Default::default()
+        };
+
+        let added = consolidator.consolidate("p", &config).await.unwrap();
+        let facts: Vec<&str> = added.iter().map(|c| c.fact.as_str()).collect();
+        assert_eq!(facts, ["喜欢猫", "周五提醒主人面试"]);
+        assert_eq!((added[0].source_kind.as_str(), added[0].source_id.as_str()), ("conversation", conversation.id.as_str()));
+        assert_eq!((added[1].source_kind.as_str(), added[1].source_id.as_str()), ("chat", "group-9"));
+        assert_eq!(db.get_memory_candidates("p", Some("pending")).unwrap().len(), 2);
+
+        let approved = consolidator.approve(&added[0].id, Some("喜欢猫，家里养了一只".to_string())).unwrap();
+        assert_eq!(approved.status, "approved");
+        let user = workspace.read("p", "USER.md").unwrap();
+        assert!(user.contains(&format!("- 喜欢猫，家里养了一只 <!-- source: conversation {} @ ", conversation.id)));
+        assert!(consolidator.approve(&added[0].id, None).is_err());
+
+        // MEMORY.md is not created by default; approving creates it
+        consolidator.approve(&added[1].id, None).unwrap();
+        let memory = workspace.read("p", "MEMORY.md").unwrap();
+        assert!(memory.contains("## 待办与承诺\n\n- 周五提醒主人面试 <!-- source: chat group-9 @ 2026-"));
+
+        // Everything was consumed: nothing new to extract on the next run
+        assert!(consolidator.consolidate("p", &config).await.unwrap().is_empty());
+        assert!(db.get_memory_candidates("p", Some("pending")).unwrap().is_empty());
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn malformed_replies_keep_the_batch_and_same_time_messages_are_not_skipped() {
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let counter = calls.clone();
+        let server = MockHttpServer::start(move |_| {
+            match counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst) {
+                0 => mock_provider::openai_completion("抱歉，我没法回答。"),
+                n => mock_provider::openai_completion(&format!(
+                    "[{{\"file\": \"MEMORY.md\", \"fact\": \"第 {} 条\", \"source\": 0}}, {{\"file\": \"MEMORY.md\", \"fact\": \"没有出处\"}}]",
+                    n
+                )),
+            }
+        }).await;
+
+        let db = Arc::new(Database::new(PathBuf::from(":memory:")).unwrap());
+        db.conn.lock().unwrap().execute_batch(
+            "INSERT INTO pets (id, name, created_at, updated_at) VALUES ('p', 'Mimi', '', '');
+             INSERT INTO conversations (id, pet_id, title, created_at, updated_at) VALUES ('c', 'p', 'Chat', '', '');
+             INSERT INTO messages (id, conversation_id, role, content, created_at) VALUES ('a', 'c', 'user', '我养了猫', '2026-10-01T08:00:00+00:00');
+             INSERT INTO messages (id, conversation_id, role, content, created_at) VALUES ('b', 'c', 'user', '还养了狗', '2026-10-01T08:00:00+00:00');",
+        ).unwrap();
+        let root = std::env::temp_dir().join(format!("petgpt_memory_{}", uuid::Uuid::new_v4()));
+        let workspace = Arc::new(WorkspaceEngine::new(root.clone()));
+        let consolidator = MemoryConsolidator::new(db.clone(), Arc::new(LlmClient::new()), workspace);
+        let config = MemoryConfig {
+            enabled: true,
+            api_key: "test-key".to_string(),
+            model: "cheap-model".to_string(),
+            base_url: Some(server.base_url()),
+            max_messages_per_run: 1,
+            ..Default::default()
+        };
+
+        // Not a JSON array: an error, and the cursor stays put
+        assert!(consolidator.consolidate("p", &config).await.is_err());
+        assert_eq!(Cursor::load(&db, "p"), Cursor::default());
+
+        // The fact without a source is dropped; the batch is retried from message "a"
+        let added = consolidator.consolidate("p", &config).await.unwrap();
+        assert_eq!(added.iter().map(|c| c.source_message_id.as_deref()).collect::<Vec<_>>(), [Some("a")]);
+        // "b" has the same created_at as "a" and is still picked up
+        let added = consolidator.consolidate("p", &config).await.unwrap();
+        assert_eq!(added.iter().map(|c| c.source_message_id.as_deref()).collect::<Vec<_>>(), [Some("b")]);
+        assert!(consolidator.consolidate("p", &config).await.unwrap().is_empty());
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 3);
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[tokio::test]
+    async fn conversations_with_memory_off_are_skipped() {
+        let server = MockHttpServer::start(|req| {
+            assert!(!req.body.contains("1234") && !req.body.contains("还养了狗"));
+            mock_provider::openai_completion("[{\"file\": \"USER.md\", \"fact\": \"养了猫\", \"source\": 0}]")
+        }).await;
+
+        let db = Arc::new(Database::new(PathBuf::from(":memory:")).unwrap());
+        db.conn.lock().unwrap().execute_batch(
+            "INSERT INTO pets (id, name, created_at, updated_at) VALUES ('p', 'Mimi', '', '');
+             INSERT INTO conversations (id, pet_id, title, created_at, updated_at, memory_enabled) VALUES ('on', 'p', 'On', '', '', 1);
+             INSERT INTO conversations (id, pet_id, title, created_at, updated_at, memory_enabled) VALUES ('off', 'p', 'Off', '', '', 0);
+             INSERT INTO conversations (id, pet_id, title, created_at, updated_at) VALUES ('default', 'p', 'Default', '', '');
+             INSERT INTO messages (id, conversation_id, role, content, created_at) VALUES ('a', 'off', 'user', '我的密码是 1234', '2026-10-01T08:00:00+00:00');
+             INSERT INTO messages (id, conversation_id, role, content, created_at) VALUES ('b', 'default', 'user', '还养了狗', '2026-10-01T08:01:00+00:00');
+             INSERT INTO messages (id, conversation_id, role, content, created_at) VALUES ('c', 'on', 'user', '我养了猫', '2026-10-01T08:02:00+00:00');",
+        ).unwrap();
+        // Conversations without their own switch follow the global default
+        db.set_setting("memoryEnabledByDefault", "false").unwrap();
+
+        let root = std::env::temp_dir().join(format!("petgpt_memory_{}", uuid::Uuid::new_v4()));
+        let workspace = Arc::new(WorkspaceEngine::new(root.clone()));
+        let consolidator = MemoryConsolidator::new(db.clone(), Arc::new(LlmClient::new()), workspace);
+        let config = MemoryConfig {
+            enabled: true,
+            api_key: "test-key".to_string(),
+            model: "cheap-model".to_string(),
+            base_url: Some(server.base_url()),
+            ..Default::default()
+        };
+
+        let added = consolidator.consolidate("p", &config).await.unwrap();
+        assert_eq!(added.iter().map(|c| c.source_id.as_str()).collect::<Vec<_>>(), ["on"]);
+        assert_eq!(Cursor::load(&db, "p").message_id, "c");
+
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
@@ -4,7 +4,7 @@
 //! native release channels. Docker is deliberately not supported here.
 
 use crate::database::{mcp_servers, Database};
-use crate::mcp::{McpManager, ServerStatus};
+use crate::mcp::{McpManager, RestartPolicy, ServerStatus};
 use chrono::Utc;
 use flate2::read::GzDecoder;
 use futures::StreamExt;
@@ -1096,6 +1096,8 @@ pub async fn qq_connector_complete_setup(
                 show_in_toolbar: Some(true),
                 toolbar_order: None,
                 max_iterations: None,
+                restart_policy: None,
+                sandbox: None,
             },
         )
         .map_err(|e| e.to_string())?
@@ -1113,6 +1115,8 @@ pub async fn qq_connector_complete_setup(
             auto_start: Some(true),
             show_in_toolbar: Some(true),
             max_iterations: None,
+            restart_policy: None,
+            sandbox: None,
         })
         .map_err(|e| e.to_string())?
     };
@@ -1120,6 +1124,10 @@ pub async fn qq_connector_complete_setup(
     tokio::time::sleep(Duration::from_millis(700)).await;
     let status = {
         let manager = mcp.read().await;
+        manager.set_restart_policy(
+            &server.id,
+            RestartPolicy::effective(server.restart_policy.as_deref(), server.auto_start),
+        );
         manager
             .start_server(
                 &server.id,
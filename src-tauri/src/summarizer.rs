@@ -0,0 +1,430 @@
+//! 会话自动标题与滚动摘要
+//!
+//! 第一轮问答落库后，用设置里配置的便宜模型给会话起标题；会话变长后把较早的消息
+//! 折叠进 conversations.summary。调用 LLM 时如果上下文超出 token 预算，
+//! 摘要已覆盖的那段历史会被替换成一条摘要 system 消息。
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter};
+use crate::database::Database;
+use crate::database::conversations::ConversationSummary;
+use crate::database::messages::Message;
+use crate::llm::{ApiFormat, ChatMessage, LlmClient, LlmRequest, MessageContent, Role};
+
+/// settings 表中保存配置的 key（JSON）
+pub const CONFIG_KEY: &str = "conversationSummarizer";
+
+/// 压缩后插入的摘要消息前缀
+const SUMMARY_PREFIX: &str = "Summary of the earlier part of this conversation:\n";
+
+/// 单条消息放进提示词时的最大字符数
+const MAX_PROMPT_CHARS_PER_MESSAGE: usize = 4000;
+const MAX_TITLE_CHARS: usize = 60;
+
+const TITLE_PROMPT: &str = "Write a short title (at most 8 words) for the conversation below. \
+Use the conversation's language. Reply with the title only, without quotes or punctuation at the end.";
+
+const SUMMARY_PROMPT: &str = "You maintain a running summary of a conversation between a user and an assistant. \
+Merge the previous summary (if any) with the new messages into one updated summary. Keep facts, decisions, \
+preferences, open questions and anything the assistant promised to do; drop small talk. \
+Use the conversation's language and reply with the summary only.";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SummarizerConfig {
+    pub enabled: bool,
+    /// 第一轮问答后自动起标题
+    pub auto_title: bool,
+    pub api_format: ApiFormat,
+    pub api_key: String,
+    pub model: String,
+    pub base_url: Option<String>,
+    /// 估算 token 超过这个值时用摘要压缩上下文
+    pub context_token_budget: usize,
+    /// 最近的这么多条消息始终原样保留，不进摘要
+    pub keep_recent_messages: usize,
+    /// 摘要之外积累了这么多条旧消息就更新一次摘要
+    pub summary_batch: usize,
+}
+
+impl Default for SummarizerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            auto_title: true,
+            api_format: ApiFormat::default(),
+            api_key: String::new(),
+            model: String::new(),
+            base_url: None,
+            context_token_budget: 24_000,
+            keep_recent_messages: 8,
+            summary_batch: 8,
+        }
+    }
+}
+
+impl SummarizerConfig {
+    pub fn load(db: &Database) -> Self {
+        db.get_setting(CONFIG_KEY)
+            .ok()
+            .flatten()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    fn usable(&self) -> bool {
+        self.enabled && !self.api_key.is_empty() && !self.model.is_empty()
+    }
+}
+
+/// 粗略估算 token 数：CJK 字符按 1 个 token，其余按 4 个字符 1 个 token
+pub fn estimate_tokens(text: &str) -> usize {
+    let (cjk, other) = text.chars().fold((0usize, 0usize), |(cjk, other), c| {
+        if matches!(c as u32, 0x3000..=0x9FFF | 0xAC00..=0xD7AF | 0xF900..=0xFAFF | 0xFF00..=0xFFEF) {
+            (cjk + 1, other)
+        } else {
+            (cjk, other + 1)
+        }
+    });
+    cjk + other.div_ceil(4)
+}
+
+/// 数据库消息的纯文本（多模态内容只取文字，图片和文件用占位符）
+pub(crate) fn message_text(message: &Message) -> String {
+    if message.content.starts_with('[') {
+        if let Ok(parts) = serde_json::from_str::<Vec<serde_json::Value>>(&message.content) {
+            return parts.iter().map(|part| match part["type"].as_str() {
+                Some("text") => part["text"].as_str().unwrap_or_default().to_string(),
+                Some("image_url") => "[image]".to_string(),
+                _ => "[file]".to_string(),
+            }).collect::<Vec<_>>().join("\n");
+        }
+    }
+    message.content.clone()
+}
+
+pub(crate) fn truncate_chars(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+fn transcript(messages: &[Message]) -> String {
+    messages.iter()
+        .map(|m| format!("{}: {}", m.role, truncate_chars(&message_text(m), MAX_PROMPT_CHARS_PER_MESSAGE)))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// 还没有正式标题：标题为空，或仍是前端用第一条用户消息填的占位标题
+fn needs_title(title: Option<&str>, path: &[Message]) -> bool {
+    let Some(first_user) = path.iter().find(|m| m.role == "user") else { return false };
+    if !path.iter().any(|m| m.role == "assistant") {
+        return false;
+    }
+    match title.map(str::trim) {
+        None | Some("") => true,
+        Some(title) => title == message_text(first_user).trim(),
+    }
+}
+
+/// 模型返回的标题只取第一行，去掉引号和结尾标点
+fn clean_title(raw: &str) -> String {
+    let line = raw.lines().map(str::trim).find(|l| !l.is_empty()).unwrap_or_default();
+    let line = line.trim_start_matches(|c| "#*\"'“”「」《》".contains(c))
+        .trim_end_matches(|c| "*\"'“”「」《》.。!！".contains(c))
+        .trim();
+    line.chars().take(MAX_TITLE_CHARS).collect()
+}
+
+/// 下一批要折叠进摘要的消息
+struct Fold<'a> {
+    /// 仍然有效的旧摘要（它覆盖的最后一条消息还在当前分支上）
+    previous: Option<&'a str>,
+    messages: &'a [Message],
+}
+
+fn next_fold<'a>(path: &'a [Message], summary: Option<&'a ConversationSummary>, config: &SummarizerConfig) -> Option<Fold<'a>> {
+    let covered = summary.and_then(|s| path.iter().position(|m| m.id == s.until_message_id).map(|i| (i + 1, s.summary.as_str())));
+    let (start, previous) = match covered {
+        Some((start, previous)) => (start, Some(previous)),
+        None => (0, None),
+    };
+    let end = path.len().saturating_sub(config.keep_recent_messages);
+    if end <= start {
+        return None;
+    }
+    let over_budget = path.iter().map(|m| estimate_tokens(&m.content)).sum::<usize>() > config.context_token_budget;
+    let pending = &path[start..end];
+    (pending.len() >= config.summary_batch.max(1) || over_budget).then_some(Fold { previous, messages: pending })
+}
+
+fn role_matches(role: &Role, stored: &str) -> bool {
+    matches!((role, stored), (Role::User, "user") | (Role::Assistant, "assistant") | (Role::System, "system"))
+}
+
+/// 用摘要替换请求里已被摘要覆盖的历史。请求历史和数据库当前分支对不上时返回 None
+fn compact_messages(messages: &[ChatMessage], path: &[Message], summary: &ConversationSummary) -> Option<Vec<ChatMessage>> {
+    let covered = path.iter().position(|m| m.id == summary.until_message_id)? + 1;
+    let lead = messages.iter().take_while(|m| m.role == Role::System).count();
+    let history = &messages[lead..];
+    if history.len() <= covered || !history.iter().zip(&path[..covered]).all(|(m, p)| role_matches(&m.role, &p.role)) {
+        return None;
+    }
+    let mut compacted = messages[..lead].to_vec();
+    compacted.push(ChatMessage {
+        role: Role::System,
+        content: MessageContent::Text(format!("{}{}", SUMMARY_PREFIX, summary.summary)),
+        tool_call_history: None,
+    });
+    compacted.extend_from_slice(&history[covered..]);
+    Some(compacted)
+}
+
+/// 请求超出 token 预算且有可用摘要时，就地压缩 `request.messages`
+pub fn compact_request(db: &Database, request: &mut LlmRequest) {
+    let config = SummarizerConfig::load(db);
+    if !config.enabled {
+        return;
+    }
+    let tokens: usize = request.messages.iter().map(|m| estimate_tokens(&m.content.as_text())).sum();
+    if tokens <= config.context_token_budget {
+        return;
+    }
+    let Ok(Some(summary)) = db.get_conversation_summary(&request.conversation_id) else { return };
+    let Ok(path) = db.get_messages_by_conversation(&request.conversation_id) else { return };
+    if let Some(messages) = compact_messages(&request.messages, &path, &summary) {
+        log::info!("[Summarizer] Compacted {} messages into the summary for {} (~{} tokens)",
+            request.messages.len() - messages.len() + 1, request.conversation_id, tokens);
+        request.messages = messages;
+    }
+}
+
+/// 后台标题 / 摘要服务
+pub struct ConversationSummarizer {
+    db: Arc<Database>,
+    llm: Arc<LlmClient>,
+    /// 正在处理的会话，避免同一会话并发跑两次
+    running: Mutex<HashSet<String>>,
+}
+
+impl ConversationSummarizer {
+    pub fn new(db: Arc<Database>, llm: Arc<LlmClient>) -> Self {
+        Self { db, llm, running: Mutex::new(HashSet::new()) }
+    }
+
+    /// 助手回复落库后调用；在后台执行，不阻塞保存
+    pub fn schedule(self: &Arc<Self>, conversation_id: &str, app: &AppHandle) {
+        let config = SummarizerConfig::load(&self.db);
+        if !config.usable() || !self.running.lock().unwrap().insert(conversation_id.to_string()) {
+            return;
+        }
+        let this = self.clone();
+        let app = app.clone();
+        let conversation_id = conversation_id.to_string();
+        tauri::async_runtime::spawn(async move {
+            let on_title = |title: &str| {
+                let _ = app.emit("conversation-title-updated", serde_json::json!({
+                    "conversationId": conversation_id,
+                    "title": title,
+                }));
+            };
+            if let Err(e) = this.process(&conversation_id, &config, on_title).await {
+                log::warn!("[Summarizer] {}: {}", conversation_id, e);
+            }
+            this.running.lock().unwrap().remove(&conversation_id);
+        });
+    }
+
+    async fn process(&self, conversation_id: &str, config: &SummarizerConfig, on_title: impl Fn(&str)) -> Result<(), String> {
+        let Some(conversation) = self.db.get_conversation_by_id(conversation_id).map_err(|e| e.to_string())? else {
+            return Ok(());
+        };
+        let path = self.db.get_messages_by_conversation(conversation_id).map_err(|e| e.to_string())?;
+
+        if config.auto_title && needs_title(conversation.title.as_deref(), &path) {
+            let exchange: Vec<Message> = path.iter().take(2).cloned().collect();
+            let title = clean_title(&self.complete(config, TITLE_PROMPT, transcript(&exchange), 32).await?);
+            if !title.is_empty() {
+                self.db.update_conversation_title(conversation_id, &title).map_err(|e| e.to_string())?;
+                on_title(&title);
+            }
+        }
+
+        let summary = self.db.get_conversation_summary(conversation_id).map_err(|e| e.to_string())?;
+        if let Some(fold) = next_fold(&path, summary.as_ref(), config) {
+            let prompt = match fold.previous {
+                Some(previous) => format!("Previous summary:\n{}\n\nNew messages:\n{}", previous, transcript(fold.messages)),
+                None => format!("New messages:\n{}", transcript(fold.messages)),
+            };
+            let updated = self.complete(config, SUMMARY_PROMPT, prompt, 1024).await?;
+            if let Some(last) = fold.messages.last().filter(|_| !updated.is_empty()) {
+                self.db.set_conversation_summary(conversation_id, &updated, &last.id).map_err(|e| e.to_string())?;
+                log::info!("[Summarizer] Folded {} messages into the summary of {}", fold.messages.len(), conversation_id);
+            }
+        }
+        Ok(())
+    }
+
+    async fn complete(&self, config: &SummarizerConfig, instruction: &str, input: String, max_tokens: u32) -> Result<String, String> {
+        let request = LlmRequest {
+            conversation_id: String::new(),
+            messages: vec![
+                ChatMessage { role: Role::System, content: MessageContent::Text(instruction.to_string()), tool_call_history: None },
+                ChatMessage { role: Role::User, content: MessageContent::Text(input), tool_call_history: None },
+            ],
+            api_format: config.api_format.clone(),
+            api_key: config.api_key.clone(),
+            model: config.model.clone(),
+            base_url: config.base_url.clone(),
+            temperature: Some(0.3),
+            max_tokens: Some(max_tokens),
+            stream: false,
+            response_format: None,
+        };
+        let response = self.llm.call(&request).await?;
+        match response.error {
+            Some(error) => Err(error),
+            None => Ok(response.content.trim().to_string()),
+        }
+    }
+}
+
+// ============ Tauri Commands ============
+
+#[tauri::command]
+pub fn get_summarizer_config(db: tauri::State<'_, Arc<Database>>) -> SummarizerConfig {
+    SummarizerConfig::load(&db)
+}
+
+#[tauri::command]
+pub fn set_summarizer_config(db: tauri::State<'_, Arc<Database>>, config: SummarizerConfig) -> Result<(), String> {
+    let json = serde_json::to_string(&config).map_err(|e| e.to_string())?;
+    db.set_setting(CONFIG_KEY, &json).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_conversation_summary(
+    db: tauri::State<'_, Arc<Database>>,
+    conversation_id: String,
+) -> Result<Option<ConversationSummary>, String> {
+    db.get_conversation_summary(&conversation_id).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use crate::database::conversations::CreateConversationData;
+    use crate::database::messages::CreateMessageData;
+    use crate::llm::mock_provider;
+    use crate::mcp::mock_server::MockHttpServer;
+
+    fn db_with_chat(turns: usize) -> (Arc<Database>, String) {
+        let db = Database::new(PathBuf::from(":memory:")).unwrap();
+        db.conn.lock().unwrap().execute_batch(
+            "INSERT INTO pets (id, name, created_at, updated_at) VALUES ('p', 'Mimi', '', '');",
+        ).unwrap();
+        let conversation = db.create_conversation(CreateConversationData {
+            pet_id: "p".to_string(),
+            title: Some("What should I name my cat?".to_string()),
+        }).unwrap();
+        for i in 0..turns {
+            for (role, content) in [("user", format!("What should I name my cat? {}", i)), ("assistant", format!("Maybe Mochi {}", i))] {
+                db.create_message(CreateMessageData {
+                    conversation_id: conversation.id.clone(),
+                    role: role.to_string(),
+                    content: if i == 0 && role == "user" { "What should I name my cat?".to_string() } else { content },
+                    tool_call_history: None,
+                    parent_id: None,
+                }).unwrap();
+            }
+        }
+        (Arc::new(db), conversation.id)
+    }
+
+    fn config(base_url: &str) -> SummarizerConfig {
+        SummarizerConfig {
+            enabled: true,
+            api_key: "test-key".to_string(),
+            model: "cheap-model".to_string(),
+            base_url: Some(base_url.to_string()),
+            keep_recent_messages: 2,
+            summary_batch: 4,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn estimates_cjk_and_latin_text() {
+        assert_eq!(estimate_tokens("猫咪在睡觉"), 5);
+        assert_eq!(estimate_tokens("abcdefgh"), 2);
+        assert_eq!(estimate_tokens(""), 0);
+    }
+
+    #[test]
+    fn placeholder_titles_are_replaced_but_renames_are_kept() {
+        let (db, id) = db_with_chat(1);
+        let path = db.get_messages_by_conversation(&id).unwrap();
+        assert!(needs_title(Some("What should I name my cat? "), &path));
+        assert!(needs_title(None, &path));
+        assert!(!needs_title(Some("Cat names"), &path));
+        assert!(!needs_title(None, &path[..1]));
+        assert_eq!(clean_title("\"Naming the cat.\"\nextra"), "Naming the cat");
+    }
+
+    #[test]
+    fn compaction_replaces_the_summarized_history() {
+        let (db, id) = db_with_chat(3);
+        let path = db.get_messages_by_conversation(&id).unwrap();
+        let summary = ConversationSummary { summary: "They discussed cat names.".to_string(), until_message_id: path[3].id.clone() };
+        let as_chat = |m: &Message| ChatMessage {
+            role: if m.role == "user" { Role::User } else { Role::Assistant },
+            content: MessageContent::Text(m.content.clone()),
+            tool_call_history: None,
+        };
+        let mut request = vec![ChatMessage { role: Role::System, content: MessageContent::Text("You are a cat.".to_string()), tool_call_history: None }];
+        request.extend(path.iter().map(as_chat));
+
+        let compacted = compact_messages(&request, &path, &summary).unwrap();
+        assert_eq!(compacted.len(), 4);
+        assert_eq!(compacted[1].content.as_text(), format!("{}They discussed cat names.", SUMMARY_PREFIX));
+        assert_eq!(compacted[2].content.as_text(), path[4].content);
+
+        // A request that doesn't line up with the stored branch is left alone
+        request.remove(1);
+        assert!(compact_messages(&request, &path, &summary).is_none());
+    }
+
+    #[tokio::test]
+    async fn titles_the_first_exchange_and_folds_older_messages() {
+        let server = MockHttpServer::start(|request| {
+            let system = request.json()["messages"][0]["content"].as_str().unwrap_or_default().to_string();
+            if system.starts_with("Write a short title") {
+                mock_provider::openai_completion("“Naming the cat”")
+            } else {
+                mock_provider::openai_completion("They picked cat names.")
+            }
+        }).await;
+        let (db, id) = db_with_chat(3);
+        let summarizer = ConversationSummarizer::new(db.clone(), Arc::new(LlmClient::new()));
+        let titles = Mutex::new(Vec::new());
+
+        summarizer.process(&id, &config(&server.base_url()), |t| titles.lock().unwrap().push(t.to_string())).await.unwrap();
+
+        assert_eq!(*titles.lock().unwrap(), ["Naming the cat"]);
+        assert_eq!(db.get_conversation_by_id(&id).unwrap().unwrap().title.as_deref(), Some("Naming the cat"));
+        let path = db.get_messages_by_conversation(&id).unwrap();
+        let summary = db.get_conversation_summary(&id).unwrap().unwrap();
+        assert_eq!(summary.summary, "They picked cat names.");
+        assert_eq!(summary.until_message_id, path[3].id);
+
+        // Nothing new to fold or title: no further LLM calls
+        let calls = server.requests().len();
+        summarizer.process(&id, &config(&server.base_url()), |_| panic!("titled twice")).await.unwrap();
+        assert_eq!(server.requests().len(), calls);
+    }
+}
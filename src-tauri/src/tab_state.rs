@@ -1,77 +1,341 @@
-use std::collections::HashMap;
-use std::sync::Mutex;
-use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
 use tauri::{AppHandle, Emitter};
-
-/// 消息内容可以是字符串或复杂对象（如多模态内容）
-#[derive(Clone, Debug, Serialize, Deserialize)]
-#[serde(untagged)]
-pub enum MessageContent {
-    Text(String),
-    Parts(Vec<serde_json::Value>),
-}
+use crate::database::Database;
+use crate::database::messages::Message as DbMessage;
+use crate::database::open_tabs::{OpenTab, TabSnapshot};
+pub use crate::llm::types::{ContentPart, MessageContent};
 
 /// 单条消息
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Message {
+    /// 数据库消息 id，用于编辑 / 切换分支；尚未落库的消息没有
+    #[serde(rename = "_id", default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
     pub role: String,
+    #[serde(deserialize_with = "deserialize_content")]
     pub content: MessageContent,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tool_call_history: Option<Vec<serde_json::Value>>,
     /// 消息创建时间（ISO 8601），用于给 LLM 注入时间戳
     #[serde(skip_serializing_if = "Option::is_none")]
     pub created_at: Option<String>,
+    /// 流式回复被崩溃 / 重启 / 取消打断，内容只到中断时为止
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub interrupted: bool,
+}
+
+/// 解析一个内容 part。前端的图片 part 有时把 mime_type 放在外层，先并入 image_url 再按 `ContentPart` 解析
+fn parse_part(mut part: Value) -> Result<ContentPart, serde_json::Error> {
+    if let Some(mime_type) = part.get("mime_type").cloned() {
+        if let Some(image_url) = part.get_mut("image_url").and_then(Value::as_object_mut) {
+            image_url.entry("mime_type").or_insert(mime_type);
+        }
+    }
+    serde_json::from_value(part)
+}
+
+/// 严格解析消息内容：任何一个 part 认不出都算失败（数据库里的内容可能本来就是以 `[` 开头的文本）
+fn parse_content(value: Value) -> Result<MessageContent, serde_json::Error> {
+    match value {
+        Value::Array(parts) => parts
+            .into_iter()
+            .map(parse_part)
+            .collect::<Result<Vec<ContentPart>, _>>()
+            .map(MessageContent::Parts),
+        other => serde_json::from_value(other),
+    }
+}
+
+/// 认不出的内容保留成文本：有 `text` 字段就取它，否则保留原始 JSON
+fn fallback_text(value: &Value) -> String {
+    value.get("text").and_then(Value::as_str).map_or_else(|| value.to_string(), str::to_string)
+}
+
+/// 宽松解析快照里的消息内容。旧版本保存的 part 结构可能和现在的 `ContentPart` 不同，
+/// 认不出的部分退化成文本，而不是让整个 tab 恢复失败
+fn parse_content_lenient(value: Value) -> MessageContent {
+    match value {
+        Value::String(text) => MessageContent::Text(text),
+        Value::Null => MessageContent::Text(String::new()),
+        Value::Array(parts) => MessageContent::Parts(parts.into_iter().map(|part| {
+            parse_part(part.clone()).unwrap_or_else(|e| {
+                eprintln!("[TabState] Keeping unrecognized content part as text: {}", e);
+                ContentPart::Text { text: fallback_text(&part) }
+            })
+        }).collect()),
+        other => {
+            eprintln!("[TabState] Keeping unrecognized message content as text");
+            MessageContent::Text(fallback_text(&other))
+        }
+    }
 }
 
-/// Tab 状态快照 - 推送给前端的数据结构
+fn deserialize_content<'de, D: Deserializer<'de>>(deserializer: D) -> Result<MessageContent, D::Error> {
+    Ok(parse_content_lenient(Value::deserialize(deserializer)?))
+}
+
+/// 快照里的消息列表；单条解析失败只丢那一条并记录日志
+fn parse_snapshot_messages(conversation_id: &str, json: &str) -> Vec<Message> {
+    let values: Vec<Value> = match serde_json::from_str(json) {
+        Ok(values) => values,
+        Err(e) => {
+            eprintln!("[TabState] Unreadable tab snapshot for {}: {}", conversation_id, e);
+            return Vec::new();
+        }
+    };
+    values.into_iter()
+        .filter_map(|value| match serde_json::from_value(value) {
+            Ok(message) => Some(message),
+            Err(e) => {
+                eprintln!("[TabState] Dropping unreadable message in tab {}: {}", conversation_id, e);
+                None
+            }
+        })
+        .collect()
+}
+
+impl From<&DbMessage> for Message {
+    /// 数据库里多模态内容和工具调用记录都存成 JSON 字符串，这里还原成前端的结构
+    fn from(msg: &DbMessage) -> Self {
+        let content = match serde_json::from_str::<Value>(&msg.content) {
+            Ok(parts) if msg.content.starts_with('[') => parse_content(parts).ok(),
+            _ => None,
+        };
+        Self {
+            id: Some(msg.id.clone()),
+            role: msg.role.clone(),
+            content: content.unwrap_or_else(|| MessageContent::Text(msg.content.clone())),
+            tool_call_history: msg.tool_call_history.as_deref().and_then(|h| serde_json::from_str(h).ok()),
+            created_at: Some(msg.created_at.clone()),
+            interrupted: false,
+        }
+    }
+}
+
+/// Tab 状态快照 - 初次加载或前端发现版本断档时获取
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TabStateSnapshot {
     pub messages: Vec<Message>,
     pub is_thinking: bool,
+    /// 已推送的最后一个操作的版本号
+    pub version: u64,
+}
+
+/// 推送给前端的增量操作，前端按顺序应用到自己的副本上
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "op", rename_all = "camelCase")]
+pub enum TabOp {
+    /// 整体替换（切换分支、初始化等）
+    Reset { messages: Vec<Message>, is_thinking: bool },
+    Append { message: Message },
+    /// 替换指定位置的消息
+    Patch { index: usize, message: Message },
+    Delete { index: usize },
+    Thinking { is_thinking: bool },
+}
+
+/// `tab-state:{conversation_id}` 事件的载荷；版本号逐个递增，断档说明漏了事件
+#[derive(Clone, Debug, Serialize)]
+pub struct TabEvent {
+    pub version: u64,
+    #[serde(flatten)]
+    pub op: TabOp,
+}
+
+/// 单个会话的 tab 数据
+#[derive(Default)]
+struct Tab {
+    messages: Vec<Message>,
+    is_thinking: bool,
+    version: u64,
+    /// 正在流式输出、尚未成为消息的回复文本
+    partial: Option<String>,
+    /// 输入框里未发送的草稿
+    draft: Option<String>,
+}
+
+impl Tab {
+    /// 应用一个操作；下标越界时返回 false，不改动任何东西
+    fn apply(&mut self, op: &TabOp) -> bool {
+        match op {
+            TabOp::Reset { messages, is_thinking } => {
+                self.messages = messages.clone();
+                self.is_thinking = *is_thinking;
+            }
+            TabOp::Append { message } => self.messages.push(message.clone()),
+            TabOp::Patch { index, message } => match self.messages.get_mut(*index) {
+                Some(slot) => *slot = message.clone(),
+                None => return false,
+            },
+            TabOp::Delete { index } => {
+                if *index >= self.messages.len() {
+                    return false;
+                }
+                self.messages.remove(*index);
+            }
+            TabOp::Thinking { is_thinking } => self.is_thinking = *is_thinking,
+        }
+        if !self.is_thinking {
+            self.partial = None;
+        }
+        true
+    }
+
+    /// 应用操作、递增版本号并交给 `emit`
+    fn commit(&mut self, op: TabOp, emit: impl FnOnce(&TabEvent)) -> bool {
+        if !self.apply(&op) {
+            return false;
+        }
+        self.version += 1;
+        emit(&TabEvent { version: self.version, op });
+        true
+    }
 }
 
 /// 简化的 Tab 状态管理 - Rust 完全拥有数据所有权
-/// 
+///
 /// 设计原则：
 /// 1. Rust 是唯一的数据源（Single Source of Truth）
 /// 2. 前端只是"订阅者"，不维护自己的消息状态
-/// 3. 任何修改都会自动推送完整状态给前端
+/// 3. 任何修改都以带版本号的增量操作推送给前端
 pub struct TabState {
-    /// 消息数据 - Rust 独占所有权
-    messages: Mutex<HashMap<String, Vec<Message>>>,
-    /// 思考状态 - Rust 独占所有权
-    thinking: Mutex<HashMap<String, bool>>,
+    /// 每个会话的 tab 数据 - Rust 独占所有权
+    tabs: Mutex<HashMap<String, Tab>>,
+    /// 自上次落盘以来改动过的会话
+    dirty: Mutex<HashSet<String>>,
 }
 
 impl TabState {
     pub fn new() -> Self {
         Self {
-            messages: Mutex::new(HashMap::new()),
-            thinking: Mutex::new(HashMap::new()),
+            tabs: Mutex::new(HashMap::new()),
+            dirty: Mutex::new(HashSet::new()),
         }
     }
 
-    /// 获取指定会话的状态快照
-    fn get_snapshot(&self, conversation_id: &str) -> TabStateSnapshot {
-        let messages = self.messages.lock().unwrap();
-        let thinking = self.thinking.lock().unwrap();
+    fn mark_dirty(&self, conversation_id: &str) {
+        self.dirty.lock().unwrap().insert(conversation_id.to_string());
+    }
 
-        TabStateSnapshot {
-            messages: messages.get(conversation_id).cloned().unwrap_or_default(),
-            is_thinking: *thinking.get(conversation_id).unwrap_or(&false),
+    /// 推送到 `tab-state:{conversation_id}` 事件（使用特定于会话的事件名，前端可以精确订阅）
+    fn emitter<'a>(app: &'a AppHandle, conversation_id: &str) -> impl FnMut(&TabEvent) + 'a {
+        let event_name = format!("tab-state:{}", conversation_id);
+        move |event| {
+            if let Err(e) = app.emit(&event_name, event) {
+                eprintln!("[TabState] Failed to emit state: {:?}", e);
+            }
         }
     }
 
-    /// 推送状态更新到前端
-    fn emit_state(&self, conversation_id: &str, app: &AppHandle) {
-        let snapshot = self.get_snapshot(conversation_id);
-        // 使用特定于会话的事件名，前端可以精确订阅
-        let event_name = format!("tab-state:{}", conversation_id);
-        if let Err(e) = app.emit(&event_name, &snapshot) {
-            eprintln!("[TabState] Failed to emit state: {:?}", e);
+    /// 应用操作并推送（所有修改都经过这里）
+    fn apply(&self, conversation_id: &str, op: TabOp, app: &AppHandle) -> bool {
+        self.apply_with(conversation_id, op, Self::emitter(app, conversation_id))
+    }
+
+    /// 应用操作并交给 `emit`；emit 在锁内调用，保证事件顺序与版本号一致
+    fn apply_with(&self, conversation_id: &str, op: TabOp, emit: impl FnOnce(&TabEvent)) -> bool {
+        {
+            let mut tabs = self.tabs.lock().unwrap();
+            if !tabs.entry(conversation_id.to_string()).or_default().commit(op, emit) {
+                return false;
+            }
+        }
+        self.mark_dirty(conversation_id);
+        true
+    }
+
+    /// 记录流式回复的当前全文（只用于崩溃恢复，不推送前端）；`done` 时清除
+    pub fn record_stream_chunk(&self, conversation_id: &str, full_text: &str, done: bool) {
+        {
+            let mut tabs = self.tabs.lock().unwrap();
+            let tab = tabs.entry(conversation_id.to_string()).or_default();
+            tab.partial = (!done).then(|| full_text.to_string());
+        }
+        self.mark_dirty(conversation_id);
+    }
+
+    /// 把改动过的会话写入 open_tabs（写后落盘，由后台线程定时调用）。
+    /// 没有打开的 tab 不会写入；写失败的留到下一轮重试。
+    pub fn flush(&self, db: &Database) {
+        let dirty: Vec<String> = self.dirty.lock().unwrap().drain().collect();
+        for conversation_id in dirty {
+            let snapshot = {
+                let tabs = self.tabs.lock().unwrap();
+                let tab = tabs.get(&conversation_id);
+                TabSnapshot {
+                    messages: serde_json::to_string(tab.map(|t| t.messages.as_slice()).unwrap_or_default())
+                        .unwrap_or_else(|_| "[]".to_string()),
+                    is_thinking: tab.is_some_and(|t| t.is_thinking),
+                    partial_reply: tab.and_then(|t| t.partial.clone()),
+                    draft: tab.and_then(|t| t.draft.clone()),
+                    conversation_id,
+                }
+            };
+            if let Err(e) = db.save_tab_snapshot(&snapshot) {
+                eprintln!("[TabState] Failed to persist {}: {:?}", snapshot.conversation_id, e);
+                self.mark_dirty(&snapshot.conversation_id);
+            }
+        }
+    }
+
+    /// 启动时从 open_tabs 恢复。中断的流式回复作为 interrupted 的助手消息保留，
+    /// thinking 状态一律清零（进程已经重启，不可能还在生成）。
+    pub fn restore(&self, db: &Database) {
+        let snapshots = match db.get_tab_snapshots() {
+            Ok(snapshots) => snapshots,
+            Err(e) => {
+                eprintln!("[TabState] Failed to load tab snapshots: {:?}", e);
+                return;
+            }
+        };
+        let mut tabs = self.tabs.lock().unwrap();
+        let mut dirty = self.dirty.lock().unwrap();
+        for snapshot in snapshots {
+            let mut messages = parse_snapshot_messages(&snapshot.conversation_id, &snapshot.messages);
+            let partial = snapshot.partial_reply.filter(|text| !text.trim().is_empty());
+            if let Some(text) = partial.filter(|_| snapshot.is_thinking) {
+                messages.push(Message {
+                    id: None,
+                    role: "assistant".to_string(),
+                    content: MessageContent::Text(text),
+                    tool_call_history: None,
+                    created_at: Some(chrono::Utc::now().to_rfc3339()),
+                    interrupted: true,
+                        });
+            }
+            tabs.insert(snapshot.conversation_id.clone(), Tab {
+                messages,
+                draft: snapshot.draft.filter(|d| !d.is_empty()),
+                ..Tab::default()
+            });
+            // 把清理后的状态写回，避免下次启动再追加一遍
+            dirty.insert(snapshot.conversation_id);
+        }
+    }
+
+    /// 获取指定会话的状态快照
+    fn get_snapshot(&self, conversation_id: &str) -> TabStateSnapshot {
+        let tabs = self.tabs.lock().unwrap();
+        match tabs.get(conversation_id) {
+            Some(tab) => TabStateSnapshot {
+                messages: tab.messages.clone(),
+                is_thinking: tab.is_thinking,
+                version: tab.version,
+            },
+            None => TabStateSnapshot { messages: Vec::new(), is_thinking: false, version: 0 },
         }
     }
+
+    /// 用数据库中的当前分支替换 tab 消息并推送（编辑 / 重新生成 / 切换分支后调用）
+    pub fn replace_with_branch(&self, conversation_id: &str, branch: &[DbMessage], app: &AppHandle) {
+        let is_thinking = self.get_snapshot(conversation_id).is_thinking;
+        let messages = branch.iter().map(Message::from).collect();
+        self.apply(conversation_id, TabOp::Reset { messages, is_thinking }, app);
+    }
 }
 
 // ============ Tauri Commands ============
@@ -93,12 +357,10 @@ pub fn init_tab_messages(
     messages: Vec<Message>,
     app: AppHandle,
 ) {
-    {
-        let mut msg_map = state.messages.lock().unwrap();
-        // 使用 entry API - 所有权转移，messages 被移动到 HashMap
-        msg_map.entry(conversation_id.clone()).or_insert(messages);
+    let snapshot = state.get_snapshot(&conversation_id);
+    if snapshot.messages.is_empty() && !messages.is_empty() {
+        state.apply(&conversation_id, TabOp::Reset { messages, is_thinking: snapshot.is_thinking }, &app);
     }
-    state.emit_state(&conversation_id, &app);
 }
 
 /// 设置 tab 消息（完全替换）
@@ -109,11 +371,8 @@ pub fn set_tab_state_messages(
     messages: Vec<Message>,
     app: AppHandle,
 ) {
-    {
-        let mut msg_map = state.messages.lock().unwrap();
-        msg_map.insert(conversation_id.clone(), messages);
-    }
-    state.emit_state(&conversation_id, &app);
+    let is_thinking = state.get_snapshot(&conversation_id).is_thinking;
+    state.apply(&conversation_id, TabOp::Reset { messages, is_thinking }, &app);
 }
 
 /// 添加一条消息（Rust 会自动推送更新）
@@ -124,14 +383,7 @@ pub fn push_tab_message(
     message: Message,
     app: AppHandle,
 ) {
-    {
-        let mut msg_map = state.messages.lock().unwrap();
-        msg_map
-            .entry(conversation_id.clone())
-            .or_default()
-            .push(message);
-    }
-    state.emit_state(&conversation_id, &app);
+    state.apply(&conversation_id, TabOp::Append { message }, &app);
 }
 
 /// 更新指定位置的消息
@@ -143,23 +395,7 @@ pub fn update_tab_state_message(
     message: Message,
     app: AppHandle,
 ) -> bool {
-    let success = {
-        let mut msg_map = state.messages.lock().unwrap();
-        if let Some(messages) = msg_map.get_mut(&conversation_id) {
-            if index < messages.len() {
-                messages[index] = message;
-                true
-            } else {
-                false
-            }
-        } else {
-            false
-        }
-    };
-    if success {
-        state.emit_state(&conversation_id, &app);
-    }
-    success
+    state.apply(&conversation_id, TabOp::Patch { index, message }, &app)
 }
 
 /// 删除指定位置的消息
@@ -170,23 +406,7 @@ pub fn delete_tab_state_message(
     index: usize,
     app: AppHandle,
 ) -> bool {
-    let success = {
-        let mut msg_map = state.messages.lock().unwrap();
-        if let Some(messages) = msg_map.get_mut(&conversation_id) {
-            if index < messages.len() {
-                messages.remove(index);
-                true
-            } else {
-                false
-            }
-        } else {
-            false
-        }
-    };
-    if success {
-        state.emit_state(&conversation_id, &app);
-    }
-    success
+    state.apply(&conversation_id, TabOp::Delete { index }, &app)
 }
 
 /// 设置 thinking 状态（Rust 会自动推送更新）
@@ -197,25 +417,167 @@ pub fn set_tab_thinking(
     is_thinking: bool,
     app: AppHandle,
 ) {
-    {
-        let mut thinking_map = state.thinking.lock().unwrap();
-        thinking_map.insert(conversation_id.clone(), is_thinking);
-    }
-    state.emit_state(&conversation_id, &app);
+    state.apply(&conversation_id, TabOp::Thinking { is_thinking }, &app);
 }
 
-/// 清空指定会话的所有状态
+/// 清空指定会话的消息和思考状态（草稿保留）
 #[tauri::command]
 pub fn clear_tab_state(
     state: tauri::State<TabState>,
     conversation_id: String,
     app: AppHandle,
 ) {
+    state.apply(&conversation_id, TabOp::Reset { messages: Vec::new(), is_thinking: false }, &app);
+}
+
+/// 上次关闭时打开的 tab（按顺序）
+#[tauri::command]
+pub fn get_open_tabs(db: tauri::State<'_, Arc<Database>>) -> Result<Vec<OpenTab>, String> {
+    db.get_open_tabs().map_err(|e| e.to_string())
+}
+
+/// 保存 tab 列表（顺序 + 当前激活的 tab），tab 列表变化时调用
+#[tauri::command]
+pub fn set_open_tabs(
+    db: tauri::State<'_, Arc<Database>>,
+    state: tauri::State<TabState>,
+    tabs: Vec<OpenTab>,
+) -> Result<(), String> {
+    db.set_open_tabs(&tabs).map_err(|e| e.to_string())?;
+    // 新打开的 tab 此前的改动可能因为还没有行而没写进去
+    for tab in &tabs {
+        state.mark_dirty(&tab.conversation_id);
+    }
+    Ok(())
+}
+
+/// 获取输入框草稿
+#[tauri::command]
+pub fn get_tab_draft(state: tauri::State<TabState>, conversation_id: String) -> String {
+    let tabs = state.tabs.lock().unwrap();
+    tabs.get(&conversation_id).and_then(|t| t.draft.clone()).unwrap_or_default()
+}
+
+/// 更新输入框草稿（空字符串表示清除），随下一次落盘写入
+#[tauri::command]
+pub fn set_tab_draft(state: tauri::State<TabState>, conversation_id: String, draft: String) {
     {
-        let mut msg_map = state.messages.lock().unwrap();
-        let mut thinking_map = state.thinking.lock().unwrap();
-        msg_map.remove(&conversation_id);
-        thinking_map.remove(&conversation_id);
+        let mut tabs = state.tabs.lock().unwrap();
+        tabs.entry(conversation_id.clone()).or_default().draft = (!draft.is_empty()).then_some(draft);
+    }
+    state.mark_dirty(&conversation_id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(role: &str, text: &str) -> Message {
+        Message {
+            id: None,
+            role: role.to_string(),
+            content: MessageContent::Text(text.to_string()),
+            tool_call_history: None,
+            created_at: None,
+            interrupted: false,
+        }
+    }
+
+    #[test]
+    fn ops_bump_the_version_and_reject_bad_indexes() {
+        let state = TabState::new();
+        let mut versions = Vec::new();
+        let mut apply = |op| state.apply_with("c", op, |event| versions.push(event.version));
+        apply(TabOp::Append { message: text("user", "hi") });
+        apply(TabOp::Append { message: text("assistant", "hello") });
+        assert!(apply(TabOp::Patch { index: 1, message: text("assistant", "hey") }));
+        assert!(!apply(TabOp::Delete { index: 5 }));
+        apply(TabOp::Thinking { is_thinking: true });
+        assert_eq!(versions, vec![1, 2, 3, 4]);
+
+        let snapshot = state.get_snapshot("c");
+        assert_eq!(snapshot.version, 4);
+        assert!(snapshot.is_thinking);
+        assert!(matches!(&snapshot.messages[1].content, MessageContent::Text(t) if t == "hey"));
+
+        let event = serde_json::to_value(TabEvent { version: 5, op: TabOp::Delete { index: 0 } }).unwrap();
+        assert_eq!(event, serde_json::json!({ "version": 5, "op": "delete", "index": 0 }));
+    }
+
+    #[test]
+    fn image_parts_accept_an_outer_mime_type() {
+        let message: Message = serde_json::from_value(serde_json::json!({
+            "role": "user",
+            "content": [
+                { "type": "text", "text": "看图" },
+                { "type": "image_url", "image_url": { "url": "/uploads/a.png" }, "mime_type": "image/png" },
+            ],
+        })).unwrap();
+        let MessageContent::Parts(parts) = &message.content else { panic!("expected parts") };
+        assert!(matches!(&parts[1], ContentPart::ImageUrl { image_url } if image_url.mime_type.as_deref() == Some("image/png")));
+    }
+
+    #[test]
+    fn unknown_content_from_older_snapshots_falls_back_to_text() {
+        let json = serde_json::json!([
+            { "role": "user", "content": [{ "type": "input_text", "text": "旧格式" }, { "type": "hologram" }] },
+            { "role": "assistant", "content": { "text": "对象内容" } },
+            { "content": "缺少 role" },
+        ]).to_string();
+        let messages = parse_snapshot_messages("c", &json);
+        assert_eq!(messages.len(), 2);
+        let MessageContent::Parts(parts) = &messages[0].content else { panic!("expected parts") };
+        assert!(matches!(&parts[0], ContentPart::Text { text } if text == "旧格式"));
+        assert!(matches!(&parts[1], ContentPart::Text { text } if text == r#"{"type":"hologram"}"#));
+        assert!(matches!(&messages[1].content, MessageContent::Text(t) if t == "对象内容"));
+        assert!(parse_snapshot_messages("c", "not json").is_empty());
+
+        // Database content stays strict: a text message that happens to be a JSON array is kept as text
+        let db_message = DbMessage {
+            id: "m".to_string(),
+            conversation_id: "c".to_string(),
+            parent_id: None,
+            role: "user".to_string(),
+            content: "[1, 2]".to_string(),
+            tool_call_history: None,
+            created_at: String::new(),
+        };
+        assert!(matches!(Message::from(&db_message).content, MessageContent::Text(t) if t == "[1, 2]"));
+    }
+
+    #[test]
+    fn interrupted_stream_survives_restart() {
+        let db = Database::new(std::path::PathBuf::from(":memory:")).unwrap();
+        db.conn.lock().unwrap().execute_batch(
+            "INSERT INTO pets (id, name, created_at, updated_at) VALUES ('p', 'Mimi', '', '');
+             INSERT INTO conversations (id, pet_id, title, created_at, updated_at) VALUES ('c', 'p', 'C', '', '');"
+        ).unwrap();
+        db.set_open_tabs(&[OpenTab { conversation_id: "c".to_string(), position: 0, is_active: true, draft: None }]).unwrap();
+
+        let state = TabState::new();
+        state.tabs.lock().unwrap().insert("c".to_string(), Tab {
+            messages: vec![text("user", "写首诗")],
+            is_thinking: true,
+            draft: Some("再来一首".to_string()),
+            ..Tab::default()
+        });
+        state.record_stream_chunk("c", "床前明月光", false);
+        state.flush(&db);
+
+        let restored = TabState::new();
+        restored.restore(&db);
+        let snapshot = restored.get_snapshot("c");
+        assert!(!snapshot.is_thinking);
+        assert_eq!(snapshot.messages.len(), 2);
+        let reply = &snapshot.messages[1];
+        assert!(reply.interrupted && reply.role == "assistant");
+        assert!(matches!(&reply.content, MessageContent::Text(t) if t == "床前明月光"));
+        assert_eq!(restored.tabs.lock().unwrap()["c"].draft.as_deref(), Some("再来一首"));
+
+        // 写回后再次恢复不会重复追加
+        restored.flush(&db);
+        let again = TabState::new();
+        again.restore(&db);
+        assert_eq!(again.get_snapshot("c").messages.len(), 2);
     }
-    state.emit_state(&conversation_id, &app);
 }
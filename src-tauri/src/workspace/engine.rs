@@ -6,6 +6,17 @@ use std::fs;
 use std::path::{Path, PathBuf};
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
 
+use serde::{Deserialize, Serialize};
+
+use super::history::{unified_diff, ChangeAction, ChangeSource, HistoryEntry, HistoryRetention, WorkspaceHistory};
+use super::media::{is_thumbnailable, render_thumbnail, sniff_file, sniff_mime, FileInfo};
+use super::patch::apply_patch as apply_unified_patch;
+use super::search::{search_tree, SearchOptions, SearchResult};
+use super::storage::{
+    scan_cleanup, CleanupFailure, CleanupOptions, CleanupReport, CleanupResult, StorageQuota, UsageReport,
+    WorkspaceStorage,
+};
+
 // ============ Error Types ============
 
 #[derive(Debug)]
@@ -24,6 +35,14 @@ pub enum WorkspaceError {
     EditMultipleMatches(String, usize, String),
     /// edit: replacement produces no change
     EditNoChange(String),
+    /// edit_batch: one edit failed, nothing was written (1-based index, reason)
+    BatchFailed(usize, String),
+    /// apply_patch: a hunk did not fit, nothing was written
+    PatchFailed(String),
+    /// search: bad regex, glob or empty query
+    InvalidPattern(String),
+    /// Write would break the pet's storage quota
+    QuotaExceeded(String),
     /// General IO error
     IoError(String),
 }
@@ -60,6 +79,18 @@ impl std::fmt::Display for WorkspaceError {
             WorkspaceError::EditNoChange(path) => {
                 write!(f, "替换后 {} 内容没有变化。", path)
             }
+            WorkspaceError::BatchFailed(index, msg) => {
+                write!(f, "第 {} 处修改失败，整批修改都没有写入文件。{}", index, msg)
+            }
+            WorkspaceError::PatchFailed(msg) => {
+                write!(f, "补丁无法应用，文件没有修改: {}", msg)
+            }
+            WorkspaceError::InvalidPattern(msg) => {
+                write!(f, "无效的搜索条件: {}", msg)
+            }
+            WorkspaceError::QuotaExceeded(msg) => {
+                write!(f, "超出存储配额，文件没有写入: {}", msg)
+            }
             WorkspaceError::IoError(msg) => {
                 write!(f, "IO 错误: {}", msg)
             }
@@ -67,19 +98,49 @@ impl std::fmt::Display for WorkspaceError {
     }
 }
 
+// ============ Batch Edit Types ============
+
+/// One replacement in `edit_batch`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EditOp {
+    pub old_text: String,
+    pub new_text: String,
+    /// Replace every occurrence instead of requiring a unique match
+    #[serde(default)]
+    pub replace_all: bool,
+}
+
+/// Outcome of `edit_batch` / `apply_patch`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct EditPreview {
+    /// False for a dry run
+    pub applied: bool,
+    /// Unified diff from the current file to `content`
+    pub diff: String,
+    pub content: String,
+}
+
 // ============ WorkspaceEngine ============
 
 pub struct WorkspaceEngine {
     /// Root workspace directory (e.g. ~/.app/workspace/)
     /// Each pet gets a subdirectory: {root_dir}/{pet_id}/
     root_dir: PathBuf,
+    /// Snapshots of every mutation, stored under {root_dir}/.history/
+    history: WorkspaceHistory,
+    /// Quotas, usage accounting and thumbnails, stored under {root_dir}/.storage/
+    storage: WorkspaceStorage,
 }
 
 impl WorkspaceEngine {
     pub fn new(root_dir: PathBuf) -> Self {
         // Ensure root directory exists
         let _ = fs::create_dir_all(&root_dir);
-        Self { root_dir }
+        let history = WorkspaceHistory::new(&root_dir);
+        let storage = WorkspaceStorage::new(&root_dir);
+        Self { root_dir, history, storage }
     }
 
     /// Get the workspace directory for a specific pet
@@ -139,6 +200,7 @@ impl WorkspaceEngine {
         pet_id: &str,
         path: &str,
         content: &str,
+        source: ChangeSource,
     ) -> Result<String, WorkspaceError> {
         let full_path = self.resolve_safe_path(pet_id, path)?;
 
@@ -147,8 +209,11 @@ impl WorkspaceEngine {
             fs::create_dir_all(parent).map_err(|e| WorkspaceError::WriteError(e.to_string()))?;
         }
 
+        let before = fs::read(&full_path).ok();
         let bytes = content.as_bytes().len();
+        self.check_quota(pet_id, before.as_deref(), bytes as u64)?;
         fs::write(&full_path, content).map_err(|e| WorkspaceError::WriteError(e.to_string()))?;
+        self.record(pet_id, &full_path, None, ChangeAction::Write, before, Some(content.as_bytes()), source);
 
         Ok(format!("成功写入 {} 字节到 {}", bytes, path))
     }
@@ -159,6 +224,7 @@ impl WorkspaceEngine {
         pet_id: &str,
         path: &str,
         content: &str,
+        source: ChangeSource,
     ) -> Result<String, WorkspaceError> {
         let full_path = self.resolve_safe_path(pet_id, path)?;
 
@@ -167,6 +233,9 @@ impl WorkspaceEngine {
             fs::create_dir_all(parent).map_err(|e| WorkspaceError::WriteError(e.to_string()))?;
         }
 
+        let before = fs::read(&full_path).ok();
+        let old_len = before.as_ref().map_or(0, |b| b.len());
+        self.check_quota(pet_id, before.as_deref(), (old_len + content.len()) as u64)?;
         use std::io::Write;
         let mut file = fs::OpenOptions::new()
             .create(true)
@@ -177,6 +246,9 @@ impl WorkspaceEngine {
         let bytes = content.as_bytes().len();
         file.write_all(content.as_bytes())
             .map_err(|e| WorkspaceError::WriteError(e.to_string()))?;
+        drop(file);
+        let after = fs::read(&full_path).map_err(|e| WorkspaceError::ReadError(e.to_string()))?;
+        self.record(pet_id, &full_path, None, ChangeAction::Append, before, Some(&after), source);
 
         Ok(format!("成功追加 {} 字节到 {}", bytes, path))
     }
@@ -187,9 +259,16 @@ impl WorkspaceEngine {
         pet_id: &str,
         path: &str,
         base64_data: &str,
+        source: ChangeSource,
     ) -> Result<String, WorkspaceError> {
         let full_path = self.resolve_safe_path(pet_id, path)?;
 
+        // Reject oversized uploads before the payload is decoded into memory
+        let padding = base64_data.bytes().rev().take(2).filter(|&b| b == b'=').count();
+        let decoded_len = (base64_data.len() / 4 * 3).saturating_sub(padding);
+        let before = fs::read(&full_path).ok();
+        self.check_quota(pet_id, before.as_deref(), decoded_len as u64)?;
+
         if let Some(parent) = full_path.parent() {
             fs::create_dir_all(parent).map_err(|e| WorkspaceError::WriteError(e.to_string()))?;
         }
@@ -198,8 +277,19 @@ impl WorkspaceEngine {
             .map_err(|e| WorkspaceError::WriteError(format!("base64 decode failed: {}", e)))?;
         let len = bytes.len();
         fs::write(&full_path, &bytes).map_err(|e| WorkspaceError::WriteError(e.to_string()))?;
+        self.record(pet_id, &full_path, None, ChangeAction::WriteBinary, before, Some(&bytes), source);
+
+        let mime = sniff_mime(&bytes, &full_path);
+        let thumbnail = self.storage.thumbnail_path(pet_id, &self.history_key(pet_id, &full_path));
+        if is_thumbnailable(mime) {
+            if let Err(e) = write_thumbnail(&thumbnail, &bytes) {
+                log::warn!("[Workspace] Failed to create thumbnail for {}: {}", path, e);
+            }
+        } else {
+            let _ = fs::remove_file(&thumbnail);
+        }
 
-        Ok(format!("成功写入 {} 字节到 {}", len, path))
+        Ok(format!("成功写入 {} 字节到 {}（{}）", len, path, mime))
     }
 
     /// Read a file as base64-encoded string (for binary files like images).
@@ -228,6 +318,7 @@ impl WorkspaceEngine {
         path: &str,
         old_text: &str,
         new_text: &str,
+        source: ChangeSource,
     ) -> Result<String, WorkspaceError> {
         let full_path = self.resolve_safe_path(pet_id, path)?;
 
@@ -238,43 +329,147 @@ impl WorkspaceEngine {
         let content =
             fs::read_to_string(&full_path).map_err(|e| WorkspaceError::ReadError(e.to_string()))?;
 
+        let (new_content, fuzzy) = self.replace_text(path, &content, old_text, new_text, false)?;
+
+        self.check_quota(pet_id, Some(content.as_bytes()), new_content.len() as u64)?;
+        fs::write(&full_path, &new_content)
+            .map_err(|e| WorkspaceError::WriteError(e.to_string()))?;
+        self.record(pet_id, &full_path, None, ChangeAction::Edit, Some(content.into_bytes()), Some(new_content.as_bytes()), source);
+
+        if fuzzy {
+            return Ok(format!("成功替换了 {} 中的文本（模糊匹配）\n\n当前文件完整内容如下：\n{}", path, new_content));
+        }
+        Ok(format!("成功替换了 {} 中的文本\n\n当前文件完整内容如下：\n{}", path, new_content))
+    }
+
+    /// One find-and-replace on in-memory content. Returns the new content and
+    /// whether the whitespace-tolerant fallback was used.
+    /// - Without `replace_all`, oldText must match exactly once
+    /// - With `replace_all`, every exact match is replaced (no fuzzy fallback)
+    fn replace_text(
+        &self,
+        path: &str,
+        content: &str,
+        old_text: &str,
+        new_text: &str,
+        replace_all: bool,
+    ) -> Result<(String, bool), WorkspaceError> {
+        if replace_all && old_text.is_empty() {
+            return Err(WorkspaceError::EditNotFound(path.to_string(), content.to_string()));
+        }
+
         // Count exact matches
         let match_count = content.matches(old_text).count();
 
         if match_count == 0 {
+            if replace_all {
+                return Err(WorkspaceError::EditNotFound(path.to_string(), content.to_string()));
+            }
             // Try fuzzy matching (normalize whitespace)
-            match self.fuzzy_edit(&content, old_text, new_text) {
-                Some(new_content) => {
-                    if new_content == content {
-                        return Err(WorkspaceError::EditNoChange(path.to_string()));
-                    }
-                    fs::write(&full_path, &new_content)
-                        .map_err(|e| WorkspaceError::WriteError(e.to_string()))?;
-                    return Ok(format!("成功替换了 {} 中的文本（模糊匹配）\n\n当前文件完整内容如下：\n{}", path, new_content));
+            return match self.fuzzy_edit(content, old_text, new_text) {
+                Some(new_content) if new_content == content => {
+                    Err(WorkspaceError::EditNoChange(path.to_string()))
                 }
-                None => return Err(WorkspaceError::EditNotFound(path.to_string(), content)),
-            }
+                Some(new_content) => Ok((new_content, true)),
+                None => Err(WorkspaceError::EditNotFound(path.to_string(), content.to_string())),
+            };
         }
 
-        if match_count > 1 {
+        if match_count > 1 && !replace_all {
             return Err(WorkspaceError::EditMultipleMatches(
                 path.to_string(),
                 match_count,
-                content,
+                content.to_string(),
             ));
         }
 
-        // Single exact match — replace
-        let new_content = content.replacen(old_text, new_text, 1);
+        let new_content = content.replace(old_text, new_text);
 
         if new_content == content {
             return Err(WorkspaceError::EditNoChange(path.to_string()));
         }
 
-        fs::write(&full_path, &new_content)
-            .map_err(|e| WorkspaceError::WriteError(e.to_string()))?;
+        Ok((new_content, false))
+    }
 
-        Ok(format!("成功替换了 {} 中的文本\n\n当前文件完整内容如下：\n{}", path, new_content))
+    // ============ Batch Edit & Patch ============
+
+    /// Apply `edits` in order to the file. Either every edit applies and the
+    /// file is written once, or nothing is written. With `dry_run` the file is
+    /// never written; the result carries the diff either way.
+    pub fn edit_batch(
+        &self,
+        pet_id: &str,
+        path: &str,
+        edits: &[EditOp],
+        dry_run: bool,
+        source: ChangeSource,
+    ) -> Result<EditPreview, WorkspaceError> {
+        if edits.is_empty() {
+            return Err(WorkspaceError::EditNoChange(path.to_string()));
+        }
+        let (full_path, content) = self.read_for_edit(pet_id, path)?;
+
+        let mut new_content = content.clone();
+        for (index, op) in edits.iter().enumerate() {
+            new_content = self
+                .replace_text(path, &new_content, &op.old_text, &op.new_text, op.replace_all)
+                .map(|(next, _)| next)
+                .map_err(|e| WorkspaceError::BatchFailed(index + 1, e.to_string()))?;
+        }
+
+        self.finish_edit(pet_id, path, &full_path, content, new_content, ChangeAction::Edit, dry_run, source)
+    }
+
+    /// Apply a unified diff to the file; `fuzz` context lines at each hunk edge
+    /// may be ignored when the file has drifted. All hunks apply or none do.
+    pub fn apply_patch(
+        &self,
+        pet_id: &str,
+        path: &str,
+        patch: &str,
+        fuzz: usize,
+        dry_run: bool,
+        source: ChangeSource,
+    ) -> Result<EditPreview, WorkspaceError> {
+        let (full_path, content) = self.read_for_edit(pet_id, path)?;
+        let new_content = apply_unified_patch(&content, patch, fuzz).map_err(WorkspaceError::PatchFailed)?;
+        if new_content == content {
+            return Err(WorkspaceError::EditNoChange(path.to_string()));
+        }
+        self.finish_edit(pet_id, path, &full_path, content, new_content, ChangeAction::Patch, dry_run, source)
+    }
+
+    fn read_for_edit(&self, pet_id: &str, path: &str) -> Result<(PathBuf, String), WorkspaceError> {
+        let full_path = self.resolve_safe_path(pet_id, path)?;
+        if !full_path.exists() {
+            return Err(WorkspaceError::FileNotFound(path.to_string()));
+        }
+        let content =
+            fs::read_to_string(&full_path).map_err(|e| WorkspaceError::ReadError(e.to_string()))?;
+        Ok((full_path, content))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn finish_edit(
+        &self,
+        pet_id: &str,
+        path: &str,
+        full_path: &Path,
+        content: String,
+        new_content: String,
+        action: ChangeAction,
+        dry_run: bool,
+        source: ChangeSource,
+    ) -> Result<EditPreview, WorkspaceError> {
+        let diff = unified_diff(path, &content, &new_content);
+        if !dry_run {
+            self.check_quota(pet_id, Some(content.as_bytes()), new_content.len() as u64)?;
+            fs::write(full_path, &new_content)
+                .map_err(|e| WorkspaceError::WriteError(e.to_string()))?;
+            self.record(pet_id, full_path, None, action, Some(content.into_bytes()), Some(new_content.as_bytes()), source);
+        }
+        Ok(EditPreview { applied: !dry_run, diff, content: new_content })
     }
 
     // ============ Fuzzy Matching ============
@@ -420,11 +615,26 @@ impl WorkspaceEngine {
         Ok(())
     }
 
+    /// Search file contents under `options.path` (default: the whole workspace).
+    /// Hidden files and binaries are skipped; caps in `options` bound the output.
+    pub fn search(
+        &self,
+        pet_id: &str,
+        options: &SearchOptions,
+    ) -> Result<SearchResult, WorkspaceError> {
+        let base = self.resolve_safe_path(pet_id, &options.path)?;
+        if !base.exists() {
+            return Err(WorkspaceError::FileNotFound(options.path.clone()));
+        }
+        search_tree(&self.pet_workspace(pet_id), &base, options)
+    }
+
     /// Delete a single file in the pet's workspace.
     pub fn delete_file(
         &self,
         pet_id: &str,
         path: &str,
+        source: ChangeSource,
     ) -> Result<String, WorkspaceError> {
         let full_path = self.resolve_safe_path(pet_id, path)?;
 
@@ -438,7 +648,10 @@ impl WorkspaceEngine {
             )));
         }
 
+        let before = fs::read(&full_path).ok();
         fs::remove_file(&full_path).map_err(|e| WorkspaceError::IoError(e.to_string()))?;
+        let _ = fs::remove_file(self.storage.thumbnail_path(pet_id, &self.history_key(pet_id, &full_path)));
+        self.record(pet_id, &full_path, None, ChangeAction::Delete, before, None, source);
 
         Ok(format!("已删除 {}", path))
     }
@@ -450,6 +663,7 @@ impl WorkspaceEngine {
         pet_id: &str,
         from: &str,
         to: &str,
+        source: ChangeSource,
     ) -> Result<String, WorkspaceError> {
         let from_path = self.resolve_safe_path(pet_id, from)?;
         let to_path = self.resolve_safe_path(pet_id, to)?;
@@ -476,23 +690,239 @@ impl WorkspaceEngine {
         }
 
         fs::rename(&from_path, &to_path).map_err(|e| WorkspaceError::IoError(e.to_string()))?;
+        let content = fs::read(&to_path).ok();
+        let from_key = self.history_key(pet_id, &from_path);
+        let from_thumbnail = self.storage.thumbnail_path(pet_id, &from_key);
+        if from_thumbnail.exists() {
+            let to_thumbnail = self.storage.thumbnail_path(pet_id, &self.history_key(pet_id, &to_path));
+            if let Some(parent) = to_thumbnail.parent() {
+                let _ = fs::create_dir_all(parent);
+            }
+            let _ = fs::rename(&from_thumbnail, &to_thumbnail);
+        }
+        self.record(pet_id, &to_path, Some(&from_key), ChangeAction::Rename, content.clone(), content.as_deref(), source);
 
         Ok(format!("已将 {} 移动到 {}", from, to))
     }
 
-    /// Delete a pet's entire workspace directory
+    /// Delete a pet's entire workspace directory, history included
     pub fn delete_workspace(&self, pet_id: &str) -> Result<(), WorkspaceError> {
         let workspace = self.pet_workspace(pet_id);
         if workspace.exists() {
             fs::remove_dir_all(&workspace)
                 .map_err(|e| WorkspaceError::IoError(e.to_string()))?;
         }
+        self.history.delete_pet(pet_id).map_err(WorkspaceError::IoError)?;
+        self.storage.delete_pet(pet_id).map_err(WorkspaceError::IoError)?;
         Ok(())
     }
+
+    // ============ History ============
+
+    /// Path relative to the pet's workspace, the key history entries are stored under
+    fn history_key(&self, pet_id: &str, full_path: &Path) -> String {
+        full_path
+            .strip_prefix(self.pet_workspace(pet_id))
+            .map(|p| p.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/"))
+            .unwrap_or_else(|_| full_path.to_string_lossy().to_string())
+    }
+
+    /// Snapshot a mutation and keep the usage total in step.
+    /// A failing history store never fails the write itself.
+    #[allow(clippy::too_many_arguments)]
+    fn record(
+        &self,
+        pet_id: &str,
+        full_path: &Path,
+        from: Option<&str>,
+        action: ChangeAction,
+        before: Option<Vec<u8>>,
+        after: Option<&[u8]>,
+        source: ChangeSource,
+    ) {
+        let old_len = before.as_ref().map_or(0, |b| b.len() as u64);
+        let new_len = after.map_or(0, |a| a.len() as u64);
+        self.storage.adjust_usage(pet_id, old_len, new_len);
+        let key = self.history_key(pet_id, full_path);
+        if let Err(e) = self.history.record(pet_id, &key, from, action, before.as_deref(), after, source) {
+            log::warn!("[Workspace] Failed to record history for {}: {}", key, e);
+        }
+    }
+
+    /// Changes to a file, newest first
+    pub fn file_history(&self, pet_id: &str, path: &str) -> Result<Vec<HistoryEntry>, WorkspaceError> {
+        let full_path = self.resolve_safe_path(pet_id, path)?;
+        Ok(self.history.file_history(pet_id, &self.history_key(pet_id, &full_path)))
+    }
+
+    /// Unified diff between two stored versions of a file (None = file absent)
+    pub fn diff_versions(
+        &self,
+        pet_id: &str,
+        path: &str,
+        from: Option<&str>,
+        to: Option<&str>,
+    ) -> Result<String, WorkspaceError> {
+        let full_path = self.resolve_safe_path(pet_id, path)?;
+        self.history
+            .diff(pet_id, &self.history_key(pet_id, &full_path), from, to)
+            .map_err(WorkspaceError::ReadError)
+    }
+
+    /// Put a stored version back in place; None deletes the file again.
+    /// The restore itself is recorded, so it can be undone too.
+    pub fn restore_version(
+        &self,
+        pet_id: &str,
+        path: &str,
+        hash: Option<&str>,
+        source: ChangeSource,
+    ) -> Result<String, WorkspaceError> {
+        let full_path = self.resolve_safe_path(pet_id, path)?;
+        let content = hash
+            .map(|h| self.history.read_version(pet_id, h))
+            .transpose()
+            .map_err(WorkspaceError::ReadError)?;
+        let before = fs::read(&full_path).ok();
+        if before.is_none() && content.is_none() {
+            return Err(WorkspaceError::FileNotFound(path.to_string()));
+        }
+        match &content {
+            Some(bytes) => {
+                self.check_quota(pet_id, before.as_deref(), bytes.len() as u64)?;
+                if let Some(parent) = full_path.parent() {
+                    fs::create_dir_all(parent).map_err(|e| WorkspaceError::WriteError(e.to_string()))?;
+                }
+                fs::write(&full_path, bytes).map_err(|e| WorkspaceError::WriteError(e.to_string()))?;
+            }
+            None => fs::remove_file(&full_path).map_err(|e| WorkspaceError::IoError(e.to_string()))?,
+        }
+        self.record(pet_id, &full_path, None, ChangeAction::Restore, before, content.as_deref(), source);
+        Ok(format!("已恢复 {}", path))
+    }
+
+    pub fn history_retention(&self) -> HistoryRetention {
+        self.history.retention()
+    }
+
+    pub fn set_history_retention(&self, retention: &HistoryRetention) -> Result<(), WorkspaceError> {
+        self.history.set_retention(retention).map_err(WorkspaceError::IoError)
+    }
+
+    // ============ Storage ============
+
+    /// Refuse a write of `new_len` bytes over `before` if it would break the pet's quota
+    fn check_quota(&self, pet_id: &str, before: Option<&[u8]>, new_len: u64) -> Result<(), WorkspaceError> {
+        let old_len = before.map_or(0, |b| b.len() as u64);
+        self.storage
+            .check_write(pet_id, &self.pet_workspace(pet_id), old_len, new_len)
+            .map_err(WorkspaceError::QuotaExceeded)
+    }
+
+    /// Disk usage by directory (down to `depth` levels) and by content type
+    pub fn usage_report(&self, pet_id: &str, depth: usize) -> UsageReport {
+        self.storage.report(pet_id, &self.pet_workspace(pet_id), depth)
+    }
+
+    /// A pet's effective quota, or the default one for `None`
+    pub fn quota(&self, pet_id: Option<&str>) -> StorageQuota {
+        match pet_id {
+            Some(pet_id) => self.storage.quota(pet_id),
+            None => self.storage.default_quota(),
+        }
+    }
+
+    /// Set the default quota or a pet's override; `quota` None resets it
+    pub fn set_quota(&self, pet_id: Option<&str>, quota: Option<StorageQuota>) -> Result<(), WorkspaceError> {
+        self.storage.set_quota(pet_id, quota).map_err(WorkspaceError::IoError)
+    }
+
+    /// Size, sniffed MIME type and modification time of a file
+    pub fn file_info(&self, pet_id: &str, path: &str) -> Result<FileInfo, WorkspaceError> {
+        let full_path = self.resolve_safe_path(pet_id, path)?;
+        let metadata = fs::metadata(&full_path).map_err(|_| WorkspaceError::FileNotFound(path.to_string()))?;
+        if metadata.is_dir() {
+            return Err(WorkspaceError::IoError(format!("{} is a directory, not a file", path)));
+        }
+        let mime = sniff_file(&full_path);
+        Ok(FileInfo {
+            path: self.history_key(pet_id, &full_path),
+            size: metadata.len(),
+            mime: mime.to_string(),
+            modified: metadata
+                .modified()
+                .map(|t| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339())
+                .unwrap_or_default(),
+            has_thumbnail: is_thumbnailable(mime),
+        })
+    }
+
+    /// Base64 PNG thumbnail of an image file. Thumbnails are made when an image is
+    /// written through `write_binary` and rebuilt here when missing or older than the file.
+    pub fn thumbnail(&self, pet_id: &str, path: &str) -> Result<String, WorkspaceError> {
+        let full_path = self.resolve_safe_path(pet_id, path)?;
+        let modified = fs::metadata(&full_path)
+            .and_then(|m| m.modified())
+            .map_err(|_| WorkspaceError::FileNotFound(path.to_string()))?;
+        let thumbnail = self.storage.thumbnail_path(pet_id, &self.history_key(pet_id, &full_path));
+        let fresh = fs::metadata(&thumbnail)
+            .and_then(|m| m.modified())
+            .is_ok_and(|t| t >= modified);
+        if !fresh {
+            if !is_thumbnailable(sniff_file(&full_path)) {
+                return Err(WorkspaceError::IoError(format!("{} 不是可以生成缩略图的图片", path)));
+            }
+            let bytes = fs::read(&full_path).map_err(|e| WorkspaceError::ReadError(e.to_string()))?;
+            write_thumbnail(&thumbnail, &bytes).map_err(WorkspaceError::ReadError)?;
+        }
+        let png = fs::read(&thumbnail).map_err(|e| WorkspaceError::ReadError(e.to_string()))?;
+        Ok(BASE64.encode(png))
+    }
+
+    /// Large or stale files under `options.roots` (social/ and training data by default)
+    pub fn cleanup_scan(&self, pet_id: &str, options: &CleanupOptions) -> Result<CleanupReport, WorkspaceError> {
+        let mut roots = Vec::new();
+        for root in &options.roots {
+            let full_path = self.resolve_safe_path(pet_id, root)?;
+            if full_path.is_dir() {
+                roots.push(full_path);
+            }
+        }
+        scan_cleanup(&self.pet_workspace(pet_id), &roots, options).map_err(WorkspaceError::InvalidPattern)
+    }
+
+    /// Delete the given files (usually picked from `cleanup_scan`). Each deletion is
+    /// recorded in history like `delete_file`; failures do not stop the rest.
+    pub fn cleanup_delete(&self, pet_id: &str, paths: &[String], source: ChangeSource) -> CleanupResult {
+        let mut result = CleanupResult::default();
+        for path in paths {
+            let size = self
+                .resolve_safe_path(pet_id, path)
+                .ok()
+                .and_then(|p| fs::metadata(p).ok())
+                .map_or(0, |m| m.len());
+            match self.delete_file(pet_id, path, source) {
+                Ok(_) => {
+                    result.deleted.push(path.clone());
+                    result.freed_bytes += size;
+                }
+                Err(e) => result.failed.push(CleanupFailure { path: path.clone(), error: e.to_string() }),
+            }
+        }
+        result
+    }
 }
 
 // ============ Utility Functions ============
 
+fn write_thumbnail(target: &Path, image: &[u8]) -> Result<(), String> {
+    let png = render_thumbnail(image)?;
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    fs::write(target, png).map_err(|e| e.to_string())
+}
+
 /// Normalize a path by resolving `.` and `..` components without filesystem access
 fn normalize_path(path: &Path) -> PathBuf {
     let mut components = Vec::new();
@@ -675,7 +1105,7 @@ mod tests {
         let pet_id = "test-pet";
 
         // Write
-        let result = engine.write(pet_id, "test.md", "hello world");
+        let result = engine.write(pet_id, "test.md", "hello world", ChangeSource::User);
         assert!(result.is_ok());
 
         // Read
@@ -690,14 +1120,14 @@ mod tests {
         let (tmp, engine) = setup_test_workspace();
         let pet_id = "test-pet";
 
-        engine.write(pet_id, "test.md", "hello world, hello rust").unwrap();
+        engine.write(pet_id, "test.md", "hello world, hello rust", ChangeSource::User).unwrap();
 
         // Should fail: "hello" matches twice
-        let result = engine.edit(pet_id, "test.md", "hello", "hi");
+        let result = engine.edit(pet_id, "test.md", "hello", "hi", ChangeSource::User);
         assert!(result.is_err());
 
         // Should succeed: unique match
-        let result = engine.edit(pet_id, "test.md", "hello world", "hi world");
+        let result = engine.edit(pet_id, "test.md", "hello world", "hi world", ChangeSource::User);
         assert!(result.is_ok());
 
         let content = engine.read(pet_id, "test.md").unwrap();
@@ -744,10 +1174,10 @@ mod tests {
         let (tmp, engine) = setup_test_workspace();
         let pet_id = "test-pet";
 
-        engine.write(pet_id, "test.md", "hello world").unwrap();
+        engine.write(pet_id, "test.md", "hello world", ChangeSource::User).unwrap();
 
         // Replacing with same text should error
-        let result = engine.edit(pet_id, "test.md", "hello", "hello");
+        let result = engine.edit(pet_id, "test.md", "hello", "hello", ChangeSource::User);
         assert!(result.is_err());
 
         cleanup(&tmp);
@@ -761,9 +1191,192 @@ mod tests {
         let result = engine.read(pet_id, "nonexistent.md");
         assert!(result.is_err());
 
-        let result = engine.edit(pet_id, "nonexistent.md", "a", "b");
+        let result = engine.edit(pet_id, "nonexistent.md", "a", "b", ChangeSource::User);
         assert!(result.is_err());
 
         cleanup(&tmp);
     }
+
+    #[test]
+    fn test_history_restore() {
+        let (tmp, engine) = setup_test_workspace();
+        let pet_id = "test-pet";
+
+        engine.write(pet_id, "MEMORY.md", "likes cats\n", ChangeSource::User).unwrap();
+        engine.edit(pet_id, "./MEMORY.md", "cats", "dogs", ChangeSource::Tool).unwrap();
+        engine.rename_file(pet_id, "MEMORY.md", "notes/MEMORY.md", ChangeSource::Tool).unwrap();
+
+        let history = engine.file_history(pet_id, "MEMORY.md").unwrap();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[0].action, ChangeAction::Rename);
+        assert_eq!(history[1].source, ChangeSource::Tool);
+        assert_eq!(history[2].before, None);
+
+        let diff = engine
+            .diff_versions(pet_id, "MEMORY.md", history[1].before.as_deref(), history[1].after.as_deref())
+            .unwrap();
+        assert!(diff.contains("-likes cats") && diff.contains("+likes dogs"));
+
+        engine.restore_version(pet_id, "MEMORY.md", history[2].after.as_deref(), ChangeSource::User).unwrap();
+        assert_eq!(engine.read(pet_id, "MEMORY.md").unwrap(), "likes cats\n");
+        assert_eq!(engine.file_history(pet_id, "MEMORY.md").unwrap()[0].action, ChangeAction::Restore);
+
+        // History lives outside the pet folder and goes away with it
+        assert!(!engine.list_dir(pet_id, "").unwrap().iter().any(|p| p.contains("history")));
+        engine.delete_workspace(pet_id).unwrap();
+        assert!(engine.file_history(pet_id, "MEMORY.md").unwrap().is_empty());
+
+        cleanup(&tmp);
+    }
+
+    #[test]
+    fn test_history_retention_collects_objects() {
+        let (tmp, engine) = setup_test_workspace();
+        let pet_id = "test-pet";
+        engine
+            .set_history_retention(&HistoryRetention { max_versions_per_file: 2, ..Default::default() })
+            .unwrap();
+
+        for n in 0..4 {
+            engine.write(pet_id, "a.md", &format!("v{}", n), ChangeSource::User).unwrap();
+        }
+        let history = engine.file_history(pet_id, "a.md").unwrap();
+        assert_eq!(history.len(), 2);
+        let objects = fs::read_dir(tmp.join(".history").join(pet_id).join("objects")).unwrap().count();
+        // v1 (before of the oldest kept entry), v2, v3
+        assert_eq!(objects, 3);
+
+        cleanup(&tmp);
+    }
+
+    #[test]
+    fn test_search() {
+        let (tmp, engine) = setup_test_workspace();
+        let pet_id = "test-pet";
+
+        engine.write(pet_id, "MEMORY.md", "# Memory\nlikes Tea\nowns a cat\n", ChangeSource::User).unwrap();
+        engine.write(pet_id, "social/people/1.md", "tea time\n", ChangeSource::User).unwrap();
+        engine.write(pet_id, "notes.txt", "tea\n", ChangeSource::User).unwrap();
+
+        let options = SearchOptions { query: "tea".into(), include: vec!["**/*.md".into()], context_lines: 1, ..Default::default() };
+        let result = engine.search(pet_id, &options).unwrap();
+        let hits: Vec<_> = result.matches.iter().map(|m| (m.path.as_str(), m.line_number)).collect();
+        assert_eq!(hits, vec![("MEMORY.md", 2), ("social/people/1.md", 1)]);
+        assert_eq!(result.matches[0].before, vec!["# Memory"]);
+        assert_eq!(result.matches[0].after, vec!["owns a cat"]);
+        assert!(result.to_text().contains("MEMORY.md:2: likes Tea"));
+
+        let options = SearchOptions { query: r"^t\w+ time$".into(), regex: true, case_sensitive: true, path: "social".into(), ..Default::default() };
+        assert_eq!(engine.search(pet_id, &options).unwrap().matches.len(), 1);
+
+        let options = SearchOptions { query: "tea".into(), max_results: 1, ..Default::default() };
+        let result = engine.search(pet_id, &options).unwrap();
+        assert!(result.truncated && result.matches.len() == 1);
+
+        let options = SearchOptions { query: "tea".into(), path: "../".into(), ..Default::default() };
+        assert!(engine.search(pet_id, &options).is_err());
+        let options = SearchOptions { query: "(".into(), regex: true, ..Default::default() };
+        assert!(matches!(engine.search(pet_id, &options), Err(WorkspaceError::InvalidPattern(_))));
+
+        cleanup(&tmp);
+    }
+
+    #[test]
+    fn test_edit_batch_is_atomic() {
+        let (tmp, engine) = setup_test_workspace();
+        let pet_id = "test-pet";
+        engine.write(pet_id, "MEMORY.md", "- tea\n- cat\n- tea again\n", ChangeSource::User).unwrap();
+
+        let op = |old: &str, new: &str, replace_all| EditOp { old_text: old.into(), new_text: new.into(), replace_all };
+
+        // Second edit fails, so the first must not be written either
+        let result = engine.edit_batch(pet_id, "MEMORY.md", &[op("cat", "dog", false), op("bird", "fish", false)], false, ChangeSource::Tool);
+        assert!(matches!(result, Err(WorkspaceError::BatchFailed(2, _))));
+        assert_eq!(engine.read(pet_id, "MEMORY.md").unwrap(), "- tea\n- cat\n- tea again\n");
+
+        let edits = [op("tea", "coffee", true), op("cat", "dog", false)];
+        let preview = engine.edit_batch(pet_id, "MEMORY.md", &edits, true, ChangeSource::Tool).unwrap();
+        assert!(!preview.applied && preview.diff.contains("+- coffee again"));
+        assert_eq!(engine.read(pet_id, "MEMORY.md").unwrap(), "- tea\n- cat\n- tea again\n");
+
+        let applied = engine.edit_batch(pet_id, "MEMORY.md", &edits, false, ChangeSource::Tool).unwrap();
+        assert!(applied.applied);
+        assert_eq!(engine.read(pet_id, "MEMORY.md").unwrap(), "- coffee\n- dog\n- coffee again\n");
+        assert_eq!(engine.file_history(pet_id, "MEMORY.md").unwrap().len(), 2);
+
+        cleanup(&tmp);
+    }
+
+    #[test]
+    fn test_apply_patch() {
+        let (tmp, engine) = setup_test_workspace();
+        let pet_id = "test-pet";
+        engine.write(pet_id, "MEMORY.md", "a\nb\nc\n", ChangeSource::User).unwrap();
+
+        let patch = "@@ -2,2 +2,2 @@\n b\n-c\n+C\n";
+        let preview = engine.apply_patch(pet_id, "MEMORY.md", patch, 0, true, ChangeSource::Tool).unwrap();
+        assert_eq!(preview.content, "a\nb\nC\n");
+        assert_eq!(engine.read(pet_id, "MEMORY.md").unwrap(), "a\nb\nc\n");
+
+        engine.apply_patch(pet_id, "MEMORY.md", patch, 0, false, ChangeSource::Tool).unwrap();
+        assert_eq!(engine.read(pet_id, "MEMORY.md").unwrap(), "a\nb\nC\n");
+        assert_eq!(engine.file_history(pet_id, "MEMORY.md").unwrap()[0].action, ChangeAction::Patch);
+
+        // Already applied: the hunk no longer fits
+        assert!(matches!(
+            engine.apply_patch(pet_id, "MEMORY.md", patch, 0, false, ChangeSource::Tool),
+            Err(WorkspaceError::PatchFailed(_))
+        ));
+
+        cleanup(&tmp);
+    }
+
+    #[test]
+    fn test_quota_and_thumbnails() {
+        let (tmp, engine) = setup_test_workspace();
+        let pet_id = "test-pet";
+        engine
+            .set_quota(Some(pet_id), Some(StorageQuota { max_workspace_bytes: 1000, max_file_bytes: 600 }))
+            .unwrap();
+
+        engine.write(pet_id, "social/a.md", &"a".repeat(500), ChangeSource::Tool).unwrap();
+        assert!(matches!(
+            engine.write(pet_id, "social/b.md", &"b".repeat(601), ChangeSource::Tool),
+            Err(WorkspaceError::QuotaExceeded(_))
+        ));
+        engine.write(pet_id, "social/b.md", &"b".repeat(400), ChangeSource::Tool).unwrap();
+        // 900 used: growing past 1000 fails, nothing is written
+        assert!(matches!(
+            engine.append(pet_id, "social/b.md", &"b".repeat(200), ChangeSource::Tool),
+            Err(WorkspaceError::QuotaExceeded(_))
+        ));
+        assert_eq!(engine.read(pet_id, "social/b.md").unwrap().len(), 400);
+        // Shrinking is always allowed, and frees room
+        engine.write(pet_id, "social/a.md", "a", ChangeSource::Tool).unwrap();
+        engine.append(pet_id, "social/b.md", &"b".repeat(200), ChangeSource::Tool).unwrap();
+        assert_eq!(engine.usage_report(pet_id, 1).total_bytes, 601);
+
+        let options = CleanupOptions { min_size_bytes: 500, older_than_days: 0, ..Default::default() };
+        let report = engine.cleanup_scan(pet_id, &options).unwrap();
+        let found: Vec<_> = report.candidates.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(found, vec!["social/b.md"]);
+        let result = engine.cleanup_delete(pet_id, &["social/b.md".to_string(), "missing.md".to_string()], ChangeSource::User);
+        assert_eq!((result.deleted.len(), result.freed_bytes, result.failed.len()), (1, 600, 1));
+        // Oversized uploads are refused from their encoded length, before decoding
+        assert!(matches!(
+            engine.write_binary(pet_id, "social/big.bin", &"!".repeat(1000), ChangeSource::Tool),
+            Err(WorkspaceError::QuotaExceeded(_))
+        ));
+        engine.set_quota(Some(pet_id), None).unwrap();
+
+        let mut png = std::io::Cursor::new(Vec::new());
+        image::RgbImage::new(512, 512).write_to(&mut png, image::ImageFormat::Png).unwrap();
+        engine.write_binary(pet_id, "social/images/cat.png", &BASE64.encode(png.get_ref()), ChangeSource::Tool).unwrap();
+        let thumbnail = BASE64.decode(engine.thumbnail(pet_id, "social/images/cat.png").unwrap()).unwrap();
+        assert_eq!(image::load_from_memory(&thumbnail).unwrap().width(), 256);
+        assert_eq!(engine.file_info(pet_id, "social/images/cat.png").unwrap().mime, "image/png");
+        assert!(engine.thumbnail(pet_id, "social/a.md").is_err());
+
+        cleanup(&tmp);
+    }
 }
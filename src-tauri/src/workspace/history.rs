@@ -0,0 +1,351 @@
+// WorkspaceHistory: snapshots of every workspace mutation so bad rewrites can be undone
+// Layout: {root_dir}/.history/{pet_id}/objects/{sha256}  (content-addressed blobs)
+//         {root_dir}/.history/{pet_id}/log.jsonl         (one HistoryEntry per change)
+//         {root_dir}/.history/retention.json             (HistoryRetention, shared by all pets)
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use similar::TextDiff;
+
+/// Directory under the workspace root that holds history; hidden so `list_dir` skips it
+pub const HISTORY_DIR: &str = ".history";
+
+/// Who made a change
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeSource {
+    /// Edited from the UI
+    #[default]
+    User,
+    /// Written by a tool call (built-in tools, MCP host)
+    Tool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeAction {
+    Write,
+    Append,
+    Edit,
+    Patch,
+    WriteBinary,
+    Delete,
+    Rename,
+    Restore,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    /// Increasing per pet
+    pub id: u64,
+    pub path: String,
+    /// Previous path of a rename
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    pub action: ChangeAction,
+    /// Content hash before the change; None when the file did not exist
+    pub before: Option<String>,
+    /// Content hash after the change; None when the file was deleted
+    pub after: Option<String>,
+    pub source: ChangeSource,
+    /// Size in bytes after the change
+    pub size: u64,
+    pub timestamp: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HistoryRetention {
+    /// Entries kept per file; older ones are pruned
+    pub max_versions_per_file: usize,
+    /// Entries older than this are pruned (0 keeps them forever).
+    /// The newest entry of each file is always kept.
+    pub max_age_days: u32,
+    /// Larger contents are logged by hash but not stored
+    pub max_snapshot_bytes: u64,
+}
+
+impl Default for HistoryRetention {
+    fn default() -> Self {
+        Self {
+            max_versions_per_file: 50,
+            max_age_days: 90,
+            max_snapshot_bytes: 10 * 1024 * 1024,
+        }
+    }
+}
+
+pub fn content_hash(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+pub struct WorkspaceHistory {
+    dir: PathBuf,
+    /// Serializes log rewrites within this process
+    lock: Mutex<()>,
+}
+
+impl WorkspaceHistory {
+    pub fn new(workspace_root: &std::path::Path) -> Self {
+        Self {
+            dir: workspace_root.join(HISTORY_DIR),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn pet_dir(&self, pet_id: &str) -> PathBuf {
+        self.dir.join(pet_id)
+    }
+
+    fn log_path(&self, pet_id: &str) -> PathBuf {
+        self.pet_dir(pet_id).join("log.jsonl")
+    }
+
+    fn object_path(&self, pet_id: &str, hash: &str) -> PathBuf {
+        self.pet_dir(pet_id).join("objects").join(hash)
+    }
+
+    // ============ Retention ============
+
+    pub fn retention(&self) -> HistoryRetention {
+        fs::read_to_string(self.dir.join("retention.json"))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn set_retention(&self, retention: &HistoryRetention) -> Result<(), String> {
+        if retention.max_versions_per_file == 0 {
+            return Err("maxVersionsPerFile must be at least 1".to_string());
+        }
+        fs::create_dir_all(&self.dir).map_err(|e| e.to_string())?;
+        let json = serde_json::to_string_pretty(retention).map_err(|e| e.to_string())?;
+        fs::write(self.dir.join("retention.json"), json).map_err(|e| e.to_string())
+    }
+
+    // ============ Recording ============
+
+    /// Store `after` (and `before` if not stored yet) and append a log entry.
+    /// Pruning runs right away so the store never grows past the retention limits.
+    #[allow(clippy::too_many_arguments)]
+    pub fn record(
+        &self,
+        pet_id: &str,
+        path: &str,
+        from: Option<&str>,
+        action: ChangeAction,
+        before: Option<&[u8]>,
+        after: Option<&[u8]>,
+        source: ChangeSource,
+    ) -> Result<HistoryEntry, String> {
+        let _guard = self.lock.lock().unwrap();
+        let retention = self.retention();
+        let before = before.map(|content| self.store(pet_id, content, &retention)).transpose()?;
+        let after_hash = after.map(|content| self.store(pet_id, content, &retention)).transpose()?;
+
+        let mut entries = self.load(pet_id);
+        let entry = HistoryEntry {
+            id: entries.last().map(|e| e.id + 1).unwrap_or(1),
+            path: path.to_string(),
+            from: from.map(|s| s.to_string()),
+            action,
+            before,
+            after: after_hash,
+            source,
+            size: after.map(|c| c.len() as u64).unwrap_or(0),
+            timestamp: Utc::now().to_rfc3339(),
+        };
+        entries.push(entry.clone());
+
+        let kept = prune(&entries, &retention, Utc::now());
+        if kept.len() == entries.len() {
+            self.append_line(pet_id, &entry)?;
+        } else {
+            self.rewrite(pet_id, &kept)?;
+            self.collect_garbage(pet_id, &kept);
+        }
+        Ok(entry)
+    }
+
+    fn store(&self, pet_id: &str, content: &[u8], retention: &HistoryRetention) -> Result<String, String> {
+        let hash = content_hash(content);
+        let path = self.object_path(pet_id, &hash);
+        if content.len() as u64 <= retention.max_snapshot_bytes && !path.exists() {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+            }
+            fs::write(&path, content).map_err(|e| e.to_string())?;
+        }
+        Ok(hash)
+    }
+
+    fn append_line(&self, pet_id: &str, entry: &HistoryEntry) -> Result<(), String> {
+        fs::create_dir_all(self.pet_dir(pet_id)).map_err(|e| e.to_string())?;
+        let line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.log_path(pet_id))
+            .map_err(|e| e.to_string())?;
+        writeln!(file, "{}", line).map_err(|e| e.to_string())
+    }
+
+    fn rewrite(&self, pet_id: &str, entries: &[HistoryEntry]) -> Result<(), String> {
+        let mut out = String::new();
+        for entry in entries {
+            out.push_str(&serde_json::to_string(entry).map_err(|e| e.to_string())?);
+            out.push('\n');
+        }
+        let tmp = self.pet_dir(pet_id).join("log.jsonl.tmp");
+        fs::write(&tmp, out).map_err(|e| e.to_string())?;
+        fs::rename(&tmp, self.log_path(pet_id)).map_err(|e| e.to_string())
+    }
+
+    /// Remove objects no remaining entry refers to
+    fn collect_garbage(&self, pet_id: &str, entries: &[HistoryEntry]) {
+        let referenced: HashSet<&str> = entries
+            .iter()
+            .flat_map(|e| [e.before.as_deref(), e.after.as_deref()])
+            .flatten()
+            .collect();
+        let Ok(objects) = fs::read_dir(self.pet_dir(pet_id).join("objects")) else { return };
+        for object in objects.flatten() {
+            let name = object.file_name().to_string_lossy().to_string();
+            if !referenced.contains(name.as_str()) {
+                let _ = fs::remove_file(object.path());
+            }
+        }
+    }
+
+    // ============ Queries ============
+
+    fn load(&self, pet_id: &str) -> Vec<HistoryEntry> {
+        fs::read_to_string(self.log_path(pet_id))
+            .map(|s| {
+                s.lines()
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Changes that touched `path` (including renames away from it), newest first
+    pub fn file_history(&self, pet_id: &str, path: &str) -> Vec<HistoryEntry> {
+        let mut entries: Vec<_> = self
+            .load(pet_id)
+            .into_iter()
+            .filter(|e| e.path == path || e.from.as_deref() == Some(path))
+            .collect();
+        entries.reverse();
+        entries
+    }
+
+    /// Content of a stored version
+    pub fn read_version(&self, pet_id: &str, hash: &str) -> Result<Vec<u8>, String> {
+        if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(format!("Invalid version hash: {}", hash));
+        }
+        fs::read(self.object_path(pet_id, hash))
+            .map_err(|_| format!("Version {} is not retained", hash))
+    }
+
+    /// Unified diff between two versions; None stands for "file did not exist"
+    pub fn diff(&self, pet_id: &str, path: &str, from: Option<&str>, to: Option<&str>) -> Result<String, String> {
+        let load = |hash: Option<&str>| -> Result<Vec<u8>, String> {
+            hash.map(|h| self.read_version(pet_id, h)).transpose().map(Option::unwrap_or_default)
+        };
+        let (old, new) = (load(from)?, load(to)?);
+        match (std::str::from_utf8(&old), std::str::from_utf8(&new)) {
+            (Ok(old), Ok(new)) => Ok(unified_diff(path, old, new)),
+            _ if old == new => Ok(String::new()),
+            _ => Ok(format!("Binary files a/{} and b/{} differ\n", path, path)),
+        }
+    }
+
+    pub fn delete_pet(&self, pet_id: &str) -> Result<(), String> {
+        let dir = self.pet_dir(pet_id);
+        if dir.exists() {
+            fs::remove_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+}
+
+pub fn unified_diff(path: &str, old: &str, new: &str) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("a/{}", path), &format!("b/{}", path))
+        .to_string()
+}
+
+/// Entries that survive `retention`, in their original order
+fn prune(entries: &[HistoryEntry], retention: &HistoryRetention, now: DateTime<Utc>) -> Vec<HistoryEntry> {
+    let cutoff = (retention.max_age_days > 0).then(|| now - Duration::days(retention.max_age_days as i64));
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    let mut keep = vec![false; entries.len()];
+    for (index, entry) in entries.iter().enumerate().rev() {
+        let count = seen.entry(entry.path.as_str()).or_insert(0);
+        *count += 1;
+        let expired = cutoff.is_some_and(|cutoff| {
+            DateTime::parse_from_rfc3339(&entry.timestamp)
+                .map(|t| t.with_timezone(&Utc) < cutoff)
+                .unwrap_or(false)
+        });
+        keep[index] = *count == 1 || (*count <= retention.max_versions_per_file && !expired);
+    }
+    entries
+        .iter()
+        .zip(keep)
+        .filter(|(_, keep)| *keep)
+        .map(|(entry, _)| entry.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: u64, path: &str, timestamp: &str) -> HistoryEntry {
+        HistoryEntry {
+            id,
+            path: path.to_string(),
+            from: None,
+            action: ChangeAction::Write,
+            before: None,
+            after: None,
+            source: ChangeSource::User,
+            size: 0,
+            timestamp: timestamp.to_string(),
+        }
+    }
+
+    #[test]
+    fn prune_keeps_newest_per_file() {
+        let now = Utc::now();
+        let old = (now - Duration::days(100)).to_rfc3339();
+        let fresh = now.to_rfc3339();
+        let entries = vec![
+            entry(1, "a.md", &old),
+            entry(2, "b.md", &old),
+            entry(3, "a.md", &fresh),
+            entry(4, "a.md", &fresh),
+            entry(5, "a.md", &fresh),
+        ];
+        let retention = HistoryRetention { max_versions_per_file: 2, ..Default::default() };
+        let ids: Vec<u64> = prune(&entries, &retention, now).iter().map(|e| e.id).collect();
+        // b.md keeps its only (expired) entry; a.md keeps the newest two
+        assert_eq!(ids, vec![2, 4, 5]);
+    }
+}
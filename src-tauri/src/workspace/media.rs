@@ -0,0 +1,167 @@
+// Content sniffing and image thumbnails for workspace attachments
+// The MIME type comes from the leading bytes first and the extension only for
+// text, so a PNG saved as `photo.txt` still reports image/png.
+
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use image::imageops::FilterType;
+use image::ImageFormat;
+use serde::{Deserialize, Serialize};
+
+/// Longest edge of a generated thumbnail, in pixels
+pub const THUMBNAIL_MAX_EDGE: u32 = 256;
+
+/// Bytes read from the start of a file for sniffing
+const SNIFF_BYTES: usize = 512;
+
+/// Images larger than this are not decoded for thumbnails
+const MAX_THUMBNAIL_SOURCE_BYTES: usize = 40 * 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FileInfo {
+    pub path: String,
+    pub size: u64,
+    /// Sniffed from the content, not just the extension
+    pub mime: String,
+    /// RFC 3339
+    pub modified: String,
+    /// `workspace_thumbnail` can serve a preview
+    pub has_thumbnail: bool,
+}
+
+/// Detect the MIME type of `bytes` (usually just the first few hundred)
+pub fn sniff_mime(bytes: &[u8], path: &Path) -> &'static str {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+        (b"ID3", "audio/mpeg"),
+        (b"OggS", "audio/ogg"),
+        (b"fLaC", "audio/flac"),
+        (b"#!SILK_V3", "audio/silk"),
+        (b"\x02#!SILK_V3", "audio/silk"),
+    ];
+    for (magic, mime) in SIGNATURES {
+        if bytes.starts_with(magic) {
+            return mime;
+        }
+    }
+    // "BM" alone is too common in text; the header's reserved bytes must be zero too
+    if bytes.len() >= 14 && bytes.starts_with(b"BM") && bytes[6..10] == [0, 0, 0, 0] {
+        return "image/bmp";
+    }
+    if bytes.len() >= 12 && bytes.starts_with(b"RIFF") {
+        match &bytes[8..12] {
+            b"WEBP" => return "image/webp",
+            b"WAVE" => return "audio/wav",
+            _ => {}
+        }
+    }
+    if bytes.len() >= 12 && &bytes[4..8] == b"ftyp" {
+        return match &bytes[8..12] {
+            b"M4A " => "audio/mp4",
+            b"heic" | b"heix" | b"mif1" => "image/heic",
+            _ => "video/mp4",
+        };
+    }
+    // MP3 frame sync without an ID3 tag
+    if bytes.len() >= 2 && bytes[0] == 0xff && bytes[1] & 0xe0 == 0xe0 {
+        return "audio/mpeg";
+    }
+
+    if !looks_like_text(bytes) {
+        return "application/octet-stream";
+    }
+    match path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase).as_deref() {
+        Some("md") | Some("markdown") => "text/markdown",
+        Some("json") => "application/json",
+        Some("jsonl") => "application/x-ndjson",
+        Some("yaml") | Some("yml") => "application/yaml",
+        Some("toml") => "application/toml",
+        Some("csv") => "text/csv",
+        Some("html") | Some("htm") => "text/html",
+        Some("svg") => "image/svg+xml",
+        _ => "text/plain",
+    }
+}
+
+/// No NUL bytes and valid UTF-8, allowing a multi-byte character cut off at the end
+fn looks_like_text(bytes: &[u8]) -> bool {
+    if bytes.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none() && bytes.len() - e.valid_up_to() < 4,
+    }
+}
+
+/// Sniff a file on disk from its first bytes
+pub fn sniff_file(path: &Path) -> &'static str {
+    let mut head = Vec::with_capacity(SNIFF_BYTES);
+    if let Ok(file) = fs::File::open(path) {
+        let _ = file.take(SNIFF_BYTES as u64).read_to_end(&mut head);
+    }
+    sniff_mime(&head, path)
+}
+
+/// Raster formats the `image` crate can decode into a thumbnail
+pub fn is_thumbnailable(mime: &str) -> bool {
+    matches!(mime, "image/png" | "image/jpeg" | "image/gif" | "image/webp" | "image/bmp")
+}
+
+/// Downscale an image so its longest edge is at most `THUMBNAIL_MAX_EDGE`, encoded as PNG.
+/// Images already that small are re-encoded unchanged.
+pub fn render_thumbnail(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    if bytes.len() > MAX_THUMBNAIL_SOURCE_BYTES {
+        return Err("image too large for a thumbnail".to_string());
+    }
+    let image = image::load_from_memory(bytes).map_err(|e| e.to_string())?;
+    let thumbnail = if image.width() > THUMBNAIL_MAX_EDGE || image.height() > THUMBNAIL_MAX_EDGE {
+        image.resize(THUMBNAIL_MAX_EDGE, THUMBNAIL_MAX_EDGE, FilterType::Triangle)
+    } else {
+        image
+    };
+    let mut out = std::io::Cursor::new(Vec::new());
+    thumbnail
+        .write_to(&mut out, ImageFormat::Png)
+        .map_err(|e| e.to_string())?;
+    Ok(out.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sniffs_content_before_extension() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+        assert_eq!(sniff_mime(png, Path::new("photo.txt")), "image/png");
+        assert_eq!(sniff_mime(b"RIFF\0\0\0\0WEBPVP8 ", Path::new("a.bin")), "image/webp");
+        assert_eq!(sniff_mime("# 记忆\n- 喜欢茶".as_bytes(), Path::new("MEMORY.md")), "text/markdown");
+        assert_eq!(sniff_mime(b"{\"a\":1}\n", Path::new("2026-01-01.jsonl")), "application/x-ndjson");
+        assert_eq!(sniff_mime(b"plain\0binary", Path::new("notes.md")), "application/octet-stream");
+        // A UTF-8 character cut at the sniff boundary is still text
+        let cut = &"你好".as_bytes()[..4];
+        assert_eq!(sniff_mime(cut, Path::new("a.txt")), "text/plain");
+    }
+
+    #[test]
+    fn thumbnails_fit_the_max_edge() {
+        let source = image::RgbaImage::from_pixel(600, 300, image::Rgba([200, 80, 40, 255]));
+        let mut png = std::io::Cursor::new(Vec::new());
+        source.write_to(&mut png, ImageFormat::Png).unwrap();
+
+        let thumbnail = render_thumbnail(png.get_ref()).unwrap();
+        let decoded = image::load_from_memory(&thumbnail).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (THUMBNAIL_MAX_EDGE, THUMBNAIL_MAX_EDGE / 2));
+        assert!(render_thumbnail(b"not an image").is_err());
+    }
+}
@@ -2,8 +2,19 @@
 // Replaces the old pets.system_instruction / pets.user_memory / longTimeMemory() pipeline
 
 pub mod engine;
-
-pub use engine::WorkspaceEngine;
+pub mod history;
+pub mod media;
+pub mod patch;
+pub mod search;
+pub mod storage;
+pub mod watcher;
+
+pub use engine::{EditOp, EditPreview, WorkspaceEngine};
+pub use history::{ChangeSource, HistoryEntry, HistoryRetention};
+pub use media::FileInfo;
+pub use search::{SearchOptions, SearchResult};
+pub use storage::{CleanupOptions, CleanupReport, CleanupResult, StorageQuota, UsageReport};
+pub use watcher::WorkspaceWatcher;
 
 use std::sync::Arc;
 use tauri::State;
@@ -33,9 +44,10 @@ pub fn workspace_write(
     pet_id: String,
     path: String,
     content: String,
+    source: Option<ChangeSource>,
 ) -> Result<String, String> {
     workspace
-        .write(&pet_id, &path, &content)
+        .write(&pet_id, &path, &content, source.unwrap_or_default())
         .map_err(|e| e.to_string())
 }
 
@@ -46,9 +58,10 @@ pub fn workspace_append(
     pet_id: String,
     path: String,
     content: String,
+    source: Option<ChangeSource>,
 ) -> Result<String, String> {
     workspace
-        .append(&pet_id, &path, &content)
+        .append(&pet_id, &path, &content, source.unwrap_or_default())
         .map_err(|e| e.to_string())
 }
 
@@ -60,9 +73,42 @@ pub fn workspace_edit(
     path: String,
     old_text: String,
     new_text: String,
+    source: Option<ChangeSource>,
 ) -> Result<String, String> {
     workspace
-        .edit(&pet_id, &path, &old_text, &new_text)
+        .edit(&pet_id, &path, &old_text, &new_text, source.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
+
+/// Apply several find-and-replace edits to one file atomically.
+/// With `dry_run` nothing is written and the diff is returned for review.
+#[tauri::command]
+pub fn workspace_edit_batch(
+    workspace: State<'_, WorkspaceState>,
+    pet_id: String,
+    path: String,
+    edits: Vec<EditOp>,
+    dry_run: Option<bool>,
+    source: Option<ChangeSource>,
+) -> Result<EditPreview, String> {
+    workspace
+        .edit_batch(&pet_id, &path, &edits, dry_run.unwrap_or(false), source.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
+
+/// Apply a unified diff to one file (default fuzz: 2 context lines)
+#[tauri::command]
+pub fn workspace_apply_patch(
+    workspace: State<'_, WorkspaceState>,
+    pet_id: String,
+    path: String,
+    patch: String,
+    fuzz: Option<usize>,
+    dry_run: Option<bool>,
+    source: Option<ChangeSource>,
+) -> Result<EditPreview, String> {
+    workspace
+        .apply_patch(&pet_id, &path, &patch, fuzz.unwrap_or(2), dry_run.unwrap_or(false), source.unwrap_or_default())
         .map_err(|e| e.to_string())
 }
 
@@ -97,9 +143,10 @@ pub fn workspace_write_binary(
     pet_id: String,
     path: String,
     base64_data: String,
+    source: Option<ChangeSource>,
 ) -> Result<String, String> {
     workspace
-        .write_binary(&pet_id, &path, &base64_data)
+        .write_binary(&pet_id, &path, &base64_data, source.unwrap_or_default())
         .map_err(|e| e.to_string())
 }
 
@@ -121,9 +168,10 @@ pub fn workspace_delete_file(
     workspace: State<'_, WorkspaceState>,
     pet_id: String,
     path: String,
+    source: Option<ChangeSource>,
 ) -> Result<String, String> {
     workspace
-        .delete_file(&pet_id, &path)
+        .delete_file(&pet_id, &path, source.unwrap_or_default())
         .map_err(|e| e.to_string())
 }
 
@@ -134,12 +182,163 @@ pub fn workspace_rename_file(
     pet_id: String,
     from: String,
     to: String,
+    source: Option<ChangeSource>,
+) -> Result<String, String> {
+    workspace
+        .rename_file(&pet_id, &from, &to, source.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
+
+/// Search file contents in the pet's workspace (substring or regex, glob filters)
+#[tauri::command]
+pub fn workspace_search(
+    workspace: State<'_, WorkspaceState>,
+    pet_id: String,
+    options: SearchOptions,
+) -> Result<SearchResult, String> {
+    workspace
+        .search(&pet_id, &options)
+        .map_err(|e| e.to_string())
+}
+
+/// List the recorded changes to a file, newest first
+#[tauri::command]
+pub fn workspace_file_history(
+    workspace: State<'_, WorkspaceState>,
+    pet_id: String,
+    path: String,
+) -> Result<Vec<HistoryEntry>, String> {
+    workspace
+        .file_history(&pet_id, &path)
+        .map_err(|e| e.to_string())
+}
+
+/// Unified diff between two versions of a file (hashes from `workspace_file_history`).
+/// A missing hash stands for the file not existing.
+#[tauri::command]
+pub fn workspace_diff_versions(
+    workspace: State<'_, WorkspaceState>,
+    pet_id: String,
+    path: String,
+    from_hash: Option<String>,
+    to_hash: Option<String>,
+) -> Result<String, String> {
+    workspace
+        .diff_versions(&pet_id, &path, from_hash.as_deref(), to_hash.as_deref())
+        .map_err(|e| e.to_string())
+}
+
+/// Restore a file to a stored version; without a hash the file is deleted again
+#[tauri::command]
+pub fn workspace_restore_version(
+    workspace: State<'_, WorkspaceState>,
+    pet_id: String,
+    path: String,
+    hash: Option<String>,
+    source: Option<ChangeSource>,
+) -> Result<String, String> {
+    workspace
+        .restore_version(&pet_id, &path, hash.as_deref(), source.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
+
+/// Get how much workspace history is kept
+#[tauri::command]
+pub fn workspace_get_history_retention(
+    workspace: State<'_, WorkspaceState>,
+) -> Result<HistoryRetention, String> {
+    Ok(workspace.history_retention())
+}
+
+/// Set how much workspace history is kept; applied on the next change of each pet
+#[tauri::command]
+pub fn workspace_set_history_retention(
+    workspace: State<'_, WorkspaceState>,
+    retention: HistoryRetention,
+) -> Result<(), String> {
+    workspace
+        .set_history_retention(&retention)
+        .map_err(|e| e.to_string())
+}
+
+/// Disk usage of the pet's workspace by directory and content type
+#[tauri::command]
+pub fn workspace_usage(
+    workspace: State<'_, WorkspaceState>,
+    pet_id: String,
+    depth: Option<usize>,
+) -> Result<UsageReport, String> {
+    Ok(workspace.usage_report(&pet_id, depth.unwrap_or(2)))
+}
+
+/// Get a pet's effective storage quota, or the default one without a pet
+#[tauri::command]
+pub fn workspace_get_quota(
+    workspace: State<'_, WorkspaceState>,
+    pet_id: Option<String>,
+) -> Result<StorageQuota, String> {
+    Ok(workspace.quota(pet_id.as_deref()))
+}
+
+/// Set the default storage quota (no pet) or a pet's override; no quota resets it
+#[tauri::command]
+pub fn workspace_set_quota(
+    workspace: State<'_, WorkspaceState>,
+    pet_id: Option<String>,
+    quota: Option<StorageQuota>,
+) -> Result<(), String> {
+    workspace
+        .set_quota(pet_id.as_deref(), quota)
+        .map_err(|e| e.to_string())
+}
+
+/// Size, sniffed MIME type and modification time of a file
+#[tauri::command]
+pub fn workspace_file_info(
+    workspace: State<'_, WorkspaceState>,
+    pet_id: String,
+    path: String,
+) -> Result<FileInfo, String> {
+    workspace
+        .file_info(&pet_id, &path)
+        .map_err(|e| e.to_string())
+}
+
+/// Base64 PNG thumbnail of an image in the pet's workspace
+#[tauri::command]
+pub fn workspace_thumbnail(
+    workspace: State<'_, WorkspaceState>,
+    pet_id: String,
+    path: String,
 ) -> Result<String, String> {
     workspace
-        .rename_file(&pet_id, &from, &to)
+        .thumbnail(&pet_id, &path)
         .map_err(|e| e.to_string())
 }
 
+/// List large or stale files under social/ and training data (or `options.roots`)
+#[tauri::command]
+pub fn workspace_cleanup_scan(
+    workspace: State<'_, WorkspaceState>,
+    pet_id: String,
+    options: Option<CleanupOptions>,
+) -> Result<CleanupReport, String> {
+    workspace
+        .cleanup_scan(&pet_id, &options.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
+
+/// Delete files picked from a cleanup scan; each deletion lands in file history
+#[tauri::command]
+pub fn workspace_cleanup_delete(
+    workspace: State<'_, WorkspaceState>,
+    pet_id: String,
+    paths: Vec<String>,
+    source: Option<ChangeSource>,
+) -> Result<CleanupResult, String> {
+    Ok(workspace.cleanup_delete(&pet_id, &paths, source.unwrap_or_default()))
+}
+
 /// Check if a file exists in the pet's workspace
 #[tauri::command]
 pub fn workspace_file_exists(
@@ -161,7 +360,7 @@ pub fn workspace_get_path(
 ) -> Result<String, String> {
     // Use write with empty-string fallback to ensure the file & dirs exist
     if ensure_exists.unwrap_or(false) && !workspace.file_exists(&pet_id, &path) {
-        workspace.write(&pet_id, &path, "").map_err(|e| e.to_string())?;
+        workspace.write(&pet_id, &path, "", ChangeSource::User).map_err(|e| e.to_string())?;
     }
     // resolve_safe_path is private, so we reconstruct the path the same way
     let full = workspace.get_full_path(&pet_id, &path).map_err(|e| e.to_string())?;
@@ -262,9 +461,9 @@ pub fn workspace_open_file(
     if needs_content {
         let content = default_content.unwrap_or_default();
         if !content.is_empty() {
-            workspace.write(&pet_id, &path, &content).map_err(|e| e.to_string())?;
+            workspace.write(&pet_id, &path, &content, ChangeSource::User).map_err(|e| e.to_string())?;
         } else if !workspace.file_exists(&pet_id, &path) {
-            workspace.write(&pet_id, &path, "").map_err(|e| e.to_string())?;
+            workspace.write(&pet_id, &path, "", ChangeSource::User).map_err(|e| e.to_string())?;
         }
     }
     let full = workspace.get_full_path(&pet_id, &path).map_err(|e| e.to_string())?;
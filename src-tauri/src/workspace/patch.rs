@@ -0,0 +1,213 @@
+// Unified-diff patch application for a single workspace file
+// Hunks are located around their stated line first, then anywhere after the
+// previous hunk; with fuzz N up to N leading/trailing context lines may be
+// ignored (same idea as GNU patch's --fuzz).
+
+#[derive(Debug, Clone, PartialEq)]
+enum HunkLine {
+    Context(String),
+    Remove(String),
+    Add(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Hunk {
+    /// 1-based start line in the original file, from the `@@ -a,b` header
+    old_start: usize,
+    lines: Vec<HunkLine>,
+}
+
+impl Hunk {
+    fn old_lines(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|l| match l {
+                HunkLine::Context(s) | HunkLine::Remove(s) => Some(s.as_str()),
+                HunkLine::Add(_) => None,
+            })
+            .collect()
+    }
+
+    fn new_lines(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|l| match l {
+                HunkLine::Context(s) | HunkLine::Add(s) => Some(s.as_str()),
+                HunkLine::Remove(_) => None,
+            })
+            .collect()
+    }
+
+    /// Drop up to `fuzz` context lines from each end
+    fn trimmed(&self, fuzz: usize) -> Hunk {
+        let leading = self.lines.iter().take_while(|l| matches!(l, HunkLine::Context(_))).count().min(fuzz);
+        let trailing = self.lines.iter().rev().take_while(|l| matches!(l, HunkLine::Context(_))).count().min(fuzz);
+        let end = self.lines.len().saturating_sub(trailing).max(leading);
+        Hunk {
+            old_start: self.old_start + leading,
+            lines: self.lines[leading..end].to_vec(),
+        }
+    }
+}
+
+fn parse_range_start(range: &str) -> Option<usize> {
+    range.split(',').next()?.parse().ok()
+}
+
+fn parse(patch: &str) -> Result<Vec<Hunk>, String> {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    let mut targets = 0;
+    let lines: Vec<&str> = patch.lines().collect();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        i += 1;
+        // A `---`/`+++` pair is a file header; a lone `--- x` inside a hunk is a removed "-- x"
+        if line.starts_with("--- ") && lines.get(i).is_some_and(|next| next.starts_with("+++ ")) {
+            i += 1;
+            targets += 1;
+            if targets > 1 {
+                return Err("补丁包含多个文件，每次只能修改一个文件".to_string());
+            }
+            continue;
+        }
+        if let Some(header) = line.strip_prefix("@@ ") {
+            let old = header
+                .split_whitespace()
+                .next()
+                .and_then(|r| r.strip_prefix('-'))
+                .and_then(parse_range_start)
+                .ok_or_else(|| format!("无法解析 hunk 头: {}", line))?;
+            hunks.push(Hunk { old_start: old, lines: Vec::new() });
+            continue;
+        }
+        let Some(hunk) = hunks.last_mut() else {
+            // `diff`, `index`, `---` and anything else before the first hunk
+            continue;
+        };
+        if line.starts_with('\\') {
+            // "\ No newline at end of file"
+            continue;
+        }
+        match line.chars().next() {
+            Some('+') => hunk.lines.push(HunkLine::Add(line[1..].to_string())),
+            Some('-') => hunk.lines.push(HunkLine::Remove(line[1..].to_string())),
+            Some(' ') => hunk.lines.push(HunkLine::Context(line[1..].to_string())),
+            // Some editors strip the single space of empty context lines
+            None => hunk.lines.push(HunkLine::Context(String::new())),
+            // Skipping it would apply a corrupted hunk only partly
+            _ => return Err(format!("hunk 中有无法识别的行: {}", line)),
+        }
+    }
+    if hunks.is_empty() {
+        return Err("补丁中没有任何 hunk（需要 unified diff 格式，以 @@ 开头）".to_string());
+    }
+    Ok(hunks)
+}
+
+fn matches_at(lines: &[String], at: usize, expected: &[&str]) -> bool {
+    at + expected.len() <= lines.len()
+        && lines[at..at + expected.len()]
+            .iter()
+            .zip(expected)
+            .all(|(have, want)| have.trim_end_matches('\r') == want.trim_end_matches('\r'))
+}
+
+/// Nearest position to `hint` (at or after `min`) where `expected` matches
+fn locate(lines: &[String], expected: &[&str], hint: usize, min: usize) -> Option<usize> {
+    if lines.len() < expected.len() {
+        return None;
+    }
+    let max = lines.len() - expected.len();
+    let hint = hint.clamp(min, max.max(min));
+    for distance in 0..=lines.len() {
+        let after = hint + distance;
+        if after <= max && matches_at(lines, after, expected) {
+            return Some(after);
+        }
+        if distance > 0 && hint >= min + distance && matches_at(lines, hint - distance, expected) {
+            return Some(hint - distance);
+        }
+        if after > max && hint < min + distance {
+            break;
+        }
+    }
+    None
+}
+
+/// Apply a unified diff to `content`. Fails without partial results if any hunk does not fit.
+pub fn apply_patch(content: &str, patch: &str, fuzz: usize) -> Result<String, String> {
+    let hunks = parse(patch)?;
+    let trailing_newline = content.is_empty() || content.ends_with('\n');
+    let mut lines: Vec<String> = content.lines().map(|l| l.to_string()).collect();
+    // Line delta from earlier hunks, so later hints stay accurate
+    let mut offset: isize = 0;
+    let mut min = 0;
+
+    for (index, hunk) in hunks.iter().enumerate() {
+        let mut placed = None;
+        for level in 0..=fuzz {
+            let candidate = hunk.trimmed(level);
+            let old = candidate.old_lines();
+            let hint = (candidate.old_start.max(1) as isize - 1 + offset).max(0) as usize;
+            if let Some(at) = locate(&lines, &old, hint, min) {
+                placed = Some((candidate, at));
+                break;
+            }
+            if level >= hunk.lines.len() {
+                break;
+            }
+        }
+        let (candidate, at) = placed.ok_or_else(|| {
+            format!("第 {} 个 hunk（原文第 {} 行附近）与文件内容对不上", index + 1, hunk.old_start)
+        })?;
+        let old_len = candidate.old_lines().len();
+        let replacement: Vec<String> = candidate.new_lines().iter().map(|s| s.to_string()).collect();
+        let new_len = replacement.len();
+        lines.splice(at..at + old_len, replacement);
+        offset += new_len as isize - old_len as isize;
+        min = at + new_len;
+    }
+
+    let newline = if content.contains("\r\n") { "\r\n" } else { "\n" };
+    let mut out = lines.join(newline);
+    if trailing_newline && !lines.is_empty() {
+        out.push_str(newline);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIGINAL: &str = "# Memory\n\n- likes tea\n- owns a cat\n- lives in Paris\n";
+
+    #[test]
+    fn applies_with_offset_and_fuzz() {
+        let patch = "--- a/MEMORY.md\n+++ b/MEMORY.md\n@@ -1,4 +1,4 @@\n # Memory\n \n-- likes tea\n+- likes coffee\n - owns a cat\n";
+        assert_eq!(
+            apply_patch(ORIGINAL, patch, 0).unwrap(),
+            "# Memory\n\n- likes coffee\n- owns a cat\n- lives in Paris\n"
+        );
+
+        // Lines were added above since the diff was made
+        let shifted = format!("intro\nintro\n{}", ORIGINAL);
+        assert!(apply_patch(&shifted, patch, 0).unwrap().contains("- likes coffee"));
+
+        // First context line no longer matches: needs fuzz 1
+        let drifted = ORIGINAL.replace("# Memory", "# Notes");
+        assert!(apply_patch(&drifted, patch, 0).is_err());
+        assert!(apply_patch(&drifted, patch, 1).unwrap().starts_with("# Notes\n\n- likes coffee"));
+    }
+
+    #[test]
+    fn rejects_patches_that_do_not_fit() {
+        let patch = "@@ -3,1 +3,1 @@\n-- likes milk\n+- likes coffee\n";
+        assert!(apply_patch(ORIGINAL, patch, 2).is_err());
+        assert!(apply_patch(ORIGINAL, "no hunks here", 0).is_err());
+
+        let corrupted = "@@ -3,2 +3,2 @@\n-- likes tea\n+- likes coffee\n*- owns a cat\n";
+        assert!(apply_patch(ORIGINAL, corrupted, 0).unwrap_err().contains("*- owns a cat"));
+    }
+}
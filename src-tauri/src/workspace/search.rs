@@ -0,0 +1,283 @@
+// Full-text search over a pet's workspace (substring or regex, grep-style results)
+// Path sandboxing is done by the caller (`WorkspaceEngine::search`); this module
+// only walks the already-resolved directory.
+
+use std::fs;
+use std::path::Path;
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use super::engine::WorkspaceError;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SearchOptions {
+    pub query: String,
+    /// Treat `query` as a regular expression instead of a literal substring
+    pub regex: bool,
+    pub case_sensitive: bool,
+    /// Directory to search, relative to the workspace root (default: whole workspace)
+    pub path: String,
+    /// Globs matched against workspace-relative paths, e.g. "social/**/*.md".
+    /// Empty means every file.
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    /// Lines of context before and after each match
+    pub context_lines: usize,
+    /// Stop after this many matches in total
+    pub max_results: usize,
+    pub max_matches_per_file: usize,
+    /// Larger files are skipped
+    pub max_file_bytes: u64,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            query: String::new(),
+            regex: false,
+            case_sensitive: false,
+            path: String::new(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            context_lines: 2,
+            max_results: 100,
+            max_matches_per_file: 20,
+            max_file_bytes: 2 * 1024 * 1024,
+        }
+    }
+}
+
+/// Hard ceilings so a tool call cannot ask for unbounded output
+const MAX_CONTEXT_LINES: usize = 10;
+const MAX_RESULTS: usize = 1000;
+const MAX_LINE_CHARS: usize = 500;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchMatch {
+    /// Workspace-relative path
+    pub path: String,
+    /// 1-based
+    pub line_number: usize,
+    pub line: String,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResult {
+    pub matches: Vec<SearchMatch>,
+    pub files_searched: usize,
+    pub files_matched: usize,
+    /// True when a cap cut the result short
+    pub truncated: bool,
+}
+
+impl SearchResult {
+    /// grep-style text (`path:line: text`, context as `path-line- text`) for tool output
+    pub fn to_text(&self) -> String {
+        if self.matches.is_empty() {
+            return format!("没有找到匹配（搜索了 {} 个文件）", self.files_searched);
+        }
+        let mut out = String::new();
+        let mut last: Option<(&str, usize)> = None;
+        for (i, m) in self.matches.iter().enumerate() {
+            // Context after a match stops where the next match in the same file begins
+            let after_end = match self.matches.get(i + 1) {
+                Some(next) if next.path == m.path => next.line_number - m.line_number - 1,
+                _ => m.after.len(),
+            };
+            let after = &m.after[..m.after.len().min(after_end)];
+            let first = m.line_number - m.before.len();
+            // Overlapping context between neighbouring matches is printed once
+            let shown_until = match last {
+                Some((path, line)) if path == m.path => line,
+                _ => 0,
+            };
+            if last.is_some() && (shown_until == 0 || first > shown_until + 1) {
+                out.push_str("--\n");
+            }
+            for (offset, line) in m.before.iter().enumerate() {
+                if first + offset > shown_until {
+                    out.push_str(&format!("{}-{}- {}\n", m.path, first + offset, line));
+                }
+            }
+            out.push_str(&format!("{}:{}: {}\n", m.path, m.line_number, m.line));
+            for (offset, line) in after.iter().enumerate() {
+                out.push_str(&format!("{}-{}- {}\n", m.path, m.line_number + 1 + offset, line));
+            }
+            last = Some((m.path.as_str(), m.line_number + after.len()));
+        }
+        out.push_str(&format!(
+            "\n共 {} 处匹配，{} 个文件{}",
+            self.matches.len(),
+            self.files_matched,
+            if self.truncated { "（结果已截断，请缩小搜索范围）" } else { "" }
+        ));
+        out
+    }
+}
+
+fn build_globs(patterns: &[String]) -> Result<Option<GlobSet>, WorkspaceError> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        let glob = Glob::new(pattern.trim_start_matches("./"))
+            .map_err(|e| WorkspaceError::InvalidPattern(e.to_string()))?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .map(Some)
+        .map_err(|e| WorkspaceError::InvalidPattern(e.to_string()))
+}
+
+fn build_matcher(options: &SearchOptions) -> Result<Regex, WorkspaceError> {
+    if options.query.is_empty() {
+        return Err(WorkspaceError::InvalidPattern("query 不能为空".to_string()));
+    }
+    let pattern = if options.regex {
+        options.query.clone()
+    } else {
+        regex::escape(&options.query)
+    };
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!options.case_sensitive)
+        .size_limit(1 << 20)
+        .build()
+        .map_err(|e| WorkspaceError::InvalidPattern(e.to_string()))
+}
+
+fn clip(line: &str) -> String {
+    match line.char_indices().nth(MAX_LINE_CHARS) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+/// Search every file under `base`. Paths in the result are relative to `workspace_root`.
+pub(super) fn search_tree(
+    workspace_root: &Path,
+    base: &Path,
+    options: &SearchOptions,
+) -> Result<SearchResult, WorkspaceError> {
+    let matcher = build_matcher(options)?;
+    let include = build_globs(&options.include)?;
+    let exclude = build_globs(&options.exclude)?;
+    let context = options.context_lines.min(MAX_CONTEXT_LINES);
+    let max_results = options.max_results.clamp(1, MAX_RESULTS);
+    let per_file = options.max_matches_per_file.max(1);
+
+    let mut files = Vec::new();
+    if base.is_file() {
+        files.push(base.to_path_buf());
+    } else {
+        collect_files(base, &mut files)?;
+    }
+    files.sort();
+
+    let mut result = SearchResult::default();
+    for file in files {
+        let relative = file
+            .strip_prefix(workspace_root)
+            .map(|p| p.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/"))
+            .unwrap_or_default();
+        if include.as_ref().is_some_and(|set| !set.is_match(&relative))
+            || exclude.as_ref().is_some_and(|set| set.is_match(&relative))
+        {
+            continue;
+        }
+        if fs::metadata(&file).map(|m| m.len() > options.max_file_bytes).unwrap_or(true) {
+            continue;
+        }
+        // Binary and non-UTF-8 files are skipped
+        let Ok(content) = fs::read_to_string(&file) else { continue };
+        if content.contains('\0') {
+            continue;
+        }
+        result.files_searched += 1;
+
+        let lines: Vec<&str> = content.lines().collect();
+        let mut in_file = 0;
+        for (index, line) in lines.iter().enumerate() {
+            if !matcher.is_match(line) {
+                continue;
+            }
+            if in_file == per_file || result.matches.len() == max_results {
+                result.truncated = true;
+                break;
+            }
+            in_file += 1;
+            result.matches.push(SearchMatch {
+                path: relative.clone(),
+                line_number: index + 1,
+                line: clip(line),
+                before: lines[index.saturating_sub(context)..index].iter().map(|l| clip(l)).collect(),
+                after: lines[index + 1..(index + 1 + context).min(lines.len())].iter().map(|l| clip(l)).collect(),
+            });
+        }
+        if in_file > 0 {
+            result.files_matched += 1;
+        }
+        if result.truncated && result.matches.len() == max_results {
+            break;
+        }
+    }
+    Ok(result)
+}
+
+/// Same traversal rules as `list_dir`: hidden entries (including the history store) are skipped
+fn collect_files(dir: &Path, out: &mut Vec<std::path::PathBuf>) -> Result<(), WorkspaceError> {
+    let read_dir = fs::read_dir(dir).map_err(|e| WorkspaceError::IoError(e.to_string()))?;
+    for entry in read_dir.flatten() {
+        let path = entry.path();
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let Ok(file_type) = entry.file_type() else { continue };
+        if file_type.is_dir() {
+            collect_files(&path, out)?;
+        } else if file_type.is_file() {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(path: &str, line_number: usize, before: &[&str], after: &[&str]) -> SearchMatch {
+        SearchMatch {
+            path: path.to_string(),
+            line_number,
+            line: format!("line {}", line_number),
+            before: before.iter().map(|s| s.to_string()).collect(),
+            after: after.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn text_output_merges_overlapping_context() {
+        let result = SearchResult {
+            matches: vec![
+                hit("a.md", 2, &["line 1"], &["line 3"]),
+                hit("a.md", 3, &["line 2"], &["line 4"]),
+                hit("b.md", 1, &[], &[]),
+            ],
+            files_searched: 2,
+            files_matched: 2,
+            truncated: false,
+        };
+        let text = result.to_text();
+        let body: Vec<&str> = text.lines().take(5).collect();
+        assert_eq!(body, vec!["a.md-1- line 1", "a.md:2: line 2", "a.md:3: line 3", "a.md-4- line 4", "--"]);
+    }
+}
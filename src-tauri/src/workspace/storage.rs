@@ -0,0 +1,463 @@
+// WorkspaceStorage: per-pet disk quotas, usage reports and cleanup scans
+// Layout: {root_dir}/.storage/quotas.json                    (QuotaConfig)
+//         {root_dir}/.storage/thumbnails/{pet_id}/{path}.png (image thumbnails)
+// Usage counts the files visible in the workspace; hidden entries (the history
+// store, this directory) are not charged to any pet.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+use chrono::{DateTime, Utc};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+
+use super::media::sniff_file;
+
+/// Directory under the workspace root for quota settings and thumbnails
+pub const STORAGE_DIR: &str = ".storage";
+
+/// Cached usage totals are recomputed after this long, so edits made outside the app catch up
+const USAGE_CACHE_TTL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct StorageQuota {
+    /// Total size of a pet's workspace (0 = unlimited)
+    pub max_workspace_bytes: u64,
+    /// Size of any single file (0 = unlimited)
+    pub max_file_bytes: u64,
+}
+
+impl Default for StorageQuota {
+    fn default() -> Self {
+        Self {
+            max_workspace_bytes: 1024 * 1024 * 1024,
+            max_file_bytes: 50 * 1024 * 1024,
+        }
+    }
+}
+
+/// Contents of quotas.json: a default plus per-pet overrides
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct QuotaConfig {
+    default: StorageQuota,
+    pets: HashMap<String, StorageQuota>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DirectoryUsage {
+    /// Workspace-relative directory, `/`-separated
+    pub path: String,
+    /// Everything below the directory, subdirectories included
+    pub bytes: u64,
+    pub files: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TypeUsage {
+    pub mime: String,
+    pub bytes: u64,
+    pub files: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageReport {
+    pub total_bytes: u64,
+    pub file_count: usize,
+    pub quota: StorageQuota,
+    /// Largest first
+    pub directories: Vec<DirectoryUsage>,
+    /// Largest first, by sniffed MIME type
+    pub types: Vec<TypeUsage>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CleanupOptions {
+    /// Directories to scan, relative to the workspace; missing ones are skipped
+    pub roots: Vec<String>,
+    /// Files at least this large are reported (0 disables the size check)
+    pub min_size_bytes: u64,
+    /// Files not modified for this many days are reported (0 disables the age check)
+    pub older_than_days: u32,
+    /// Globs that are never reported as stale (memory notes, index files).
+    /// They are still reported when large.
+    pub keep: Vec<String>,
+    pub max_results: usize,
+}
+
+impl Default for CleanupOptions {
+    fn default() -> Self {
+        Self {
+            roots: vec!["social".to_string(), "training".to_string()],
+            min_size_bytes: 5 * 1024 * 1024,
+            older_than_days: 30,
+            keep: vec!["**/*.md".to_string(), "**/index.*".to_string()],
+            max_results: 200,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CleanupCandidate {
+    pub path: String,
+    pub size: u64,
+    pub mime: String,
+    /// RFC 3339
+    pub modified: String,
+    pub large: bool,
+    pub stale: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CleanupReport {
+    /// Largest first
+    pub candidates: Vec<CleanupCandidate>,
+    /// Size of every candidate found, including ones cut by `max_results`
+    pub total_bytes: u64,
+    pub truncated: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CleanupResult {
+    pub deleted: Vec<String>,
+    pub freed_bytes: u64,
+    pub failed: Vec<CleanupFailure>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CleanupFailure {
+    pub path: String,
+    pub error: String,
+}
+
+pub struct WorkspaceStorage {
+    dir: PathBuf,
+    /// Serializes quotas.json rewrites within this process
+    lock: Mutex<()>,
+    /// pet_id -> (bytes, computed at)
+    usage: Mutex<HashMap<String, (u64, Instant)>>,
+    /// Parsed quotas.json; refreshed whenever this process rewrites it
+    config: Mutex<Option<QuotaConfig>>,
+}
+
+impl WorkspaceStorage {
+    pub fn new(workspace_root: &Path) -> Self {
+        Self {
+            dir: workspace_root.join(STORAGE_DIR),
+            lock: Mutex::new(()),
+            usage: Mutex::new(HashMap::new()),
+            config: Mutex::new(None),
+        }
+    }
+
+    pub fn thumbnail_path(&self, pet_id: &str, key: &str) -> PathBuf {
+        self.dir.join("thumbnails").join(pet_id).join(format!("{}.png", key))
+    }
+
+    pub fn delete_pet(&self, pet_id: &str) -> Result<(), String> {
+        self.usage.lock().unwrap_or_else(|e| e.into_inner()).remove(pet_id);
+        let thumbnails = self.dir.join("thumbnails").join(pet_id);
+        if thumbnails.exists() {
+            fs::remove_dir_all(&thumbnails).map_err(|e| e.to_string())?;
+        }
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut config = self.config();
+        if config.pets.remove(pet_id).is_some() {
+            self.save_config(&config)?;
+        }
+        Ok(())
+    }
+
+    // ============ Quotas ============
+
+    fn config(&self) -> QuotaConfig {
+        let mut cached = self.config.lock().unwrap_or_else(|e| e.into_inner());
+        cached
+            .get_or_insert_with(|| {
+                fs::read_to_string(self.dir.join("quotas.json"))
+                    .ok()
+                    .and_then(|s| serde_json::from_str(&s).ok())
+                    .unwrap_or_default()
+            })
+            .clone()
+    }
+
+    fn save_config(&self, config: &QuotaConfig) -> Result<(), String> {
+        let mut cached = self.config.lock().unwrap_or_else(|e| e.into_inner());
+        // On failure the next read goes back to disk
+        *cached = None;
+        fs::create_dir_all(&self.dir).map_err(|e| e.to_string())?;
+        let json = serde_json::to_string_pretty(config).map_err(|e| e.to_string())?;
+        fs::write(self.dir.join("quotas.json"), json).map_err(|e| e.to_string())?;
+        *cached = Some(config.clone());
+        Ok(())
+    }
+
+    /// The pet's override, or the default quota
+    pub fn quota(&self, pet_id: &str) -> StorageQuota {
+        let mut config = self.config();
+        config.pets.remove(pet_id).unwrap_or(config.default)
+    }
+
+    pub fn default_quota(&self) -> StorageQuota {
+        self.config().default
+    }
+
+    /// Set the default quota (`pet_id` None) or a pet's override; `quota` None
+    /// removes the override so the pet follows the default again.
+    pub fn set_quota(&self, pet_id: Option<&str>, quota: Option<StorageQuota>) -> Result<(), String> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut config = self.config();
+        match (pet_id, quota) {
+            (None, Some(quota)) => config.default = quota,
+            (None, None) => config.default = StorageQuota::default(),
+            (Some(pet_id), Some(quota)) => {
+                config.pets.insert(pet_id.to_string(), quota);
+            }
+            (Some(pet_id), None) => {
+                config.pets.remove(pet_id);
+            }
+        }
+        self.save_config(&config)
+    }
+
+    // ============ Usage ============
+
+    /// Total visible bytes in the pet's workspace, cached briefly
+    pub fn usage(&self, pet_id: &str, pet_dir: &Path) -> u64 {
+        let mut cache = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((bytes, at)) = cache.get(pet_id) {
+            if at.elapsed() < USAGE_CACHE_TTL {
+                return *bytes;
+            }
+        }
+        let mut files = Vec::new();
+        collect_files(pet_dir, &mut files);
+        let bytes = files.iter().map(|(_, size, _)| size).sum();
+        cache.insert(pet_id.to_string(), (bytes, Instant::now()));
+        bytes
+    }
+
+    /// Keep a cached total in step with a write made through the engine
+    pub fn adjust_usage(&self, pet_id: &str, old_len: u64, new_len: u64) {
+        let mut cache = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((bytes, _)) = cache.get_mut(pet_id) {
+            *bytes = (*bytes + new_len).saturating_sub(old_len);
+        }
+    }
+
+    /// Reject a write that would replace `old_len` bytes with `new_len` bytes
+    /// if it breaks either limit. Shrinking writes always pass.
+    pub fn check_write(&self, pet_id: &str, pet_dir: &Path, old_len: u64, new_len: u64) -> Result<(), String> {
+        if new_len <= old_len {
+            return Ok(());
+        }
+        let quota = self.quota(pet_id);
+        if quota.max_file_bytes > 0 && new_len > quota.max_file_bytes {
+            return Err(format!(
+                "文件大小 {} 超过单文件上限 {}",
+                format_bytes(new_len),
+                format_bytes(quota.max_file_bytes)
+            ));
+        }
+        if quota.max_workspace_bytes > 0 {
+            let after = (self.usage(pet_id, pet_dir) + new_len).saturating_sub(old_len);
+            if after > quota.max_workspace_bytes {
+                return Err(format!(
+                    "写入后工作区将占用 {}，超过上限 {}。可以先清理 social/ 下的旧文件",
+                    format_bytes(after),
+                    format_bytes(quota.max_workspace_bytes)
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Usage by directory (down to `depth` levels) and by content type.
+    /// Also refreshes the cached total.
+    pub fn report(&self, pet_id: &str, pet_dir: &Path, depth: usize) -> UsageReport {
+        let mut files = Vec::new();
+        collect_files(pet_dir, &mut files);
+
+        let mut directories: BTreeMap<String, (u64, usize)> = BTreeMap::new();
+        let mut types: BTreeMap<&'static str, (u64, usize)> = BTreeMap::new();
+        let mut total_bytes = 0;
+        for (path, size, _) in &files {
+            total_bytes += size;
+            let relative = relative_key(pet_dir, path);
+            let parts: Vec<&str> = relative.split('/').collect();
+            for level in 1..parts.len().min(depth + 1) {
+                let entry = directories.entry(parts[..level].join("/")).or_default();
+                entry.0 += size;
+                entry.1 += 1;
+            }
+            let entry = types.entry(sniff_file(path)).or_default();
+            entry.0 += size;
+            entry.1 += 1;
+        }
+        self.usage
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(pet_id.to_string(), (total_bytes, Instant::now()));
+
+        let mut directories: Vec<DirectoryUsage> = directories
+            .into_iter()
+            .map(|(path, (bytes, files))| DirectoryUsage { path, bytes, files })
+            .collect();
+        directories.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.path.cmp(&b.path)));
+        let mut types: Vec<TypeUsage> = types
+            .into_iter()
+            .map(|(mime, (bytes, files))| TypeUsage { mime: mime.to_string(), bytes, files })
+            .collect();
+        types.sort_by(|a, b| b.bytes.cmp(&a.bytes).then_with(|| a.mime.cmp(&b.mime)));
+
+        UsageReport {
+            total_bytes,
+            file_count: files.len(),
+            quota: self.quota(pet_id),
+            directories,
+            types,
+        }
+    }
+}
+
+// ============ Cleanup ============
+
+/// Find large or stale files under `roots` (absolute, already inside `pet_dir`)
+pub fn scan_cleanup(pet_dir: &Path, roots: &[PathBuf], options: &CleanupOptions) -> Result<CleanupReport, String> {
+    let keep = build_globs(&options.keep)?;
+    let stale_before = (options.older_than_days > 0)
+        .then(|| SystemTime::now() - Duration::from_secs(u64::from(options.older_than_days) * 86_400));
+
+    let mut files = Vec::new();
+    for root in roots {
+        collect_files(root, &mut files);
+    }
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    files.dedup_by(|a, b| a.0 == b.0);
+
+    let mut report = CleanupReport::default();
+    for (path, size, modified) in files {
+        let relative = relative_key(pet_dir, &path);
+        let large = options.min_size_bytes > 0 && size >= options.min_size_bytes;
+        let stale = stale_before.is_some_and(|before| modified < before)
+            && !keep.as_ref().is_some_and(|set| set.is_match(&relative));
+        if !large && !stale {
+            continue;
+        }
+        report.total_bytes += size;
+        report.candidates.push(CleanupCandidate {
+            mime: sniff_file(&path).to_string(),
+            path: relative,
+            size,
+            modified: DateTime::<Utc>::from(modified).to_rfc3339(),
+            large,
+            stale,
+        });
+    }
+    report.candidates.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| a.path.cmp(&b.path)));
+    let max_results = options.max_results.max(1);
+    if report.candidates.len() > max_results {
+        report.candidates.truncate(max_results);
+        report.truncated = true;
+    }
+    Ok(report)
+}
+
+fn build_globs(patterns: &[String]) -> Result<Option<GlobSet>, String> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern.trim_start_matches("./")).map_err(|e| e.to_string())?);
+    }
+    builder.build().map(Some).map_err(|e| e.to_string())
+}
+
+// ============ Helpers ============
+
+/// Every visible regular file below `dir` with its size and modification time.
+/// Hidden entries and symlinks are skipped, like `list_dir` and search.
+fn collect_files(dir: &Path, out: &mut Vec<(PathBuf, u64, SystemTime)>) {
+    let Ok(read_dir) = fs::read_dir(dir) else { return };
+    for entry in read_dir.flatten() {
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let Ok(file_type) = entry.file_type() else { continue };
+        if file_type.is_dir() {
+            collect_files(&entry.path(), out);
+        } else if file_type.is_file() {
+            let Ok(metadata) = entry.metadata() else { continue };
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            out.push((entry.path(), metadata.len(), modified));
+        }
+    }
+}
+
+fn relative_key(base: &Path, path: &Path) -> String {
+    path.strip_prefix(base)
+        .map(|p| p.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>().join("/"))
+        .unwrap_or_default()
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_groups_by_directory_and_type() {
+        let root = std::env::temp_dir().join(format!("petgpt-storage-{}", uuid::Uuid::new_v4()));
+        let pet_dir = root.join("pet");
+        fs::create_dir_all(pet_dir.join("social/images")).unwrap();
+        fs::create_dir_all(pet_dir.join(".cache")).unwrap();
+        fs::write(pet_dir.join("SOUL.md"), "# Soul\n").unwrap();
+        fs::write(pet_dir.join("social/images/a.png"), b"\x89PNG\r\n\x1a\n0123456789").unwrap();
+        fs::write(pet_dir.join("social/notes.md"), "note").unwrap();
+        fs::write(pet_dir.join(".cache/ignored"), "hidden").unwrap();
+
+        let storage = WorkspaceStorage::new(&root);
+        let report = storage.report("pet", &pet_dir, 2);
+        assert_eq!(report.file_count, 3);
+        assert_eq!(report.total_bytes, 7 + 18 + 4);
+        assert_eq!(
+            report.directories,
+            vec![
+                DirectoryUsage { path: "social".to_string(), bytes: 22, files: 2 },
+                DirectoryUsage { path: "social/images".to_string(), bytes: 18, files: 1 },
+            ]
+        );
+        assert_eq!(report.types[0].mime, "image/png");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
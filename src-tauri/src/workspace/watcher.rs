@@ -0,0 +1,180 @@
+// Filesystem watcher over the workspace root and the global Skills root
+// Edits made outside the app (an external editor on SOUL.md, a skill folder
+// dropped into place) are debounced and reported as one event per pet, so the
+// frontend can drop cached prompt files and refresh open file views.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+use notify_debouncer_mini::notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use serde::Serialize;
+
+/// Tauri event emitted with a `FilesChanged` payload
+pub const FILES_CHANGED_EVENT: &str = "workspace-files-changed";
+
+/// Quiet period before a burst of filesystem events is reported
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchScope {
+    /// A pet workspace (`{workspace_root}/{pet_id}/...`), private Skills included
+    Workspace,
+    /// The global Skill library shared by every pet
+    Skills,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FilesChanged {
+    pub scope: WatchScope,
+    /// Set for `Workspace` changes only
+    pub pet_id: Option<String>,
+    /// Changed paths relative to the pet workspace (or the Skills root), `/`-separated
+    pub paths: Vec<String>,
+}
+
+/// Keeps the OS watcher alive; dropping it stops the notifications
+pub struct WorkspaceWatcher {
+    _debouncer: Debouncer<RecommendedWatcher>,
+}
+
+impl WorkspaceWatcher {
+    /// Watch both roots recursively and call `on_change` once per pet (or for the
+    /// Skills root) after each debounced burst.
+    pub fn start<F>(workspace_root: PathBuf, skills_root: PathBuf, on_change: F) -> Result<Self, String>
+    where
+        F: Fn(FilesChanged) + Send + 'static,
+    {
+        for root in [&workspace_root, &skills_root] {
+            fs::create_dir_all(root).map_err(|e| format!("Failed to create {}: {}", root.display(), e))?;
+        }
+
+        // The OS reports canonical paths (e.g. /private/var on macOS), so match against those
+        let workspace_canonical = workspace_root.canonicalize().unwrap_or_else(|_| workspace_root.clone());
+        let skills_canonical = skills_root.canonicalize().unwrap_or_else(|_| skills_root.clone());
+        let roots = (workspace_canonical, skills_canonical);
+
+        let mut debouncer = new_debouncer(DEBOUNCE, move |result: DebounceEventResult| match result {
+            Ok(events) => {
+                let paths: Vec<PathBuf> = events.into_iter().map(|e| e.path).collect();
+                for change in group_changes(&roots.0, &roots.1, &paths) {
+                    on_change(change);
+                }
+            }
+            Err(e) => log::warn!("[Workspace] File watcher error: {}", e),
+        })
+        .map_err(|e| format!("Failed to start file watcher: {}", e))?;
+
+        for root in [&workspace_root, &skills_root] {
+            debouncer
+                .watcher()
+                .watch(root, RecursiveMode::Recursive)
+                .map_err(|e| format!("Failed to watch {}: {}", root.display(), e))?;
+        }
+
+        Ok(Self { _debouncer: debouncer })
+    }
+}
+
+/// Relative `/`-joined path, or None for the root itself and for anything hidden
+/// (the `.history` store, editor swap files such as `.SOUL.md.swp`)
+fn visible_relative(root: &Path, path: &Path) -> Option<Vec<String>> {
+    let relative = path.strip_prefix(root).ok()?;
+    let mut parts = Vec::new();
+    for component in relative.components() {
+        let Component::Normal(name) = component else { return None };
+        let name = name.to_string_lossy();
+        if name.starts_with('.') || name.ends_with('~') {
+            return None;
+        }
+        parts.push(name.into_owned());
+    }
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts)
+    }
+}
+
+/// Bucket raw event paths into one `FilesChanged` per pet workspace and one for
+/// the Skills root. Paths outside both roots are dropped.
+fn group_changes(workspace_root: &Path, skills_root: &Path, paths: &[PathBuf]) -> Vec<FilesChanged> {
+    let mut groups: BTreeMap<(WatchScope, Option<String>), Vec<String>> = BTreeMap::new();
+    for path in paths {
+        let (scope, pet_id, relative) = if let Some(parts) = visible_relative(skills_root, path) {
+            (WatchScope::Skills, None, parts.join("/"))
+        } else if let Some(parts) = visible_relative(workspace_root, path) {
+            let Some((pet_id, rest)) = parts.split_first() else { continue };
+            // The pet folder itself (created or removed) is reported as ""
+            (WatchScope::Workspace, Some(pet_id.clone()), rest.join("/"))
+        } else {
+            continue;
+        };
+        let entry = groups.entry((scope, pet_id)).or_default();
+        if !entry.contains(&relative) {
+            entry.push(relative);
+        }
+    }
+    groups
+        .into_iter()
+        .map(|((scope, pet_id), mut paths)| {
+            paths.sort();
+            FilesChanged { scope, pet_id, paths }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_paths_by_pet_and_skips_hidden_entries() {
+        let workspace = Path::new("/data/workspace");
+        let skills = Path::new("/data/skills");
+        let paths: Vec<PathBuf> = [
+            "/data/workspace/pet-1/SOUL.md",
+            "/data/workspace/pet-1/skills/draw/SKILL.md",
+            "/data/workspace/pet-1/SOUL.md",
+            "/data/workspace/pet-1/.SOUL.md.swp",
+            "/data/workspace/.history/pet-1/log.jsonl",
+            "/data/workspace/pet-2/social/notes/a.md",
+            "/data/skills/weather/SKILL.md",
+            "/data/workspace",
+            "/elsewhere/file.md",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+
+        let changes = group_changes(workspace, skills, &paths);
+        assert_eq!(
+            changes,
+            vec![
+                FilesChanged {
+                    scope: WatchScope::Workspace,
+                    pet_id: Some("pet-1".to_string()),
+                    paths: vec!["SOUL.md".to_string(), "skills/draw/SKILL.md".to_string()],
+                },
+                FilesChanged {
+                    scope: WatchScope::Workspace,
+                    pet_id: Some("pet-2".to_string()),
+                    paths: vec!["social/notes/a.md".to_string()],
+                },
+                FilesChanged {
+                    scope: WatchScope::Skills,
+                    pet_id: None,
+                    paths: vec!["weather/SKILL.md".to_string()],
+                },
+            ]
+        );
+        assert_eq!(
+            serde_json::to_value(&changes[2]).unwrap(),
+            serde_json::json!({ "scope": "skills", "petId": null, "paths": ["weather/SKILL.md"] })
+        );
+    }
+}
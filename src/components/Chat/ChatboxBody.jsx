@@ -3,6 +3,7 @@ import ChatboxTitleBar from '../Layout/ChatboxTitleBar';
 import ChatboxInputArea from './ChatboxInputArea';
 import ChatboxMessageArea from './ChatboxMessageArea';
 import useActiveTabState from './useActiveTabState';
+import ToolApprovalDialog from './ToolApprovalDialog';
 import {
   COMPACT_CHAT_VIEW,
   EMPTY_CHAT_PRESENTATION,
@@ -154,6 +155,8 @@ export const Chatbox = () => {
 
   // Track if default assistant has been loaded
   const defaultAssistantLoadedRef = useRef(false);
+  // 上次打开的 tab 恢复完成前不回写，避免空列表覆盖掉它们
+  const tabsRestoredRef = useRef(false);
   
   // Keep a ref to the latest navBarChats for use in event handlers
   const navBarChatsRef = useRef(navBarChats);
@@ -309,6 +312,19 @@ export const Chatbox = () => {
     });
   }, [conversations]);
 
+  // tab 列表、顺序或激活 tab 变化时写回 Rust，重启后恢复
+  useEffect(() => {
+    if (!tabsRestoredRef.current) return;
+    const openTabs = tabs.map((tab, position) => ({
+      conversationId: tab.id,
+      position,
+      isActive: tab.id === activeTabId,
+    }));
+    tauri.setOpenTabs(openTabs).catch(error => {
+      console.error('[ChatboxBody] Failed to persist open tabs:', error);
+    });
+  }, [tabs, activeTabId]);
+
   // 监听 Rust 端发送的鼠标悬停事件
   useEffect(() => {
     let unlisten;
@@ -618,11 +634,42 @@ export const Chatbox = () => {
       }
     };
 
+    // 恢复上次打开的 tab；消息和中断的回复已由 Rust 从 SQLite 载入 TabState
+    const restoreOpenTabs = async () => {
+      try {
+        const savedTabs = await tauri.getOpenTabs();
+        const restored = [];
+        for (const saved of savedTabs || []) {
+          const conversation = await fetchConversationById(saved.conversationId).catch(() => null);
+          if (!conversation) continue;
+          restored.push({
+            id: saved.conversationId,
+            label: conversation.title || "Chat",
+            petId: conversation.petId,
+            messages: conversation.history,
+            isActive: saved.isActive,
+          });
+        }
+        if (!isMounted || restored.length === 0) return false;
+        const activeId = (restored.find(t => t.isActive) || restored[restored.length - 1]).id;
+        setTabs(restored.map(t => ({ ...t, isActive: t.id === activeId })));
+        handleTabClick(activeId);
+        return true;
+      } catch (error) {
+        console.error('[ChatboxBody] Error restoring open tabs:', error);
+        return false;
+      } finally {
+        tabsRestoredRef.current = true;
+      }
+    };
+
     // 直接使用 Tauri listen API
     const setup = async () => {
       unlisten = await listen('character-id', (event) => {
         handleCharacterId(event.payload);
       });
+
+      const hasRestoredTabs = await restoreOpenTabs();
       
       // 检查是否有待处理的 character-id (在 listener ready 之前发送的)
       try {
@@ -651,6 +698,10 @@ export const Chatbox = () => {
         return;
       }
       defaultAssistantLoadedRef.current = true;
+      if (hasRestoredTabs) {
+        console.log('[ChatboxBody] Restored open tabs, skipping default assistant');
+        return;
+      }
       
       // Skip if tabs already exist
       // Note: tabs.length check here captures initial value, which should be 0
@@ -1511,6 +1562,9 @@ export const Chatbox = () => {
         </div>
       </div>
       
+      {/* MCP tool approval prompts */}
+      <ToolApprovalDialog />
+
       {/* Transfer Modal */}
       {showTransferModal && (
         <div className="fixed inset-0 bg-black/50 flex items-center justify-center z-50">
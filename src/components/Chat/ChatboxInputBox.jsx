@@ -21,7 +21,7 @@ import * as tauri from '../../utils/tauri';
 import { shouldInjectTime, buildTimeContext } from '../../utils/timeInjection';
 import { listen } from '@tauri-apps/api/event';
 import { normalizeApiProviders } from '../../utils/apiProviders';
-import { buildSkillCatalogPrompt, createSkillToolContext, getEnabledSkills, getSkillToolDefinitions } from '../../utils/skills/index.js';
+import { createSkillToolContext, getEnabledSkills, getSkillToolDefinitions } from '../../utils/skills/index.js';
 import {
   captureSubagentPermission,
   isSubagentEnabledForConversation,
@@ -271,15 +271,39 @@ export const ChatboxInputBox = ({
   
   const memoryEnabled = getMemoryEnabledForConversation(currentConvId);
   
-  // 设置当前会话的记忆状态
+  // 设置当前会话的记忆状态；已创建的会话同时写入数据库，记忆整理和 system prompt 都以它为准
   const setMemoryEnabled = (value) => {
     const convId = authoritativeConversationId || 'temp';
     const currentValue = getMemoryEnabledForConversation(convId);
+    const next = typeof value === 'function' ? value(currentValue) : value;
     setMemoryEnabledByConversation(prev => ({
       ...prev,
-      [convId]: typeof value === 'function' ? value(currentValue) : value
+      [convId]: next
     }));
+    if (convId !== 'temp') {
+      tauri.setConversationMemoryEnabled(convId, next).catch((error) => {
+        console.error('[ChatboxInputBox] Failed to save memory switch:', error);
+      });
+    }
   };
+
+  // 打开已有会话时读取它保存的记忆开关；从未保存过的会话固化当前值
+  useEffect(() => {
+    const convId = authoritativeConversationId;
+    if (!convId || convId === 'temp' || convId in memoryEnabledByConversation) return;
+    let cancelled = false;
+    tauri.getConversationMemoryEnabled(convId).then((stored) => {
+      if (cancelled) return;
+      if (stored === null || stored === undefined) {
+        tauri.setConversationMemoryEnabled(convId, getMemoryEnabledForConversation(convId)).catch(() => {});
+        return;
+      }
+      setMemoryEnabledByConversation(prev => (convId in prev ? prev : { ...prev, [convId]: stored }));
+    }).catch((error) => {
+      console.error('[ChatboxInputBox] Failed to load memory switch:', error);
+    });
+    return () => { cancelled = true; };
+  }, [authoritativeConversationId]);
   
   // 稳定的空 Set 引用，避免每次渲染创建新对象导致无限循环
   const emptySetRef = useRef(new Set());
@@ -765,6 +789,33 @@ export const ChatboxInputBox = ({
   // console.log('[ChatboxInputBox] userMessages:', userMessages);
   // 将 userText 从全局状态中移除，改为本地状态管理
   const [userText, setUserText] = useState("");
+
+  // 草稿按会话保存在 Rust TabState（写后落盘，重启后恢复）；切换会话时载入该会话的草稿
+  const draftConversationRef = useRef(null);
+  useEffect(() => {
+    const conversationId = authoritativeConversationId;
+    draftConversationRef.current = conversationId;
+    if (!conversationId) return;
+    let cancelled = false;
+    tauri.getTabDraft(conversationId).then(draft => {
+      if (!cancelled) setUserText(draft || "");
+    }).catch(error => {
+      console.error('[ChatboxInputBox] Failed to load draft:', error);
+    });
+    return () => { cancelled = true; };
+  }, [authoritativeConversationId]);
+
+  useEffect(() => {
+    const conversationId = draftConversationRef.current;
+    if (!conversationId) return;
+    const timer = setTimeout(() => {
+      tauri.setTabDraft(conversationId, String(userText)).catch(error => {
+        console.error('[ChatboxInputBox] Failed to save draft:', error);
+      });
+    }, 500);
+    return () => clearTimeout(timer);
+  }, [userText]);
+
   const [characterId, setCharacterId] = useState(null);
   const [petInfo, setPetInfo] = useState(null);
   const capabilityPetId = activePetId || petInfo?._id;
@@ -1539,31 +1590,15 @@ export const ChatboxInputBox = ({
         console.warn('[Skills] Failed to load enabled skills:', error);
       }
 
-      if (!isDefaultPersonality) {
-        const systemContent = await buildSystemPrompt({
-          petId: petInfo._id,
-          memoryEnabled,
-          skills: enabledSkills,
-        });
-        const systemPrompt = { role: "system", content: systemContent };
-        fullMessages = [...timestampedHistory, systemPrompt, { role: "user", content: timestampedContent }];
-      } else {
-        let systemContent = '';
-        if (memoryEnabled) {
-          systemContent = await buildSystemPrompt({
-            petId: petInfo._id,
-            memoryEnabled: true,
-            skills: enabledSkills,
-          });
-        } else if (enabledSkills.length > 0) {
-          // Default personality with memory disabled should not accidentally
-          // re-introduce SOUL.md merely because Skills are enabled.
-          systemContent = buildSkillCatalogPrompt(enabledSkills);
-        }
-        systemContent += '\nYou are a helpful assistant.';
-        const systemPrompt = { role: "system", content: systemContent };
-        fullMessages = [...timestampedHistory, systemPrompt, { role: "user", content: timestampedContent }];
-      }
+      // System prompt 由 Rust 组装：默认模型关闭记忆时只带 Skill 目录和一句基础指令，
+      // 其余情况注入人格/记忆文件，各节按 token 预算截断
+      const systemContent = await buildSystemPrompt({
+        petId: petInfo._id,
+        conversationId: sendingConversationId !== 'temp' ? sendingConversationId : null,
+        memoryEnabled,
+      });
+      const systemPrompt = { role: "system", content: systemContent };
+      fullMessages = [...timestampedHistory, systemPrompt, { role: "user", content: timestampedContent }];
       
       if (messageAttachments.length > 0) {
           setAttachments([]);
@@ -1830,6 +1865,8 @@ When using tools, please follow these guidelines:
         console.log('[handleSend] ★★★ 新对话创建完成: id=', newConversation?._id);
         if (newConversation) {
             sendingConversationId = newConversation._id;
+            // 新 Tab 上的记忆开关随会话一起保存
+            await tauri.setConversationMemoryEnabled(sendingConversationId, memoryEnabled);
             // 初始化 Rust TabState
             await tauri.setTabStateMessages(sendingConversationId, [...currentMsgs, botReply]);
             // 如果用户还在当前页面，更新 ref
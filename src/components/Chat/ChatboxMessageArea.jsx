@@ -721,7 +721,8 @@ const ChatboxMessageArea = ({
     if (activeConvId) {
         try {
             await tauri.updateTabStateMessage(activeConvId, msgIndex, updatedMsg);
-            await tauri.updateConversation(activeConvId, { history: newMessages });
+            // 编辑会产生新分支，原消息及其后续回复保留在旧分支上
+            await tauri.updateConversation(activeConvId, { history: newMessages, keepBranches: true });
         } catch (error) {
             console.error("Failed to save edit:", error);
         }
@@ -804,8 +805,10 @@ const ChatboxMessageArea = ({
         try {
             // 新方案: 更新 Rust TabState 和后端
             await tauri.setTabStateMessages(activeConvId, newMessages);
+            // 旧回复保留为兄弟分支，新回复保存时会挂在同一条 user 消息下
             await tauri.updateConversation(activeConvId, {
-                history: newMessages
+                history: newMessages,
+                keepBranches: true
             });
 
             setTimeout(() => {
@@ -1113,6 +1116,10 @@ const ChatboxMessageArea = ({
                 </div>
               </div>
             ))}
+            {/* 重启前没有生成完的回复（由 Rust TabState 恢复） */}
+            {!isUser && msg.interrupted && (
+              <div className="px-1 text-xs italic text-gray-400">Reply interrupted</div>
+            )}
             {!isUser && messageQuickReplies.length > 0 && (
               <QuickReplySuggestions
                 suggestions={messageQuickReplies}
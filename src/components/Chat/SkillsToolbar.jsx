@@ -3,7 +3,7 @@ import { createPortal } from 'react-dom';
 import { FaPuzzlePiece, FaSpinner, FaTriangleExclamation } from 'react-icons/fa6';
 import { FiCheck, FiRefreshCw } from 'react-icons/fi';
 import * as tauri from '../../utils/tauri';
-import { listSkills, loadSkillConfig, setSkillEnabled } from '../../utils/skills/index.js';
+import { isSkillFilesChange, listSkills, loadSkillConfig, setSkillEnabled } from '../../utils/skills/index.js';
 import { buildSkillsToolbarRows, canToggleToolbarSkill, isSkillsConfigUpdate } from './skillsToolbarModel.js';
 
 const POPOVER_WIDTH = 320;
@@ -91,6 +91,14 @@ export default function SkillsToolbar({
     };
   }, [petId, refresh]);
 
+  // SKILL.md edited or a Skill folder added outside PetGPT.
+  useEffect(() => {
+    if (!petId) return undefined;
+    return tauri.onWorkspaceFilesChanged((change) => {
+      if (isSkillFilesChange(change, petId)) refresh({ quiet: true });
+    });
+  }, [petId, refresh]);
+
   const updatePosition = useCallback(() => {
     if (!buttonRef.current) return;
     const rect = buttonRef.current.getBoundingClientRect();
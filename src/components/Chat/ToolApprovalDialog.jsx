@@ -0,0 +1,117 @@
+/**
+ * MCP Tool Approval Dialog
+ * 回答 Rust 端的 mcp-tool-approval-requested 审批请求（权限策略为 ask 的工具调用）
+ */
+import React, { useEffect, useState } from 'react';
+import { FiTool, FiAlertTriangle } from 'react-icons/fi';
+import * as tauri from '../../utils/tauri';
+
+const MAX_ARGS_CHARS = 2000;
+
+const formatArgs = (args) => {
+  if (args == null) return '';
+  const text = JSON.stringify(args, null, 2);
+  return text.length > MAX_ARGS_CHARS ? `${text.slice(0, MAX_ARGS_CHARS)}\n…` : text;
+};
+
+export const ToolApprovalDialog = () => {
+  // 按到达顺序排队，一次只显示一个
+  const [queue, setQueue] = useState([]);
+  const [remember, setRemember] = useState(false);
+
+  useEffect(() => {
+    const timers = new Map();
+    const drop = (requestId) => {
+      clearTimeout(timers.get(requestId));
+      timers.delete(requestId);
+      setQueue(prev => prev.filter(r => r.requestId !== requestId));
+    };
+
+    const unlisten = tauri.mcp.onToolApprovalRequested((event) => {
+      const request = event?.payload;
+      if (!request?.requestId) return;
+      setQueue(prev => [...prev, request]);
+      // Rust 端超时后会直接拒绝，这里同步移除
+      timers.set(request.requestId, setTimeout(() => drop(request.requestId), (request.timeoutSecs || 120) * 1000));
+    });
+
+    return () => {
+      unlisten();
+      timers.forEach(clearTimeout);
+    };
+  }, []);
+
+  const current = queue[0];
+
+  const respond = async (approved) => {
+    if (!current) return;
+    setQueue(prev => prev.filter(r => r.requestId !== current.requestId));
+    setRemember(false);
+    try {
+      await tauri.mcp.respondToolApproval(current.requestId, approved, remember && !!current.petId);
+    } catch (error) {
+      console.error('[MCP] Failed to answer tool approval:', error);
+    }
+  };
+
+  if (!current) return null;
+
+  const args = formatArgs(current.arguments);
+
+  return (
+    <div className="fixed inset-0 bg-black/50 flex items-center justify-center z-50">
+      <div className="bg-white rounded-xl shadow-xl w-96 max-h-[80vh] flex flex-col">
+        <div className="p-4 border-b border-gray-200">
+          <h3 className="font-semibold text-gray-800 flex items-center gap-2">
+            <FiTool className="text-blue-500" />
+            Allow tool call?
+          </h3>
+          <p className="text-sm text-gray-500 mt-1 break-all">
+            <span className="font-medium text-gray-700">{current.toolName}</span>
+            {' '}on {current.serverName}
+          </p>
+          {current.destructive && (
+            <p className="text-xs text-amber-600 mt-2 flex items-center gap-1">
+              <FiAlertTriangle />
+              The server marks this tool as destructive
+            </p>
+          )}
+        </div>
+        {args && (
+          <pre className="flex-1 overflow-auto m-3 p-2 rounded bg-gray-50 text-xs text-gray-700 whitespace-pre-wrap break-all">
+            {args}
+          </pre>
+        )}
+        <div className="p-3 border-t border-gray-200 flex items-center gap-2">
+          {current.petId && (
+            <label className="flex items-center gap-1 text-xs text-gray-600 flex-1 cursor-pointer">
+              <input
+                type="checkbox"
+                checked={remember}
+                onChange={(e) => setRemember(e.target.checked)}
+              />
+              Remember for this assistant
+            </label>
+          )}
+          {queue.length > 1 && (
+            <span className="text-xs text-gray-400">+{queue.length - 1} more</span>
+          )}
+          <button
+            onClick={() => respond(false)}
+            className="ml-auto px-3 py-1.5 text-sm rounded-lg text-gray-600 hover:bg-gray-100 transition-colors"
+          >
+            Deny
+          </button>
+          <button
+            onClick={() => respond(true)}
+            className="px-3 py-1.5 text-sm rounded-lg bg-blue-500 text-white hover:bg-blue-600 transition-colors"
+          >
+            Allow
+          </button>
+        </div>
+      </div>
+    </div>
+  );
+};
+
+export default ToolApprovalDialog;
@@ -4,6 +4,7 @@ import { FiRefreshCw } from 'react-icons/fi';
 import { Alert, Badge, Button } from '../UI/ui';
 import * as tauri from '../../utils/tauri';
 import {
+  isSkillFilesChange,
   listSkills,
   loadSkillConfig,
   setSkillEnabled,
@@ -97,6 +98,13 @@ const AssistantSkillsSelector = ({ petId }) => {
     };
   }, [petId, loadSkills]);
 
+  useEffect(() => {
+    if (!petId) return undefined;
+    return tauri.onWorkspaceFilesChanged((change) => {
+      if (isSkillFilesChange(change, petId)) loadSkills({ quiet: true });
+    });
+  }, [petId, loadSkills]);
+
   const handleToggle = async (skill, enabled) => {
     setError('');
     setBusySkillIds(current => new Set(current).add(skill.id));
@@ -14,6 +14,7 @@ import * as tauri from '../../utils/tauri';
 import {
   createGlobalSkillTemplate,
   deleteGlobalSkill,
+  isSkillFilesChange,
   listGlobalSkills,
   openGlobalSkillsFolder,
 } from '../../utils/skills/index.js';
@@ -131,6 +132,11 @@ const SkillsPanel = () => {
     scanSkills();
   }, [scanSkills]);
 
+  // Skills copied into or edited in the global folder outside PetGPT.
+  useEffect(() => tauri.onWorkspaceFilesChanged((change) => {
+    if (isSkillFilesChange(change)) scanSkills({ background: true });
+  }), [scanSkills]);
+
   const handleCreateTemplate = async (event) => {
     event.preventDefault();
     const skillId = templateName.trim();
@@ -1452,6 +1452,8 @@ const McpServerForm = ({ server, onSave, onCancel }) => {
   const [url, setUrl] = useState(server?.url || '');
   const [apiKey, setApiKey] = useState(server?.apiKey || '');
   const [autoStart, setAutoStart] = useState(server?.autoStart || false);
+  // '' = default (on-failure for auto-start servers, never otherwise)
+  const [restartPolicy, setRestartPolicy] = useState(server?.restartPolicy || '');
   const [icon, setIcon] = useState(server?.icon || '🔧');
   const [showInToolbar, setShowInToolbar] = useState(server?.showInToolbar !== false);
   // Max iterations: null/undefined means unlimited, number means limited
@@ -1463,6 +1465,31 @@ const McpServerForm = ({ server, onSave, onCancel }) => {
   const [testResult, setTestResult] = useState(null);
   const [saving, setSaving] = useState(false);
   const [showApiKey, setShowApiKey] = useState(false);
+  const [oauthAuthorized, setOauthAuthorized] = useState(false);
+  const [authorizing, setAuthorizing] = useState(false);
+
+  useEffect(() => {
+    if (!server?._id || (server.transport || 'stdio') !== 'http') return;
+    tauri.mcp.isAuthorized(server._id).then(setOauthAuthorized).catch(() => {});
+  }, [server]);
+
+  const handleAuthorize = async () => {
+    setError('');
+    setAuthorizing(true);
+    try {
+      await tauri.mcp.authorize(server._id);
+      setOauthAuthorized(true);
+    } catch (err) {
+      setError(err.message || err.toString() || 'Authorization failed');
+    } finally {
+      setAuthorizing(false);
+    }
+  };
+
+  const handleLogout = async () => {
+    await tauri.mcp.logout(server._id);
+    setOauthAuthorized(false);
+  };
 
   // 构建配置对象
   const buildConfig = () => {
@@ -1470,6 +1497,7 @@ const McpServerForm = ({ server, onSave, onCancel }) => {
       name: name.trim(),
       transport,
       autoStart,
+      restartPolicy,
       icon,
       showInToolbar,
       maxIterations: isUnlimited ? null : (maxIterations || 10)
@@ -1560,6 +1588,7 @@ const McpServerForm = ({ server, onSave, onCancel }) => {
     if (transport !== (server.transport || 'stdio')) return true;
     if (name !== server.name) return true;
     if (autoStart !== (server.autoStart || false)) return true;
+    if (restartPolicy !== (server.restartPolicy || '')) return true;
     
     if (transport === 'stdio') {
       if (command !== (server.command || '')) return true;
@@ -1720,6 +1749,25 @@ const McpServerForm = ({ server, onSave, onCancel }) => {
               </button>
             </div>
           </FormGroup>
+
+          {server?._id && (
+            <FormGroup>
+              <Label>OAuth</Label>
+              <div className="flex items-center gap-3">
+                <span className="text-sm text-gray-500">
+                  {oauthAuthorized ? 'Signed in' : 'Not signed in'}
+                </span>
+                <button
+                  type="button"
+                  onClick={oauthAuthorized ? handleLogout : handleAuthorize}
+                  disabled={authorizing}
+                  className="px-3 py-1 text-sm rounded-md border border-slate-200 text-slate-600 hover:bg-slate-50 disabled:opacity-50 transition-colors"
+                >
+                  {authorizing ? 'Waiting for browser...' : oauthAuthorized ? 'Sign out' : 'Sign in'}
+                </button>
+              </div>
+            </FormGroup>
+          )}
         </>
       )}
       
@@ -1743,6 +1791,16 @@ const McpServerForm = ({ server, onSave, onCancel }) => {
           label="Show in toolbar"
         />
       </div>
+
+      <FormGroup>
+        <Label>Restart Policy</Label>
+        <Select value={restartPolicy} onChange={(e) => setRestartPolicy(e.target.value)}>
+          <option value="">Default ({autoStart ? 'on failure' : 'never'})</option>
+          <option value="never">Never</option>
+          <option value="on-failure">On failure (with backoff)</option>
+          <option value="always">Always</option>
+        </Select>
+      </FormGroup>
       
       {/* Max Iterations Setting */}
       <FormGroup>
@@ -2156,12 +2214,164 @@ const ModelsPanel = () => {
   );
 };
 
+const IMPORT_ACTION_TONES = {
+  create: 'green',
+  rename: 'blue',
+  overwrite: 'amber',
+  skip: 'gray',
+  invalid: 'red',
+};
+
+/**
+ * 导入 mcpServers 格式的 JSON 配置：先 dry-run 预览，再确认导入
+ */
+const McpImportPanel = ({ json, onDone, onCancel }) => {
+  const [onConflict, setOnConflict] = useState('skip');
+  const [preview, setPreview] = useState(null);
+  const [error, setError] = useState('');
+  const [importing, setImporting] = useState(false);
+
+  useEffect(() => {
+    setError('');
+    tauri.mcp.importServers(json, onConflict, true)
+      .then(setPreview)
+      .catch((err) => {
+        setPreview(null);
+        setError(err.message || err.toString());
+      });
+  }, [json, onConflict]);
+
+  const handleImport = async () => {
+    setImporting(true);
+    setError('');
+    try {
+      const report = await tauri.mcp.importServers(json, onConflict, false);
+      const imported = report.items.filter(item => item.serverId).length;
+      await tauri.mcp.emitServersUpdated({ action: 'imported', count: imported });
+      onDone();
+    } catch (err) {
+      setError(err.message || err.toString() || 'Import failed');
+    } finally {
+      setImporting(false);
+    }
+  };
+
+  const importable = preview?.items.filter(item => !['skip', 'invalid'].includes(item.action)).length || 0;
+
+  return (
+    <div className="space-y-4">
+      <FormGroup>
+        <Label>When a name already exists</Label>
+        <Select value={onConflict} onChange={(e) => setOnConflict(e.target.value)}>
+          <option value="skip">Skip</option>
+          <option value="rename">Import with a new name</option>
+          <option value="overwrite">Overwrite the existing server</option>
+        </Select>
+      </FormGroup>
+
+      {error && <Alert variant="error">{error}</Alert>}
+
+      {preview && (
+        <div className="space-y-2">
+          {preview.items.map(item => (
+            <div key={item.sourceName} className="p-2 rounded-md border border-slate-200 text-sm">
+              <div className="flex items-center gap-2">
+                <Badge tone={IMPORT_ACTION_TONES[item.action] || 'gray'}>{item.action}</Badge>
+                <span className="font-medium text-slate-700">{item.name}</span>
+                {item.name !== item.sourceName && (
+                  <span className="text-slate-400">(from {item.sourceName})</span>
+                )}
+                <span className="ml-auto text-xs text-slate-400">{item.transport}</span>
+              </div>
+              {item.error && <div className="mt-1 text-xs text-red-500">{item.error}</div>}
+              {item.warnings.map(warning => (
+                <div key={warning} className="mt-1 text-xs text-amber-600">{warning}</div>
+              ))}
+            </div>
+          ))}
+        </div>
+      )}
+
+      <div className="flex justify-end gap-2">
+        <Button variant="secondary" onClick={onCancel}>Cancel</Button>
+        <Button variant="primary" onClick={handleImport} disabled={importing || importable === 0}>
+          {importing ? 'Importing...' : `Import ${importable} server${importable === 1 ? '' : 's'}`}
+        </Button>
+      </div>
+    </div>
+  );
+};
+
+/**
+ * PetGPT 自身作为 MCP Server（供其他 agent / IDE 连接）
+ */
+const McpHostCard = () => {
+  const [status, setStatus] = useState(null);
+  const [busy, setBusy] = useState(false);
+  const [error, setError] = useState('');
+
+  useEffect(() => {
+    tauri.mcp.host.status().then(setStatus).catch(() => {});
+  }, []);
+
+  const run = async (action) => {
+    setBusy(true);
+    setError('');
+    try {
+      setStatus(await action());
+    } catch (err) {
+      setError(err.message || err.toString());
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  if (!status) return null;
+
+  return (
+    <Card
+      title="PetGPT as MCP Server"
+      description="Let other agents and IDEs read your assistants' memory and conversations"
+    >
+      <div className="space-y-2 text-sm">
+        <div className="flex items-center gap-2">
+          <Badge tone={status.running ? 'green' : 'gray'}>{status.running ? 'Running' : 'Stopped'}</Badge>
+          <div className="ml-auto flex gap-2">
+            {status.running && (
+              <Button variant="secondary" disabled={busy} onClick={() => run(tauri.mcp.host.resetToken)}>
+                Reset token
+              </Button>
+            )}
+            <Button
+              variant={status.running ? 'secondary' : 'primary'}
+              disabled={busy}
+              onClick={() => run(status.running ? tauri.mcp.host.stop : () => tauri.mcp.host.start())}
+            >
+              {status.running ? 'Stop' : 'Start'}
+            </Button>
+          </div>
+        </div>
+        {error && <Alert variant="error">{error}</Alert>}
+        {status.running && (
+          <>
+            <div className="text-slate-600">URL: <code className="select-all">{status.url}</code></div>
+            <div className="text-slate-600">Header: <code className="select-all">Authorization: Bearer {status.token}</code></div>
+          </>
+        )}
+        <div className="text-slate-500">stdio: <code className="select-all">{status.stdioCommand}</code></div>
+      </div>
+    </Card>
+  );
+};
+
 const McpServersPanel = () => {
   const [servers, setServers] = useState([]);
   const [serverStatuses, setServerStatuses] = useState({});
   const [loading, setLoading] = useState(true);
   const [isCreating, setIsCreating] = useState(false);
   const [editingServer, setEditingServer] = useState(null);
+  const [importJson, setImportJson] = useState(null);
+  const [exportMessage, setExportMessage] = useState('');
   
   const loadServers = useCallback(async () => {
     try {
@@ -2236,12 +2446,42 @@ const McpServersPanel = () => {
     setIsCreating(false);
   };
 
+  const handleImportFile = async () => {
+    try {
+      const selected = await tauri.selectFile({
+        filters: [{ name: 'MCP Config', extensions: ['json'] }],
+        multiple: false,
+        directory: false,
+      });
+      if (!selected) return;
+      setImportJson(await tauri.readFile(selected));
+    } catch (err) {
+      alert('Failed to read config file: ' + (err.message || err));
+    }
+  };
+
+  const handleExport = async () => {
+    try {
+      const json = await tauri.mcp.exportServers();
+      await navigator.clipboard.writeText(json);
+      setExportMessage(`Copied ${servers.length} server config${servers.length === 1 ? '' : 's'} to clipboard`);
+      setTimeout(() => setExportMessage(''), 3000);
+    } catch (err) {
+      alert('Failed to export servers: ' + (err.message || err));
+    }
+  };
+
+  const handleImportDone = () => {
+    setImportJson(null);
+    loadServers();
+  };
+
   const handleCancel = () => {
     setIsCreating(false);
     setEditingServer(null);
   };
 
-  const showForm = isCreating || editingServer;
+  const showForm = isCreating || editingServer || importJson !== null;
 
   return (
     <>
@@ -2251,17 +2491,40 @@ const McpServersPanel = () => {
           <div className="text-base font-semibold text-slate-800">
             MCP Servers ({servers.length})
           </div>
-          <Button variant="primary" onClick={() => setIsCreating(true)}>
-            <FaPlus className="w-4 h-4" />
-            New
-          </Button>
+          <div className="flex items-center gap-2">
+            {exportMessage && <span className="text-xs text-slate-500">{exportMessage}</span>}
+            <Button variant="secondary" onClick={handleImportFile}>
+              <FaFile className="w-4 h-4" />
+              Import
+            </Button>
+            <Button variant="secondary" onClick={handleExport} disabled={servers.length === 0}>
+              <FaDownload className="w-4 h-4" />
+              Export
+            </Button>
+            <Button variant="primary" onClick={() => setIsCreating(true)}>
+              <FaPlus className="w-4 h-4" />
+              New
+            </Button>
+          </div>
         </div>
       )}
 
       {/* Scrollable content */}
       <div className="flex-1 min-h-0 overflow-y-auto px-4 py-3 space-y-3">
         {!showForm && <QqConnectorPanel onReady={loadServers} />}
-        {showForm ? (
+        {!showForm && <McpHostCard />}
+        {importJson !== null ? (
+          <Card
+            title="Import MCP Servers"
+            description="Servers from an mcpServers JSON config (Claude Desktop, Cursor, VS Code...)"
+          >
+            <McpImportPanel
+              json={importJson}
+              onDone={handleImportDone}
+              onCancel={() => setImportJson(null)}
+            />
+          </Card>
+        ) : showForm ? (
           <Card
             title={editingServer ? `Edit: ${editingServer.name}` : "New MCP Server"}
             description="Configure an MCP server for tool integration"
@@ -0,0 +1,38 @@
+import test from 'node:test';
+import assert from 'node:assert/strict';
+
+import { applyTabStateEvent, createTabStateMirror } from '../tabStateOps.js';
+
+const user = { role: 'user', content: 'hi' };
+const reply = { role: 'assistant', content: 'hello' };
+
+test('applies ops in version order', () => {
+  let state = applyTabStateEvent(null, { version: 3, op: 'reset', messages: [user], is_thinking: false });
+  state = applyTabStateEvent(state, { version: 4, op: 'thinking', is_thinking: true });
+  state = applyTabStateEvent(state, { version: 5, op: 'append', message: reply });
+  state = applyTabStateEvent(state, { version: 6, op: 'patch', index: 1, message: { ...reply, content: 'hey' } });
+  assert.deepEqual(state, {
+    messages: [user, { ...reply, content: 'hey' }],
+    is_thinking: true,
+    version: 6,
+  });
+  state = applyTabStateEvent(state, { version: 7, op: 'delete', index: 0 });
+  assert.equal(state.messages.length, 1);
+});
+
+test('ignores stale events and reports gaps', () => {
+  const state = { messages: [user], is_thinking: false, version: 2 };
+  assert.equal(applyTabStateEvent(state, { version: 2, op: 'append', message: reply }), state);
+  assert.equal(applyTabStateEvent(state, { version: 4, op: 'append', message: reply }), null);
+  assert.equal(applyTabStateEvent(null, { version: 1, op: 'append', message: reply }), null);
+});
+
+test('mirror refetches after a gap', async () => {
+  const seen = [];
+  const snapshot = { messages: [user, reply], is_thinking: false, version: 9 };
+  const onEvent = createTabStateMirror(async () => snapshot, (state) => seen.push(state));
+  onEvent({ version: 9, op: 'append', message: reply });
+  await new Promise((resolve) => setTimeout(resolve, 0));
+  onEvent({ version: 10, op: 'thinking', is_thinking: true });
+  assert.deepEqual(seen, [snapshot, { ...snapshot, is_thinking: true, version: 10 }]);
+});
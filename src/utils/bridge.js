@@ -6,6 +6,8 @@
  * 在 Tauri 环境下，它使用 @tauri-apps/api 的 invoke 和 listen
  */
 
+import { createTabStateMirror } from './tabStateOps.js';
+
 // 检测运行环境
 export const isTauri = () => {
   return window.__TAURI_INTERNALS__ !== undefined;
@@ -1379,118 +1381,15 @@ export const transferConversation = async (conversationId, newPetId) => {
   return false;
 };
 
-// ============ Tab Message Cache API (Rust 内存缓存) ============
-
-/**
- * 获取指定会话的缓存消息
- * @param {string} conversationId - 会话 ID
- * @returns {Promise<Array>} 消息数组
- */
-export const getTabMessages = async (conversationId) => {
-  if (!isTauri()) return [];
-  
-  const { invoke } = await getTauriApi();
-  return invoke('get_tab_messages', { conversationId });
-};
-
-/**
- * 设置指定会话的消息（完全替换）
- * @param {string} conversationId - 会话 ID
- * @param {Array} messages - 消息数组
- */
-export const setTabMessages = async (conversationId, messages) => {
-  if (!isTauri()) return;
-  
-  const { invoke } = await getTauriApi();
-  return invoke('set_tab_messages', { conversationId, messages });
-};
-
-/**
- * 添加一条消息到指定会话
- * @param {string} conversationId - 会话 ID
- * @param {Object} message - 消息对象 { role, content, toolCallHistory? }
- */
-export const addTabMessage = async (conversationId, message) => {
-  if (!isTauri()) return;
-  
-  const { invoke } = await getTauriApi();
-  return invoke('add_tab_message', { conversationId, message });
-};
-
-/**
- * 更新指定位置的消息
- * @param {string} conversationId - 会话 ID
- * @param {number} index - 消息索引
- * @param {Object} message - 新消息内容
- * @returns {Promise<boolean>} 是否更新成功
- */
-export const updateTabMessage = async (conversationId, index, message) => {
-  if (!isTauri()) return false;
-  
-  const { invoke } = await getTauriApi();
-  return invoke('update_tab_message', { conversationId, index, message });
-};
-
-/**
- * 删除指定位置的消息
- * @param {string} conversationId - 会话 ID
- * @param {number} index - 消息索引
- * @returns {Promise<boolean>} 是否删除成功
- */
-export const deleteTabMessage = async (conversationId, index) => {
-  if (!isTauri()) return false;
-  
-  const { invoke } = await getTauriApi();
-  return invoke('delete_tab_message', { conversationId, index });
-};
-
-/**
- * 清空指定会话的消息
- * @param {string} conversationId - 会话 ID
- */
-export const clearTabMessages = async (conversationId) => {
-  if (!isTauri()) return;
-  
-  const { invoke } = await getTauriApi();
-  return invoke('clear_tab_messages', { conversationId });
-};
-
-/**
- * 获取消息数量
- * @param {string} conversationId - 会话 ID
- * @returns {Promise<number>} 消息数量
- */
-export const getTabMessagesCount = async (conversationId) => {
-  if (!isTauri()) return 0;
-  
-  const { invoke } = await getTauriApi();
-  return invoke('get_tab_messages_count', { conversationId });
-};
-
-/**
- * 监听消息更新事件
- * @param {Function} callback - 回调函数，参数为 conversationId
- * @returns {Promise<Function>} 取消监听的函数
- */
-export const onTabMessagesUpdated = async (callback) => {
-  if (!isTauri()) return () => {};
-  
-  const { listen } = await getTauriApi();
-  const unlisten = await listen('tab-messages-updated', (event) => {
-    callback(event.payload);
-  });
-  return unlisten;
-};
-
 // ============ Tab State API (新的 Rust-owned 状态管理) ============
 
 /**
  * 获取指定会话的完整状态（用于初始加载）
  * @param {string} conversationId - 会话 ID
- * @returns {Promise<{messages: Array, is_thinking: boolean}>} Tab 状态快照
+ * @returns {Promise<{messages: Array, is_thinking: boolean, version: number}>} Tab 状态快照
  */
 export const getTabState = async (conversationId) => {
-  if (!isTauri()) return { messages: [], is_thinking: false };
+  if (!isTauri()) return { messages: [], is_thinking: false, version: 0 };
   
   const { invoke } = await getTauriApi();
   return invoke('get_tab_state', { conversationId });
@@ -1593,8 +1492,9 @@ export const subscribeTabState = async (conversationId, callback) => {
   
   const { listen } = await getTauriApi();
   const eventName = `tab-state:${conversationId}`;
+  const onEvent = createTabStateMirror(() => getTabState(conversationId), callback);
   const unlisten = await listen(eventName, (event) => {
-    callback(event.payload);
+    onEvent(event.payload);
   });
   return unlisten;
 };
@@ -2162,6 +2062,7 @@ export const mcp = {
       const tools = await invoke('mcp_get_all_tools');
       // 转换为与 Electron 兼容的格式
       return tools.map(t => ({
+        toolId: t.toolId,
         serverId: t.serverId,
         serverName: t.serverName,
         name: t.tool.name,
@@ -2829,14 +2730,6 @@ const bridge = {
   transferConversation,
   
   // Tab Message Cache (Rust 内存缓存) - legacy API
-  getTabMessages,
-  setTabMessages,
-  addTabMessage,
-  updateTabMessage,
-  deleteTabMessage,
-  clearTabMessages,
-  getTabMessagesCount,
-  onTabMessagesUpdated,
   
   // Tab State (新的 Rust-owned 状态管理)
   getTabState,
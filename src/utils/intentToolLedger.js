@@ -24,6 +24,7 @@ const NON_REPEAT_OPTIONAL_TOOL_NAMES = new Set([
 const REPLAYABLE_READ_TOOL_NAMES = new Set([
   'social_tree',
   'social_read',
+  'social_search',
   'history_read',
   'daily_read',
   'daily_list',
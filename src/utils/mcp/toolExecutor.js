@@ -107,6 +107,7 @@ if (tauri.mcp?.onServersUpdated) {
 
 // 工具执行超时配置 (毫秒)
 const TOOL_EXECUTION_TIMEOUT_MS = 64000; // 64s for individual tool call
+const TOOL_APPROVAL_TIMEOUT_MS = 120000; // 与 permissions.rs 的 APPROVAL_TIMEOUT_SECS 一致，交互调用额外等这么久
 const DEFAULT_TOOL_TIMEOUT_MS = 60000; // 1 minute default
 
 /**
@@ -119,7 +120,21 @@ const DEFAULT_TOOL_TIMEOUT_MS = 60000; // 1 minute default
 const getDeclaredToolNames = (mcpTools) => new Set(
   (Array.isArray(mcpTools) ? mcpTools : [])
     .filter(tool => tool?.name)
-    .map(tool => tool.serverName ? `${tool.serverName}__${tool.name}` : tool.name),
+    .map(getToolCallName),
+);
+
+/**
+ * 工具暴露给模型的名称：优先使用 Rust 端分配的命名空间 ID（toolId），
+ * 否则回退到 serverName__toolName
+ */
+export const getToolCallName = (tool) =>
+  tool.toolId || (tool.serverName ? `${tool.serverName}__${tool.name}` : tool.name);
+
+// 模型调用名 -> 工具，用于反查所属服务器和 toolId
+const indexToolsByCallName = (mcpTools) => new Map(
+  (Array.isArray(mcpTools) ? mcpTools : [])
+    .filter(tool => tool?.name)
+    .map(tool => [getToolCallName(tool), tool]),
 );
 
 const undeclaredToolResult = (toolName) => ({
@@ -129,21 +144,24 @@ const undeclaredToolResult = (toolName) => ({
 /**
  * 获取可用的 MCP 工具列表
  * 
+ * @param {string|null} petId - 宠物 ID（可选），用于过滤该宠物禁用的工具
  * @returns {Promise<Array>} MCP 工具数组
  */
-export const getMcpTools = async () => {
+export const getMcpTools = async (petId = null) => {
   try {
     if (!tauri.mcp?.getAllTools) {
       console.log('[MCP] MCP API not available');
       return [];
     }
     
-    const rawTools = await tauri.mcp.getAllTools();
+    // 传入 petId 时，Rust 端会去掉该宠物禁用的工具
+    const rawTools = await tauri.mcp.getAllTools(petId);
     
     // 扁平化 Rust 返回的嵌套结构
-    // Rust 返回: { serverId, serverName, tool: { name, description, inputSchema, annotations } }
+    // Rust 返回: { toolId, serverId, serverName, tool: { name, description, inputSchema, annotations } }
     // 前端需要保留 annotations，供调用方根据 readOnlyHint 等元数据收窄工具权限。
     const tools = rawTools.map(item => ({
+      toolId: item.toolId,
       serverId: item.serverId,
       serverName: item.serverName,
       name: item.tool?.name,
@@ -228,10 +246,14 @@ export const executeMcpTool = async (serverName, toolName, args, options = {}) =
  * @param {Object} options - 选项
  * @param {number} options.timeout - 超时时间（毫秒），默认 5 分钟
  * @param {AbortSignal} options.abortSignal - 取消信号
+ * @param {string} options.toolId - Rust 端分配的命名空间工具 ID（可选）
+ * @param {string} options.petId - 宠物 ID（可选），用于权限策略和审计
+ * @param {boolean} options.interactive - 用户能在聊天界面回答审批弹窗（可选）
  * @returns {Promise<*>} 执行结果
  */
 export const executeToolByName = async (toolName, args, options = {}) => {
-  const timeout = options.timeout || TOOL_EXECUTION_TIMEOUT_MS;
+  const timeout = options.timeout
+    || (options.interactive ? TOOL_EXECUTION_TIMEOUT_MS + TOOL_APPROVAL_TIMEOUT_MS : TOOL_EXECUTION_TIMEOUT_MS);
   
   try {
     if (!tauri.mcp?.callToolByName) {
@@ -246,7 +268,10 @@ export const executeToolByName = async (toolName, args, options = {}) => {
     console.log(`[MCP] Executing tool by name: ${toolName} (timeout: ${timeout}ms)`, args);
     
     // 创建一个可以被取消的 Promise
-    const toolPromise = tauri.mcp.callToolByName(toolName, args);
+    // 有 toolId 时由 Rust 端反查服务器，避免同名服务器/工具的歧义
+    const toolPromise = options.toolId && tauri.mcp.callToolById
+      ? tauri.mcp.callToolById(options.toolId, args, options.petId, options.interactive)
+      : tauri.mcp.callToolByName(toolName, args, { petId: options.petId, interactive: options.interactive });
     
     // 如果有 abortSignal，监听取消事件
     if (options.abortSignal) {
@@ -461,8 +486,8 @@ export const convertToolsForLLM = (mcpTools, apiFormat) => {
   // 为工具添加服务器前缀以确保唯一性
   const toolsWithPrefix = mcpTools.map(tool => ({
     ...tool,
-    // 使用双下划线分隔服务器名和工具名
-    name: tool.serverName ? `${tool.serverName}__${tool.name}` : tool.name
+    // 命名空间 ID 或 serverName__toolName
+    name: getToolCallName(tool)
   }));
   
   if (apiFormat === 'gemini_official') {
@@ -544,6 +569,7 @@ export const callLLMWithTools = async ({
   const adapter = pickAdapter(apiFormat);
   const llmTools = convertToolsForLLM(mcpTools, apiFormat);
   const declaredToolNames = getDeclaredToolNames(mcpTools);
+  const toolsByCallName = indexToolsByCallName(mcpTools);
 
   // 对于 Gemini，清理历史消息中缺少 thought_signature 的工具调用
   let initialMessages = [...messages];
@@ -778,7 +804,7 @@ export const callLLMWithTools = async ({
 
         // 提取服务器名称（格式: serverName__toolName）
         const parts = call.name.split('__');
-        const serverName = parts.length > 1 ? parts[0] : null;
+        const serverName = toolsByCallName.get(call.name)?.serverName ?? (parts.length > 1 ? parts[0] : null);
 
         // 检查该服务器是否达到限制
         if (isDeclared && serverName) {
@@ -863,7 +889,9 @@ export const callLLMWithTools = async ({
             } else if (isSubagent && builtinToolContext) {
               toolResult = await executeSubagentTool(call.name, call.arguments, builtinToolContext);
             } else {
-              toolResult = await executeToolByName(call.name, call.arguments);
+              toolResult = await executeToolByName(call.name, call.arguments, {
+                toolId: toolsByCallName.get(call.name)?.toolId,
+              });
             }
           }
           if (toolResult && (
@@ -1080,6 +1108,7 @@ export const callLLMStreamWithTools = async ({
   const adapter = pickAdapter(apiFormat);
   const llmTools = convertToolsForLLM(mcpTools, apiFormat);
   const declaredToolNames = getDeclaredToolNames(mcpTools);
+  const toolsByCallName = indexToolsByCallName(mcpTools);
   
   // 对于 Gemini，清理历史消息中缺少 thought_signature 的工具调用
   // 这些消息来自数据库历史，没有签名会导致 API 报错
@@ -1316,7 +1345,7 @@ export const callLLMStreamWithTools = async ({
       
       // 提取服务器名称（格式: serverName__toolName）
       const parts = call.name.split('__');
-      const serverName = parts.length > 1 ? parts[0] : null;
+      const serverName = toolsByCallName.get(call.name)?.serverName ?? (parts.length > 1 ? parts[0] : null);
       
       // 检查该服务器是否达到限制
       if (isDeclared && serverName) {
@@ -1415,7 +1444,12 @@ export const callLLMStreamWithTools = async ({
           } else if (isSubagent && builtinToolContext) {
             toolResult = await executeSubagentTool(call.name, call.arguments, builtinToolContext);
           } else {
-            toolResult = await executeToolByName(call.name, call.arguments);
+            // 聊天界面里的调用：需要审批时由 ToolApprovalDialog 询问用户
+            toolResult = await executeToolByName(call.name, call.arguments, {
+              toolId: toolsByCallName.get(call.name)?.toolId,
+              petId: builtinToolContext?.petId,
+              interactive: true,
+            });
           }
         }
 
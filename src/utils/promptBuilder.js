@@ -1,12 +1,13 @@
 /**
  * promptBuilder.js — 基于 SOUL.md / USER.md / MEMORY.md 文件的 system prompt 构建器
  * 
- * 替换 ChatboxInputBox.jsx 中基于 isDefaultPersonality × memoryEnabled 的 4 分支逻辑。
- * 核心原则：全文本注入，无向量搜索，每轮对话重新从磁盘读取。
+ * 聊天的 system prompt 由 Rust 端 `get_system_prompt` 组装（时间、基础指令、人格/记忆文件、
+ * Skill 目录，每一节按 token 预算截断）。这里保留文件读取与截断工具，供社交循环复用，
+ * 以及内置工具定义和旧数据迁移。文件内容按 pet 缓存，
+ * 文件监听器报告变更（含外部编辑器修改）时立即失效，下一轮即读到新内容。
  */
 
 import * as tauri from './tauri';
-import { buildSkillCatalogPrompt } from './skills/index.js';
 
 // ============ 常量 ============
 
@@ -19,19 +20,72 @@ const HEAD_RATIO = 0.7;
 /** 截断时尾部保留比例 */
 const TAIL_RATIO = 0.2;
 
+/** 缓存兜底有效期：万一变更事件丢失，最多这么久后重新读盘 */
+const FILE_CACHE_TTL_MS = 60_000;
+
+// ============ 文件缓存 ============
+
+/** Map<petId, Map<path, { content, readAt }>> */
+const fileCache = new Map();
+
+/** 每次失效 +1；读盘期间发生过失效的结果不写回缓存 */
+let cacheGeneration = 0;
+
+let unsubscribeFileChanges = null;
+
+/**
+ * 按文件变更事件失效缓存。paths 含 ''（整个 pet 目录被创建/删除）时清空该 pet。
+ * @param {{ scope?: string, petId?: string, paths?: string[] }} change
+ */
+export function invalidatePromptFiles({ scope = 'workspace', petId, paths = [] } = {}) {
+  if (scope !== 'workspace') return;
+  cacheGeneration++;
+  if (!petId) {
+    fileCache.clear();
+    return;
+  }
+  const petFiles = fileCache.get(petId);
+  if (!petFiles) return;
+  if (paths.length === 0 || paths.includes('')) {
+    fileCache.delete(petId);
+    return;
+  }
+  for (const path of paths) {
+    petFiles.delete(path);
+  }
+}
+
+function ensureFileWatch() {
+  if (!unsubscribeFileChanges) {
+    unsubscribeFileChanges = tauri.onWorkspaceFilesChanged(invalidatePromptFiles);
+  }
+}
+
 // ============ 文件读取 ============
 
 /**
  * 安全读取工作区文件，文件不存在或出错时返回 null
  */
 async function safeReadFile(petId, path) {
+  ensureFileWatch();
+  const cached = fileCache.get(petId)?.get(path);
+  if (cached && Date.now() - cached.readAt < FILE_CACHE_TTL_MS) {
+    return cached.content;
+  }
+
+  const generation = cacheGeneration;
+  let content;
   try {
-    const content = await tauri.workspaceRead(petId, path);
-    return content || null;
+    content = (await tauri.workspaceRead(petId, path)) || null;
   } catch {
     // 文件不存在或读取失败，静默跳过
-    return null;
+    content = null;
   }
+  if (generation === cacheGeneration) {
+    if (!fileCache.has(petId)) fileCache.set(petId, new Map());
+    fileCache.get(petId).set(path, { content, readAt: Date.now() });
+  }
+  return content;
 }
 
 /**
@@ -73,110 +127,20 @@ export function truncateContent(content, maxChars = MAX_FILE_CHARS) {
   return `${head}\n\n[...内容被截断，完整内容请使用 read 工具查看原文件...]\n（截断：保留了 ${headLen}+${tailLen} 字符，共 ${content.length} 字符）\n\n${tail}`;
 }
 
-// ============ 动态引导指令 ============
-
-/**
- * 根据 SOUL.md 状态生成引导指令
- */
-function soulGuidance(soulContent) {
-  if (!soulContent) {
-    return '你还没有人格定义。在第一次对话时，和主人一起创建 SOUL.md。';
-  }
-  return '请根据上述人格定义来塑造你的性格和语气。';
-}
-
-/**
- * 根据 USER.md 状态生成引导指令
- */
-function userGuidance(userContent) {
-  if (!userContent) {
-    return '你对主人还不太了解。在对话中自然地了解他们，并使用 edit 工具更新 USER.md。';
-  }
-  // 简单判断内容是否丰富：去掉模板标记后超过 100 字符
-  const stripped = userContent.replace(/<!--.*?-->/gs, '').replace(/[#\-*_]/g, '').trim();
-  if (stripped.length < 100) {
-    return '你对主人还不太了解。在对话中自然地了解他们，并使用 edit 工具更新 USER.md。';
-  }
-  return '你已经了解了主人的一些信息（见上方）。在对话中获知新信息时，使用 edit 工具更新 USER.md。不要猜测，只记录主人明确告诉你的事实。';
-}
-
-/**
- * 根据 MEMORY.md 状态生成引导指令
- */
-function memoryGuidance(memoryContent) {
-  if (!memoryContent) {
-    return '你还没有长期记忆。当有值得记住的事时，使用 write 工具创建 MEMORY.md。';
-  }
-  if (memoryContent.length > MAX_FILE_CHARS * 0.8) {
-    return '你的记忆快满了。请在本次对话中整理 MEMORY.md，移除过时内容，合并重复信息。';
-  }
-  return '遇到值得记住的信息时，使用 edit 工具更新 MEMORY.md。定期整理，保持精炼。';
-}
-
 // ============ System Prompt 构建 ============
 
 /**
- * 构建完整的 system prompt
+ * 获取 Rust 端组装好的 system prompt
  * 
  * @param {Object} params
  * @param {string} params.petId - 宠物 ID
- * @param {boolean} params.memoryEnabled - 记忆开关是否开启
- * @param {string} [params.timeContext] - 时间注入上下文（可选）
- * @param {Array} [params.skills] - 本轮已启用的 Skill 元数据（只注入目录，不注入全文）
+ * @param {string} [params.conversationId] - 会话 ID（尚未创建的新会话不传）
+ * @param {boolean} params.memoryEnabled - Tab 上的记忆开关，会话保存过自己的设置时以会话为准
  * @returns {Promise<string>} 完整的 system prompt 内容
  */
-export async function buildSystemPrompt({ petId, memoryEnabled, timeContext, skills = [] }) {
-  const sections = [];
-
-  // === 时间上下文 ===
-  if (timeContext) {
-    sections.push(timeContext);
-  }
-
-  // === 始终读取 SOUL.md ===
-  const soulContent = await readSoulFile(petId);
-  const soulTruncated = truncateContent(soulContent);
-
-  sections.push('# 人格');
-  if (soulTruncated) {
-    sections.push(soulTruncated);
-  } else {
-    sections.push('（空）');
-  }
-  sections.push(soulGuidance(soulContent));
-
-  // === 记忆 ON 时：读取 USER.md 和 MEMORY.md ===
-  if (memoryEnabled) {
-    const userContent = await readUserFile(petId);
-    const userTruncated = truncateContent(userContent);
-
-    sections.push('# 用户信息');
-    if (userTruncated) {
-      sections.push(userTruncated);
-    } else {
-      sections.push('（空）');
-    }
-    sections.push(userGuidance(userContent));
-
-    const memoryContent = await readMemoryFile(petId);
-    const memoryTruncated = truncateContent(memoryContent);
-
-    sections.push('# 记忆');
-    if (memoryTruncated) {
-      sections.push(memoryTruncated);
-    } else {
-      sections.push('（空）');
-    }
-    sections.push(memoryGuidance(memoryContent));
-  }
-
-  // === Skills：只注入 name/description/version 目录，正文由 skill_load 渐进读取 ===
-  const skillCatalog = buildSkillCatalogPrompt(skills);
-  if (skillCatalog) {
-    sections.push(skillCatalog);
-  }
-
-  return sections.join('\n\n');
+export async function buildSystemPrompt({ petId, conversationId, memoryEnabled }) {
+  const { prompt } = await tauri.getSystemPrompt(petId, conversationId || null, memoryEnabled);
+  return prompt;
 }
 
 /**
@@ -240,7 +204,7 @@ export function getBuiltinToolDefinitions(memoryEnabled) {
     type: 'function',
     function: {
       name: 'edit',
-      description: '通过精确文本查找替换来编辑文件。oldText 必须精确匹配文件中的内容。' +
+      description: '通过精确文本查找替换来编辑文件。oldText 必须精确匹配文件中的内容；多处修改用 edits 一次完成。' +
         (memoryEnabled
           ? '可编辑 SOUL.md（需用户确认）、USER.md、MEMORY.md。'
           : '仅可编辑 SOUL.md（需用户确认）。'),
@@ -258,9 +222,47 @@ export function getBuiltinToolDefinitions(memoryEnabled) {
           newText: {
             type: 'string',
             description: '替换后的新文本'
+          },
+          edits: {
+            type: 'array',
+            description: '一次做多处修改时使用（代替 oldText/newText）：按顺序应用，任何一处失败则整批都不写入',
+            items: {
+              type: 'object',
+              properties: {
+                oldText: { type: 'string', description: '要替换的精确文本' },
+                newText: { type: 'string', description: '替换后的新文本' },
+                replaceAll: { type: 'boolean', description: '替换所有出现处（默认 false，要求唯一匹配）' }
+              },
+              required: ['oldText', 'newText']
+            }
+          }
+        },
+        required: ['path']
+      }
+    }
+  });
+
+  // search 工具：在可访问的文件中全文搜索，避免为了找一句话整篇 read
+  tools.push({
+    type: 'function',
+    function: {
+      name: 'search',
+      description: '在工作区文件中搜索文本，返回带行号和上下文的匹配行。' +
+        (memoryEnabled ? '搜索范围：SOUL.md、USER.md、MEMORY.md。' : '搜索范围：SOUL.md。') +
+        '记忆文件很长时，先搜索再用 edit 精确修改。',
+      parameters: {
+        type: 'object',
+        properties: {
+          query: {
+            type: 'string',
+            description: '要搜索的文本（默认按字面子串、忽略大小写匹配）'
+          },
+          regex: {
+            type: 'boolean',
+            description: '把 query 当作正则表达式（默认 false）'
           }
         },
-        required: ['path', 'oldText', 'newText']
+        required: ['query']
       }
     }
   });
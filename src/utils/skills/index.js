@@ -1,7 +1,6 @@
 import * as tauri from '../tauri';
 import {
   authorizeSkillId,
-  buildSkillCatalogPrompt,
   filterEnabledSkills,
   formatSkillDocumentResult,
   formatSkillResourceResult,
@@ -178,4 +177,4 @@ export function createSkillToolContext(petId, skills) {
 }
 
 // Keep named imports discoverable from this public module.
-export { buildSkillCatalogPrompt, getSkillToolDefinitions, isSkillTool };
+export { getSkillToolDefinitions, isSkillTool };
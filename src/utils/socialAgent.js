@@ -11,7 +11,7 @@ import { writeIntentTrace } from './intentTraining';
 import { createIntentOptionalToolLedger, formatIntentOptionalToolLedgerResume, recordIntentOptionalToolUse } from './intentToolLedger';
 import { socialTargetDir } from './socialTargetType.js';
 import { seedToolDocs } from './toolDocs';
-import { executeToolByName, getMcpTools, resolveImageUrls } from './mcp/toolExecutor';
+import { executeToolByName, getMcpTools, getToolCallName, resolveImageUrls } from './mcp/toolExecutor';
 import { callLLMWithTools } from './mcp/toolExecutor';
 import { getSocialFileToolDefinitions, getHistoryToolDefinitions, getGroupLogToolDefinitions, getStickerToolDefinitions, getBufferSearchToolDefinitions, resetStickerCooldown, getIntentPlanToolDefinitions, executeStickerBuiltinTool, getSubagentToolDefinition, getCcHistoryToolDefinition, getCcReadToolDefinition, getMdOrganizeToolDefinition, getScreenshotToolDefinition, getImageSendToolDefinition, getImageListToolDefinition, getWebshotToolDefinition, getWebshotSendToolDefinition, getChatSearchToolDefinition, getChatContextToolDefinition, getVoiceSendToolDefinition, getGenerateImageSendToolDefinition, getSituationToolDefinition, autoFixPlanArgs } from './workspace/socialToolExecutor';
 import { subagentRegistry, initSubagentListeners, destroySubagentListeners, killBySource } from './subagentManager';
@@ -3230,7 +3230,7 @@ ${fileContext ? `\n文件说明：${fileContext}\n` : ''}
             && isReadOnlyIntentExternalTool(t)
           );
           for (const tool of externalTools) {
-            intentExternalToolNames.add(tool.serverName ? `${tool.serverName}__${tool.name}` : tool.name);
+            intentExternalToolNames.add(getToolCallName(tool));
           }
           if (intentLurkMode !== 'full-lurk' && externalTools.length > 0) {
             intentMcpTools = [...intentMcpTools, ...externalTools];
@@ -0,0 +1,71 @@
+/**
+ * Apply one `tab-state:{conversationId}` event from Rust to a local mirror.
+ *
+ * Rust pushes versioned ops (reset / append / patch / delete / thinking)
+ * instead of the whole tab on every change. Returns the next state, the same
+ * state for stale events, or null when the mirror is missing or a version was
+ * skipped and the caller must refetch with `get_tab_state`.
+ */
+export function applyTabStateEvent(state, event) {
+  if (!event || typeof event.version !== 'number') return null;
+  if (event.op === 'reset') {
+    return { messages: event.messages || [], is_thinking: Boolean(event.is_thinking), version: event.version };
+  }
+  if (!state) return null;
+  if (event.version <= state.version) return state;
+  if (event.version !== state.version + 1) return null;
+
+  const next = { ...state, version: event.version };
+  switch (event.op) {
+    case 'append':
+      next.messages = [...state.messages, event.message];
+      return next;
+    case 'patch':
+      if (event.index >= state.messages.length) return null;
+      next.messages = state.messages.map((message, index) => (index === event.index ? event.message : message));
+      return next;
+    case 'delete':
+      if (event.index >= state.messages.length) return null;
+      next.messages = state.messages.filter((_, index) => index !== event.index);
+      return next;
+    case 'thinking':
+      next.is_thinking = Boolean(event.is_thinking);
+      return next;
+    default:
+      return null;
+  }
+}
+
+/**
+ * Keep a mirror of one tab in sync and hand every full state to `callback`.
+ * `fetchState` loads a snapshot ({ messages, is_thinking, version }) after a gap.
+ */
+export function createTabStateMirror(fetchState, callback) {
+  let state = null;
+  let resyncing = null;
+
+  const resync = () => {
+    if (!resyncing) {
+      resyncing = Promise.resolve(fetchState())
+        .then((snapshot) => {
+          if (!state || snapshot.version >= state.version) {
+            state = snapshot;
+            callback(state);
+          }
+        })
+        .catch((error) => console.error('[TabState] Failed to resync tab state:', error))
+        .finally(() => { resyncing = null; });
+    }
+    return resyncing;
+  };
+
+  return (event) => {
+    const next = applyTabStateEvent(state, event);
+    if (next === null) {
+      resync();
+    } else if (next !== state) {
+      state = next;
+      callback(state);
+    }
+  };
+}
@@ -11,6 +11,7 @@ import { ask, open as dialogOpen } from '@tauri-apps/plugin-dialog';
 import { open as shellOpen } from '@tauri-apps/plugin-shell';
 import { readTextFile } from '@tauri-apps/plugin-fs';
 import { normalizeChatWindowActivation } from './chatFocusModel.js';
+import { createTabStateMirror } from './tabStateOps.js';
 
 const subscribeToTauriEvent = (eventName, callback) => {
   const hasEventRuntime = typeof window !== 'undefined'
@@ -244,6 +245,12 @@ export const createConversation = async (data) => {
   return conv;
 };
 
+// 会话自己的记忆开关；未设置过时返回 null
+export const getConversationMemoryEnabled = (id) => invoke('get_conversation_memory_enabled', { id });
+
+export const setConversationMemoryEnabled = (id, enabled) =>
+  invoke('set_conversation_memory_enabled', { id, enabled });
+
 export const updateConversation = async (id, data) => {
   console.log('[tauri.js updateConversation] ★★★ called with id=', id, 'title=', data.title, 'historyLength=', data.history?.length);
   // Handle title update
@@ -251,30 +258,22 @@ export const updateConversation = async (id, data) => {
     console.log('[tauri.js updateConversation] updating title to:', data.title);
     await invoke('update_conversation_title', { id, title: data.title });
   }
-  // Handle history update - save each message
+  // Handle history update - 保存为当前分支：复用相同前缀，只写入新增/改动的消息
+  // data.keepBranches 为 true 时（编辑、重新生成）被替换掉的旧消息保留为兄弟分支
   if (data.history) {
-    console.log('[tauri.js updateConversation] clearing old messages for convId=', id);
-    await invoke('clear_conversation_messages', { conversationId: id });
-    console.log('[tauri.js updateConversation] saving', data.history.length, 'messages to convId=', id);
-    for (let i = 0; i < data.history.length; i++) {
-      const msg = data.history[i];
-      const contentStr = typeof msg.content === 'string' ? msg.content : JSON.stringify(msg.content);
-      console.log(`[tauri.js updateConversation] saving msg[${i}] role=${msg.role} content_len=${contentStr.length} to convId=${id}`);
-      await invoke('create_message', {
-        data: {
-          conversationId: id,
-          role: msg.role,
-          content: contentStr,
-          toolCallHistory: msg.toolCallHistory ? JSON.stringify(msg.toolCallHistory) : null,
-        }
-      });
-    }
-    console.log('[tauri.js updateConversation] ✅ all', data.history.length, 'messages saved to convId=', id);
-    // 🔍 保存后立即回读验证
-    const verifyMessages = await invoke('get_messages', { conversationId: id });
-    console.log(`[tauri.js updateConversation] 🔍 VERIFY: DB中实际有 ${verifyMessages.length} 条消息 (convId=${id})`);
-    if (verifyMessages.length !== data.history.length) {
-      console.error(`[tauri.js updateConversation] ❌ 数据不一致! 写入=${data.history.length} 读回=${verifyMessages.length}`);
+    const history = data.history.map((msg) => ({
+      role: msg.role,
+      content: typeof msg.content === 'string' ? msg.content : JSON.stringify(msg.content),
+      toolCallHistory: msg.toolCallHistory ? JSON.stringify(msg.toolCallHistory) : null,
+    }));
+    console.log('[tauri.js updateConversation] saving', history.length, 'messages as active branch of convId=', id, 'keepBranches=', !!data.keepBranches);
+    const saved = await invoke('save_conversation_branch', {
+      conversationId: id,
+      history,
+      keepBranches: !!data.keepBranches,
+    });
+    if (saved.length !== history.length) {
+      console.error(`[tauri.js updateConversation] ❌ 数据不一致! 写入=${history.length} 读回=${saved.length}`);
     }
   } else {
     console.log('[tauri.js updateConversation] ⚠️ NO history provided, skipping message save');
@@ -305,6 +304,20 @@ export const createMessage = (conversationId, role, content, toolCallHistory = n
     }
   });
 
+// ==================== Message Branches ====================
+// 消息以树的形式保存；下面的命令会同时把当前分支推送到 TabState
+
+export const getMessageTree = (conversationId) => invoke('get_message_tree', { conversationId });
+
+export const getMessageBranches = (conversationId) => invoke('get_message_branches', { conversationId });
+
+export const editMessage = (id, content) =>
+  invoke('edit_message', { id, content: typeof content === 'string' ? content : JSON.stringify(content) });
+
+export const regenerateMessage = (id) => invoke('regenerate_message', { id });
+
+export const switchMessageBranch = (id) => invoke('switch_message_branch', { id });
+
 // ==================== Tab State (Rust-owned) ====================
 
 export const getTabState = (conversationId) => 
@@ -331,11 +344,24 @@ export const setTabThinking = (conversationId, isThinking) =>
 export const clearTabState = (conversationId) => 
   invoke('clear_tab_state', { conversationId });
 
+// Rust 推送带版本号的增量操作，这里维护副本，回调仍然拿到完整的 { messages, is_thinking }
 export const subscribeTabState = async (conversationId, callback) => {
   const eventName = `tab-state:${conversationId}`;
-  return listen(eventName, (event) => callback(event.payload));
+  const onEvent = createTabStateMirror(() => getTabState(conversationId), callback);
+  return listen(eventName, (event) => onEvent(event.payload));
 };
 
+// 打开的 tab 与草稿（Rust 写后落盘，重启后恢复）
+export const getOpenTabs = () => invoke('get_open_tabs');
+
+export const setOpenTabs = (tabs) => invoke('set_open_tabs', { tabs });
+
+export const getTabDraft = (conversationId) =>
+  invoke('get_tab_draft', { conversationId });
+
+export const setTabDraft = (conversationId, draft) =>
+  invoke('set_tab_draft', { conversationId, draft });
+
 // ==================== LLM ====================
 
 /**
@@ -511,6 +537,10 @@ export const mcp = {
   createServer: (data) => invoke('create_mcp_server', { data }),
   updateServer: (id, data) => invoke('update_mcp_server', { id, data }),
   deleteServer: (id) => invoke('delete_mcp_server', { id }),
+  // `mcpServers` JSON config files (onConflict: 'skip' | 'rename' | 'overwrite')
+  importServers: (json, onConflict = 'skip', dryRun = false) =>
+    invoke('import_mcp_servers', { json, onConflict, dryRun }),
+  exportServers: (ids = null) => invoke('export_mcp_servers', { ids }),
   
   // Runtime
   startServer: (id) => invoke('mcp_start_server', { serverId: id }),
@@ -518,12 +548,14 @@ export const mcp = {
   restartServer: (id) => invoke('mcp_restart_server', { serverId: id }),
   getServerStatus: (id) => invoke('mcp_get_server_status', { serverId: id }),
   getAllStatuses: () => invoke('mcp_get_all_statuses'),
-  getAllTools: () => invoke('mcp_get_all_tools'),
-  callTool: (serverId, toolName, args) => 
-    invoke('mcp_call_tool', { serverId, toolName, arguments: args }),
+  // petId: 去掉该宠物禁用的工具
+  getAllTools: (petId = null) => invoke('mcp_get_all_tools', { petId }),
+  // interactive: 有人能回答审批弹窗（聊天界面）；否则需要审批的调用直接被拒绝，不等超时
+  callTool: (serverId, toolName, args, petId = null, interactive = false) => 
+    invoke('mcp_call_tool', { serverId, toolName, arguments: args, petId, interactive }),
   // 通过完整工具名（格式：ServerName__tool_name）调用工具
   // 需要先查找 serverId
-  callToolByName: async (fullToolName, args) => {
+  callToolByName: async (fullToolName, args, { petId = null, interactive = false } = {}) => {
     console.log('[MCP] callToolByName:', fullToolName, args);
     const parts = fullToolName.split('__');
     if (parts.length < 2) {
@@ -542,8 +574,11 @@ export const mcp = {
     
     // 注意：Rust 端 id 字段序列化为 _id
     console.log('[MCP] Calling mcp_call_tool with serverId:', server._id, 'toolName:', toolName);
-    return invoke('mcp_call_tool', { serverId: server._id, toolName, arguments: args });
+    return invoke('mcp_call_tool', { serverId: server._id, toolName, arguments: args, petId, interactive });
   },
+  // 通过 mcp_get_all_tools 返回的 toolId 调用工具（Rust 端反查 serverId）
+  callToolById: (toolId, args, petId = null, interactive = false) =>
+    invoke('mcp_call_tool_by_id', { toolId, arguments: args, petId, interactive }),
   isServerRunning: (id) => invoke('mcp_is_server_running', { serverId: id }),
   testServer: (config) => invoke('mcp_test_server', { 
     transport: config.transport || 'stdio',
@@ -556,8 +591,65 @@ export const mcp = {
   cancelAllToolCalls: () => invoke('mcp_cancel_all_tool_calls'),
   resetCancellation: () => invoke('mcp_reset_cancellation'),
   setSamplingConfig: (serverId, config) => invoke('mcp_set_sampling_config', { serverId, config }),
+  getResourceTemplates: (serverId) => invoke('mcp_get_resource_templates', { serverId }),
+  readResource: (serverId, uri) => invoke('mcp_read_resource', { serverId, uri }),
+  readResourceTemplate: (serverId, uriTemplate, variables = {}) =>
+    invoke('mcp_read_resource_template', { serverId, uriTemplate, variables }),
+  // 读取资源并转换为聊天附件（blob 保存到 uploads 目录）
+  readResourceAsAttachments: (serverId, uri) => invoke('mcp_read_resource_as_attachments', { serverId, uri }),
+  subscribeResource: (serverId, uri) => invoke('mcp_subscribe_resource', { serverId, uri }),
+  unsubscribeResource: (serverId, uri) => invoke('mcp_unsubscribe_resource', { serverId, uri }),
+  onResourceUpdated: (callback) => subscribeToTauriEvent('mcp-resource-updated', callback),
+  // Supervisor lifecycle changes: { serverId, state, error, restartCount }
+  onServerStatus: (callback) => subscribeToTauriEvent('mcp-server-status', callback),
+  // OAuth: the backend sends the authorization URL back as an event; open it in the system browser
+  authorize: async (serverId) => {
+    const unlisten = subscribeToTauriEvent('mcp-oauth-authorize-url', (event) => {
+      if (event.payload?.serverId === serverId) shellOpen(event.payload.url);
+    });
+    try {
+      return await invoke('mcp_oauth_authorize', { serverId });
+    } finally {
+      unlisten();
+    }
+  },
+  logout: (serverId) => invoke('mcp_oauth_logout', { serverId }),
+  isAuthorized: (serverId) => invoke('mcp_oauth_is_authorized', { serverId }),
   emitServersUpdated: (payload = {}) => emit('mcp-servers-updated', payload),
   onServersUpdated: (callback) => subscribeToTauriEvent('mcp-servers-updated', callback),
+
+  // Tool permissions (action: 'allow' | 'ask' | 'deny'; argPattern: 'key=glob' or a glob over the JSON args)
+  getToolPolicies: (serverId = null) => invoke('get_mcp_tool_policies', { serverId }),
+  createToolPolicy: (data) => invoke('create_mcp_tool_policy', { data }),
+  deleteToolPolicy: (id) => invoke('delete_mcp_tool_policy', { id }),
+  getToolAuditLog: (serverId = null, petId = null, limit = 200) =>
+    invoke('get_mcp_tool_audit_log', { serverId, petId, limit }),
+  clearToolAuditLog: () => invoke('clear_mcp_tool_audit_log'),
+  // Tool result size limits (toolName '*' = server default):
+  // { maxTextChars, maxImageDimension, maxImageBytes, spill }
+  getToolResultLimits: (serverId = null) => invoke('get_mcp_tool_result_limits', { serverId }),
+  setToolResultLimits: (serverId, toolName, limits) =>
+    invoke('set_mcp_tool_result_limits', { serverId, toolName, limits }),
+  deleteToolResultLimits: (serverId, toolName) =>
+    invoke('delete_mcp_tool_result_limits', { serverId, toolName }),
+  // Per-pet tool toggles: disabled tools are left out of getAllTools(petId)
+  getPetDisabledTools: (petId) => invoke('get_pet_disabled_tools', { petId }),
+  setPetToolEnabled: (petId, serverId, toolName, enabled) =>
+    invoke('set_pet_tool_enabled', { petId, serverId, toolName, enabled }),
+  // { requestId, serverId, serverName, toolName, arguments, petId, destructive, timeoutSecs }
+  onToolApprovalRequested: (callback) => subscribeToTauriEvent('mcp-tool-approval-requested', callback),
+  respondToolApproval: (requestId, approved, remember = false) =>
+    invoke('mcp_respond_tool_approval', { requestId, approved, remember }),
+
+  // Built-in MCP server: exposes PetGPT to other MCP hosts over loopback HTTP (or `--mcp-stdio`)
+  host: {
+    start: (port = null) => invoke('mcp_host_start', { port }),
+    stop: () => invoke('mcp_host_stop'),
+    status: () => invoke('mcp_host_status'),
+    resetToken: () => invoke('mcp_host_reset_token'),
+  },
+  // A message was added to a conversation from outside the UI (built-in MCP server)
+  onConversationMessageAdded: (callback) => subscribeToTauriEvent('conversation-message-added', callback),
 };
 
 // ==================== Managed native QQ connector ====================
@@ -878,16 +970,61 @@ export const workspaceRead = async (petId, path) => {
   return invoke('workspace_read', { petId, path });
 };
 
-export const workspaceWrite = async (petId, path, content) => {
-  return invoke('workspace_write', { petId, path, content });
+// 工作区文件变更监听：
+// - 后端文件监听器（外部编辑器改了 SOUL.md、拖入 Skill 等）防抖后广播 workspace-files-changed
+// - 本窗口内的写入在命令返回后立即本地通知，不用等监听器的防抖
+// payload: { scope: 'workspace' | 'skills', petId, paths }，paths 为相对路径
+const workspaceChangeListeners = new Set();
+
+const notifyWorkspaceChanged = (petId, paths) => {
+  const change = { scope: 'workspace', petId, paths };
+  for (const callback of workspaceChangeListeners) {
+    try {
+      callback(change);
+    } catch (error) {
+      console.warn('[tauri.workspace] change listener failed:', error);
+    }
+  }
 };
 
-export const workspaceAppend = async (petId, path, content) => {
-  return invoke('workspace_append', { petId, path, content });
+export const onWorkspaceFilesChanged = (callback) => {
+  workspaceChangeListeners.add(callback);
+  const unlisten = subscribeToTauriEvent('workspace-files-changed', (event) => callback(event.payload));
+  return () => {
+    workspaceChangeListeners.delete(callback);
+    unlisten();
+  };
+};
+
+const withChangeNotice = async (petId, paths, promise) => {
+  const result = await promise;
+  notifyWorkspaceChanged(petId, paths);
+  return result;
 };
 
-export const workspaceEdit = async (petId, path, oldText, newText) => {
-  return invoke('workspace_edit', { petId, path, oldText, newText });
+// 写入类命令的 source: 'user'（默认，界面编辑）或 'tool'（工具调用），记录在文件历史里
+export const workspaceWrite = async (petId, path, content, { source } = {}) => {
+  return withChangeNotice(petId, [path], invoke('workspace_write', { petId, path, content, source }));
+};
+
+export const workspaceAppend = async (petId, path, content, { source } = {}) => {
+  return withChangeNotice(petId, [path], invoke('workspace_append', { petId, path, content, source }));
+};
+
+export const workspaceEdit = async (petId, path, oldText, newText, { source } = {}) => {
+  return withChangeNotice(petId, [path], invoke('workspace_edit', { petId, path, oldText, newText, source }));
+};
+
+// 批量编辑：edits = [{ oldText, newText, replaceAll }]，任何一处失败都不写入；dryRun 只返回 diff
+export const workspaceEditBatch = async (petId, path, edits, { dryRun = false, source } = {}) => {
+  const pending = invoke('workspace_edit_batch', { petId, path, edits, dryRun, source });
+  return dryRun ? pending : withChangeNotice(petId, [path], pending);
+};
+
+// 应用 unified diff 补丁，fuzz 为允许忽略的边缘上下文行数（默认 2）
+export const workspaceApplyPatch = async (petId, path, patch, { fuzz, dryRun = false, source } = {}) => {
+  const pending = invoke('workspace_apply_patch', { petId, path, patch, fuzz, dryRun, source });
+  return dryRun ? pending : withChangeNotice(petId, [path], pending);
 };
 
 export const workspaceEnsureDefaultFiles = async (petId, petName) => {
@@ -898,16 +1035,16 @@ export const workspaceListDir = async (petId, path) => {
   return invoke('workspace_list_dir', { petId, path });
 };
 
-export const workspaceDeleteFile = async (petId, path) => {
-  return invoke('workspace_delete_file', { petId, path });
+export const workspaceDeleteFile = async (petId, path, { source } = {}) => {
+  return withChangeNotice(petId, [path], invoke('workspace_delete_file', { petId, path, source }));
 };
 
-export const workspaceRenameFile = async (petId, from, to) => {
-  return invoke('workspace_rename_file', { petId, from, to });
+export const workspaceRenameFile = async (petId, from, to, { source } = {}) => {
+  return withChangeNotice(petId, [from, to], invoke('workspace_rename_file', { petId, from, to, source }));
 };
 
-export const workspaceWriteBinary = async (petId, path, base64Data) => {
-  return invoke('workspace_write_binary', { petId, path, base64Data });
+export const workspaceWriteBinary = async (petId, path, base64Data, { source } = {}) => {
+  return withChangeNotice(petId, [path], invoke('workspace_write_binary', { petId, path, base64Data, source }));
 };
 
 export const workspaceReadBinary = async (petId, path) => {
@@ -922,6 +1059,63 @@ export const workspaceGetPath = async (petId, path, ensureExists = false) => {
   return invoke('workspace_get_path', { petId, path, ensureExists });
 };
 
+// 全文搜索：options = { query, regex, caseSensitive, path, include, exclude, contextLines, maxResults }
+export const workspaceSearch = async (petId, options) => {
+  return invoke('workspace_search', { petId, options });
+};
+
+// 文件历史：每次写入都会留快照，可对比、回滚
+export const workspaceFileHistory = async (petId, path) => {
+  return invoke('workspace_file_history', { petId, path });
+};
+
+export const workspaceDiffVersions = async (petId, path, fromHash, toHash) => {
+  return invoke('workspace_diff_versions', { petId, path, fromHash, toHash });
+};
+
+export const workspaceRestoreVersion = async (petId, path, hash, { source } = {}) => {
+  return withChangeNotice(petId, [path], invoke('workspace_restore_version', { petId, path, hash, source }));
+};
+
+export const workspaceGetHistoryRetention = async () => {
+  return invoke('workspace_get_history_retention');
+};
+
+export const workspaceSetHistoryRetention = async (retention) => {
+  return invoke('workspace_set_history_retention', { retention });
+};
+
+// 空间占用与配额：usage 按目录/类型统计；quota 省略 petId 时读写默认配额，quota 传 null 恢复默认
+export const workspaceUsage = async (petId, depth) => {
+  return invoke('workspace_usage', { petId, depth });
+};
+
+export const workspaceGetQuota = async (petId) => {
+  return invoke('workspace_get_quota', { petId });
+};
+
+export const workspaceSetQuota = async (petId, quota) => {
+  return invoke('workspace_set_quota', { petId, quota });
+};
+
+// 文件信息（按内容嗅探的 MIME）与图片缩略图（base64 PNG）
+export const workspaceFileInfo = async (petId, path) => {
+  return invoke('workspace_file_info', { petId, path });
+};
+
+export const workspaceThumbnail = async (petId, path) => {
+  return invoke('workspace_thumbnail', { petId, path });
+};
+
+// 清理：scan 列出 social/、training 下的大文件和久未修改的文件，delete 删除选中的文件（进文件历史）
+export const workspaceCleanupScan = async (petId, options) => {
+  return invoke('workspace_cleanup_scan', { petId, options });
+};
+
+export const workspaceCleanupDelete = async (petId, paths, { source } = {}) => {
+  return withChangeNotice(petId, paths, invoke('workspace_cleanup_delete', { petId, paths, source }));
+};
+
 // ==================== Skills (read-only runtime) ====================
 
 export const skillsList = (petId) =>
@@ -1033,6 +1227,61 @@ export const runTrainingExport = async (options) => {
 
 export const getHomeDir = () => invoke('get_home_dir');
 
+// ==================== Conversation Export / Import ====================
+// format: 'markdown' | 'html' | 'json'（json 为无损格式，可再导入）
+
+export const exportConversation = (id, format, outputPath) =>
+  invoke('export_conversation', { id, format, outputPath });
+
+export const exportPetConversations = (petId, format, outputDir) =>
+  invoke('export_pet_conversations', { petId, format, outputDir });
+
+export const importConversation = (petId, path) =>
+  invoke('import_conversation', { petId, path });
+
+// 导入 ChatGPT / Claude 官方导出（ZIP 或 conversations.json）；dryRun 只返回统计
+export const importChatArchive = (petId, path, dryRun = false) =>
+  invoke('import_chat_archive', { petId, path, dryRun });
+
+// 自动标题 / 滚动摘要
+export const getSummarizerConfig = () => invoke('get_summarizer_config');
+
+export const setSummarizerConfig = (config) => invoke('set_summarizer_config', { config });
+
+export const getConversationSummary = (conversationId) =>
+  invoke('get_conversation_summary', { conversationId });
+
+// 记忆整理：从最近的对话和主人的 QQ 消息中提炼事实，审核通过后写入 USER.md / MEMORY.md
+export const getMemoryConfig = () => invoke('get_memory_config');
+
+export const setMemoryConfig = (config) => invoke('set_memory_config', { config });
+
+// 立即整理一次，返回新加入审核队列的候选
+export const memoryConsolidate = (petId) => invoke('memory_consolidate', { petId });
+
+// status: 'pending' | 'approved' | 'rejected'，不传返回全部
+export const memoryListCandidates = (petId, status) =>
+  invoke('memory_list_candidates', { petId, status });
+
+// fact 为用户修改后的文本，不传则按原文写入
+export const memoryApproveCandidate = async (id, fact) => {
+  const candidate = await invoke('memory_approve_candidate', { id, fact });
+  notifyWorkspaceChanged(candidate.petId, [candidate.file]);
+  return candidate;
+};
+
+export const memoryRejectCandidate = (id) => invoke('memory_reject_candidate', { id });
+
+// Rust 端组装的 system prompt；返回 { prompt, tokens, memoryEnabled, sections }，sections 为每一节的 token 明细
+// 记忆开关优先取会话自己保存的设置；会话尚未创建时用 memoryEnabled，都没有则用全局默认（memoryEnabledByDefault）
+export const getSystemPrompt = (petId, conversationId, memoryEnabled) =>
+  invoke('get_system_prompt', { petId, conversationId, memoryEnabled });
+
+// 每一节的 token 上限：{ system, soul, user, memory, skills }
+export const getPromptBudget = () => invoke('get_prompt_budget');
+
+export const setPromptBudget = (budget) => invoke('set_prompt_budget', { budget });
+
 // Model Configs (alias to pets with model type)
 export const getModelConfigs = async () => {
   const pets = await getPets();
@@ -1174,6 +1423,8 @@ const tauri = {
   getConversationById,
   getConversationWithHistory,
   createConversation,
+  getConversationMemoryEnabled,
+  setConversationMemoryEnabled,
   updateConversation,
   deleteConversation,
   getOrphanConversations,
@@ -1184,6 +1435,11 @@ const tauri = {
   // Messages
   getMessages,
   createMessage,
+  getMessageTree,
+  getMessageBranches,
+  editMessage,
+  regenerateMessage,
+  switchMessageBranch,
   
   // Tab State
   getTabState,
@@ -1195,6 +1451,10 @@ const tauri = {
   setTabThinking,
   clearTabState,
   subscribeTabState,
+  getOpenTabs,
+  setOpenTabs,
+  getTabDraft,
+  setTabDraft,
   
   // LLM
   llmCall,
@@ -1320,6 +1580,8 @@ const tauri = {
   workspaceWrite,
   workspaceAppend,
   workspaceEdit,
+  workspaceEditBatch,
+  workspaceApplyPatch,
   workspaceEnsureDefaultFiles,
   workspaceListDir,
   workspaceDeleteFile,
@@ -1327,6 +1589,20 @@ const tauri = {
   workspaceWriteBinary,
   workspaceFileExists,
   workspaceGetPath,
+  workspaceSearch,
+  workspaceFileHistory,
+  workspaceDiffVersions,
+  workspaceRestoreVersion,
+  workspaceGetHistoryRetention,
+  workspaceSetHistoryRetention,
+  onWorkspaceFilesChanged,
+  workspaceUsage,
+  workspaceGetQuota,
+  workspaceSetQuota,
+  workspaceFileInfo,
+  workspaceThumbnail,
+  workspaceCleanupScan,
+  workspaceCleanupDelete,
   workspaceDeleteFolder,
   workspaceOpenFolder,
   workspaceOpenSubfolder,
@@ -1342,6 +1618,22 @@ const tauri = {
   skillsOpenGlobalFolder,
   runTrainingExport,
   getHomeDir,
+  exportConversation,
+  exportPetConversations,
+  importConversation,
+  importChatArchive,
+  getSummarizerConfig,
+  setSummarizerConfig,
+  getConversationSummary,
+  getMemoryConfig,
+  setMemoryConfig,
+  memoryConsolidate,
+  memoryListCandidates,
+  memoryApproveCandidate,
+  memoryRejectCandidate,
+  getSystemPrompt,
+  getPromptBudget,
+  setPromptBudget,
 
   // TTS
   elevenlabsTts,
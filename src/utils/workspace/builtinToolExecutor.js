@@ -1,7 +1,7 @@
 /**
  * builtinToolExecutor.js — 内置工具执行器
  * 
- * 处理 read / write / edit 三个内置工具的执行逻辑。
+ * 处理 read / write / edit / search 内置工具的执行逻辑。
  * 这些工具操作 pet workspace 中的文件（SOUL.md, USER.md, MEMORY.md 等）。
  * 
  * 与 MCP 工具的区别：
@@ -16,7 +16,10 @@ import { isPathAllowed, isSoulFile } from '../promptBuilder';
 
 // ============ 工具名常量 ============
 
-const BUILTIN_TOOL_NAMES = new Set(['read', 'write', 'edit', 'generate_image']);
+const BUILTIN_TOOL_NAMES = new Set(['read', 'write', 'edit', 'search', 'generate_image']);
+
+// 普通聊天可访问的文件，和 promptBuilder.isPathAllowed 保持一致
+const CHAT_FILES = ['SOUL.md', 'USER.md', 'MEMORY.md'];
 
 function pathDeniedMessage(memoryEnabled) {
   return memoryEnabled
@@ -41,7 +44,11 @@ export function isBuiltinTool(toolName) {
  */
 async function confirmSoulModification(action, args) {
   let message;
-  if (action === 'write') {
+  if (action === 'batch') {
+    // args.diff 来自 dry-run 预览
+    const diff = args.diff || '';
+    message = `AI 想要一次修改你的人格文件 SOUL.md 的多处内容：\n\n${diff.slice(0, 1500)}${diff.length > 1500 ? '\n...(内容过长已截断)' : ''}\n\n是否允许？`;
+  } else if (action === 'write') {
     const preview = args.content?.slice(0, 500) || '';
     message = `AI 想要覆盖你的人格文件 SOUL.md：\n\n${preview}${args.content?.length > 500 ? '\n...(内容过长已截断)' : ''}\n\n是否允许？`;
   } else {
@@ -96,19 +103,62 @@ async function executeWrite(petId, args, memoryEnabled) {
   }
 
   try {
-    const result = await tauri.workspaceWrite(petId, path, content);
+    const result = await tauri.workspaceWrite(petId, path, content, { source: 'tool' });
     return { content: [{ type: 'text', text: result }] };
   } catch (err) {
     return { error: err.toString() };
   }
 }
 
+/**
+ * 校验 edit 工具的 edits 数组，返回 Rust 侧 EditOp 列表或错误信息
+ */
+export function normalizeBatchEdits(edits) {
+  if (!Array.isArray(edits) || edits.length === 0) return { error: 'edits 必须是非空数组' };
+  const ops = [];
+  for (const [index, item] of edits.entries()) {
+    if (!item?.oldText) return { error: `edits[${index}] 缺少 oldText` };
+    if (item.newText === undefined || item.newText === null) return { error: `edits[${index}] 缺少 newText` };
+    ops.push({ oldText: String(item.oldText), newText: String(item.newText), replaceAll: Boolean(item.replaceAll) });
+  }
+  return { ops };
+}
+
+/**
+ * 批量编辑：全部成功才写入，返回 diff
+ */
+async function executeBatchEdit(petId, path, edits, { confirmSoul = false } = {}) {
+  const { ops, error } = normalizeBatchEdits(edits);
+  if (error) return { error };
+  try {
+    if (confirmSoul) {
+      const preview = await tauri.workspaceEditBatch(petId, path, ops, { dryRun: true });
+      const confirmed = await confirmSoulModification('batch', { diff: preview.diff });
+      if (!confirmed) {
+        return { content: [{ type: 'text', text: '用户拒绝了此次修改。' }] };
+      }
+    }
+    const result = await tauri.workspaceEditBatch(petId, path, ops, { source: 'tool' });
+    return { content: [{ type: 'text', text: `成功应用 ${ops.length} 处修改到 ${path}\n\n${result.diff}` }] };
+  } catch (err) {
+    return { error: err.toString() };
+  }
+}
+
 /**
  * 执行内置 edit 工具
  */
 async function executeEdit(petId, args, memoryEnabled) {
-  const { path, oldText, newText } = args;
+  const { path, oldText, newText, edits } = args;
   if (!path) return { error: '缺少 path 参数' };
+
+  if (edits !== undefined) {
+    if (!isPathAllowed(path, memoryEnabled)) {
+      return { error: pathDeniedMessage(memoryEnabled) };
+    }
+    return executeBatchEdit(petId, path, edits, { confirmSoul: isSoulFile(path) });
+  }
+
   if (!oldText) return { error: '缺少 oldText 参数' };
   if (newText === undefined || newText === null) return { error: '缺少 newText 参数' };
 
@@ -126,13 +176,63 @@ async function executeEdit(petId, args, memoryEnabled) {
   }
 
   try {
-    const result = await tauri.workspaceEdit(petId, path, oldText, newText);
+    const result = await tauri.workspaceEdit(petId, path, oldText, newText, { source: 'tool' });
     return { content: [{ type: 'text', text: result }] };
   } catch (err) {
     return { error: err.toString() };
   }
 }
 
+/**
+ * 把 workspace_search 的结果格式化成 grep 风格文本：
+ * 匹配行 "路径:行号: 内容"，上下文行 "路径-行号- 内容"，不相邻的片段之间用 "--" 分隔
+ */
+export function formatSearchResult(result) {
+  const matches = result?.matches || [];
+  if (matches.length === 0) {
+    return `没有找到匹配（搜索了 ${result?.filesSearched ?? 0} 个文件）`;
+  }
+  const lines = [];
+  let lastPath = null;
+  let lastLine = 0;
+  matches.forEach((m, index) => {
+    // 上下文行只显示到同一文件下一处匹配之前
+    const next = matches[index + 1];
+    const after = next?.path === m.path ? m.after.slice(0, Math.max(0, next.lineNumber - m.lineNumber - 1)) : m.after;
+    const first = m.lineNumber - m.before.length;
+    if (lastPath !== null && (m.path !== lastPath || first > lastLine + 1)) lines.push('--');
+    m.before.forEach((text, i) => {
+      if (m.path !== lastPath || first + i > lastLine) lines.push(`${m.path}-${first + i}- ${text}`);
+    });
+    lines.push(`${m.path}:${m.lineNumber}: ${m.line}`);
+    after.forEach((text, i) => lines.push(`${m.path}-${m.lineNumber + 1 + i}- ${text}`));
+    lastPath = m.path;
+    lastLine = m.lineNumber + after.length;
+  });
+  lines.push('', `共 ${matches.length} 处匹配，${result.filesMatched} 个文件${result.truncated ? '（结果已截断，请缩小搜索范围）' : ''}`);
+  return lines.join('\n');
+}
+
+/**
+ * 执行内置 search 工具：只在当前允许访问的文件里搜索
+ */
+async function executeSearch(petId, args, memoryEnabled) {
+  const { query, regex } = args;
+  if (!query) return { error: '缺少 query 参数' };
+
+  try {
+    const result = await tauri.workspaceSearch(petId, {
+      query,
+      regex: Boolean(regex),
+      include: memoryEnabled ? CHAT_FILES : ['SOUL.md'],
+      maxResults: 50,
+    });
+    return { content: [{ type: 'text', text: formatSearchResult(result) }] };
+  } catch (err) {
+    return { error: err.toString() };
+  }
+}
+
 // ============ 图像生成 ============
 
 /**
@@ -213,7 +313,7 @@ function pickApiKey(raw) {
 /**
  * 执行内置工具
  *
- * @param {string} toolName - 工具名: 'read' | 'write' | 'edit' | 'generate_image'
+ * @param {string} toolName - 工具名: 'read' | 'write' | 'edit' | 'search' | 'generate_image'
  * @param {Object} args - 工具参数
  * @param {Object} context - 执行上下文
  * @param {string} context.petId - 当前宠物 ID
@@ -242,6 +342,8 @@ export async function executeBuiltinTool(toolName, args, context) {
       return executeWrite(petId, args, memoryEnabled);
     case 'edit':
       return executeEdit(petId, args, memoryEnabled);
+    case 'search':
+      return executeSearch(petId, args, memoryEnabled);
     default:
       return { error: `未知的内置工具: ${toolName}` };
   }
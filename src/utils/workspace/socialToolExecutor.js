@@ -14,6 +14,7 @@ import { downloadUrlAsBase64 } from '../tauri.js';
 import { notifySubagentChange } from '../subagentManager.js';
 import { isSubagentRuntimeEnabled } from '../subagentCapability.js';
 import { socialTargetDir } from '../socialTargetType.js';
+import { formatSearchResult, normalizeBatchEdits } from './builtinToolExecutor.js';
 
 // ============ 常量 ============
 
@@ -107,7 +108,7 @@ function runtimeGuardError(context, actionLabel) {
 
 // ============ 通用文件工具 ============
 
-const SOCIAL_FILE_TOOL_NAMES = new Set(['social_tree', 'social_read', 'social_write', 'social_edit', 'social_delete', 'social_rename']);
+const SOCIAL_FILE_TOOL_NAMES = new Set(['social_tree', 'social_read', 'social_search', 'social_write', 'social_edit', 'social_delete', 'social_rename']);
 
 /** 检查工具名是否为社交通用文件工具 */
 export function isSocialFileTool(toolName) {
@@ -148,6 +149,36 @@ export function getSocialFileToolDefinitions() {
         },
       },
     },
+    {
+      type: 'function',
+      function: {
+        name: 'social_search',
+        description: '在社交工作区 social/ 中全文搜索，返回带行号和上下文的匹配行（格式 "路径:行号: 内容"）。找人、找旧约定、找某个话题记在哪时先搜再读，不要逐个文件 social_read。',
+        parameters: {
+          type: 'object',
+          properties: {
+            query: {
+              type: 'string',
+              description: '要搜索的文本（默认按字面子串、忽略大小写匹配）',
+            },
+            regex: {
+              type: 'boolean',
+              description: '把 query 当作正则表达式（默认 false）',
+            },
+            path: {
+              type: 'string',
+              description: '只搜索这个目录或文件（必须以 social/ 开头，默认整个 social/）',
+            },
+            include: {
+              type: 'array',
+              items: { type: 'string' },
+              description: '只搜索匹配这些 glob 的文件，如 ["social/people/*.md"]',
+            },
+          },
+          required: ['query'],
+        },
+      },
+    },
     {
       type: 'function',
       function: {
@@ -173,7 +204,7 @@ export function getSocialFileToolDefinitions() {
       type: 'function',
       function: {
         name: 'social_edit',
-        description: '通过精确文本查找替换来编辑社交工作区中的一个文件。使用前必须先调用 social_read 获取当前内容。每次只改一处，改完再 read 确认结果。如果需要大幅修改（超过一半内容），直接用 social_write 覆盖更可靠。',
+        description: '通过精确文本查找替换来编辑社交工作区中的一个文件。使用前必须先调用 social_read 获取当前内容。单处修改用 oldText/newText；同一文件要改多处时用 edits 一次提交（全部成功才写入，返回 diff）。如果需要大幅修改（超过一半内容），直接用 social_write 覆盖更可靠。',
         parameters: {
           type: 'object',
           properties: {
@@ -189,8 +220,21 @@ export function getSocialFileToolDefinitions() {
               type: 'string',
               description: '替换后的新文本',
             },
+            edits: {
+              type: 'array',
+              description: '多处修改（代替 oldText/newText）：按顺序应用，任何一处失败则整批都不写入',
+              items: {
+                type: 'object',
+                properties: {
+                  oldText: { type: 'string', description: '要替换的原文，从 social_read 结果精确复制' },
+                  newText: { type: 'string', description: '替换后的新文本' },
+                  replaceAll: { type: 'boolean', description: '替换所有出现处（默认 false，要求唯一匹配）' },
+                },
+                required: ['oldText', 'newText'],
+              },
+            },
           },
-          required: ['path', 'oldText', 'newText'],
+          required: ['path'],
         },
       },
     },
@@ -377,6 +421,30 @@ async function executeSocialRead(petId, args) {
   }
 }
 
+async function executeSocialSearch(petId, args) {
+  const { query, regex, path, include } = args;
+  if (!query) return { error: '缺少 query 参数' };
+  const trimmed = typeof path === 'string' ? path.replace(/^\.\/|\/+$/g, '') : '';
+  const searchPath = trimmed && trimmed !== SOCIAL_DIR ? canonicalizeSocialPath(trimmed) : SOCIAL_DIR;
+  if (!searchPath) return { error: '路径必须是 social/ 下的安全路径' };
+
+  try {
+    const result = await tauri.workspaceSearch(petId, {
+      query,
+      regex: Boolean(regex),
+      path: searchPath,
+      include: Array.isArray(include) ? include : [],
+      maxResults: 50,
+    });
+    return { content: [{ type: 'text', text: formatSearchResult(result) }] };
+  } catch (e) {
+    if (e?.toString?.()?.includes('不存在')) {
+      return { content: [{ type: 'text', text: `（目录不存在: ${searchPath}）` }] };
+    }
+    return { error: e.toString() };
+  }
+}
+
 async function executeSocialWrite(petId, args) {
   const { path, content } = args;
   if (!path) return { error: '缺少 path 参数' };
@@ -389,7 +457,7 @@ async function executeSocialWrite(petId, args) {
   }
 
   try {
-    const result = await tauri.workspaceWrite(petId, canonicalPath, content);
+    const result = await tauri.workspaceWrite(petId, canonicalPath, content, { source: 'tool' });
     return { content: [{ type: 'text', text: result }] };
   } catch (err) {
     return { error: err.toString() };
@@ -397,8 +465,9 @@ async function executeSocialWrite(petId, args) {
 }
 
 async function executeSocialEdit(petId, args) {
-  const { path, oldText, newText } = args;
+  const { path, oldText, newText, edits } = args;
   if (!path) return { error: '缺少 path 参数' };
+  if (edits !== undefined) return executeSocialBatchEdit(petId, path, edits);
   if (!oldText) return { error: '缺少 oldText 参数' };
   if (newText === undefined || newText === null) return { error: '缺少 newText 参数' };
   const canonicalPath = canonicalizeSocialPath(path);
@@ -406,13 +475,28 @@ async function executeSocialEdit(petId, args) {
   if (isReadOnlyPath(canonicalPath)) return { error: `${canonicalPath} 是系统自动维护的只读文件，不允许手动编辑。` };
 
   try {
-    const result = await tauri.workspaceEdit(petId, canonicalPath, oldText, newText);
+    const result = await tauri.workspaceEdit(petId, canonicalPath, oldText, newText, { source: 'tool' });
     return { content: [{ type: 'text', text: result }] };
   } catch (err) {
     return { error: err.toString() };
   }
 }
 
+async function executeSocialBatchEdit(petId, path, edits) {
+  const canonicalPath = canonicalizeSocialPath(path);
+  if (!canonicalPath) return { error: '路径必须是 social/ 下的安全文件路径' };
+  if (isReadOnlyPath(canonicalPath)) return { error: `${canonicalPath} 是系统自动维护的只读文件，不允许手动编辑。` };
+  const { ops, error } = normalizeBatchEdits(edits);
+  if (error) return { error };
+
+  try {
+    const result = await tauri.workspaceEditBatch(petId, canonicalPath, ops, { source: 'tool' });
+    return { content: [{ type: 'text', text: `成功应用 ${ops.length} 处修改到 ${canonicalPath}\n\n${result.diff}` }] };
+  } catch (err) {
+    return { error: err.toString() };
+  }
+}
+
 async function executeSocialDelete(petId, args) {
   const { path } = args;
   if (!path) return { error: '缺少 path 参数' };
@@ -479,6 +563,9 @@ export async function executeSocialFileTool(toolName, args, context) {
         }
       }
       break;
+    case 'social_search':
+      result = await executeSocialSearch(petId, args);
+      break;
     case 'social_write': {
       // 拦截写入 reply_brief.md 的调用：如果思考过程中有新消息，强制先 social_edit 再重试
       const isReplyBrief = args?.path?.includes('reply_brief.md');